@@ -0,0 +1,149 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use galacticplayground::args::args;
+use galacticplayground::contracts::kale::{count_leading_zeros, parse_pail_zeros, work_hash};
+use stellar_xdr::curr::{
+    AccountId, ContractDataDurability, ContractDataEntry, ExtensionPoint, Hash, HostFunction,
+    InvokeContractArgs, InvokeHostFunctionOp, LedgerEntryData, LedgerFootprint, Limits, Memo,
+    MuxedAccount, Operation, OperationBody, Preconditions, PublicKey, ScAddress, ScSymbol, ScVal,
+    SequenceNumber, SorobanResources, SorobanTransactionData, Transaction, TransactionEnvelope,
+    TransactionExt, TransactionV1Envelope, Uint256, WriteXdr,
+};
+
+const ENTROPY: [u8; 32] = [7; 32];
+const FARMER: [u8; 32] = [42; 32];
+
+/// Nonces hashed per iteration of the batched benchmark, like one miner work unit
+const BATCH_SIZE: u64 = 1024;
+
+fn farmer_address() -> ScAddress {
+    ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(FARMER))))
+}
+
+fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+fn bench_work_hash(c: &mut Criterion) {
+    c.bench_function("work_hash", |b| {
+        b.iter(|| work_hash(black_box(1000), black_box(123_456), &ENTROPY, &FARMER))
+    });
+
+    let mut group = c.benchmark_group("work_hash_batch");
+    group.throughput(Throughput::Elements(BATCH_SIZE));
+    group.bench_function("best_of_1024", |b| {
+        b.iter(|| {
+            (0..BATCH_SIZE)
+                .map(|nonce| count_leading_zeros(&work_hash(1000, nonce, &ENTROPY, &FARMER)))
+                .max()
+        })
+    });
+    group.finish();
+}
+
+/// A work invocation with the Soroban data simulation would add, as prepare assembles it
+fn work_transaction() -> Transaction {
+    let args = args()
+        .address(farmer_address())
+        .bytes_n(&[0u8; 32])
+        .u64(123_456)
+        .build()
+        .unwrap();
+
+    Transaction {
+        source_account: MuxedAccount::Ed25519(Uint256(FARMER)),
+        fee: 100,
+        seq_num: SequenceNumber(1),
+        cond: Preconditions::None,
+        memo: Memo::None,
+        operations: vec![Operation {
+            source_account: None,
+            body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                host_function: HostFunction::InvokeContract(InvokeContractArgs {
+                    contract_address: ScAddress::Contract(Hash([1; 32])),
+                    function_name: ScSymbol("work".try_into().unwrap()),
+                    args: args.try_into().unwrap(),
+                }),
+                auth: Default::default(),
+            }),
+        }]
+        .try_into()
+        .unwrap(),
+        ext: TransactionExt::V1(SorobanTransactionData {
+            ext: ExtensionPoint::V0,
+            resources: SorobanResources {
+                footprint: LedgerFootprint {
+                    read_only: Default::default(),
+                    read_write: Default::default(),
+                },
+                instructions: 5_000_000,
+                read_bytes: 2_000,
+                write_bytes: 500,
+            },
+            resource_fee: 50_000,
+        }),
+    }
+}
+
+fn bench_xdr_assembly(c: &mut Criterion) {
+    c.bench_function("work_envelope_xdr", |b| {
+        b.iter_batched(
+            work_transaction,
+            |tx| {
+                TransactionEnvelope::Tx(TransactionV1Envelope {
+                    tx,
+                    signatures: Default::default(),
+                })
+                .to_xdr_base64(Limits::none())
+                .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+/// A Pail entry as read from the ledger: {sequence, gap, stake, zeros}
+fn pail_entry_xdr() -> String {
+    let pail = args()
+        .map([
+            (symbol("gap"), ScVal::U32(12)),
+            (symbol("sequence"), ScVal::U32(1_000_000)),
+            (symbol("stake"), ScVal::from(10_000_000i128)),
+            (symbol("zeros"), ScVal::U32(7)),
+        ])
+        .build()
+        .unwrap()
+        .remove(0);
+    let key = args()
+        .symbol("Pail")
+        .address(farmer_address())
+        .u32(1000)
+        .build_vec()
+        .unwrap();
+
+    LedgerEntryData::ContractData(ContractDataEntry {
+        ext: ExtensionPoint::V0,
+        contract: ScAddress::Contract(Hash([1; 32])),
+        key,
+        durability: ContractDataDurability::Temporary,
+        val: pail,
+    })
+    .to_xdr_base64(Limits::none())
+    .unwrap()
+}
+
+fn bench_pail_parsing(c: &mut Criterion) {
+    let entry_xdr = pail_entry_xdr();
+    assert_eq!(parse_pail_zeros(&entry_xdr).unwrap(), Some(7));
+
+    c.bench_function("parse_pail_zeros", |b| {
+        b.iter(|| parse_pail_zeros(black_box(&entry_xdr)).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_work_hash,
+    bench_xdr_assembly,
+    bench_pail_parsing
+);
+criterion_main!(benches);
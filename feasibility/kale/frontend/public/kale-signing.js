@@ -0,0 +1,222 @@
+/**
+ * KALE signing helper
+ *
+ * Wraps the prepare -> sign -> submit flow of the KALE farm server so third-party
+ * pages can integrate with a single script tag:
+ *
+ *   <script src="http://localhost:3737/static/kale-signing.js"></script>
+ *   <script>
+ *     const { hash } = await KaleSigning.plant({ publicKey, amount: '0' });
+ *   </script>
+ *
+ * Signing uses Albedo (`window.albedo`, from @albedo-link/intent) or Freighter
+ * (`window.freighterApi`, from @stellar/freighter-api), whichever is available,
+ * unless a wallet is requested explicitly with `wallet: 'albedo' | 'freighter'`.
+ *
+ * Smart wallet farmers (C... addresses signed with a passkey) pass `passkey: { keyId }`
+ * (the hex credential ID) and a `feeSource` in the request: the passkey authorizes the
+ * call through WebAuthn, then the wallet signs as the fee source.
+ *
+ * Every failure is thrown as a KaleSigningError with a `stage` of
+ * 'prepare', 'sign' or 'submit'.
+ */
+(function (global) {
+  'use strict';
+
+  // Default to the server this script was loaded from
+  var currentScript = document.currentScript;
+  var DEFAULT_SERVER_URL = currentScript ? new URL(currentScript.src).origin : '';
+
+  function KaleSigningError(stage, message, cause) {
+    this.name = 'KaleSigningError';
+    this.stage = stage;
+    this.message = message;
+    this.cause = cause;
+    this.stack = new Error(message).stack;
+  }
+  KaleSigningError.prototype = Object.create(Error.prototype);
+  KaleSigningError.prototype.constructor = KaleSigningError;
+
+  function errorMessage(error) {
+    if (!error) return 'Unknown error';
+    if (typeof error === 'string') return error;
+    if (error.message) return error.message;
+    if (error.error) return errorMessage(error.error);
+    return JSON.stringify(error);
+  }
+
+  async function postJson(serverUrl, path, body, stage) {
+    var response;
+    try {
+      response = await fetch(serverUrl + path, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body),
+      });
+    } catch (error) {
+      throw new KaleSigningError(stage, 'Could not reach the KALE server: ' + errorMessage(error), error);
+    }
+
+    var data;
+    try {
+      data = await response.json();
+    } catch (error) {
+      throw new KaleSigningError(stage, 'Invalid response from the KALE server', error);
+    }
+
+    if (!response.ok) {
+      throw new KaleSigningError(stage, data.error || 'Request to ' + path + ' failed', data);
+    }
+
+    return data;
+  }
+
+  function detectWallet(requested) {
+    if (requested) return requested;
+    if (global.albedo) return 'albedo';
+    if (global.freighterApi) return 'freighter';
+    throw new KaleSigningError('sign', 'No supported wallet found (load Albedo or Freighter)');
+  }
+
+  async function signXdr(wallet, xdr, network) {
+    try {
+      if (wallet === 'albedo') {
+        if (!global.albedo) throw new Error('Albedo is not loaded');
+        var albedoResult = await global.albedo.tx({ xdr: xdr, network: network, submit: false });
+        return albedoResult.signed_envelope_xdr;
+      }
+
+      if (wallet === 'freighter') {
+        if (!global.freighterApi) throw new Error('Freighter is not loaded');
+        var freighterResult = await global.freighterApi.signTransaction(xdr, {
+          networkPassphrase: network,
+        });
+        // Freighter v2+ returns an object, older versions the XDR string itself
+        if (typeof freighterResult === 'string') return freighterResult;
+        if (freighterResult.error) throw freighterResult.error;
+        return freighterResult.signedTxXdr;
+      }
+    } catch (error) {
+      throw new KaleSigningError('sign', 'Signing was rejected or failed: ' + errorMessage(error), error);
+    }
+
+    throw new KaleSigningError('sign', 'Unsupported wallet: ' + wallet);
+  }
+
+  function toHex(buffer) {
+    return Array.prototype.map
+      .call(new Uint8Array(buffer), function (byte) {
+        return ('0' + byte.toString(16)).slice(-2);
+      })
+      .join('');
+  }
+
+  function fromHex(hex) {
+    var bytes = new Uint8Array(hex.length / 2);
+    for (var i = 0; i < bytes.length; i++) {
+      bytes[i] = parseInt(hex.substr(i * 2, 2), 16);
+    }
+    return bytes;
+  }
+
+  // Have the passkey sign the smart wallet's authorization, returning the updated XDR
+  async function signWithPasskey(serverUrl, prepared, wallet, passkey) {
+    var challenge = await postJson(
+      serverUrl,
+      '/api/passkey/challenge',
+      { xdr: prepared.xdr, wallet: wallet },
+      'sign'
+    );
+
+    var credential;
+    try {
+      credential = await navigator.credentials.get({
+        publicKey: {
+          challenge: fromHex(challenge.challenge),
+          allowCredentials: passkey.keyId ? [{ type: 'public-key', id: fromHex(passkey.keyId) }] : [],
+          userVerification: 'preferred',
+        },
+      });
+    } catch (error) {
+      throw new KaleSigningError('sign', 'Passkey signing was rejected or failed: ' + errorMessage(error), error);
+    }
+
+    var attached = await postJson(
+      serverUrl,
+      '/api/passkey/attach',
+      {
+        xdr: prepared.xdr,
+        wallet: wallet,
+        keyId: toHex(credential.rawId),
+        authenticatorData: toHex(credential.response.authenticatorData),
+        clientDataJson: toHex(credential.response.clientDataJSON),
+        signature: toHex(credential.response.signature),
+      },
+      'sign'
+    );
+    return attached.xdr;
+  }
+
+  /**
+   * Run a full prepare -> sign -> submit flow for one action
+   *
+   * @param {string} action - 'plant', 'work', 'harvest' or 'trustline'
+   * @param {object} body - The prepare request body (e.g. { publicKey, amount })
+   * @param {object} [options] - { serverUrl, wallet, passkey, onStage(stage) }
+   * @returns {Promise<{hash: string}>}
+   */
+  async function run(action, body, options) {
+    options = options || {};
+    var serverUrl = options.serverUrl || DEFAULT_SERVER_URL;
+    var onStage = options.onStage || function () {};
+
+    onStage('prepare');
+    var prepared = await postJson(serverUrl, '/api/' + action + '/prepare', body, 'prepare');
+
+    onStage('sign');
+    var xdr = prepared.xdr;
+    if (options.passkey) {
+      xdr = await signWithPasskey(serverUrl, prepared, body.publicKey, options.passkey);
+    }
+    var signedXdr = await signXdr(detectWallet(options.wallet), xdr, prepared.network);
+
+    onStage('submit');
+    var submitted = await postJson(serverUrl, '/api/' + action + '/submit', { signedXdr: signedXdr }, 'submit');
+
+    return { hash: submitted.hash };
+  }
+
+  global.KaleSigning = {
+    KaleSigningError: KaleSigningError,
+    run: run,
+    plant: function (params, options) {
+      return run(
+        'plant',
+        { publicKey: params.publicKey, amount: String(params.amount || '0'), feeSource: params.feeSource },
+        options
+      );
+    },
+    work: function (params, options) {
+      return run(
+        'work',
+        {
+          publicKey: params.publicKey,
+          nonce: String(params.nonce),
+          blockIndex: params.blockIndex,
+          feeSource: params.feeSource,
+        },
+        options
+      );
+    },
+    harvest: function (params, options) {
+      return run(
+        'harvest',
+        { publicKey: params.publicKey, blockIndex: params.blockIndex, feeSource: params.feeSource },
+        options
+      );
+    },
+    trustline: function (params, options) {
+      return run('trustline', { publicKey: params.publicKey }, options);
+    },
+  };
+})(window);
@@ -2,6 +2,7 @@ import React from "react";
 import { useAuth } from "./hooks/useAuth";
 import { useUserData } from "./hooks/useUserData";
 import { useTransactions } from "./hooks/useTransactions";
+import { useNetwork } from "./hooks/useNetwork";
 import { Header } from "./components/Header";
 import { ContractInfo } from "./components/ContractInfo";
 import { AccountSection } from "./components/AccountSection";
@@ -13,6 +14,9 @@ import { TransactionProgress } from "./components/TransactionProgress";
 import { styles } from "./styles";
 
 const App: React.FC = () => {
+  // Network the server farms on
+  const networkInfo = useNetwork();
+
   // User data hook
   const {
     accountStatus,
@@ -72,15 +76,16 @@ const App: React.FC = () => {
   return (
     <div style={styles.container}>
       <div style={styles.card}>
-        <Header />
+        <Header network={networkInfo.network} />
 
         {/* Main UI - Unified View */}
         {transactionStep === "idle" && (
           <div style={styles.unifiedContainer}>
-            <ContractInfo />
+            <ContractInfo networkInfo={networkInfo} />
 
             <AccountSection
               authState={authState}
+              explorerUrl={networkInfo.explorerUrl}
               onConnect={handleConnectWallet}
               onLogout={handleLogoutWithReset}
             />
@@ -91,6 +96,7 @@ const App: React.FC = () => {
                 <FundingSection
                   accountStatus={accountStatus}
                   fundingState={fundingState}
+                  friendbot={networkInfo.friendbot}
                   onFund={handleFundAccount}
                 />
 
@@ -150,7 +156,7 @@ const App: React.FC = () => {
           harvestState={harvestState}
           trustlineState={trustlineState}
           blockInfo={blockInfo}
-          onPlantRetry={handlePlantTransaction}
+          onPlantRetry={() => handlePlantTransaction()}
           onWorkRetry={handleWorkTransaction}
           onBackToFarm={() => setTransactionStep("idle")}
         />
@@ -4,12 +4,14 @@ import { styles } from "../styles";
 
 interface AccountSectionProps {
   authState: AuthState;
+  explorerUrl: string | null;
   onConnect: () => void;
   onLogout: () => void;
 }
 
 export const AccountSection: React.FC<AccountSectionProps> = ({
   authState,
+  explorerUrl,
   onConnect,
   onLogout,
 }) => {
@@ -20,13 +22,17 @@ export const AccountSection: React.FC<AccountSectionProps> = ({
           <>
             <div style={styles.accountInfo}>
               <span style={styles.label}>Account:</span>
-              <a
-                href={`https://stellar.expert/explorer/testnet/account/${authState.publicKey}`}
-                target="_blank"
-                style={styles.value}
-              >
-                {authState.publicKey}
-              </a>
+              {explorerUrl ? (
+                <a
+                  href={`${explorerUrl}/account/${authState.publicKey}`}
+                  target="_blank"
+                  style={styles.value}
+                >
+                  {authState.publicKey}
+                </a>
+              ) : (
+                <span style={styles.value}>{authState.publicKey}</span>
+              )}
             </div>
             <button style={styles.logoutButton} onClick={onLogout}>
               Logout
@@ -1,18 +1,28 @@
 import React from "react";
 import { styles } from "../styles";
-import { CONTRACT_ADDRESS } from "../constants";
+import { NetworkInfo } from "../types";
+
+interface ContractInfoProps {
+  networkInfo: NetworkInfo;
+}
+
+export const ContractInfo: React.FC<ContractInfoProps> = ({ networkInfo }) => {
+  const { contractId, explorerUrl } = networkInfo;
 
-export const ContractInfo: React.FC = () => {
   return (
     <div style={styles.contractRow}>
       <span style={styles.contractLabel}>Contract:</span>
-      <a
-        href={`https://stellar.expert/explorer/testnet/contract/${CONTRACT_ADDRESS}`}
-        target="_blank"
-        style={styles.contractHash}
-      >
-        {CONTRACT_ADDRESS}
-      </a>
+      {explorerUrl ? (
+        <a
+          href={`${explorerUrl}/contract/${contractId}`}
+          target="_blank"
+          style={styles.contractHash}
+        >
+          {contractId}
+        </a>
+      ) : (
+        <span style={styles.contractHash}>{contractId}</span>
+      )}
     </div>
   );
 };
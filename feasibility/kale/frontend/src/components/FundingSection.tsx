@@ -6,12 +6,14 @@ import { MIN_XLM_BALANCE } from '../constants';
 interface FundingSectionProps {
   accountStatus: AccountStatus;
   fundingState: FundingState;
+  friendbot: boolean; // false on mainnet
   onFund: () => void;
 }
 
 export const FundingSection: React.FC<FundingSectionProps> = ({
   accountStatus,
   fundingState,
+  friendbot,
   onFund,
 }) => {
   // Hide section if account is funded or was just successfully funded
@@ -23,23 +25,32 @@ export const FundingSection: React.FC<FundingSectionProps> = ({
   return (
     <div style={styles.actionSection}>
       <h3 style={styles.sectionTitle}>Fund Account</h3>
-      {!accountStatus.exists ? (
+      {!friendbot ? (
+        <p style={styles.hint}>
+          {accountStatus.exists
+            ? `Balance: ${(accountStatus.xlmBalance / 10000000).toFixed(2)} XLM. `
+            : "Account doesn't exist yet. "}
+          Send at least 50 XLM to this account to get started.
+        </p>
+      ) : !accountStatus.exists ? (
         <p style={styles.hint}>Account doesn't exist yet. Fund it with Friendbot to get started.</p>
       ) : (
         <p style={styles.hint}>
           Balance: {(accountStatus.xlmBalance / 10000000).toFixed(2)} XLM (need at least 50 XLM)
         </p>
       )}
-      <button
-        style={{
-          ...styles.button,
-          ...(fundingState.status === 'funding' ? styles.buttonDisabled : {}),
-        }}
-        onClick={onFund}
-        disabled={fundingState.status === 'funding'}
-      >
-        {fundingState.status === 'funding' ? 'Funding...' : 'Fund with Friendbot'}
-      </button>
+      {friendbot && (
+        <button
+          style={{
+            ...styles.button,
+            ...(fundingState.status === 'funding' ? styles.buttonDisabled : {}),
+          }}
+          onClick={onFund}
+          disabled={fundingState.status === 'funding'}
+        >
+          {fundingState.status === 'funding' ? 'Funding...' : 'Fund with Friendbot'}
+        </button>
+      )}
       {fundingState.status === 'error' && (
         <div style={styles.errorBanner}>
           {fundingState.error}
@@ -1,11 +1,15 @@
 import React from 'react';
 import { styles } from '../styles';
 
-export const Header: React.FC = () => {
+interface HeaderProps {
+  network: string;
+}
+
+export const Header: React.FC<HeaderProps> = ({ network }) => {
   return (
     <div style={styles.titleRow}>
       <h1 style={styles.title}>KALE Farming</h1>
-      <div style={styles.testnetBadge}>testnet</div>
+      <div style={styles.testnetBadge}>{network}</div>
     </div>
   );
 };
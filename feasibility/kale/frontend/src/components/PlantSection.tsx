@@ -1,14 +1,16 @@
-import React from "react";
+import React, { useState } from "react";
 import { AccountStatus, PailData, PlantState } from "../types";
 import { styles } from "../styles";
 import { MIN_XLM_BALANCE } from "../constants";
+import { formatKale, parseKale } from "../utils/amount";
 
 interface PlantSectionProps {
   accountStatus: AccountStatus | null;
   currentPailData: PailData | null;
   publicKey?: string;
   plantState: PlantState;
-  onPlant: () => void;
+  /** Called with the stake in stroops */
+  onPlant: (amount: string) => void;
 }
 
 export const PlantSection: React.FC<PlantSectionProps> = ({
@@ -31,22 +33,63 @@ export const PlantSection: React.FC<PlantSectionProps> = ({
     plantState.status === "signing" ||
     plantState.status === "submitting";
 
+  // The server checks the stake against the balance too, but catching it here saves a round trip
+  const [stake, setStake] = useState("0");
+  const stakeStroops = parseKale(stake);
+  const maxStakeable = accountStatus?.kaleBalance ?? 0;
+  const stakeError =
+    stakeStroops === null
+      ? "Enter a KALE amount of 0 or more, with up to 7 decimals"
+      : Number(stakeStroops) > maxStakeable
+      ? `You can stake at most ${formatKale(maxStakeable)} KALE`
+      : null;
+  const plantDisabled =
+    currentPailData?.hasPail ||
+    !isReadyToPlant() ||
+    isPlantProcessing ||
+    stakeError !== null;
+
   return (
     <div style={styles.actionSection}>
       {currentPailData?.hasPail ? (
         <div style={styles.statusMessage}>✓ Kale planted in current field!</div>
       ) : null}
+      {accountStatus?.hasTrustline && (
+        <p style={styles.hint}>
+          KALE balance: {(accountStatus.kaleBalance / 10000000).toFixed(2)} KALE
+        </p>
+      )}
+      {!currentPailData?.hasPail && accountStatus?.hasTrustline && (
+        <p style={styles.hint}>
+          Stake:{" "}
+          <input
+            type="text"
+            inputMode="decimal"
+            value={stake}
+            onChange={(e) => setStake(e.target.value)}
+            disabled={isPlantProcessing}
+            style={{ width: "120px" }}
+          />{" "}
+          KALE{" "}
+          <button
+            type="button"
+            onClick={() => setStake(formatKale(maxStakeable))}
+            disabled={isPlantProcessing}
+          >
+            Max
+          </button>
+        </p>
+      )}
+      {!currentPailData?.hasPail && stakeError && (
+        <p style={styles.hint}>{stakeError}</p>
+      )}
       <button
         style={{
           ...styles.button,
-          ...(currentPailData?.hasPail || !isReadyToPlant() || isPlantProcessing
-            ? styles.buttonDisabled
-            : {}),
+          ...(plantDisabled ? styles.buttonDisabled : {}),
         }}
-        onClick={onPlant}
-        disabled={
-          currentPailData?.hasPail || !isReadyToPlant() || isPlantProcessing
-        }
+        onClick={() => stakeStroops !== null && onPlant(stakeStroops)}
+        disabled={plantDisabled}
       >
         {isPlantProcessing ? "Planting..." : "Plant KALE seed!"}
       </button>
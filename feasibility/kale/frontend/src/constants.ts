@@ -1,5 +1,16 @@
+import { NetworkInfo } from './types';
+
 export const STORAGE_KEY = 'kale_wallet_pubkey';
 export const CONTRACT_ADDRESS = 'CDSWUUXGPWDZG76ISK6SUCVPZJMD5YUV66J2FXFXFGDX25XKZJIEITAO';
 export const KALE_ISSUER = 'GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB';
+// Shown until /api/network answers; the server's default network
+export const DEFAULT_NETWORK: NetworkInfo = {
+  network: 'testnet',
+  passphrase: 'Test SDF Network ; September 2015',
+  contractId: CONTRACT_ADDRESS,
+  kaleIssuer: KALE_ISSUER,
+  explorerUrl: 'https://stellar.expert/explorer/testnet',
+  friendbot: true,
+};
 export const MIN_XLM_BALANCE = 50 * 10000000; // 50 XLM in stroops
 export const MINING_DURATION = 1000; // 1 second in milliseconds
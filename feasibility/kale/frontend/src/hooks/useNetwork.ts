@@ -0,0 +1,21 @@
+import { useState, useEffect } from 'react';
+import { NetworkInfo } from '../types';
+import { DEFAULT_NETWORK } from '../constants';
+
+// The network the server farms on, for the badge, explorer links and funding options
+export const useNetwork = () => {
+  const [networkInfo, setNetworkInfo] = useState<NetworkInfo>(DEFAULT_NETWORK);
+
+  useEffect(() => {
+    fetch('/api/network')
+      .then((response) => (response.ok ? response.json() : null))
+      .then((info: NetworkInfo | null) => {
+        if (info) {
+          setNetworkInfo(info);
+        }
+      })
+      .catch((error) => console.error('Failed to load network info:', error));
+  }, []);
+
+  return networkInfo;
+};
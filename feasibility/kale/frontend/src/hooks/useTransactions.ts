@@ -1,4 +1,4 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, useRef } from 'react';
 import albedo from '@albedo-link/intent';
 import {
   TransactionStep,
@@ -48,6 +48,9 @@ export const useTransactions = ({
 }: UseTransactionsProps) => {
   const [transactionStep, setTransactionStep] = useState<TransactionStep>('idle');
   const [plantState, setPlantState] = useState<PlantState>({ status: 'idle' });
+  // Stake in stroops of the last plant attempt, reused on retry. 0 KALE by default - the
+  // contract accepts 0 to allow participation without initial tokens
+  const plantAmount = useRef('0');
   const [workState, setWorkState] = useState<WorkState>({ status: 'idle' });
   const [harvestState, setHarvestState] = useState<HarvestState>({ status: 'idle' });
   const [fundingState, setFundingState] = useState<FundingState>({ status: 'idle' });
@@ -66,27 +69,69 @@ export const useTransactions = ({
     }
   }, [currentPailData, plantState.status, workState.status]);
 
-  const handlePlantTransaction = async () => {
+  // Archived contract entries must be restored before a transaction touching them can run;
+  // the prepare endpoints then hand back a restore transaction instead
+  const submitRestore = async (prepareData: { xdr: string; network: string }) => {
+    const signResponse = await albedo.tx({
+      xdr: prepareData.xdr,
+      network: prepareData.network,
+      submit: false,
+    });
+
+    const restoreResponse = await fetch('/api/restore/submit', {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json',
+      },
+      body: JSON.stringify({
+        signedXdr: signResponse.signed_envelope_xdr,
+      }),
+    });
+
+    const restoreData = await restoreResponse.json();
+
+    if (!restoreResponse.ok || restoreData.status === 'FAILED') {
+      throw new Error(restoreData.error || 'Failed to restore archived contract data');
+    }
+  };
+
+  // POST to a prepare endpoint, restoring archived entries first if the server asks for it
+  const prepare = async (path: string, body: object) => {
+    const request = () =>
+      fetch(path, {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(body),
+      });
+
+    let prepareResponse = await request();
+    let prepareData = await prepareResponse.json();
+
+    if (prepareResponse.ok && prepareData.restoreRequired) {
+      await submitRestore(prepareData);
+      prepareResponse = await request();
+      prepareData = await prepareResponse.json();
+    }
+
+    return { prepareResponse, prepareData };
+  };
+
+  const handlePlantTransaction = async (amount?: string) => {
     if (!publicKey) return;
+    if (amount !== undefined) plantAmount.current = amount;
 
     setIsTransacting?.(true);
     setPlantState({ status: 'preparing' });
 
     try {
       // Request the prepared transaction from backend
-      const prepareResponse = await fetch('/api/plant/prepare', {
-        method: 'POST',
-        headers: {
-          'Content-Type': 'application/json',
-        },
-        body: JSON.stringify({
-          publicKey: publicKey,
-          amount: '0', // 0 KALE - contract accepts 0 to allow participation without initial tokens
-        }),
+      const { prepareResponse, prepareData } = await prepare('/api/plant/prepare', {
+        publicKey: publicKey,
+        amount: plantAmount.current,
       });
 
-      const prepareData = await prepareResponse.json();
-
       if (!prepareResponse.ok) {
         throw new Error(prepareData.error || 'Failed to prepare transaction');
       }
@@ -132,6 +177,10 @@ export const useTransactions = ({
         throw new Error(submitData.error || 'Failed to submit transaction');
       }
 
+      if (submitData.status === 'FAILED') {
+        throw new Error(`Transaction failed on-chain (${submitData.hash})`);
+      }
+
       setPlantState({ status: 'success', txHash: submitData.hash });
       onPlantSuccess?.();
 
@@ -225,18 +274,17 @@ export const useTransactions = ({
       // Prepare the work transaction
       setWorkState(prev => ({ ...prev, status: 'preparing' }));
 
-      const prepareResponse = await fetch('/api/work/prepare', {
-        method: 'POST',
-        headers: {
-          'Content-Type': 'application/json',
-        },
-        body: JSON.stringify({
-          publicKey: publicKey,
-          nonce: bestNonce.toString(),
-        }),
+      const { prepareResponse, prepareData } = await prepare('/api/work/prepare', {
+        publicKey: publicKey,
+        nonce: bestNonce.toString(),
+        blockIndex: blockInfo.blockIndex,
       });
 
-      const prepareData = await prepareResponse.json();
+      // The server found a better entry on-chain than our local pail data knew about
+      if (prepareData.code === 'WorkAlreadyBetter') {
+        setWorkState({ status: 'failed_to_improve', bestZeros });
+        return;
+      }
 
       if (!prepareResponse.ok) {
         throw new Error(prepareData.error || 'Failed to prepare transaction');
@@ -274,6 +322,10 @@ export const useTransactions = ({
         throw new Error(submitData.error || 'Failed to submit transaction');
       }
 
+      if (submitData.status === 'FAILED') {
+        throw new Error(`Transaction failed on-chain (${submitData.hash})`);
+      }
+
       setWorkState({ status: 'success', txHash: submitData.hash, bestZeros });
       onWorkSuccess?.();
 
@@ -402,6 +454,10 @@ export const useTransactions = ({
         throw new Error(submitData.error || 'Failed to submit trustline transaction');
       }
 
+      if (submitData.status === 'FAILED') {
+        throw new Error(`Transaction failed on-chain (${submitData.hash})`);
+      }
+
       setTrustlineState({ status: 'success', txHash: submitData.hash });
       onTrustlineSuccess?.();
 
@@ -437,19 +493,11 @@ export const useTransactions = ({
 
     try {
       // Prepare the harvest transaction
-      const prepareResponse = await fetch('/api/harvest/prepare', {
-        method: 'POST',
-        headers: {
-          'Content-Type': 'application/json',
-        },
-        body: JSON.stringify({
-          publicKey: publicKey,
-          blockIndex: blockIndex,
-        }),
+      const { prepareResponse, prepareData } = await prepare('/api/harvest/prepare', {
+        publicKey: publicKey,
+        blockIndex: blockIndex,
       });
 
-      const prepareData = await prepareResponse.json();
-
       if (!prepareResponse.ok) {
         throw new Error(prepareData.error || 'Failed to prepare transaction');
       }
@@ -486,6 +534,10 @@ export const useTransactions = ({
         throw new Error(submitData.error || 'Failed to submit transaction');
       }
 
+      if (submitData.status === 'FAILED') {
+        throw new Error(`Transaction failed on-chain (${submitData.hash})`);
+      }
+
       setHarvestState({ status: 'success', txHash: submitData.hash });
       onHarvestSuccess?.(blockIndex);
 
@@ -1,4 +1,4 @@
-import { useState, useEffect, useCallback } from 'react';
+import { useState, useEffect, useCallback, useRef } from 'react';
 import { AccountStatus, BlockInfo, PailData, FieldData, FarmerPailData } from '../types';
 
 const KNOWN_FARMERS_KEY = 'kale_known_farmers';
@@ -35,6 +35,7 @@ export const useUserData = () => {
   const [publicKey, setPublicKey] = useState<string | null>(null);
   const [knownFarmers, setKnownFarmers] = useState<Set<string>>(loadKnownFarmers());
   const [isTransacting, setIsTransacting] = useState<boolean>(false);
+  const [liveConnected, setLiveConnected] = useState<boolean>(false);
 
   // Helper function to check if fields data has actually changed
   const areFieldsEqual = (fields1: FieldData[], fields2: FieldData[]): boolean => {
@@ -243,16 +244,58 @@ export const useUserData = () => {
     setPublicKey(null);
   };
 
-  // Polling effect - refetch fields data every 5 seconds (paused during transactions)
+  // Keep the latest loader for the WebSocket handler without reconnecting on every change
+  const loadFieldsDataRef = useRef(loadFieldsData);
+  loadFieldsDataRef.current = loadFieldsData;
+
+  // Live updates - the server pushes block changes, confirmations and balances
+  useEffect(() => {
+    if (!publicKey) return;
+
+    const protocol = window.location.protocol === 'https:' ? 'wss' : 'ws';
+    const socket = new WebSocket(
+      `${protocol}://${window.location.host}/ws?publicKey=${encodeURIComponent(publicKey)}`
+    );
+
+    socket.onopen = () => setLiveConnected(true);
+    socket.onclose = () => setLiveConnected(false);
+    socket.onmessage = (message) => {
+      const event = JSON.parse(message.data);
+      switch (event.type) {
+        case 'block':
+          loadFieldsDataRef.current(publicKey);
+          break;
+        case 'receipt':
+          if (event.kind === 'plant' && event.status === 'SUCCESS') {
+            setHasPlanted(true);
+          }
+          loadFieldsDataRef.current(publicKey);
+          break;
+        case 'account':
+          setAccountStatus({
+            exists: event.exists,
+            xlmBalance: event.xlmBalance,
+            hasTrustline: event.hasTrustline,
+            kaleBalance: event.kaleBalance,
+          });
+          break;
+      }
+    };
+
+    return () => socket.close();
+  }, [publicKey]);
+
+  // Polling effect - refetch fields data every 5 seconds while the WebSocket is down
+  // (paused during transactions)
   useEffect(() => {
-    if (!publicKey || isTransacting) return;
+    if (!publicKey || isTransacting || liveConnected) return;
 
     const intervalId = setInterval(() => {
       loadFieldsData(publicKey);
     }, 5000);
 
     return () => clearInterval(intervalId);
-  }, [publicKey, knownFarmers, loadFieldsData, isTransacting]);
+  }, [publicKey, knownFarmers, loadFieldsData, isTransacting, liveConnected]);
 
   return {
     accountStatus,
@@ -30,6 +30,16 @@ export interface AccountStatus {
   exists: boolean;
   xlmBalance: number; // in stroops
   hasTrustline: boolean;
+  kaleBalance: number; // in stroops
+}
+
+export interface NetworkInfo {
+  network: string; // testnet, mainnet, futurenet or local
+  passphrase: string;
+  contractId: string;
+  kaleIssuer: string;
+  explorerUrl: string | null; // append /tx/, /account/ or /contract/ paths
+  friendbot: boolean; // whether /api/fund_account works on this network
 }
 
 export interface FundingState {
@@ -0,0 +1,23 @@
+/** Stroops per KALE (7 decimal places, like other Stellar assets) */
+export const STROOPS_PER_KALE = 10000000;
+
+/**
+ * Parse a KALE amount typed by the farmer into stroops, as the decimal string the API expects
+ * Returns null unless it is a non-negative number with at most 7 decimal places
+ */
+export const parseKale = (input: string): string | null => {
+  const match = /^(\d*)(?:\.(\d{0,7}))?$/.exec(input.trim());
+  if (!match || (match[1] === '' && !match[2])) return null;
+  const whole = match[1] || '0';
+  const fraction = (match[2] || '').padEnd(7, '0');
+  return (whole + fraction).replace(/^0+(?=\d)/, '');
+};
+
+/**
+ * Format stroops as KALE without trailing zeros, e.g. 12500000 -> "1.25"
+ */
+export const formatKale = (stroops: number): string => {
+  const whole = Math.floor(stroops / STROOPS_PER_KALE);
+  const fraction = String(stroops % STROOPS_PER_KALE).padStart(7, '0').replace(/0+$/, '');
+  return fraction ? `${whole}.${fraction}` : String(whole);
+};
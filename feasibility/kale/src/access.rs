@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use axum::http::HeaderMap;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// An IP network in CIDR notation, e.g. "10.0.0.0/8" or "2001:db8::/32"
+///
+/// A bare address is treated as a single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Whether the address falls inside this network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            // IPv4-mapped IPv6 peers (::ffff:a.b.c.d) should match IPv4 ranges
+            (IpAddr::V4(_), IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+                Some(ip) => self.contains(IpAddr::V4(ip)),
+                None => false,
+            },
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let network: IpAddr = address
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid IP address in '{}'", s))?;
+        let max_prefix_len = if network.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .with_context(|| format!("Invalid prefix length in '{}'", s))?,
+            None => max_prefix_len,
+        };
+
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+/// Compare the first `prefix_len` bits of two addresses
+fn prefix_matches(network: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = (prefix_len / 8) as usize;
+    let remaining_bits = prefix_len % 8;
+
+    if network[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+
+    if remaining_bits == 0 {
+        return true;
+    }
+
+    let mask = 0xffu8 << (8 - remaining_bits);
+    network[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denial {
+    /// The client matched a denied range
+    Denied,
+    /// An allow list is configured and the client isn't on it
+    NotAllowed,
+}
+
+/// Which clients may call protected endpoints, and how to identify them behind a proxy
+#[derive(Debug, Clone, Default)]
+pub struct AccessPolicy {
+    /// If non-empty, only these ranges are let through
+    pub allowed: Vec<Cidr>,
+    /// Always refused, even if also allowed
+    pub denied: Vec<Cidr>,
+    /// Reverse proxies whose X-Forwarded-For header is believed
+    pub trusted_proxies: Vec<Cidr>,
+}
+
+impl AccessPolicy {
+    /// Load the policy from comma-separated CIDR lists in `KALE_ALLOWED_CIDRS`,
+    /// `KALE_DENIED_CIDRS` and `KALE_TRUSTED_PROXIES`
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            allowed: cidrs_from_env("KALE_ALLOWED_CIDRS")?,
+            denied: cidrs_from_env("KALE_DENIED_CIDRS")?,
+            trusted_proxies: cidrs_from_env("KALE_TRUSTED_PROXIES")?,
+        })
+    }
+
+    /// Determine the real client address of a request
+    ///
+    /// X-Forwarded-For is only honoured when the direct peer is a trusted proxy. The
+    /// header is walked from the right (the hop closest to us), skipping further trusted
+    /// proxies, so a client can't spoof its address by prepending entries.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted_proxy(peer) {
+            return peer;
+        }
+
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|entry| entry.trim().parse().ok())
+            .collect();
+
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted_proxy(**ip))
+            .or(forwarded.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+
+    /// Check a client address against the deny and allow lists
+    pub fn check(&self, ip: IpAddr) -> Result<(), Denial> {
+        if self.denied.iter().any(|cidr| cidr.contains(ip)) {
+            return Err(Denial::Denied);
+        }
+
+        if !self.allowed.is_empty() && !self.allowed.iter().any(|cidr| cidr.contains(ip)) {
+            return Err(Denial::NotAllowed);
+        }
+
+        Ok(())
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+fn cidrs_from_env(name: &str) -> Result<Vec<Cidr>> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| entry.parse().with_context(|| format!("Invalid {}", name)))
+            .collect(),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Running count of refused requests, by reason
+#[derive(Debug, Default)]
+pub struct DenialCounters {
+    denied: AtomicU64,
+    not_allowed: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DenialStats {
+    pub denied: u64,
+    #[serde(rename = "notAllowed")]
+    pub not_allowed: u64,
+}
+
+impl DenialCounters {
+    pub fn record(&self, denial: Denial) {
+        let counter = match denial {
+            Denial::Denied => &self.denied,
+            Denial::NotAllowed => &self.not_allowed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> DenialStats {
+        DenialStats {
+            denied: self.denied.load(Ordering::Relaxed),
+            not_allowed: self.not_allowed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip_and_check() {
+        let policy = AccessPolicy {
+            allowed: vec!["10.0.0.0/8".parse().unwrap()],
+            denied: vec!["10.6.6.0/24".parse().unwrap()],
+            trusted_proxies: vec!["127.0.0.1".parse().unwrap()],
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 10.1.2.3".parse().unwrap());
+
+        // Behind the trusted proxy, the rightmost untrusted hop is the client
+        let proxy: IpAddr = "127.0.0.1".parse().unwrap();
+        let client = policy.client_ip(proxy, &headers);
+        assert_eq!(client, "10.1.2.3".parse::<IpAddr>().unwrap());
+        assert_eq!(policy.check(client), Ok(()));
+
+        // Untrusted peers can't claim another address
+        let peer: IpAddr = "8.8.8.8".parse().unwrap();
+        assert_eq!(policy.client_ip(peer, &headers), peer);
+        assert_eq!(policy.check(peer), Err(Denial::NotAllowed));
+
+        assert_eq!(
+            policy.check("10.6.6.1".parse().unwrap()),
+            Err(Denial::Denied)
+        );
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    }
+}
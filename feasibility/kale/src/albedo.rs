@@ -1,19 +1,68 @@
 use anyhow::{anyhow, Result};
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    body::Body,
+    extract::{ws::WebSocketUpgrade, ConnectInfo, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
+use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
 
-use crate::contracts::kale::Kale;
-
-const SERVER_PORT: u16 = 3737;
+use galacticplayground::config::{Config, ConfigSource};
+use galacticplayground::contracts::changes::{self, FarmSnapshot};
+use galacticplayground::contracts::kale::{
+    Block, BlockReport, DifficultyEstimate, FarmError, FarmInvocation, Kale, KaleError,
+    ParticipationProof, PrepareTimings, PreparedTransaction, UnharvestedBlock,
+};
+use galacticplayground::diagnostics::TransactionDiagnostics;
+use galacticplayground::network::{FeeBreakdown, NetworkLimits};
+use galacticplayground::passkey::{self, PasskeyAssertion};
+use galacticplayground::payout::{self, Payout, WorkerContribution};
+use galacticplayground::rpc::{RpcError, SorobanRpc, SubmittedTransaction, TransactionStatus};
+use galacticplayground::watcher::BlockWatcher;
+
+use crate::access::{AccessPolicy, DenialCounters, DenialStats};
+use crate::alerts::{AlertTracker, Balances};
+use crate::congestion::{self, CongestionMonitor, SubmitAdvice, Urgency};
+use crate::dashboard::{self, DashboardMetrics, FarmerMetrics};
+use crate::fallback_ui::{self, FRONTEND_DIR};
+use crate::farmer::MiningReport;
+use crate::fleet::{self, Fleet, FleetKeyStatus};
+use crate::i18n::{self, Locale};
+use crate::idempotency::{
+    self, Claim, IdempotencyStore, StoredResponse, IDEMPOTENCY_KEY_HEADER,
+    IDEMPOTENT_REPLAYED_HEADER,
+};
+use crate::limits::{LimitExceeded, LimitTracker};
+use crate::live::{LiveEvent, LiveUpdates};
+use crate::maintenance::{Maintenance, MaintenanceStatus};
+use crate::notify::{Email, Notifier};
+use crate::ownership::KeyChallenges;
+use crate::pause::{Pauses, Subsystem};
+use crate::preferences::{NotificationKind, PreferenceStore, Preferences};
+use crate::price::{Price, PriceFeed};
+use crate::signing::{self, ReportError, SigningJob, SigningJobs, SigningState};
+use crate::systemd;
+
+/// How long startup waits for cache priming before accepting requests anyway
+const PRIME_TIMEOUT_SECS: u64 = 10;
+
+/// How often the server checks for a new block, and for farm changes while WebSocket
+/// clients are connected
+const BLOCK_WATCH_INTERVAL_SECS: u64 = 5;
+
+/// Blocks `/api/harvestable` checks by default (two hours of 5-minute blocks), and at most
+/// (a day, about as long as pails stay in temporary storage)
+const DEFAULT_HARVEST_LOOKBACK: u32 = 24;
+const MAX_HARVEST_LOOKBACK: u32 = 288;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PubkeyResponse {
@@ -26,12 +75,25 @@ pub struct PlantPrepareRequest {
     #[serde(rename = "publicKey")]
     pub public_key: String,
     pub amount: String,
+    #[serde(rename = "feeSource", default)]
+    pub fee_source: Option<String>, // account paying the fee, if not the farmer
 }
 
 #[derive(Debug, Serialize)]
 pub struct PlantPrepareResponse {
     pub xdr: String,
     pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<PrepareTimings>, // only with the debug header
+    /// The XDR is a RestoreFootprint transaction: submit it via /api/restore/submit, then
+    /// prepare again
+    #[serde(rename = "restoreRequired")]
+    pub restore_required: bool,
+    /// External signers report progress on this job at `signingStatusUrl`
+    #[serde(rename = "signingJob")]
+    pub signing_job: String,
+    #[serde(rename = "signingStatusUrl")]
+    pub signing_status_url: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,13 +103,59 @@ pub struct PlantSubmitRequest {
 }
 
 #[derive(Debug, Serialize)]
-pub struct PlantSubmitResponse {
-    pub hash: String,
+pub struct ErrorResponse {
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>, // machine-readable error kind
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ErrorResponse {
+    /// Creates an error response with just a message
+    pub fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            code: None,
+            details: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub error: String,
+pub struct ReloadResponse {
+    /// The RPC endpoint calls now go to, redacted
+    pub endpoint: String,
+    /// Settings the config file changes that only apply after a restart
+    #[serde(rename = "restartRequired")]
+    pub restart_required: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FleetStatusResponse {
+    /// No checked key has a problem
+    pub healthy: bool,
+    pub keys: Vec<FleetKeyStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PauseRequest {
+    pub subsystem: Subsystem,
+    pub paused: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PauseStatusResponse {
+    /// Subsystems paused now; everything else runs
+    pub paused: Vec<Subsystem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServerInfoResponse {
+    pub version: &'static str,
+    pub network: &'static str,
+    /// Whether maintenance mode is on, its banner, and any scheduled maintenance
+    pub maintenance: MaintenanceStatus,
 }
 
 #[derive(Debug, Deserialize)]
@@ -67,6 +175,82 @@ pub struct BlockInfoResponse {
     pub block_index: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub entropy: Option<String>, // hex-encoded, None if nobody has planted yet
+    #[serde(rename = "predictedCloseAt", skip_serializing_if = "Option::is_none")]
+    pub predicted_close_at: Option<u64>, // unix seconds
+    #[serde(rename = "predictedCloseLow", skip_serializing_if = "Option::is_none")]
+    pub predicted_close_low: Option<u64>,
+    #[serde(rename = "predictedCloseHigh", skip_serializing_if = "Option::is_none")]
+    pub predicted_close_high: Option<u64>,
+    /// Last ledger the block info changed in; pass it back as `?since=` to poll for changes
+    pub ledger: u32,
+}
+
+/// Any block's Block entry; stakes are decimal strings of stroops
+#[derive(Debug, Serialize)]
+pub struct BlockResponse {
+    #[serde(rename = "blockIndex")]
+    pub block_index: u32,
+    pub timestamp: u64,  // unix seconds
+    pub entropy: String, // hex-encoded
+    #[serde(rename = "minGap")]
+    pub min_gap: u32,
+    #[serde(rename = "maxGap")]
+    pub max_gap: u32,
+    #[serde(rename = "minStake")]
+    pub min_stake: String,
+    #[serde(rename = "maxStake")]
+    pub max_stake: String,
+    #[serde(rename = "minZeros")]
+    pub min_zeros: u32,
+    #[serde(rename = "maxZeros")]
+    pub max_zeros: u32,
+    #[serde(rename = "stakedTotal")]
+    pub staked_total: String,
+    #[serde(rename = "normalizedTotal")]
+    pub normalized_total: String,
+}
+
+/// The KALE contract's instance storage, for debugging FarmIndex and FarmBlock lookups
+#[derive(Debug, Serialize)]
+pub struct ContractStateResponse {
+    #[serde(rename = "contractId")]
+    pub contract_id: String,
+    /// Last ledger the contract instance changed in
+    #[serde(rename = "lastModifiedLedger")]
+    pub last_modified_ledger: u32,
+    /// Values by key name, e.g. "FarmIndex"
+    pub storage: serde_json::Value,
+}
+
+impl BlockResponse {
+    fn new(block_index: u32, block: Block) -> Self {
+        Self {
+            block_index,
+            timestamp: block.timestamp,
+            entropy: hex::encode(block.entropy),
+            min_gap: block.min_gap,
+            max_gap: block.max_gap,
+            min_stake: block.min_stake.to_string(),
+            max_stake: block.max_stake.to_string(),
+            min_zeros: block.min_zeros,
+            max_zeros: block.max_zeros,
+            staked_total: block.staked_total.to_string(),
+            normalized_total: block.normalized_total.to_string(),
+        }
+    }
+}
+
+/// `?since=ledgerSeq` on polled endpoints: answer 204 if nothing changed after that ledger
+#[derive(Debug, Deserialize)]
+pub struct SinceQuery {
+    pub since: Option<u32>,
+}
+
+impl SinceQuery {
+    /// Whether the client already has the state last changed in `changed_ledger`
+    fn is_current(&self, changed_ledger: Option<u32>) -> bool {
+        matches!((self.since, changed_ledger), (Some(since), Some(changed)) if since >= changed)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -74,12 +258,27 @@ pub struct WorkPrepareRequest {
     #[serde(rename = "publicKey")]
     pub public_key: String,
     pub nonce: String, // u64 as string
+    #[serde(rename = "blockIndex", default)]
+    pub block_index: Option<u32>, // block the nonce was mined for
+    #[serde(rename = "feeSource", default)]
+    pub fee_source: Option<String>, // account paying the fee, if not the farmer
 }
 
 #[derive(Debug, Serialize)]
 pub struct WorkPrepareResponse {
     pub xdr: String,
     pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<PrepareTimings>, // only with the debug header
+    /// The XDR is a RestoreFootprint transaction: submit it via /api/restore/submit, then
+    /// prepare again
+    #[serde(rename = "restoreRequired")]
+    pub restore_required: bool,
+    /// External signers report progress on this job at `signingStatusUrl`
+    #[serde(rename = "signingJob")]
+    pub signing_job: String,
+    #[serde(rename = "signingStatusUrl")]
+    pub signing_status_url: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,11 +287,6 @@ pub struct WorkSubmitRequest {
     pub signed_xdr: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct WorkSubmitResponse {
-    pub hash: String,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct PailDataRequest {
     #[serde(rename = "publicKey")]
@@ -109,6 +303,10 @@ pub struct PailDataResponse {
     pub has_worked: bool,
     #[serde(rename = "leadingZeros")]
     pub leading_zeros: u32,
+    /// Last ledger the pail changed in (0 before it exists); absent while a plant
+    /// submitted through this server is still pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -117,12 +315,25 @@ pub struct HarvestPrepareRequest {
     pub public_key: String,
     #[serde(rename = "blockIndex")]
     pub block_index: u32,
+    #[serde(rename = "feeSource", default)]
+    pub fee_source: Option<String>, // account paying the fee, if not the farmer
 }
 
 #[derive(Debug, Serialize)]
 pub struct HarvestPrepareResponse {
     pub xdr: String,
     pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<PrepareTimings>, // only with the debug header
+    /// The XDR is a RestoreFootprint transaction: submit it via /api/restore/submit, then
+    /// prepare again
+    #[serde(rename = "restoreRequired")]
+    pub restore_required: bool,
+    /// External signers report progress on this job at `signingStatusUrl`
+    #[serde(rename = "signingJob")]
+    pub signing_job: String,
+    #[serde(rename = "signingStatusUrl")]
+    pub signing_status_url: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -131,24 +342,22 @@ pub struct HarvestSubmitRequest {
     pub signed_xdr: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct HarvestSubmitResponse {
-    pub hash: String,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct AccountStatusRequest {
     #[serde(rename = "publicKey")]
     pub public_key: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AccountStatusResponse {
     pub exists: bool,
     #[serde(rename = "xlmBalance")]
     pub xlm_balance: i64, // in stroops
     #[serde(rename = "hasTrustline")]
     pub has_trustline: bool,
+    /// KALE held in the account's trustline (the balance the KALE SAC reports)
+    #[serde(rename = "kaleBalance")]
+    pub kale_balance: i64, // in stroops
 }
 
 #[derive(Debug, Deserialize)]
@@ -162,16 +371,66 @@ pub struct FundAccountResponse {
     pub success: bool,
 }
 
+/// The network the server farms on, for the frontend's badge, links and funding options
+#[derive(Debug, Serialize)]
+pub struct NetworkInfoResponse {
+    pub network: &'static str,
+    pub passphrase: String,
+    #[serde(rename = "contractId")]
+    pub contract_id: String,
+    #[serde(rename = "kaleIssuer")]
+    pub kale_issuer: String,
+    /// Block explorer base URL, None if the network has no public explorer
+    #[serde(rename = "explorerUrl")]
+    pub explorer_url: Option<&'static str>,
+    /// Whether /api/fund_account can fund accounts
+    pub friendbot: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrustlinesQuery {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+}
+
+/// A trustline as reported by Horizon; amounts are decimal strings with 7 places
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustlineInfo {
+    #[serde(rename(serialize = "assetType", deserialize = "asset_type"))]
+    pub asset_type: String,
+    /// None for liquidity pool shares
+    #[serde(rename(serialize = "assetCode", deserialize = "asset_code"))]
+    pub asset_code: Option<String>,
+    #[serde(rename(serialize = "assetIssuer", deserialize = "asset_issuer"))]
+    pub asset_issuer: Option<String>,
+    #[serde(rename(serialize = "liquidityPoolId", deserialize = "liquidity_pool_id"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liquidity_pool_id: Option<String>,
+    pub balance: String,
+    pub limit: Option<String>,
+    #[serde(rename(serialize = "isAuthorized", deserialize = "is_authorized"))]
+    pub is_authorized: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrustlinesResponse {
+    pub trustlines: Vec<TrustlineInfo>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TrustlinePrepareRequest {
     #[serde(rename = "publicKey")]
     pub public_key: String,
+    #[serde(rename = "feeSource", default)]
+    pub fee_source: Option<String>, // account paying the fee, if not the farmer
 }
 
 #[derive(Debug, Serialize)]
 pub struct TrustlinePrepareResponse {
     pub xdr: String,
     pub network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<PrepareTimings>, // only with the debug header
 }
 
 #[derive(Debug, Deserialize)]
@@ -180,9 +439,54 @@ pub struct TrustlineSubmitRequest {
     pub signed_xdr: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PreferencesQuery {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreferencesUpdateRequest {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+    /// The farmer's signature (hex) of their challenge; needed to set `email`,
+    /// `notifications` or `alerts`
+    #[serde(default)]
+    pub signature: Option<String>,
+    #[serde(flatten)]
+    pub preferences: Preferences,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreferencesChallengeRequest {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+}
+
 #[derive(Debug, Serialize)]
-pub struct TrustlineSubmitResponse {
-    pub hash: String,
+pub struct PreferencesChallengeResponse {
+    /// The message to sign with the account's key, as a SEP-53 signed message
+    pub challenge: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailVerifyRequest {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+    /// The code emailed to the address
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeeExplainRequest {
+    /// A prepared transaction, as returned by a prepare endpoint
+    pub xdr: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreSubmitRequest {
+    #[serde(rename = "signedXdr")]
+    pub signed_xdr: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -193,6 +497,31 @@ pub struct AllFarmersRequest {
     pub farmer_addresses: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct LiveQuery {
+    #[serde(rename = "publicKey")]
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockFarmersQuery {
+    #[serde(rename = "blockIndex")]
+    pub block_index: Option<u32>, // the current block if not given
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarvestableQuery {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+    /// How many blocks before the current one to check
+    pub lookback: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HarvestableResponse {
+    pub blocks: Vec<UnharvestedBlock>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct FarmerPailInfo {
     #[serde(rename = "farmerAddress")]
@@ -210,6 +539,128 @@ pub struct AllFarmersResponse {
     pub farmers: Vec<FarmerPailInfo>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PayoutPrepareRequest {
+    /// The pool account that farmed and pays out the reward
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+    #[serde(rename = "totalReward")]
+    pub total_reward: String, // stroops, as a string to avoid JSON number precision loss
+    pub contributions: Vec<WorkerContribution>,
+    #[serde(rename = "feeSource", default)]
+    pub fee_source: Option<String>, // account paying the fee, if not the pool
+}
+
+#[derive(Debug, Serialize)]
+pub struct PayoutPrepareResponse {
+    pub payouts: Vec<Payout>,
+    pub xdr: String,
+    pub network: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshXdrRequest {
+    /// An unsigned plant, work or harvest transaction from a prepare endpoint
+    pub xdr: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefreshXdrResponse {
+    pub xdr: String,
+    pub network: String,
+    /// The refreshed call: "plant", "work" or "harvest"
+    pub function: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<PrepareTimings>, // only with the debug header
+    #[serde(rename = "restoreRequired")]
+    pub restore_required: bool,
+    #[serde(rename = "signingJob")]
+    pub signing_job: String,
+    #[serde(rename = "signingStatusUrl")]
+    pub signing_status_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeeBumpPrepareRequest {
+    /// A transaction the farmer already signed, from any of the prepare endpoints
+    #[serde(rename = "signedXdr")]
+    pub signed_xdr: String,
+    /// The sponsor account paying the fee
+    #[serde(rename = "feeSource")]
+    pub fee_source: String,
+    #[serde(rename = "maxFee")]
+    pub max_fee: String, // stroops, as a string to avoid JSON number precision loss
+    /// Earlier fee bumps of the same transaction; with it the bump bids the configured fee
+    /// escalation for this retry instead of `maxFee`, which stays the most it pays
+    #[serde(default)]
+    pub retry: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeeBumpPrepareResponse {
+    pub xdr: String, // for the sponsor to sign
+    pub network: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasskeyChallengeRequest {
+    /// A prepared transaction for a smart wallet farmer
+    pub xdr: String,
+    /// The smart wallet contract (C...)
+    pub wallet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasskeyChallengeResponse {
+    pub challenge: String, // hex, for navigator.credentials.get
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasskeyAttachRequest {
+    pub xdr: String,
+    pub wallet: String,
+    // The WebAuthn assertion, each field hex encoded
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    #[serde(rename = "clientDataJson")]
+    pub client_data_json: String,
+    pub signature: String, // DER, as WebAuthn returns it
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasskeyAttachResponse {
+    pub xdr: String, // for the fee source to sign
+    pub network: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitAdviceQuery {
+    /// plant, work, harvest, trustline or payout
+    pub kind: String,
+}
+
+/// A progress report from an external signer
+#[derive(Debug, Deserialize)]
+pub struct SigningStatusReport {
+    pub state: SigningState,
+    /// Why the transaction was rejected, shown to the farmer
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SigningStatusResponse {
+    #[serde(rename = "jobId")]
+    pub job_id: String,
+    #[serde(flatten)]
+    pub job: SigningJob,
+}
+
+/// Longest rejection reason a signer may report
+const MAX_REJECTION_REASON_LEN: usize = 500;
+
 /// Represents the state of the Albedo authentication process
 #[derive(Clone)]
 struct AlbedoState {
@@ -220,12 +671,44 @@ struct AlbedoState {
 
 /// Shared state for the KALE contract client
 struct AppState {
+    config: Config,
+    /// Where `config` was read from, to reread its RPC endpoints on reload
+    config_source: ConfigSource,
     kale: Kale,
+    limits: LimitTracker,
+    access: AccessPolicy,
+    denials: DenialCounters,
+    price: PriceFeed,
+    idempotency: IdempotencyStore,
+    preferences: PreferenceStore,
+    challenges: KeyChallenges,
+    alerts: AlertTracker,
+    live: LiveUpdates,
+    notifier: Notifier,
+    blocks: BlockWatcher,
+    signing: SigningJobs,
+    congestion: CongestionMonitor,
+    maintenance: Maintenance,
+    pauses: Pauses,
+    fleet: Fleet,
 }
 
 /// Initiates Albedo wallet authentication and plant transaction flow
 /// Returns the user's public key and transaction hash after successful plant
-pub async fn authenticate_and_plant(kale_client: Kale) -> Result<(String, String)> {
+pub async fn authenticate_and_plant(
+    config: Config,
+    config_source: ConfigSource,
+    kale_client: Kale,
+    limits: LimitTracker,
+    access: AccessPolicy,
+    price: PriceFeed,
+    idempotency: IdempotencyStore,
+    preferences: PreferenceStore,
+    live: LiveUpdates,
+    notifier: Notifier,
+    maintenance: Maintenance,
+    fleet: Fleet,
+) -> Result<(String, String)> {
     // Create shared state to store the result
     let auth_state = Arc::new(Mutex::new(AlbedoState {
         pub_key: None,
@@ -234,54 +717,248 @@ pub async fn authenticate_and_plant(kale_client: Kale) -> Result<(String, String
     }));
 
     // Build the URL
-    let auth_url = format!("http://localhost:{}", SERVER_PORT);
+    let auth_url = format!("http://localhost:{}", config.server_port);
 
     println!("Please open the following URL in your browser:");
     println!("{}", auth_url);
 
     // Start the local HTTP server
-    let result = start_server(auth_state.clone(), kale_client).await?;
+    let result = start_server(
+        config,
+        config_source,
+        auth_state.clone(),
+        kale_client,
+        limits,
+        access,
+        price,
+        idempotency,
+        preferences,
+        live,
+        notifier,
+        maintenance,
+        fleet,
+    )
+    .await?;
 
     Ok(result)
 }
 
 /// Starts a local HTTP server to serve the frontend and handle responses
 async fn start_server(
+    config: Config,
+    config_source: ConfigSource,
     auth_state: Arc<Mutex<AlbedoState>>,
     kale_client: Kale,
+    limits: LimitTracker,
+    access: AccessPolicy,
+    price: PriceFeed,
+    idempotency: IdempotencyStore,
+    preferences: PreferenceStore,
+    live: LiveUpdates,
+    notifier: Notifier,
+    maintenance: Maintenance,
+    fleet: Fleet,
 ) -> Result<(String, String)> {
     let auth_state_clone = auth_state.clone();
-    let app_state = Arc::new(AppState { kale: kale_client });
+    let port = config.server_port;
+    let app_state = Arc::new(AppState {
+        congestion: CongestionMonitor::new(&config.horizon_url),
+        config,
+        config_source,
+        kale: kale_client,
+        limits,
+        access,
+        denials: DenialCounters::default(),
+        price,
+        idempotency,
+        preferences,
+        challenges: KeyChallenges::default(),
+        alerts: AlertTracker::default(),
+        live,
+        notifier,
+        blocks: BlockWatcher::new(tokio::time::Duration::from_secs(BLOCK_WATCH_INTERVAL_SECS)),
+        signing: SigningJobs::default(),
+        maintenance,
+        pauses: Pauses::default(),
+        fleet,
+    });
+
+    // Warm caches before accepting requests, but don't let a slow RPC node block startup;
+    // on timeout priming keeps running in the background and /readyz reports it
+    let priming = tokio::spawn({
+        let app_state = app_state.clone();
+        async move { app_state.kale.prime_caches().await }
+    });
+    let prime_timeout = tokio::time::Duration::from_secs(PRIME_TIMEOUT_SECS);
+    match tokio::time::timeout(prime_timeout, priming).await {
+        Ok(Ok(Ok(()))) => println!("✓ Caches primed"),
+        Ok(Ok(Err(e))) => eprintln!("Warning: Failed to prime caches: {}", e),
+        Ok(Err(e)) => eprintln!("Warning: Cache priming task failed: {}", e),
+        Err(_) => eprintln!(
+            "Warning: Cache priming still running after {}s, starting server anyway",
+            PRIME_TIMEOUT_SECS
+        ),
+    }
+
+    // One poll of the contract feeds every connected WebSocket client
+    spawn_block_watcher(app_state.clone());
+    spawn_reload_on_hangup(app_state.clone());
+    spawn_fleet_checks(app_state.clone());
+    if app_state.notifier.enabled() {
+        spawn_notifications(app_state.clone());
+    }
+    if app_state.live.replaying() {
+        println!("Replaying recorded live events to WebSocket clients");
+        let app_state = app_state.clone();
+        tokio::spawn(async move { app_state.live.replay().await });
+    }
+
+    // Mutating endpoints replay their first response when retried with the same Idempotency-Key
+    let idempotent_layer =
+        middleware::from_fn_with_state(app_state.clone(), replay_idempotent_requests);
+    // and are refused in maintenance mode, while reads stay up
+    let maintenance_layer =
+        middleware::from_fn_with_state(app_state.clone(), refuse_during_maintenance);
+    let submits = Router::new()
+        .route("/api/plant/submit", post(handle_plant_submit))
+        .route("/api/work/submit", post(handle_work_submit))
+        .route("/api/harvest/submit", post(handle_harvest_submit))
+        .route("/api/trustline/submit", post(handle_trustline_submit))
+        .route("/api/restore/submit", post(handle_restore_submit))
+        .route_layer(idempotent_layer.clone())
+        .route_layer(maintenance_layer.clone());
+    let prepares = Router::new()
+        .route("/api/plant/prepare", post(handle_plant_prepare))
+        .route("/api/work/prepare", post(handle_work_prepare))
+        .route("/api/harvest/prepare", post(handle_harvest_prepare))
+        .route("/api/trustline/prepare", post(handle_trustline_prepare))
+        .route("/api/payout/prepare", post(handle_payout_prepare))
+        .route("/api/feebump/prepare", post(handle_feebump_prepare))
+        .route("/api/refresh_xdr", post(handle_refresh_xdr))
+        .route("/api/passkey/challenge", post(handle_passkey_challenge))
+        .route("/api/passkey/attach", post(handle_passkey_attach))
+        .route("/api/fund_account", post(handle_fund_account))
+        .route_layer(idempotent_layer)
+        .route_layer(maintenance_layer);
+
+    // Endpoints that spend funds or expose server internals are limited to allowed hosts
+    let protected = Router::new()
+        .route("/api/access/denials", get(handle_access_denials))
+        .route(
+            "/api/admin/maintenance",
+            get(handle_get_maintenance).post(handle_set_maintenance),
+        )
+        .route("/api/admin/reload", post(handle_reload))
+        .route("/api/fleet/status", get(handle_fleet_status))
+        .route(
+            "/api/admin/pause",
+            get(handle_get_pauses).post(handle_set_pause),
+        )
+        .route("/dashboard", get(serve_dashboard))
+        .merge(submits)
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            enforce_access,
+        ));
+
+    // Without a built frontend the app page would be blank, so serve a status page instead
+    let frontend_built = fallback_ui::frontend_built();
+    let app_page = if frontend_built {
+        get(serve_kale)
+    } else {
+        eprintln!(
+            "Warning: Frontend not built ({}/bundle.js missing), serving the built-in status page",
+            FRONTEND_DIR
+        );
+        get(serve_status_page)
+    };
 
     // Create the router
     let app = Router::new()
         .route("/", get(serve_landing))
-        .route("/app/kale", get(serve_kale))
+        .route("/readyz", get(handle_readyz))
+        .route("/api/server_info", get(handle_server_info))
+        .route("/app/kale", app_page)
+        .route("/static/kale-signing.js", get(serve_signing_helper))
         .route("/api/pubkey", post(handle_pubkey))
-        .route("/api/plant/prepare", post(handle_plant_prepare))
-        .route("/api/plant/submit", post(handle_plant_submit))
         .route("/api/check_planted", post(handle_check_planted))
+        .route("/api/network", get(handle_network))
         .route("/api/block_info", get(handle_block_info))
-        .route("/api/work/prepare", post(handle_work_prepare))
-        .route("/api/work/submit", post(handle_work_submit))
+        .route("/api/network_limits", get(handle_network_limits))
+        .route("/api/fee_explain", post(handle_fee_explain))
+        .route(
+            "/api/preferences",
+            get(handle_get_preferences).post(handle_update_preferences),
+        )
+        .route(
+            "/api/preferences/challenge",
+            post(handle_preferences_challenge),
+        )
+        .route("/api/preferences/verify_email", post(handle_verify_email))
+        .route("/api/price", get(handle_price))
+        .route("/api/submit_advice", get(handle_submit_advice))
+        .route("/api/reports/latest", get(handle_latest_report))
         .route("/api/pail_data", post(handle_pail_data))
-        .route("/api/harvest/prepare", post(handle_harvest_prepare))
-        .route("/api/harvest/submit", post(handle_harvest_submit))
         .route("/api/account_status", post(handle_account_status))
-        .route("/api/fund_account", post(handle_fund_account))
-        .route("/api/trustline/prepare", post(handle_trustline_prepare))
-        .route("/api/trustline/submit", post(handle_trustline_submit))
+        .route("/api/trustlines", get(handle_trustlines))
         .route("/api/all_farmers", post(handle_all_farmers))
+        .route("/api/block_farmers", get(handle_block_farmers))
+        .route("/api/harvestable", get(handle_harvestable))
+        .route("/api/difficulty", get(handle_difficulty))
+        .route("/api/block/:block_index", get(handle_block))
+        .route("/api/contract_state", get(handle_contract_state))
+        .route("/ws", get(handle_ws))
+        // Not host-restricted: external signers run elsewhere, and the job ID is the secret
+        .route(
+            "/api/signing/:job_id/status",
+            get(handle_signing_status).post(handle_signing_report),
+        )
+        .route("/api/tx/:hash/diagnostics", get(handle_tx_diagnostics))
+        .route(
+            "/api/proof/:public_key/:block_index",
+            get(handle_participation_proof),
+        )
+        .merge(prepares)
+        .merge(protected);
+    let app = if frontend_built {
+        app.fallback_service(ServeDir::new(FRONTEND_DIR))
+    } else {
+        app.fallback(serve_status_page)
+    };
+    let app = app
         .with_state((auth_state_clone, app_state))
-        .fallback_service(ServeDir::new("frontend/dist"));
-
-    // Bind to the server port
-    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", SERVER_PORT)).await?;
+        // Allow third-party pages using the signing helper to call the API
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods([Method::GET, Method::POST])
+                .allow_headers([
+                    header::CONTENT_TYPE,
+                    HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+                ])
+                .expose_headers([HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER)]),
+        );
+
+    // Use the socket systemd passed in if socket-activated, otherwise bind the server port
+    let listener = match systemd::activated_listener()? {
+        Some(listener) => {
+            println!("Using socket passed in by systemd");
+            tokio::net::TcpListener::from_std(listener)?
+        }
+        None => tokio::net::TcpListener::bind(format!("127.0.0.1:{}", port)).await?,
+    };
 
-    println!("Server listening on http://localhost:{}", SERVER_PORT);
+    println!("Server listening on http://localhost:{}", port);
 
     // Spawn the server in a background task
-    let server_handle = tokio::spawn(async move { axum::serve(listener, app).await });
+    let server_handle = tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+    });
+    systemd::notify("READY=1");
 
     // Wait for authentication first
     let pub_key = loop {
@@ -316,457 +993,2217 @@ async fn start_server(
     }
 }
 
-/// Serves the landing page
-async fn serve_landing() -> impl IntoResponse {
-    Html(include_str!("../frontend/public/landing.html"))
+/// Refuses requests from clients outside the configured allow/deny lists
+async fn enforce_access(
+    State(app_state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = app_state.access.client_ip(peer.ip(), request.headers());
+
+    match app_state.access.check(client) {
+        Ok(()) => next.run(request).await,
+        Err(denial) => {
+            app_state.denials.record(denial);
+            eprintln!(
+                "Refused {} from {} ({:?})",
+                request.uri().path(),
+                client,
+                denial
+            );
+            (
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    error: "Access denied for this client address".to_string(),
+                    code: Some("access_denied".to_string()),
+                    details: None,
+                }),
+            )
+                .into_response()
+        }
+    }
 }
 
-/// Serves the KALE app HTML page
-async fn serve_kale() -> impl IntoResponse {
-    Html(include_str!("../frontend/public/index.html"))
+/// Refuses mutating requests with the operator's banner while in maintenance mode
+async fn refuse_during_maintenance(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(banner) = app_state.maintenance.banner() else {
+        return next.run(request).await;
+    };
+    let scheduled = app_state.maintenance.status().scheduled;
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ErrorResponse {
+            error: banner,
+            code: Some("maintenance".to_string()),
+            details: scheduled.map(|scheduled| serde_json::json!({ "scheduled": scheduled })),
+        }),
+    )
+        .into_response()
 }
 
-/// Handles the public key POST request from the frontend
-async fn handle_pubkey(
-    State((auth_state, _app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<PubkeyResponse>,
-) -> impl IntoResponse {
-    let mut state_guard = auth_state.lock().await;
+/// Largest request or response body buffered for idempotent replay
+const IDEMPOTENT_BODY_LIMIT: usize = 1024 * 1024;
+
+/// Replays the first response to a request retried with the same `Idempotency-Key`
+///
+/// Requests without the header pass through. Keys are scoped to the caller: the account
+/// the request is for, or the client address if it names none. Server errors are not
+/// stored, so a retry after one runs the request again.
+async fn replay_idempotent_requests(
+    State(app_state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return next.run(request).await;
+    };
+    // Keys are per endpoint, so one key may be reused for a prepare and its submit
+    let key = format!("{} {}", request.uri().path(), key);
+
+    let client = app_state.access.client_ip(peer.ip(), request.headers());
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT).await else {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ErrorResponse::new("Request body too large")),
+        )
+            .into_response();
+    };
+    let caller = idempotency::caller(&body).unwrap_or_else(|| client.to_string());
+    let key = format!("{} {}", caller, key);
+
+    let claim = match app_state.idempotency.claim(&key, &body) {
+        Ok(claim) => claim,
+        Err(e) => {
+            eprintln!("Warning: Idempotency store unavailable: {:#}", e);
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorResponse::new(
+                    "Idempotency-Key could not be checked; retry later",
+                )),
+            )
+                .into_response();
+        }
+    };
+    match claim {
+        Claim::New => {}
+        Claim::Replay(stored) => {
+            let mut response = (stored.status, stored.body).into_response();
+            if let Some(content_type) = stored.content_type {
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, content_type);
+            }
+            response
+                .headers_mut()
+                .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+            return response;
+        }
+        Claim::InProgress => {
+            return (
+                StatusCode::CONFLICT,
+                Json(ErrorResponse::new(
+                    "A request with this Idempotency-Key is still being processed",
+                )),
+            )
+                .into_response()
+        }
+        Claim::Mismatch => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse::new(
+                    "Idempotency-Key was already used with a different request body",
+                )),
+            )
+                .into_response()
+        }
+    }
 
-    if let Some(pubkey) = payload.pubkey {
-        state_guard.pub_key = Some(pubkey);
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        release_idempotency_key(&app_state, &key);
+        return response;
     }
 
-    if let Some(error) = payload.error {
-        state_guard.error = Some(error);
+    let (parts, body) = response.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT).await else {
+        release_idempotency_key(&app_state, &key);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Failed to read response body")),
+        )
+            .into_response();
+    };
+    let stored = app_state.idempotency.complete(
+        &key,
+        StoredResponse {
+            status: parts.status,
+            content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+            body: body.clone(),
+        },
+    );
+    if let Err(e) = stored {
+        eprintln!("Warning: Failed to store idempotent response: {:#}", e);
     }
 
-    state_guard.completed = true;
+    Response::from_parts(parts, Body::from(body))
+}
 
-    Json(serde_json::json!({"status": "ok"}))
+fn release_idempotency_key(app_state: &AppState, key: &str) {
+    if let Err(e) = app_state.idempotency.release(key) {
+        eprintln!("Warning: Failed to release Idempotency-Key: {:#}", e);
+    }
 }
 
-/// Handles the plant transaction preparation request
-async fn handle_plant_prepare(
+/// Handles reporting how many requests the access policy has refused
+async fn handle_access_denials(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<PlantPrepareRequest>,
-) -> Result<Json<PlantPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Json<DenialStats> {
+    Json(app_state.denials.snapshot())
+}
+
+/// Handles reporting the server version, network and maintenance status (banner and schedule)
+async fn handle_server_info(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Json<ServerInfoResponse> {
+    Json(ServerInfoResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        network: app_state.config.network.name(),
+        maintenance: app_state.maintenance.status(),
+    })
+}
+
+/// Handles reading the maintenance status (allowed hosts only)
+async fn handle_get_maintenance(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Json<MaintenanceStatus> {
+    Json(app_state.maintenance.status())
+}
+
+/// Handles switching maintenance mode and setting the banner and schedule (allowed hosts only)
+async fn handle_set_maintenance(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(status): Json<MaintenanceStatus>,
+) -> Result<Json<MaintenanceStatus>, (StatusCode, Json<ErrorResponse>)> {
+    let previous = app_state.maintenance.set(status.clone()).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!("{:#}", e))),
+        )
+    })?;
+    if previous.active != status.active {
+        println!(
+            "Maintenance mode {}",
+            if status.active { "on" } else { "off" }
+        );
+    }
+    Ok(Json(status))
+}
+
+/// Handles reporting the fleet keys' last health checks (allowed hosts only)
+async fn handle_fleet_status(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Json<FleetStatusResponse> {
+    let keys = app_state.fleet.status();
+    Json(FleetStatusResponse {
+        healthy: keys.iter().all(|key| {
+            key.last_check
+                .as_ref()
+                .is_none_or(|health| health.problems.is_empty())
+        }),
+        keys,
+    })
+}
+
+/// Handles reporting which background subsystems are paused (allowed hosts only)
+async fn handle_get_pauses(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Json<PauseStatusResponse> {
+    Json(PauseStatusResponse {
+        paused: app_state.pauses.paused(),
+    })
+}
+
+/// Handles pausing or resuming one background subsystem (allowed hosts only)
+async fn handle_set_pause(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<PauseRequest>,
+) -> Result<Json<PauseStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if matches!(payload.subsystem, Subsystem::Harvester | Subsystem::Miner) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!(
+                "{} runs in the farm daemon; pause it with the daemon's --pause-file",
+                payload.subsystem.name()
+            ))),
+        ));
+    }
+
+    if app_state.pauses.set(payload.subsystem, payload.paused) {
+        if payload.subsystem == Subsystem::BlockWatcher {
+            app_state.blocks.set_paused(payload.paused);
+        }
+        println!(
+            "{} {}",
+            if payload.paused { "Paused" } else { "Resumed" },
+            payload.subsystem.name()
+        );
+    }
+    Ok(Json(PauseStatusResponse {
+        paused: app_state.pauses.paused(),
+    }))
+}
+
+/// Handles rereading the config file's RPC endpoints, like SIGHUP (allowed hosts only)
+async fn handle_reload(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Result<Json<ReloadResponse>, (StatusCode, Json<ErrorResponse>)> {
+    reload_config(&app_state).await.map(Json).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!("{:#}", e))),
+        )
+    })
+}
+
+/// Rereads the config file and switches the RPC client to its endpoints
+///
+/// WebSocket sessions, signing jobs and calls under way carry on undisturbed. If the file
+/// doesn't parse or the new endpoints serve another network, nothing changes.
+async fn reload_config(app_state: &AppState) -> anyhow::Result<ReloadResponse> {
+    let config = app_state.config_source.load()?;
+    app_state.kale.reload_endpoints(&config).await?;
+
+    let endpoint = app_state.kale.active_endpoint();
+    let restart_required = app_state.config.restart_required(&config);
+    println!("✓ Config reloaded, RPC calls go to {}", endpoint);
+    if !restart_required.is_empty() {
+        eprintln!(
+            "Warning: Restart to apply the changed {}",
+            restart_required.join(", ")
+        );
+    }
+    Ok(ReloadResponse {
+        endpoint,
+        restart_required,
+    })
+}
+
+/// Reports whether startup cache priming has completed
+async fn handle_readyz(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> impl IntoResponse {
+    if app_state.kale.is_primed() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "warming up")
+    }
+}
+
+/// Serves the landing page
+async fn serve_landing() -> impl IntoResponse {
+    Html(include_str!("../frontend/public/landing.html"))
+}
+
+/// Serves the KALE app HTML page
+async fn serve_kale() -> impl IntoResponse {
+    Html(include_str!("../frontend/public/index.html"))
+}
+
+/// Serves the built-in status page when the frontend hasn't been built
+///
+/// Also used as the fallback for unknown paths, which get it with a 404.
+async fn serve_status_page(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    uri: Uri,
+) -> impl IntoResponse {
+    let block_index = app_state.kale.get_block_index().await.ok();
+    let page = fallback_ui::render_status_page(block_index, app_state.kale.is_primed());
+
+    let status = if uri.path() == "/app/kale" {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    };
+    (status, Html(page))
+}
+
+/// Serves the operator dashboard, gathering each metric fresh so it needs no collector
+async fn serve_dashboard(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Html<String> {
+    let started = Instant::now();
+    let block_info = app_state.kale.get_block_info().await;
+    let rpc_latency_ms = started.elapsed().as_millis();
+
+    let (block_index, has_entropy, rpc_error) = match block_info {
+        Ok((block_index, entropy)) => (Some(block_index), entropy.is_some(), None),
+        Err(e) => (None, false, Some(e.to_string())),
+    };
+
+    let block_closes_in = match block_index {
+        Some(block_index) => app_state
+            .kale
+            .predict_block_close(block_index)
+            .await
+            .ok()
+            .flatten()
+            .map(|prediction| {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                prediction.predicted_close_at as i64 - now as i64
+            }),
+        None => None,
+    };
+
+    let mut fleet = Vec::new();
+    for key in app_state.fleet.keys() {
+        let unharvested = match app_state
+            .kale
+            .find_unharvested_blocks(&key.account, MAX_HARVEST_LOOKBACK)
+            .await
+        {
+            Ok(blocks) => Some(blocks.len()),
+            Err(e) => {
+                eprintln!("Warning: Failed to read backlog of {}: {}", key.account, e);
+                None
+            }
+        };
+        fleet.push(FarmerMetrics {
+            account: key.account.clone(),
+            unharvested,
+            mining: key
+                .status_file
+                .as_deref()
+                .and_then(|path| MiningReport::read(path).ok()),
+        });
+    }
+
+    Html(dashboard::render(&DashboardMetrics {
+        network: app_state.config.network.name(),
+        ready: app_state.kale.is_primed(),
+        rpc_latency_ms,
+        rpc_error,
+        block_index,
+        has_entropy,
+        block_closes_in,
+        fees: app_state.congestion.current().await.ok(),
+        live_clients: app_state.live.client_count(),
+        denials: app_state.denials.snapshot(),
+        paused: app_state.pauses.paused(),
+        fleet,
+    }))
+}
+
+/// Serves the prepare -> sign -> submit helper script for third-party pages
+async fn serve_signing_helper() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/javascript")],
+        include_str!("../frontend/public/kale-signing.js"),
+    )
+}
+
+/// Handles the public key POST request from the frontend
+async fn handle_pubkey(
+    State((auth_state, _app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<PubkeyResponse>,
+) -> impl IntoResponse {
+    let mut state_guard = auth_state.lock().await;
+
+    if let Some(pubkey) = payload.pubkey {
+        state_guard.pub_key = Some(pubkey);
+    }
+
+    if let Some(error) = payload.error {
+        state_guard.error = Some(error);
+    }
+
+    state_guard.completed = true;
+
+    Json(serde_json::json!({"status": "ok"}))
+}
+
+/// Maps a failed transaction preparation to an error response
+///
+/// Known farming errors are the user's to fix, so they are reported as bad requests
+/// in the caller's language; anything else is mapped like any other contract error.
+fn prepare_error(locale: Locale, error: KaleError) -> (StatusCode, Json<ErrorResponse>) {
+    match &error {
+        KaleError::Farm(farm_error) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: i18n::farm_error_message(locale, farm_error),
+                code: farm_error_code(farm_error),
+                details: farm_error_details(farm_error),
+            }),
+        ),
+        _ => kale_error("Failed to prepare transaction", error),
+    }
+}
+
+/// Maps a failed contract client call to an error response, prefixed with what failed
+fn kale_error(action: &str, error: KaleError) -> (StatusCode, Json<ErrorResponse>) {
+    let mut response = ErrorResponse::new(format!("{}: {}", action, error));
+    // Other prepared transactions hold the account's sequence numbers; tell clients when to retry
+    if let KaleError::Rpc(RpcError::SequenceContention(contention)) = &error {
+        response.code = Some("sequence_contention".to_string());
+        response.details = Some(serde_json::json!({
+            "sequence": contention.sequence,
+            "retryAfterSecs": contention.retry_after_secs,
+        }));
+    }
+    (kale_error_status(&error), Json(response))
+}
+
+/// The HTTP status for a contract client error
+///
+/// Bad input from the caller is a 400, a missing ledger entry a 404, a call the
+/// contract or network refuses a 409/422, and an unreachable RPC node a 502.
+fn kale_error_status(error: &KaleError) -> StatusCode {
+    match error {
+        KaleError::Farm(_) | KaleError::InvalidArguments(_) => StatusCode::BAD_REQUEST,
+        KaleError::NoEntropy(_) => StatusCode::CONFLICT,
+        KaleError::ResourceLimit(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        KaleError::UnexpectedData(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        KaleError::Rpc(error) => match error {
+            RpcError::BadStrkey { .. }
+            | RpcError::BadTransaction(_)
+            | RpcError::BadHash(_)
+            | RpcError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            RpcError::EntryNotFound(_) => StatusCode::NOT_FOUND,
+            RpcError::SimulationFailed(_) | RpcError::FeeOverflow { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            RpcError::SequenceContention(_) => StatusCode::CONFLICT,
+            RpcError::Transport { .. }
+            | RpcError::TransactionDropped { .. }
+            | RpcError::EntryUnavailable(_) => StatusCode::BAD_GATEWAY,
+            RpcError::InvalidEndpoint(_) | RpcError::UnexpectedEntry(_) | RpcError::Xdr(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        },
+    }
+}
+
+/// Machine-readable code for the farm errors clients are expected to handle
+fn farm_error_code(error: &FarmError) -> Option<String> {
+    match error {
+        FarmError::InsufficientBalance { .. } => Some("InsufficientBalance".to_string()),
+        FarmError::NegativeStake { .. } => Some("NegativeStake".to_string()),
+        FarmError::NoPail { .. } => Some("NoPail".to_string()),
+        FarmError::WorkAlreadyBetter { .. } => Some("WorkAlreadyBetter".to_string()),
+        _ => None,
+    }
+}
+
+/// Structured fields for the farm errors clients are expected to handle
+///
+/// Stake errors carry the allowed range in stroops, as strings like other amounts:
+/// `minStake` is always 0, and `maxStake` and `maxStakeable` are the balance when known.
+fn farm_error_details(error: &FarmError) -> Option<serde_json::Value> {
+    match error {
+        FarmError::InsufficientBalance {
+            requested,
+            available,
+        } => Some(serde_json::json!({
+            "requested": requested.to_string(),
+            "available": available.to_string(),
+            "minStake": "0",
+            "maxStake": available.to_string(),
+            "maxStakeable": available.to_string(),
+        })),
+        FarmError::NegativeStake { requested } => Some(serde_json::json!({
+            "requested": requested.to_string(),
+            "minStake": "0",
+        })),
+        FarmError::NoPail { block_index } => Some(serde_json::json!({ "blockIndex": block_index })),
+        FarmError::WorkAlreadyBetter {
+            existing_zeros,
+            candidate_zeros,
+        } => Some(serde_json::json!({
+            "existingZeros": existing_zeros,
+            "candidateZeros": candidate_zeros,
+        })),
+        _ => None,
+    }
+}
+
+/// Header that asks prepare endpoints to include per-stage timings
+const DEBUG_TIMINGS_HEADER: &str = "x-kale-debug-timings";
+
+/// Returns the prepared transaction's stage timings if the caller asked for them
+fn requested_timings(
+    headers: &HeaderMap,
+    prepared: &PreparedTransaction,
+) -> Option<PrepareTimings> {
+    headers
+        .contains_key(DEBUG_TIMINGS_HEADER)
+        .then_some(prepared.timings)
+}
+
+/// Checks a plant stake against the operator's per-block limits, in stroops and USD
+async fn check_stake_limits(
+    app_state: &AppState,
+    amount: i128,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    app_state.limits.check_stake(amount).map_err(over_limit)?;
+    if app_state.limits.max_stake_usd().is_some() {
+        // Fail closed: a USD limit can't be enforced without a price
+        let price = app_state
+            .price
+            .current()
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Json(ErrorResponse::new(
+                        "Stake limit is set in USD but the KALE price is unavailable",
+                    )),
+                )
+            })?;
+        app_state
+            .limits
+            .check_stake_usd(price.usd_value(amount))
+            .map_err(over_limit)?;
+    }
+    Ok(())
+}
+
+/// Maps an exceeded spending limit to a structured `over_limit` error response
+fn over_limit(limit: LimitExceeded) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match limit {
+        LimitExceeded::StakePerBlock { .. } | LimitExceeded::StakeUsd { .. } => {
+            StatusCode::FORBIDDEN
+        }
+        LimitExceeded::TransactionsPerDay { .. } => StatusCode::TOO_MANY_REQUESTS,
+    };
+
+    (
+        status,
+        Json(ErrorResponse {
+            error: limit.to_string(),
+            code: Some("over_limit".to_string()),
+            details: serde_json::to_value(&limit).ok(),
+        }),
+    )
+}
+
+/// Counts a submitted transaction against its farmer's daily limit and, if it
+/// wasn't confirmed in time, watches it in the background, rebroadcasting it if the
+/// network loses it
+fn record_submission(
+    app_state: &Arc<AppState>,
+    kind: &'static str,
+    signed_xdr: &str,
+    submitted: &SubmittedTransaction,
+) {
+    let farmer = match SorobanRpc::transaction_farmer(signed_xdr) {
+        Ok(farmer) => {
+            app_state.limits.record_transaction(&farmer);
+            Some(farmer)
+        }
+        Err(e) => {
+            eprintln!("Warning: could not count transaction against limits: {}", e);
+            None
+        }
+    };
+
+    let app_state = app_state.clone();
+    let (signed_xdr, tx_hash) = (signed_xdr.to_string(), submitted.hash.clone());
+    let status = submitted.status;
+    tokio::spawn(async move {
+        let status = match status {
+            TransactionStatus::Pending => {
+                match app_state.kale.watch_submission(&signed_xdr, &tx_hash).await {
+                    Ok(status) => {
+                        println!("Transaction {} completed: {}", tx_hash, status);
+                        if status == "SUCCESS" {
+                            TransactionStatus::Success
+                        } else {
+                            TransactionStatus::Failed
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Warning: {}", e);
+                        return;
+                    }
+                }
+            }
+            status => status,
+        };
+
+        if let Some(farmer) = farmer {
+            if kind == "work" && status == TransactionStatus::Failed {
+                notify_work_failed(&app_state, &farmer, &tx_hash).await;
+            }
+            publish_confirmation(&app_state, farmer, kind, tx_hash, status).await;
+        }
+    });
+}
+
+/// Pushes a transaction's receipt, and the account's balances after it, to WebSocket clients
+async fn publish_confirmation(
+    app_state: &AppState,
+    public_key: String,
+    kind: &'static str,
+    hash: String,
+    status: TransactionStatus,
+) {
+    let result = match app_state.kale.get_transaction_diagnostics(&hash).await {
+        Ok(diagnostics) => Some(diagnostics.receipt()),
+        Err(e) => {
+            eprintln!("Warning: Failed to decode the result of {}: {}", hash, e);
+            None
+        }
+    };
+    app_state.live.publish(LiveEvent::Receipt {
+        public_key: public_key.clone(),
+        kind,
+        hash,
+        status,
+        result,
+    });
+
+    match account_status(app_state, &public_key).await {
+        Ok(status) => app_state
+            .live
+            .publish(LiveEvent::Account { public_key, status }),
+        Err((_, Json(error))) => {
+            eprintln!("Warning: Failed to refresh account status: {}", error.error)
+        }
+    }
+}
+
+/// Reloads the config on SIGHUP, which `systemctl reload` sends
+fn spawn_reload_on_hangup(app_state: Arc<AppState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            eprintln!("Warning: Can't reload on SIGHUP: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            if let Err(e) = reload_config(&app_state).await {
+                eprintln!("Warning: Config not reloaded: {:#}", e);
+            }
+        }
+    });
+}
+
+/// Runs the shared block watcher, and polls the contract for pail changes, pushing both
+/// to WebSocket clients
+fn spawn_block_watcher(app_state: Arc<AppState>) {
+    app_state.blocks.on_block({
+        let app_state = app_state.clone();
+        move |update| {
+            app_state.live.publish(LiveEvent::Block {
+                block_index: update.block_index,
+                entropy: update.entropy.map(hex::encode),
+            });
+            std::future::ready(())
+        }
+    });
+    tokio::spawn({
+        let app_state = app_state.clone();
+        async move { app_state.blocks.run(&app_state.kale).await }
+    });
+
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(tokio::time::Duration::from_secs(BLOCK_WATCH_INTERVAL_SECS));
+        let mut farm: Option<FarmSnapshot> = None;
+        loop {
+            interval.tick().await;
+
+            // Snapshots cost an events scan and a pail batch, so only take them while watched
+            if app_state.live.client_count() == 0
+                || app_state.live.replaying()
+                || app_state.pauses.is_paused(Subsystem::Indexer)
+            {
+                farm = None;
+                continue;
+            }
+            match app_state.kale.snapshot_farm(farm.as_ref()).await {
+                Ok(snapshot) => {
+                    if let Some(previous) = &farm {
+                        let changes = changes::diff(previous, &snapshot);
+                        if !changes.is_empty() {
+                            app_state.live.publish(LiveEvent::Farm { changes });
+                        }
+                    }
+                    farm = Some(snapshot);
+                }
+                Err(e) => eprintln!("Warning: Failed to snapshot farm: {}", e),
+            }
+        }
+    });
+}
+
+/// Emails farmers when a block they worked can be harvested, a report at each block
+/// rollover, a daily summary, and when their balance alerts are met, per their
+/// notification preferences
+fn spawn_notifications(app_state: Arc<AppState>) {
+    app_state.blocks.on_block({
+        let app_state = app_state.clone();
+        move |_update| {
+            let app_state = app_state.clone();
+            async move {
+                if !app_state.pauses.is_paused(Subsystem::Notifications) {
+                    check_balance_alerts(&app_state).await
+                }
+            }
+        }
+    });
+    app_state.blocks.on_block({
+        let app_state = app_state.clone();
+        move |update| {
+            let app_state = app_state.clone();
+            async move {
+                if !update.is_new_block()
+                    || update.block_index == 0
+                    || app_state.pauses.is_paused(Subsystem::Notifications)
+                {
+                    return;
+                }
+                // The block that just closed is the one that can be harvested now
+                let block_index = update.block_index - 1;
+                for (farmer, email) in app_state
+                    .preferences
+                    .subscribers(NotificationKind::HarvestAvailable)
+                {
+                    match app_state.kale.get_pail_data(&farmer, block_index).await {
+                        Ok((true, true, _)) => {
+                            let ready = Email::HarvestAvailable {
+                                farmer: &farmer,
+                                block_index,
+                            };
+                            app_state.notifier.send(&email, ready).await;
+                        }
+                        Ok(_) => {}
+                        Err(e) => eprintln!(
+                            "Warning: Failed to check block {} for {}: {}",
+                            block_index, farmer, e
+                        ),
+                    }
+                }
+                send_block_reports(&app_state, block_index).await;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let day = tokio::time::Duration::from_secs(24 * 60 * 60);
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + day, day);
+        loop {
+            interval.tick().await;
+            if app_state.pauses.is_paused(Subsystem::Notifications) {
+                continue;
+            }
+            for (farmer, email) in app_state
+                .preferences
+                .subscribers(NotificationKind::DailySummary)
+            {
+                // A day of blocks, as far back as harvests can reach
+                let unharvested = match app_state
+                    .kale
+                    .find_unharvested_blocks(&farmer, MAX_HARVEST_LOOKBACK)
+                    .await
+                {
+                    Ok(unharvested) => unharvested,
+                    Err(e) => {
+                        eprintln!("Warning: Failed to summarize {}: {}", farmer, e);
+                        continue;
+                    }
+                };
+                let summary = Email::DailySummary {
+                    farmer: &farmer,
+                    unharvested: &unharvested,
+                };
+                app_state.notifier.send(&email, summary).await;
+            }
+        }
+    });
+}
+
+/// Emails each farmer who asked for block reports their part in a block that just closed
+///
+/// Only farmers who planted or worked through this server are in a report.
+async fn send_block_reports(app_state: &AppState, block_index: u32) {
+    let subscribers = app_state
+        .preferences
+        .subscribers(NotificationKind::BlockReport);
+    if subscribers.is_empty() {
+        return;
+    }
+    let report = match app_state.kale.get_block_report(block_index).await {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Warning: Failed to report block {}: {}", block_index, e);
+            return;
+        }
+    };
+    for summary in &report.farmers {
+        for (_, email) in subscribers
+            .iter()
+            .filter(|(farmer, _)| *farmer == summary.farmer)
+        {
+            let email_report = Email::BlockReport {
+                block_index,
+                summary,
+            };
+            app_state.notifier.send(email, email_report).await;
+        }
+    }
+}
+
+/// Reads the balances of every farmer with alerts and emails those just met
+///
+/// Runs at each watcher update: a new block, or entropy for the current one.
+async fn check_balance_alerts(app_state: &AppState) {
+    for (farmer, email, alerts) in app_state.preferences.alert_subscribers() {
+        let spendable_xlm = match app_state.kale.get_spendable_xlm(&farmer).await {
+            Ok(spendable_xlm) => spendable_xlm,
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to read the XLM balance of {}: {}",
+                    farmer, e
+                );
+                continue;
+            }
+        };
+        let kale = match app_state.kale.check_kale_trustline(&farmer).await {
+            Ok((has_trustline, balance)) => has_trustline.then_some(balance),
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to read the KALE balance of {}: {}",
+                    farmer, e
+                );
+                continue;
+            }
+        };
+        let balances = Balances {
+            spendable_xlm,
+            kale,
+        };
+        for alert in app_state.alerts.check(&farmer, &alerts, balances) {
+            let met = Email::BalanceAlert {
+                farmer: &farmer,
+                alert,
+                balance: balances.get(alert.balance),
+            };
+            app_state.notifier.send(&email, met).await;
+        }
+    }
+}
+
+/// Checks the fleet keys in turn, each once per KALE_FLEET_CHECK_SECS, and emails the fleet
+/// file's `notify` address when one becomes unhealthy
+fn spawn_fleet_checks(app_state: Arc<AppState>) {
+    if app_state.fleet.keys().is_empty() {
+        return;
+    }
+    println!(
+        "Checking {} fleet keys, one every {}s",
+        app_state.fleet.keys().len(),
+        app_state.fleet.check_spacing().as_secs()
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(app_state.fleet.check_spacing());
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        for key in app_state.fleet.keys().iter().cycle() {
+            interval.tick().await;
+            let health =
+                fleet::check(&app_state.kale, key, app_state.fleet.min_spendable_xlm()).await;
+            let problems = health.problems.clone();
+            if !problems.is_empty() {
+                eprintln!(
+                    "Warning: Fleet key {} failed its health check: {}",
+                    key.account,
+                    problems.join("; ")
+                );
+            }
+            if !app_state.fleet.record(&key.account, health)
+                || app_state.pauses.is_paused(Subsystem::Notifications)
+            {
+                continue;
+            }
+            if let Some(to) = app_state.fleet.notify() {
+                let email = Email::FleetKeyUnhealthy {
+                    account: &key.account,
+                    problems: &problems,
+                };
+                app_state.notifier.send(to, email).await;
+            }
+        }
+    });
+}
+
+/// Emails a farmer whose work transaction failed, if they asked to hear about it
+async fn notify_work_failed(app_state: &AppState, farmer: &str, hash: &str) {
+    let subscribers = app_state
+        .preferences
+        .subscribers(NotificationKind::WorkFailed);
+    if let Some((_, email)) = subscribers
+        .iter()
+        .find(|(public_key, _)| public_key == farmer)
+    {
+        app_state
+            .notifier
+            .send(email, Email::WorkFailed { farmer, hash })
+            .await;
+    }
+}
+
+/// Handles a WebSocket connection for live block, transaction and account updates
+///
+/// Clients pass `?publicKey=` to also receive updates about their own account.
+async fn handle_ws(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Query(query): Query<LiveQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| async move { app_state.live.serve(socket, query.public_key).await })
+}
+
+/// Handles reading a signing job's state
+async fn handle_signing_status(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Path(job_id): Path<String>,
+) -> Result<Json<SigningStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let job = app_state
+        .signing
+        .get(&job_id)
+        .ok_or_else(|| signing_error(ReportError::UnknownJob))?;
+
+    Ok(Json(SigningStatusResponse { job_id, job }))
+}
+
+/// Handles an external signer reporting that it viewed, signed or rejected a transaction
+///
+/// The report is pushed to the farmer's WebSocket clients so the UI can stop waiting.
+async fn handle_signing_report(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Path(job_id): Path<String>,
+    Json(payload): Json<SigningStatusReport>,
+) -> Result<Json<SigningStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if payload
+        .reason
+        .as_ref()
+        .is_some_and(|reason| reason.len() > MAX_REJECTION_REASON_LEN)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!(
+                "Rejection reason is limited to {} bytes",
+                MAX_REJECTION_REASON_LEN
+            ))),
+        ));
+    }
+
+    let job = app_state
+        .signing
+        .report(&job_id, payload.state, payload.reason)
+        .map_err(signing_error)?;
+
+    app_state.live.publish(LiveEvent::Signing {
+        public_key: job.public_key.clone(),
+        job_id: job_id.clone(),
+        kind: job.kind,
+        state: job.state,
+        reason: job.reason.clone(),
+    });
+
+    Ok(Json(SigningStatusResponse { job_id, job }))
+}
+
+fn signing_error(error: ReportError) -> (StatusCode, Json<ErrorResponse>) {
+    let (status, code) = match error {
+        ReportError::UnknownJob => (StatusCode::NOT_FOUND, "unknown_signing_job"),
+        ReportError::Finished(_) => (StatusCode::CONFLICT, "signing_finished"),
+        ReportError::NotReportable => (StatusCode::BAD_REQUEST, "invalid_signing_state"),
+    };
+
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: Some(code.to_string()),
+            details: None,
+        }),
+    )
+}
+
+/// Handles the plant transaction preparation request
+async fn handle_plant_prepare(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    headers: HeaderMap,
+    Json(payload): Json<PlantPrepareRequest>,
+) -> Result<Json<PlantPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
     // Parse the amount
-    let amount: i128 = payload
-        .amount
-        .parse()
-        .map_err(|_| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Invalid amount format".to_string(),
-                }),
-            )
-        })?;
+    let amount: i128 = payload.amount.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Invalid amount format".to_string())),
+        )
+    })?;
+
+    // Enforce the operator's spending limits before building anything
+    check_stake_limits(&app_state, amount).await?;
+    app_state
+        .limits
+        .check_transaction(&payload.public_key)
+        .map_err(over_limit)?;
+
+    // Prepare the transaction
+    let prepared = match app_state
+        .kale
+        .prepare_plant_transaction(&payload.public_key, amount, payload.fee_source.as_deref())
+        .await
+    {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let error = prepare_error(Locale::negotiate(&headers), e);
+            return Err(with_block_stakes(&app_state, error).await);
+        }
+    };
+
+    // Return the full network passphrase (Albedo requires the full passphrase)
+    let network = app_state.kale.network_passphrase();
+
+    let signing_job = app_state.signing.create(&payload.public_key, "plant");
+
+    Ok(Json(PlantPrepareResponse {
+        timings: requested_timings(&headers, &prepared),
+        restore_required: prepared.restore,
+        signing_status_url: signing::status_path(&signing_job),
+        signing_job,
+        xdr: prepared.xdr,
+        network: network.to_string(),
+    }))
+}
+
+/// Adds the stakes planted in the current block so far to a stake error, as a guide to
+/// what others are staking
+///
+/// Best effort: the error is returned unchanged if the block can't be read.
+async fn with_block_stakes(
+    app_state: &AppState,
+    mut error: (StatusCode, Json<ErrorResponse>),
+) -> (StatusCode, Json<ErrorResponse>) {
+    let is_stake_error = matches!(
+        error.1.code.as_deref(),
+        Some("InsufficientBalance" | "NegativeStake")
+    );
+    if !is_stake_error {
+        return error;
+    }
+    let Ok(block_index) = app_state.kale.get_block_index().await else {
+        return error;
+    };
+    let stakes = app_state.kale.get_block_stakes(block_index).await;
+    let details = error.1.details.as_mut().and_then(|d| d.as_object_mut());
+    if let (Ok(Some((min, max))), Some(details)) = (stakes, details) {
+        details.insert(
+            "blockStakes".to_string(),
+            serde_json::json!({
+                "blockIndex": block_index,
+                "min": min.to_string(),
+                "max": max.to_string(),
+            }),
+        );
+    }
+    error
+}
+
+/// Handles the plant transaction submission request
+async fn handle_plant_submit(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<PlantSubmitRequest>,
+) -> Result<Json<SubmittedTransaction>, (StatusCode, Json<ErrorResponse>)> {
+    // Submit the signed transaction
+    let submitted = app_state
+        .kale
+        .submit_plant_transaction(&payload.signed_xdr)
+        .await
+        .map_err(|e| kale_error("Failed to submit transaction", e))?;
+
+    record_submission(&app_state, "plant", &payload.signed_xdr, &submitted);
+
+    println!("\n✓ Transaction submitted successfully!");
+    println!(
+        "Transaction hash: {} ({:?})",
+        submitted.hash, submitted.status
+    );
+
+    Ok(Json(submitted))
+}
+
+/// Handles checking if the farmer has planted in the current block
+async fn handle_check_planted(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<CheckPlantedRequest>,
+) -> Result<Json<CheckPlantedResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let has_planted = app_state
+        .kale
+        .has_planted(&payload.public_key)
+        .await
+        .map_err(|e| kale_error("Failed to check planted status", e))?;
+
+    Ok(Json(CheckPlantedResponse { has_planted }))
+}
+
+/// Handles getting the current block information
+async fn handle_block_info(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Query(since): Query<SinceQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let (block_index, entropy, changed_ledger) = app_state
+        .kale
+        .get_block_info_versioned()
+        .await
+        .map_err(|e| kale_error("Failed to get block info", e))?;
+
+    if since.is_current(Some(changed_ledger)) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    // The prediction is best-effort and must not break block info
+    let prediction = app_state
+        .kale
+        .predict_block_close(block_index)
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: Failed to predict block close: {}", e);
+            None
+        });
+
+    Ok(Json(BlockInfoResponse {
+        block_index,
+        entropy: entropy.map(|e| hex::encode(e)),
+        predicted_close_at: prediction.map(|p| p.predicted_close_at),
+        predicted_close_low: prediction.map(|p| p.low),
+        predicted_close_high: prediction.map(|p| p.high),
+        ledger: changed_ledger,
+    })
+    .into_response())
+}
+
+/// Handles the work transaction preparation request
+async fn handle_work_prepare(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    headers: HeaderMap,
+    Json(payload): Json<WorkPrepareRequest>,
+) -> Result<Json<WorkPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Parse the nonce
+    let nonce: u64 = payload.nonce.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Invalid nonce format".to_string())),
+        )
+    })?;
+
+    app_state
+        .limits
+        .check_transaction(&payload.public_key)
+        .map_err(over_limit)?;
+
+    // Prepare the transaction (hash will be calculated in the backend)
+    let prepared = app_state
+        .kale
+        .prepare_work_transaction(
+            &payload.public_key,
+            nonce,
+            payload.block_index,
+            payload.fee_source.as_deref(),
+        )
+        .await
+        .map_err(|e| prepare_error(Locale::negotiate(&headers), e))?;
+
+    let network = app_state.kale.network_passphrase();
+
+    let signing_job = app_state.signing.create(&payload.public_key, "work");
+
+    Ok(Json(WorkPrepareResponse {
+        timings: requested_timings(&headers, &prepared),
+        restore_required: prepared.restore,
+        signing_status_url: signing::status_path(&signing_job),
+        signing_job,
+        xdr: prepared.xdr,
+        network: network.to_string(),
+    }))
+}
+
+/// Handles the work transaction submission request
+async fn handle_work_submit(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<WorkSubmitRequest>,
+) -> Result<Json<SubmittedTransaction>, (StatusCode, Json<ErrorResponse>)> {
+    // Submit the signed transaction
+    let submitted = app_state
+        .kale
+        .submit_work_transaction(&payload.signed_xdr)
+        .await
+        .map_err(|e| kale_error("Failed to submit transaction", e))?;
+
+    record_submission(&app_state, "work", &payload.signed_xdr, &submitted);
+
+    println!("\n✓ Work transaction submitted successfully!");
+    println!(
+        "Transaction hash: {} ({:?})",
+        submitted.hash, submitted.status
+    );
+
+    Ok(Json(submitted))
+}
+
+/// Handles getting Pail data for a farmer in a specific block
+async fn handle_pail_data(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Query(since): Query<SinceQuery>,
+    Json(payload): Json<PailDataRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let ((has_pail, has_worked, leading_zeros), changed_ledger) = app_state
+        .kale
+        .get_pail_data_versioned(&payload.public_key, payload.block_index)
+        .await
+        .map_err(|e| kale_error("Failed to get pail data", e))?;
+
+    if since.is_current(changed_ledger) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    Ok(Json(PailDataResponse {
+        has_pail,
+        has_worked,
+        leading_zeros,
+        ledger: changed_ledger,
+    })
+    .into_response())
+}
+
+/// Handles the harvest transaction preparation request
+async fn handle_harvest_prepare(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    headers: HeaderMap,
+    Json(payload): Json<HarvestPrepareRequest>,
+) -> Result<Json<HarvestPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
+    app_state
+        .limits
+        .check_transaction(&payload.public_key)
+        .map_err(over_limit)?;
 
     // Prepare the transaction
-    let tx_xdr = app_state
+    let prepared = app_state
         .kale
-        .prepare_plant_transaction(&payload.public_key, amount)
+        .prepare_harvest_transaction(
+            &payload.public_key,
+            payload.block_index,
+            payload.fee_source.as_deref(),
+        )
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to prepare transaction: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to prepare transaction", e))?;
 
-    // Return the full network passphrase (Albedo requires the full passphrase)
     let network = app_state.kale.network_passphrase();
 
-    Ok(Json(PlantPrepareResponse {
-        xdr: tx_xdr,
+    let signing_job = app_state.signing.create(&payload.public_key, "harvest");
+
+    Ok(Json(HarvestPrepareResponse {
+        timings: requested_timings(&headers, &prepared),
+        restore_required: prepared.restore,
+        signing_status_url: signing::status_path(&signing_job),
+        signing_job,
+        xdr: prepared.xdr,
         network: network.to_string(),
     }))
 }
 
-/// Handles the plant transaction submission request
-async fn handle_plant_submit(
+/// Prepares a transaction again from one prepared earlier, e.g. after it expired unsigned
+///
+/// The farmer, fee source, function and arguments come from the old XDR, so clients don't
+/// have to send them again; the sequence number, fees and footprint are fresh. The same
+/// limits and checks as the original prepare endpoint apply.
+async fn handle_refresh_xdr(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<PlantSubmitRequest>,
-) -> Result<Json<PlantSubmitResponse>, (StatusCode, Json<ErrorResponse>)> {
+    headers: HeaderMap,
+    Json(payload): Json<RefreshXdrRequest>,
+) -> Result<Json<RefreshXdrResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let call = app_state
+        .kale
+        .decode_farm_call(&payload.xdr)
+        .map_err(|e| kale_error("Failed to refresh transaction", e))?;
+
+    if let FarmInvocation::Plant { amount } = call.invocation {
+        check_stake_limits(&app_state, amount).await?;
+    }
+    app_state
+        .limits
+        .check_transaction(&call.farmer)
+        .map_err(over_limit)?;
+
+    let prepared = app_state
+        .kale
+        .refresh_farm_call(&call)
+        .await
+        .map_err(|e| prepare_error(Locale::negotiate(&headers), e))?;
+
+    let function = call.invocation.function_name();
+    let signing_job = app_state.signing.create(&call.farmer, function);
+
+    Ok(Json(RefreshXdrResponse {
+        timings: requested_timings(&headers, &prepared),
+        restore_required: prepared.restore,
+        signing_status_url: signing::status_path(&signing_job),
+        signing_job,
+        xdr: prepared.xdr,
+        network: app_state.kale.network_passphrase().to_string(),
+        function: function.to_string(),
+    }))
+}
+
+/// Handles the harvest transaction submission request
+async fn handle_harvest_submit(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<HarvestSubmitRequest>,
+) -> Result<Json<SubmittedTransaction>, (StatusCode, Json<ErrorResponse>)> {
     // Submit the signed transaction
-    let tx_hash = app_state
+    let submitted = app_state
         .kale
-        .submit_plant_transaction(&payload.signed_xdr)
+        .submit_harvest_transaction(&payload.signed_xdr)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to submit transaction: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to submit transaction", e))?;
 
-    println!("\n✓ Transaction submitted successfully!");
-    println!("Transaction hash: {}", tx_hash);
+    record_submission(&app_state, "harvest", &payload.signed_xdr, &submitted);
 
-    Ok(Json(PlantSubmitResponse { hash: tx_hash }))
+    println!("\n✓ Harvest transaction submitted successfully!");
+    println!(
+        "Transaction hash: {} ({:?})",
+        submitted.hash, submitted.status
+    );
+
+    Ok(Json(submitted))
 }
 
-/// Handles checking if the farmer has planted in the current block
-async fn handle_check_planted(
+/// Handles checking account status (balance and trustline)
+async fn handle_account_status(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<CheckPlantedRequest>,
-) -> Result<Json<CheckPlantedResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let has_planted = app_state
+    Json(payload): Json<AccountStatusRequest>,
+) -> Result<Json<AccountStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    Ok(Json(account_status(&app_state, &payload.public_key).await?))
+}
+
+/// An account's XLM balance and KALE trustline
+async fn account_status(
+    app_state: &AppState,
+    public_key: &str,
+) -> Result<AccountStatusResponse, (StatusCode, Json<ErrorResponse>)> {
+    // Check XLM balance
+    let xlm_balance = app_state
         .kale
-        .has_planted(&payload.public_key)
+        .get_xlm_balance(public_key)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to check planted status: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to check balance", e))?;
 
-    Ok(Json(CheckPlantedResponse { has_planted }))
+    let (exists, balance) = match xlm_balance {
+        Some(bal) => (true, bal),
+        None => (false, 0),
+    };
+
+    // Check KALE trustline
+    let (has_trustline, kale_balance) = app_state
+        .kale
+        .check_kale_trustline(public_key)
+        .await
+        .map_err(|e| kale_error("Failed to check trustline", e))?;
+
+    Ok(AccountStatusResponse {
+        exists,
+        xlm_balance: balance,
+        has_trustline,
+        kale_balance,
+    })
 }
 
-/// Handles getting the current block information
-async fn handle_block_info(
+/// Handles describing the network the server farms on
+async fn handle_network(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Json<NetworkInfoResponse> {
+    let config = &app_state.config;
+    Json(NetworkInfoResponse {
+        network: config.network.name(),
+        passphrase: config.network_passphrase.clone(),
+        contract_id: config.contract_id.clone(),
+        kale_issuer: config.kale_issuer.clone(),
+        explorer_url: config.explorer_url(),
+        friendbot: config.friendbot_url.is_some(),
+    })
+}
+
+/// Handles funding an account via friendbot
+async fn handle_fund_account(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<FundAccountRequest>,
+) -> Result<Json<FundAccountResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Mainnet accounts have to be funded with real XLM
+    let Some(friendbot_url) = &app_state.config.friendbot_url else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!(
+                "There is no friendbot on {}; send XLM to the account instead",
+                app_state.config.network.name()
+            ))),
+        ));
+    };
+
+    // Call friendbot
+    let friendbot_url = format!("{}?addr={}", friendbot_url, payload.public_key);
+
+    let client = reqwest::Client::new();
+    let response = client.get(&friendbot_url).send().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new(format!(
+                "Failed to call friendbot: {}",
+                e
+            ))),
+        )
+    })?;
+
+    if response.status().is_success() {
+        println!("\n✓ Account funded successfully via friendbot!");
+        Ok(Json(FundAccountResponse { success: true }))
+    } else {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new(format!(
+                "Friendbot request failed: {}",
+                error_text
+            ))),
+        ))
+    }
+}
+
+/// Handles preparing a trustline transaction
+async fn handle_trustline_prepare(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-) -> Result<Json<BlockInfoResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (block_index, entropy) = app_state
+    headers: HeaderMap,
+    Json(payload): Json<TrustlinePrepareRequest>,
+) -> Result<Json<TrustlinePrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
+    app_state
+        .limits
+        .check_transaction(&payload.public_key)
+        .map_err(over_limit)?;
+
+    // Prepare the trustline transaction
+    let prepared = app_state
         .kale
-        .get_block_info()
+        .prepare_add_kale_trustline_transaction(&payload.public_key, payload.fee_source.as_deref())
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to get block info: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to prepare trustline transaction", e))?;
 
-    Ok(Json(BlockInfoResponse {
-        block_index,
-        entropy: entropy.map(|e| hex::encode(e)),
+    let network = app_state.kale.network_passphrase();
+
+    Ok(Json(TrustlinePrepareResponse {
+        timings: requested_timings(&headers, &prepared),
+        xdr: prepared.xdr,
+        network: network.to_string(),
     }))
 }
 
-/// Handles the work transaction preparation request
-async fn handle_work_prepare(
+/// Handles submitting a trustline transaction
+async fn handle_trustline_submit(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<WorkPrepareRequest>,
-) -> Result<Json<WorkPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Parse the nonce
-    let nonce: u64 = payload.nonce.parse().map_err(|_| {
+    Json(payload): Json<TrustlineSubmitRequest>,
+) -> Result<Json<SubmittedTransaction>, (StatusCode, Json<ErrorResponse>)> {
+    // Submit the signed transaction
+    let submitted = app_state
+        .kale
+        .submit_trustline_transaction(&payload.signed_xdr)
+        .await
+        .map_err(|e| kale_error("Failed to submit trustline transaction", e))?;
+
+    record_submission(&app_state, "trustline", &payload.signed_xdr, &submitted);
+
+    println!("\n✓ Trustline transaction submitted successfully!");
+    println!(
+        "Transaction hash: {} ({:?})",
+        submitted.hash, submitted.status
+    );
+
+    Ok(Json(submitted))
+}
+
+/// Handles submitting a RestoreFootprint transaction handed out by a prepare endpoint
+async fn handle_restore_submit(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<RestoreSubmitRequest>,
+) -> Result<Json<SubmittedTransaction>, (StatusCode, Json<ErrorResponse>)> {
+    let submitted = app_state
+        .kale
+        .submit_restore_transaction(&payload.signed_xdr)
+        .await
+        .map_err(|e| kale_error("Failed to submit restore transaction", e))?;
+
+    record_submission(&app_state, "restore", &payload.signed_xdr, &submitted);
+
+    println!("\n✓ Restore transaction submitted successfully!");
+    println!(
+        "Transaction hash: {} ({:?})",
+        submitted.hash, submitted.status
+    );
+
+    Ok(Json(submitted))
+}
+
+/// Handles getting pail data for a list of farmers in a specific block
+async fn handle_all_farmers(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<AllFarmersRequest>,
+) -> Result<Json<AllFarmersResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Fetch pail data for all the farmer addresses provided in batched requests
+    let pails = app_state
+        .kale
+        .get_pails_for_block(&payload.farmer_addresses, payload.block_index)
+        .await
+        .map_err(|e| kale_error("Failed to get pail data", e))?;
+
+    let mut farmers_info = Vec::new();
+
+    for (farmer_address, pail) in payload.farmer_addresses.into_iter().zip(pails) {
+        match pail {
+            Ok((has_pail, has_worked, leading_zeros)) => {
+                // Only include farmers who actually planted
+                if has_pail {
+                    farmers_info.push(FarmerPailInfo {
+                        farmer_address,
+                        has_pail,
+                        has_worked,
+                        leading_zeros,
+                    });
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to get pail data for farmer {}: {}",
+                    farmer_address, e
+                );
+                // Continue with other farmers even if one fails
+            }
+        }
+    }
+
+    Ok(Json(AllFarmersResponse {
+        farmers: farmers_info,
+    }))
+}
+
+/// Handles listing every farmer that planted in a block, discovered from contract events
+async fn handle_block_farmers(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Query(query): Query<BlockFarmersQuery>,
+) -> Result<Json<AllFarmersResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let block_index = match query.block_index {
+        Some(block_index) => block_index,
+        None => app_state
+            .kale
+            .get_block_index()
+            .await
+            .map_err(|e| kale_error("Failed to get block index", e))?,
+    };
+
+    let farmers = app_state
+        .kale
+        .discover_block_farmers(block_index)
+        .await
+        .map_err(|e| kale_error("Failed to discover farmers", e))?;
+
+    Ok(Json(AllFarmersResponse {
+        farmers: farmers
+            .into_iter()
+            .map(|farmer| FarmerPailInfo {
+                farmer_address: farmer.farmer_address,
+                has_pail: true,
+                has_worked: farmer.has_worked,
+                leading_zeros: farmer.leading_zeros,
+            })
+            .collect(),
+    }))
+}
+
+/// Handles listing the recent blocks a farmer planted in and hasn't harvested yet
+async fn handle_harvestable(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Query(query): Query<HarvestableQuery>,
+) -> Result<Json<HarvestableResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let lookback = query
+        .lookback
+        .unwrap_or(DEFAULT_HARVEST_LOOKBACK)
+        .min(MAX_HARVEST_LOOKBACK);
+
+    let blocks = app_state
+        .kale
+        .find_unharvested_blocks(&query.public_key, lookback)
+        .await
+        .map_err(|e| kale_error("Failed to find unharvested blocks", e))?;
+
+    Ok(Json(HarvestableResponse { blocks }))
+}
+
+/// Handles estimating the leading zeros worth mining for, from recent blocks
+async fn handle_difficulty(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Result<Json<DifficultyEstimate>, (StatusCode, Json<ErrorResponse>)> {
+    let estimate = app_state
+        .kale
+        .estimate_difficulty()
+        .await
+        .map_err(|e| kale_error("Failed to estimate difficulty", e))?;
+
+    Ok(Json(estimate))
+}
+
+/// Handles reading any block's Block entry, e.g. a past block's
+async fn handle_block(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Path(block_index): Path<u32>,
+) -> Result<Json<BlockResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let block = app_state
+        .kale
+        .get_block(block_index)
+        .await
+        .map_err(|e| kale_error("Failed to get block", e))?;
+
+    match block {
+        Some(block) => Ok(Json(BlockResponse::new(block_index, block))),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(format!(
+                "Block {} has no entry: nobody planted in it, or it expired from temporary storage",
+                block_index
+            ))),
+        )),
+    }
+}
+
+/// Handles dumping the contract's instance storage
+async fn handle_contract_state(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Result<Json<ContractStateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let storage = app_state
+        .kale
+        .dump_instance_storage()
+        .await
+        .map_err(|e| kale_error("Failed to read instance storage", e))?;
+
+    Ok(Json(ContractStateResponse {
+        contract_id: app_state.config.contract_id.clone(),
+        last_modified_ledger: storage.last_modified_ledger,
+        storage: storage.to_json(),
+    }))
+}
+
+/// Handles explaining the outcome of a submitted transaction
+async fn handle_tx_diagnostics(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Path(hash): Path<String>,
+) -> Result<Json<TransactionDiagnostics>, (StatusCode, Json<ErrorResponse>)> {
+    let diagnostics = app_state
+        .kale
+        .get_transaction_diagnostics(&hash)
+        .await
+        .map_err(|e| kale_error("Failed to get transaction diagnostics", e))?;
+
+    Ok(Json(diagnostics))
+}
+
+/// Handles building a block-participation proof for a farmer
+async fn handle_participation_proof(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Path((public_key, block_index)): Path<(String, u32)>,
+) -> Result<Json<ParticipationProof>, (StatusCode, Json<ErrorResponse>)> {
+    let proof = app_state
+        .kale
+        .get_participation_proof(&public_key, block_index)
+        .await
+        .map_err(|e| kale_error("Failed to build participation proof", e))?;
+
+    Ok(Json(proof))
+}
+
+/// Rejects anything but a G... public key as a preferences owner
+fn check_preferences_key(public_key: &str) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if stellar_strkey::ed25519::PublicKey::from_string(public_key).is_err() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Invalid public key")),
+        ));
+    }
+    Ok(())
+}
+
+/// Handles getting a farmer's saved UI preferences; their email address is never included
+async fn handle_get_preferences(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Query(query): Query<PreferencesQuery>,
+) -> Result<Json<Preferences>, (StatusCode, Json<ErrorResponse>)> {
+    check_preferences_key(&query.public_key)?;
+
+    Ok(Json(app_state.preferences.get(&query.public_key)))
+}
+
+/// Handles saving a farmer's UI preferences; fields left out keep their saved values
+///
+/// Setting `email` or `notifications` takes a signature of the farmer's challenge, so only
+/// the key's holder can choose where its emails go. A new address gets a verification
+/// code and no notifications until the code is entered.
+async fn handle_update_preferences(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<PreferencesUpdateRequest>,
+) -> Result<Json<Preferences>, (StatusCode, Json<ErrorResponse>)> {
+    check_preferences_key(&payload.public_key)?;
+    let save_error = |e: anyhow::Error| {
         (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid nonce format".to_string(),
-            }),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new(format!(
+                "Failed to save preferences: {:#}",
+                e
+            ))),
         )
-    })?;
+    };
 
-    // Prepare the transaction (hash will be calculated in the backend)
-    let tx_xdr = app_state
-        .kale
-        .prepare_work_transaction(&payload.public_key, nonce)
-        .await
-        .map_err(|e| {
+    if let Err(e) = payload.preferences.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!("{:#}", e))),
+        ));
+    }
+
+    let mut update = payload.preferences;
+    if update.email.is_some() || update.notifications.is_some() || update.alerts.is_some() {
+        let signature = payload.signature.as_deref().ok_or_else(|| {
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to prepare transaction: {}", e),
-                }),
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new(
+                    "email, notifications and alerts need a signature of \
+                     /api/preferences/challenge",
+                )),
             )
         })?;
+        app_state
+            .challenges
+            .verify(&payload.public_key, signature)
+            .map_err(|e| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(ErrorResponse::new(format!("{:#}", e))),
+                )
+            })?;
+    }
 
-    let network = app_state.kale.network_passphrase();
+    if let Some(address) = update.email.take() {
+        if !app_state.notifier.enabled() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("This server doesn't send emails")),
+            ));
+        }
+        let code = app_state
+            .preferences
+            .set_email(&payload.public_key, &address)
+            .map_err(save_error)?;
+        let verify = Email::VerifyAddress {
+            farmer: &payload.public_key,
+            code: &code,
+        };
+        app_state.notifier.send(&address, verify).await;
+    }
 
-    Ok(Json(WorkPrepareResponse {
-        xdr: tx_xdr,
-        network: network.to_string(),
-    }))
+    let preferences = app_state
+        .preferences
+        .update(&payload.public_key, update)
+        .map_err(save_error)?;
+
+    Ok(Json(preferences))
 }
 
-/// Handles the work transaction submission request
-async fn handle_work_submit(
+/// Handles issuing the message a farmer signs to change their email, notifications or alerts
+async fn handle_preferences_challenge(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<WorkSubmitRequest>,
-) -> Result<Json<WorkSubmitResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Submit the signed transaction
-    let tx_hash = app_state
-        .kale
-        .submit_work_transaction(&payload.signed_xdr)
-        .await
+    Json(payload): Json<PreferencesChallengeRequest>,
+) -> Result<Json<PreferencesChallengeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    check_preferences_key(&payload.public_key)?;
+
+    let challenge = app_state
+        .challenges
+        .issue(&payload.public_key, "preferences")
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to submit transaction: {}", e),
-                }),
+                Json(ErrorResponse::new(format!("{:#}", e))),
             )
         })?;
 
-    println!("\n✓ Work transaction submitted successfully!");
-    println!("Transaction hash: {}", tx_hash);
-
-    Ok(Json(WorkSubmitResponse { hash: tx_hash }))
+    Ok(Json(PreferencesChallengeResponse { challenge }))
 }
 
-/// Handles getting Pail data for a farmer in a specific block
-async fn handle_pail_data(
+/// Handles confirming a farmer's email address with the code sent to it
+async fn handle_verify_email(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<PailDataRequest>,
-) -> Result<Json<PailDataResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (has_pail, has_worked, leading_zeros) = app_state
-        .kale
-        .get_pail_data(&payload.public_key, payload.block_index)
-        .await
+    Json(payload): Json<EmailVerifyRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    check_preferences_key(&payload.public_key)?;
+
+    let verified = app_state
+        .preferences
+        .verify_email(&payload.public_key, payload.code.trim())
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to get pail data: {}", e),
-                }),
+                Json(ErrorResponse::new(format!(
+                    "Failed to save preferences: {:#}",
+                    e
+                ))),
             )
         })?;
+    if !verified {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "Wrong or expired code; after 5 wrong codes the address has to be set again",
+            )),
+        ));
+    }
 
-    Ok(Json(PailDataResponse {
-        has_pail,
-        has_worked,
-        leading_zeros,
-    }))
+    Ok(Json(serde_json::json!({"status": "verified"})))
 }
 
-/// Handles the harvest transaction preparation request
-async fn handle_harvest_prepare(
+/// Handles breaking down the fee of a prepared transaction
+async fn handle_fee_explain(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<HarvestPrepareRequest>,
-) -> Result<Json<HarvestPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Prepare the transaction
-    let tx_xdr = app_state
+    Json(payload): Json<FeeExplainRequest>,
+) -> Result<Json<FeeBreakdown>, (StatusCode, Json<ErrorResponse>)> {
+    let breakdown = app_state
         .kale
-        .prepare_harvest_transaction(&payload.public_key, payload.block_index)
+        .explain_fee(&payload.xdr)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to prepare transaction: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to explain fee", e))?;
 
-    let network = app_state.kale.network_passphrase();
-
-    Ok(Json(HarvestPrepareResponse {
-        xdr: tx_xdr,
-        network: network.to_string(),
-    }))
+    Ok(Json(breakdown))
 }
 
-/// Handles the harvest transaction submission request
-async fn handle_harvest_submit(
+/// Handles getting the network's per-transaction Soroban limits and fee rates
+async fn handle_network_limits(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<HarvestSubmitRequest>,
-) -> Result<Json<HarvestSubmitResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Submit the signed transaction
-    let tx_hash = app_state
+) -> Result<Json<NetworkLimits>, (StatusCode, Json<ErrorResponse>)> {
+    let limits = app_state
         .kale
-        .submit_harvest_transaction(&payload.signed_xdr)
+        .get_network_limits()
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to submit transaction: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to get network limits", e))?;
 
-    println!("\n✓ Harvest transaction submitted successfully!");
-    println!("Transaction hash: {}", tx_hash);
+    Ok(Json(limits.clone()))
+}
 
-    Ok(Json(HarvestSubmitResponse { hash: tx_hash }))
+/// Handles getting the current KALE/USD price from the configured price source
+async fn handle_price(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+) -> Result<Json<Price>, (StatusCode, Json<ErrorResponse>)> {
+    match app_state.price.current().await {
+        Ok(Some(price)) => Ok(Json(price)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("No price source is configured")),
+        )),
+        Err(e) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse::new(format!("Failed to get price: {}", e))),
+        )),
+    }
 }
 
-/// Handles checking account status (balance and trustline)
-async fn handle_account_status(
+/// Handles advising whether to submit a transaction now or wait out a congested ledger
+async fn handle_submit_advice(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<AccountStatusRequest>,
-) -> Result<Json<AccountStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Check XLM balance
-    let xlm_balance = app_state
-        .kale
-        .get_xlm_balance(&payload.public_key)
-        .await
-        .map_err(|e| {
+    Query(query): Query<SubmitAdviceQuery>,
+) -> Result<Json<SubmitAdvice>, (StatusCode, Json<ErrorResponse>)> {
+    let urgency = Urgency::of_kind(&query.kind).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "kind must be plant, work, harvest, trustline or payout",
+            )),
+        )
+    })?;
+
+    let stats = app_state.congestion.current().await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse::new(format!(
+                "Failed to get fee stats: {:#}",
+                e
+            ))),
+        )
+    })?;
+
+    Ok(Json(congestion::advise(stats, urgency)))
+}
+
+/// Handles splitting a pool reward between workers and preparing the KALE payments
+///
+/// The contributions are the verified shares the pool coordinator collected; the returned
+/// transaction pays every worker from the pool account and is for the operator to review and sign.
+async fn handle_payout_prepare(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<PayoutPrepareRequest>,
+) -> Result<Json<PayoutPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let total_reward = payload
+        .total_reward
+        .parse::<i128>()
+        .ok()
+        .filter(|reward| *reward > 0)
+        .ok_or_else(|| {
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to check balance: {}", e),
-                }),
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "Invalid total reward: must be a positive integer in stroops",
+                )),
             )
         })?;
 
-    let (exists, balance) = match xlm_balance {
-        Some(bal) => (true, bal),
-        None => (false, 0),
-    };
+    let payouts = payout::split_rewards(total_reward, &payload.contributions).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!("Invalid payout: {}", e))),
+        )
+    })?;
+    if payouts.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("No worker has any verified shares")),
+        ));
+    }
 
-    // Check KALE trustline
-    let (has_trustline, _) = app_state
+    let prepared = app_state
         .kale
-        .check_kale_trustline(&payload.public_key)
+        .prepare_kale_payouts_transaction(
+            &payload.public_key,
+            &payouts,
+            payload.fee_source.as_deref(),
+        )
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to check trustline: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to prepare payout transaction", e))?;
 
-    Ok(Json(AccountStatusResponse {
-        exists,
-        xlm_balance: balance,
-        has_trustline,
+    let network = app_state.kale.network_passphrase();
+
+    Ok(Json(PayoutPrepareResponse {
+        payouts,
+        xdr: prepared.xdr,
+        network: network.to_string(),
     }))
 }
 
-/// Handles funding an account via friendbot
-async fn handle_fund_account(
-    State((_auth_state, _app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<FundAccountRequest>,
-) -> Result<Json<FundAccountResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Call friendbot
-    let friendbot_url = format!(
-        "https://friendbot.stellar.org?addr={}",
-        payload.public_key
-    );
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&friendbot_url)
-        .send()
-        .await
-        .map_err(|e| {
+/// Handles wrapping a farmer's signed transaction in a fee bump paid by a sponsor
+///
+/// The sponsor signs the returned envelope, which is submitted to the same endpoint the
+/// inner transaction would have gone to.
+async fn handle_feebump_prepare(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<FeeBumpPrepareRequest>,
+) -> Result<Json<FeeBumpPrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let max_fee = payload
+        .max_fee
+        .parse::<i64>()
+        .ok()
+        .filter(|fee| *fee > 0)
+        .ok_or_else(|| {
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to call friendbot: {}", e),
-                }),
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "Invalid max fee: must be a positive integer in stroops",
+                )),
             )
         })?;
 
-    if response.status().is_success() {
-        println!("\n✓ Account funded successfully via friendbot!");
-        Ok(Json(FundAccountResponse { success: true }))
-    } else {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Friendbot request failed: {}", error_text),
-            }),
-        ))
-    }
+    let max_fee = match payload.retry {
+        Some(retry) => app_state
+            .kale
+            .escalated_fee_bump(&payload.signed_xdr, retry)
+            .map_err(|e| kale_error("Failed to prepare fee bump", e))?
+            .min(max_fee),
+        None => max_fee,
+    };
+
+    let xdr = SorobanRpc::wrap_fee_bump(&payload.signed_xdr, &payload.fee_source, max_fee)
+        .map_err(|e| kale_error("Failed to prepare fee bump", e.into()))?;
+
+    let network = app_state.kale.network_passphrase();
+
+    Ok(Json(FeeBumpPrepareResponse {
+        xdr,
+        network: network.to_string(),
+    }))
 }
 
-/// Handles preparing a trustline transaction
-async fn handle_trustline_prepare(
+/// Handles computing the WebAuthn challenge for a smart wallet's authorization
+async fn handle_passkey_challenge(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<TrustlinePrepareRequest>,
-) -> Result<Json<TrustlinePrepareResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Prepare the trustline transaction
-    let tx_xdr = app_state
+    Json(payload): Json<PasskeyChallengeRequest>,
+) -> Result<Json<PasskeyChallengeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let challenge = passkey::auth_challenge(
+        &payload.xdr,
+        &payload.wallet,
+        app_state.kale.network_passphrase(),
+    )
+    .map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!("{:#}", e))),
+        )
+    })?;
+
+    Ok(Json(PasskeyChallengeResponse {
+        challenge: hex::encode(challenge),
+    }))
+}
+
+/// Handles putting a passkey assertion into a smart wallet's authorization
+///
+/// The transaction is simulated again with the signed authorization, since verifying the
+/// passkey signature costs resources the prepare simulation didn't account for.
+async fn handle_passkey_attach(
+    State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
+    Json(payload): Json<PasskeyAttachRequest>,
+) -> Result<Json<PasskeyAttachResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |e: anyhow::Error| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(format!("{:#}", e))),
+        )
+    };
+    let decode = |field: &str, value: &str| {
+        hex::decode(value).map_err(|_| anyhow!("Invalid {}: expected hex", field))
+    };
+    let assertion = PasskeyAssertion {
+        key_id: decode("keyId", &payload.key_id).map_err(bad_request)?,
+        authenticator_data: decode("authenticatorData", &payload.authenticator_data)
+            .map_err(bad_request)?,
+        client_data_json: decode("clientDataJson", &payload.client_data_json)
+            .map_err(bad_request)?,
+        signature: decode("signature", &payload.signature).map_err(bad_request)?,
+    };
+
+    let signed = passkey::attach_assertion(&payload.xdr, &payload.wallet, &assertion)
+        .map_err(bad_request)?;
+    let xdr = app_state
         .kale
-        .prepare_add_kale_trustline_transaction(&payload.public_key)
+        .finalize_signed_auth(&signed)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to prepare trustline transaction: {}", e),
-                }),
-            )
-        })?;
+        .map_err(|e| kale_error("Failed to simulate signed authorization", e))?;
 
     let network = app_state.kale.network_passphrase();
 
-    Ok(Json(TrustlinePrepareResponse {
-        xdr: tx_xdr,
+    Ok(Json(PasskeyAttachResponse {
+        xdr,
         network: network.to_string(),
     }))
 }
 
-/// Handles submitting a trustline transaction
-async fn handle_trustline_submit(
+/// Handles getting the summary of the last completed block for this server's farmers
+async fn handle_latest_report(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<TrustlineSubmitRequest>,
-) -> Result<Json<TrustlineSubmitResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Submit the signed transaction
-    let tx_hash = app_state
+) -> Result<Json<BlockReport>, (StatusCode, Json<ErrorResponse>)> {
+    let report_error = |e: KaleError| kale_error("Failed to build block report", e);
+
+    let current_block = app_state
         .kale
-        .submit_trustline_transaction(&payload.signed_xdr)
+        .get_block_index()
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to submit trustline transaction: {}", e),
-                }),
-            )
-        })?;
-
-    println!("\n✓ Trustline transaction submitted successfully!");
-    println!("Transaction hash: {}", tx_hash);
+        .map_err(report_error)?;
+    let report = app_state
+        .kale
+        .get_block_report(current_block.saturating_sub(1))
+        .await
+        .map_err(report_error)?;
 
-    Ok(Json(TrustlineSubmitResponse { hash: tx_hash }))
+    Ok(Json(report))
 }
 
-/// Handles getting pail data for a list of farmers in a specific block
-async fn handle_all_farmers(
+/// Handles listing all of an account's trustlines with balances and limits
+///
+/// The RPC can only look up trustlines for known assets, so this asks Horizon for the
+/// account's full balance list.
+async fn handle_trustlines(
     State((_auth_state, app_state)): State<(Arc<Mutex<AlbedoState>>, Arc<AppState>)>,
-    Json(payload): Json<AllFarmersRequest>,
-) -> Result<Json<AllFarmersResponse>, (StatusCode, Json<ErrorResponse>)> {
-    // Fetch pail data for each farmer address provided
-    let mut farmers_info = Vec::new();
+    Query(query): Query<TrustlinesQuery>,
+) -> Result<Json<TrustlinesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    #[derive(Deserialize)]
+    struct HorizonAccount {
+        balances: Vec<TrustlineInfo>,
+    }
 
-    for farmer_address in payload.farmer_addresses {
-        match app_state
-            .kale
-            .get_pail_data(&farmer_address, payload.block_index)
-            .await
-        {
-            Ok((has_pail, has_worked, leading_zeros)) => {
-                // Only include farmers who actually planted
-                if has_pail {
-                    farmers_info.push(FarmerPailInfo {
-                        farmer_address,
-                        has_pail,
-                        has_worked,
-                        leading_zeros,
-                    });
-                }
-            }
-            Err(e) => {
-                eprintln!(
-                    "Warning: Failed to get pail data for farmer {}: {}",
-                    farmer_address, e
-                );
-                // Continue with other farmers even if one fails
-            }
-        }
+    if stellar_strkey::ed25519::PublicKey::from_string(&query.public_key).is_err() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Invalid public key")),
+        ));
     }
 
-    Ok(Json(AllFarmersResponse {
-        farmers: farmers_info,
-    }))
+    let horizon_error = |e: reqwest::Error| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse::new(format!(
+                "Failed to get account from Horizon: {}",
+                e
+            ))),
+        )
+    };
+
+    let url = format!(
+        "{}/accounts/{}",
+        app_state.config.horizon_url, query.public_key
+    );
+    let response = reqwest::get(&url).await.map_err(horizon_error)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("Account not found")),
+        ));
+    }
+
+    let account: HorizonAccount = response
+        .error_for_status()
+        .map_err(horizon_error)?
+        .json()
+        .await
+        .map_err(horizon_error)?;
+
+    // The native XLM balance is not a trustline
+    let trustlines = account
+        .balances
+        .into_iter()
+        .filter(|balance| balance.asset_type != "native")
+        .collect();
+
+    Ok(Json(TrustlinesResponse { trustlines }))
 }
@@ -0,0 +1,132 @@
+use crate::preferences::{AlertBalance, BalanceAlert};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// A farmer's balances as last read; None where the account or KALE trustline is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Balances {
+    pub spendable_xlm: Option<i64>,
+    pub kale: Option<i64>,
+}
+
+impl Balances {
+    pub fn get(&self, balance: AlertBalance) -> Option<i64> {
+        match balance {
+            AlertBalance::SpendableXlm => self.spendable_xlm,
+            AlertBalance::Kale => self.kale,
+        }
+    }
+}
+
+/// Which balance alerts are currently met, so each fires once per crossing
+///
+/// An alert fires when its condition becomes true and is re-armed once the condition
+/// stops holding; a balance sitting under a threshold doesn't send an email per ledger.
+/// Kept in memory, so a restart fires the alerts that are met again.
+#[derive(Default)]
+pub struct AlertTracker {
+    firing: Mutex<HashSet<(String, BalanceAlert)>>,
+}
+
+impl AlertTracker {
+    /// The alerts of `farmer` that just became true for `balances`
+    pub fn check<'a>(
+        &self,
+        farmer: &str,
+        alerts: &'a [BalanceAlert],
+        balances: Balances,
+    ) -> Vec<&'a BalanceAlert> {
+        let mut firing = self.firing.lock().unwrap();
+        // Forget alerts the farmer removed, so re-adding one fires it again
+        firing.retain(|(public_key, alert)| public_key != farmer || alerts.contains(alert));
+
+        let mut fired = Vec::new();
+        for alert in alerts {
+            let key = (farmer.to_string(), alert.clone());
+            if alert.is_met(balances.get(alert.balance)) {
+                if firing.insert(key) {
+                    fired.push(alert);
+                }
+            } else {
+                firing.remove(&key);
+            }
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preferences::AlertCondition;
+
+    #[test]
+    fn test_alert_fires_once_per_crossing() {
+        let low_xlm = BalanceAlert {
+            balance: AlertBalance::SpendableXlm,
+            condition: AlertCondition::Below,
+            threshold: "20000000".to_string(),
+            message: None,
+        };
+        let sweep = BalanceAlert {
+            balance: AlertBalance::Kale,
+            condition: AlertCondition::Above,
+            threshold: "5000000000".to_string(),
+            message: Some("sweep".to_string()),
+        };
+        let alerts = vec![low_xlm.clone(), sweep.clone()];
+        let balances = |xlm, kale| Balances {
+            spendable_xlm: Some(xlm),
+            kale,
+        };
+        let tracker = AlertTracker::default();
+
+        assert!(tracker
+            .check("GA", &alerts, balances(30_000_000, Some(0)))
+            .is_empty());
+        assert_eq!(
+            tracker.check("GA", &alerts, balances(10_000_000, Some(0))),
+            vec![&low_xlm]
+        );
+        // Still low: no second email
+        assert!(tracker
+            .check("GA", &alerts, balances(5_000_000, Some(0)))
+            .is_empty());
+        // Topped up, then low again
+        assert!(tracker
+            .check("GA", &alerts, balances(30_000_000, Some(0)))
+            .is_empty());
+        assert_eq!(
+            tracker.check("GA", &alerts, balances(10_000_000, Some(6_000_000_000))),
+            vec![&low_xlm, &sweep]
+        );
+        // Another farmer's alerts are tracked apart
+        assert_eq!(
+            tracker.check("GB", &alerts, balances(10_000_000, None)),
+            vec![&low_xlm]
+        );
+
+        // A removed and re-added alert fires again
+        tracker.check(
+            "GA",
+            &alerts[..1],
+            balances(10_000_000, Some(6_000_000_000)),
+        );
+        assert_eq!(
+            tracker.check("GA", &alerts, balances(10_000_000, Some(6_000_000_000))),
+            vec![&sweep]
+        );
+    }
+
+    #[test]
+    fn test_missing_trustline_is_below_any_threshold() {
+        let alert = BalanceAlert {
+            balance: AlertBalance::Kale,
+            condition: AlertCondition::Below,
+            threshold: "0".to_string(),
+            message: None,
+        };
+        assert!(alert.is_met(None));
+        assert!(!alert.is_met(Some(0)));
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::contracts::kale::{Kale, PreparedTransaction};
+use crate::rpc::SubmittedTransaction;
+
+/// The farming operations a KALE bot needs, independent of where they run
+///
+/// Implemented by [`Kale`] (talks to Soroban RPC directly) and by
+/// [`HttpKaleClient`](crate::client::HttpKaleClient) (talks to a running server),
+/// so a bot can switch between embedded and remote operation by changing one type.
+/// Prepare methods return unsigned transaction XDR (base64); submit methods take
+/// the signed XDR and wait for the transaction to succeed or fail (see
+/// [`SubmittedTransaction`]). If the call needs archived contract entries restored first,
+/// prepare methods fail; restore them with [`Kale::submit_restore_transaction`].
+#[async_trait]
+pub trait KaleApi: Send + Sync {
+    /// Current farm block index
+    async fn get_block_index(&self) -> Result<u32>;
+
+    /// Current block index and entropy (None if nobody has planted yet)
+    async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)>;
+
+    /// Whether the farmer has planted in the current block
+    async fn has_planted(&self, farmer_public_key: &str) -> Result<bool>;
+
+    /// (has_pail, has_worked, leading_zeros) for a farmer in a block
+    async fn get_pail_data(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<(bool, bool, u32)>;
+
+    async fn prepare_plant(&self, farmer_public_key: &str, amount: i128) -> Result<String>;
+
+    async fn submit_plant(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction>;
+
+    /// `mined_block_index` is the block the nonce was mined for, if known
+    async fn prepare_work(
+        &self,
+        farmer_public_key: &str,
+        nonce: u64,
+        mined_block_index: Option<u32>,
+    ) -> Result<String>;
+
+    async fn submit_work(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction>;
+
+    async fn prepare_harvest(&self, farmer_public_key: &str, block_index: u32) -> Result<String>;
+
+    async fn submit_harvest(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction>;
+}
+
+/// The XDR of a prepared call, refusing restore transactions that the plain submit calls
+/// would mistake for the call itself
+fn prepared_xdr(prepared: PreparedTransaction) -> Result<String> {
+    if prepared.restore {
+        anyhow::bail!("Archived contract entries must be restored before this call");
+    }
+    Ok(prepared.xdr)
+}
+
+#[async_trait]
+impl KaleApi for Kale {
+    async fn get_block_index(&self) -> Result<u32> {
+        Ok(Kale::get_block_index(self).await?)
+    }
+
+    async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)> {
+        Ok(Kale::get_block_info(self).await?)
+    }
+
+    async fn has_planted(&self, farmer_public_key: &str) -> Result<bool> {
+        Ok(Kale::has_planted(self, farmer_public_key).await?)
+    }
+
+    async fn get_pail_data(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<(bool, bool, u32)> {
+        Ok(Kale::get_pail_data(self, farmer_public_key, block_index).await?)
+    }
+
+    async fn prepare_plant(&self, farmer_public_key: &str, amount: i128) -> Result<String> {
+        let prepared = self
+            .prepare_plant_transaction(farmer_public_key, amount, None)
+            .await?;
+        prepared_xdr(prepared)
+    }
+
+    async fn submit_plant(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        Ok(self.submit_plant_transaction(signed_tx_xdr).await?)
+    }
+
+    async fn prepare_work(
+        &self,
+        farmer_public_key: &str,
+        nonce: u64,
+        mined_block_index: Option<u32>,
+    ) -> Result<String> {
+        let prepared = self
+            .prepare_work_transaction(farmer_public_key, nonce, mined_block_index, None)
+            .await?;
+        prepared_xdr(prepared)
+    }
+
+    async fn submit_work(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        Ok(self.submit_work_transaction(signed_tx_xdr).await?)
+    }
+
+    async fn prepare_harvest(&self, farmer_public_key: &str, block_index: u32) -> Result<String> {
+        let prepared = self
+            .prepare_harvest_transaction(farmer_public_key, block_index, None)
+            .await?;
+        prepared_xdr(prepared)
+    }
+
+    async fn submit_harvest(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        Ok(self.submit_harvest_transaction(signed_tx_xdr).await?)
+    }
+}
@@ -0,0 +1,152 @@
+use stellar_xdr::curr::{Error, ScAddress, ScBytes, ScMap, ScSymbol, ScVal, ScVec};
+
+/// Start building the arguments of a contract call, or the parts of a storage key
+///
+/// ```ignore
+/// let args = args().address(farmer).i128(amount).build()?;
+/// let key = args().symbol("Pail").address(farmer).u32(block_index).build_vec()?;
+/// ```
+pub fn args() -> ArgsBuilder {
+    ArgsBuilder::default()
+}
+
+/// Contract call arguments, appended in parameter order
+///
+/// Conversions that can fail (symbols over 32 characters, oversized collections) don't
+/// interrupt the chain; the first failure is returned by [`ArgsBuilder::build`].
+#[derive(Debug, Default)]
+pub struct ArgsBuilder {
+    args: Vec<ScVal>,
+    error: Option<Error>,
+}
+
+impl ArgsBuilder {
+    /// `Address`
+    pub fn address(self, address: ScAddress) -> Self {
+        self.push(Ok(ScVal::Address(address)))
+    }
+
+    pub fn bool(self, value: bool) -> Self {
+        self.push(Ok(value.into()))
+    }
+
+    pub fn u32(self, value: u32) -> Self {
+        self.push(Ok(value.into()))
+    }
+
+    pub fn u64(self, value: u64) -> Self {
+        self.push(Ok(value.into()))
+    }
+
+    pub fn i64(self, value: i64) -> Self {
+        self.push(Ok(value.into()))
+    }
+
+    /// `i128`, e.g. a token amount in stroops
+    pub fn i128(self, value: i128) -> Self {
+        self.push(Ok(value.into()))
+    }
+
+    /// `Symbol` (at most 32 characters)
+    pub fn symbol(self, symbol: &str) -> Self {
+        self.push(
+            symbol
+                .try_into()
+                .map(|symbol| ScVal::Symbol(ScSymbol(symbol))),
+        )
+    }
+
+    /// `BytesN<N>`; the length is checked at compile time
+    pub fn bytes_n<const N: usize>(self, bytes: &[u8; N]) -> Self {
+        self.push(
+            bytes
+                .to_vec()
+                .try_into()
+                .map(|bytes| ScVal::Bytes(ScBytes(bytes))),
+        )
+    }
+
+    /// `Option<T>`, encoded as the value itself or `Void` for None
+    pub fn option<T: Into<ScVal>>(self, value: Option<T>) -> Self {
+        self.push(Ok(value.into()))
+    }
+
+    /// `Vec<T>`
+    pub fn vec<T: Into<ScVal>>(self, items: impl IntoIterator<Item = T>) -> Self {
+        let items: Vec<ScVal> = items.into_iter().map(Into::into).collect();
+        self.push(items.try_into().map(|items| ScVal::Vec(Some(ScVec(items)))))
+    }
+
+    /// `Map<K, V>`; entries are sorted by key, as the host requires
+    pub fn map<K, V>(self, entries: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<ScVal>,
+        V: Into<ScVal>,
+    {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()));
+        self.push(ScMap::sorted_from_pairs::<ScVal, ScVal, _>(entries).map(ScVal::from))
+    }
+
+    /// Any other value, already encoded
+    pub fn val(self, value: ScVal) -> Self {
+        self.push(Ok(value))
+    }
+
+    /// The arguments, in order
+    pub fn build(self) -> Result<Vec<ScVal>, Error> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.args),
+        }
+    }
+
+    /// The arguments as a single `Vec` value, the shape of the contract's storage keys
+    pub fn build_vec(self) -> Result<ScVal, Error> {
+        Ok(ScVal::Vec(Some(ScVec(self.build()?.try_into()?))))
+    }
+
+    fn push(mut self, value: Result<ScVal, Error>) -> Self {
+        match value {
+            Ok(value) => self.args.push(value),
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_xdr::curr::Int128Parts;
+
+    #[test]
+    fn test_builder_encodings() {
+        let built = args()
+            .i128(-1)
+            .option(None::<u32>)
+            .option(Some(7u32))
+            .map([(2u32, true), (1u32, false)])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            built[0],
+            ScVal::I128(Int128Parts {
+                hi: -1,
+                lo: u64::MAX
+            })
+        );
+        assert_eq!(built[1], ScVal::Void);
+        assert_eq!(built[2], ScVal::U32(7));
+        let ScVal::Map(Some(map)) = &built[3] else {
+            panic!("expected a map, got {:?}", built[3]);
+        };
+        assert_eq!(map[0].key, ScVal::U32(1));
+
+        assert!(args().symbol(&"x".repeat(33)).u32(1).build().is_err());
+    }
+}
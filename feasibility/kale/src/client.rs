@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::KaleApi;
+use crate::rpc::SubmittedTransaction;
+
+/// A KALE client that talks to a remote galactic-playground server over its REST API
+pub struct HttpKaleClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+#[derive(Deserialize)]
+struct PreparedResponse {
+    xdr: String,
+    #[serde(rename = "restoreRequired", default)]
+    restore_required: bool,
+}
+
+impl PreparedResponse {
+    /// The prepared XDR, unless the server handed back a restore transaction instead
+    fn into_xdr(self) -> Result<String> {
+        if self.restore_required {
+            anyhow::bail!(
+                "Archived contract entries must be restored first; submit the server's \
+                restore transaction through /api/restore/submit"
+            );
+        }
+        Ok(self.xdr)
+    }
+}
+
+#[derive(Deserialize)]
+struct BlockInfoResponse {
+    #[serde(rename = "blockIndex")]
+    block_index: u32,
+    entropy: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CheckPlantedResponse {
+    has_planted: bool,
+}
+
+#[derive(Deserialize)]
+struct PailDataResponse {
+    #[serde(rename = "hasPail")]
+    has_pail: bool,
+    #[serde(rename = "hasWorked")]
+    has_worked: bool,
+    #[serde(rename = "leadingZeros")]
+    leading_zeros: u32,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl HttpKaleClient {
+    /// Create a client for the server at `base_url`, e.g. "http://localhost:3737"
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach KALE server for {}", path))?;
+
+        Self::parse(path, response).await
+    }
+
+    async fn post<T: DeserializeOwned>(&self, path: &str, body: serde_json::Value) -> Result<T> {
+        let response = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach KALE server for {}", path))?;
+
+        Self::parse(path, response).await
+    }
+
+    /// Decode a response, surfacing the server's error message on failure
+    async fn parse<T: DeserializeOwned>(path: &str, response: reqwest::Response) -> Result<T> {
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            let message = serde_json::from_str::<ErrorResponse>(&body)
+                .map(|error| error.error)
+                .unwrap_or(body);
+            anyhow::bail!("{} failed ({}): {}", path, status, message);
+        }
+
+        serde_json::from_str(&body).with_context(|| format!("Invalid response from {}", path))
+    }
+}
+
+#[async_trait]
+impl KaleApi for HttpKaleClient {
+    async fn get_block_index(&self) -> Result<u32> {
+        Ok(self.get_block_info().await?.0)
+    }
+
+    async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)> {
+        let response: BlockInfoResponse = self.get("/api/block_info").await?;
+
+        let entropy = match response.entropy {
+            Some(entropy) => {
+                let bytes = hex::decode(&entropy).context("Block entropy is not valid hex")?;
+                Some(
+                    bytes
+                        .try_into()
+                        .map_err(|_| anyhow::anyhow!("Block entropy is not 32 bytes"))?,
+                )
+            }
+            None => None,
+        };
+
+        Ok((response.block_index, entropy))
+    }
+
+    async fn has_planted(&self, farmer_public_key: &str) -> Result<bool> {
+        let response: CheckPlantedResponse = self
+            .post(
+                "/api/check_planted",
+                json!({ "publicKey": farmer_public_key }),
+            )
+            .await?;
+        Ok(response.has_planted)
+    }
+
+    async fn get_pail_data(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<(bool, bool, u32)> {
+        let response: PailDataResponse = self
+            .post(
+                "/api/pail_data",
+                json!({ "publicKey": farmer_public_key, "blockIndex": block_index }),
+            )
+            .await?;
+        Ok((
+            response.has_pail,
+            response.has_worked,
+            response.leading_zeros,
+        ))
+    }
+
+    async fn prepare_plant(&self, farmer_public_key: &str, amount: i128) -> Result<String> {
+        let response: PreparedResponse = self
+            .post(
+                "/api/plant/prepare",
+                json!({ "publicKey": farmer_public_key, "amount": amount.to_string() }),
+            )
+            .await?;
+        response.into_xdr()
+    }
+
+    async fn submit_plant(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        self.post("/api/plant/submit", json!({ "signedXdr": signed_tx_xdr }))
+            .await
+    }
+
+    async fn prepare_work(
+        &self,
+        farmer_public_key: &str,
+        nonce: u64,
+        mined_block_index: Option<u32>,
+    ) -> Result<String> {
+        let response: PreparedResponse = self
+            .post(
+                "/api/work/prepare",
+                json!({
+                    "publicKey": farmer_public_key,
+                    "nonce": nonce.to_string(),
+                    "blockIndex": mined_block_index,
+                }),
+            )
+            .await?;
+        response.into_xdr()
+    }
+
+    async fn submit_work(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        self.post("/api/work/submit", json!({ "signedXdr": signed_tx_xdr }))
+            .await
+    }
+
+    async fn prepare_harvest(&self, farmer_public_key: &str, block_index: u32) -> Result<String> {
+        let response: PreparedResponse = self
+            .post(
+                "/api/harvest/prepare",
+                json!({ "publicKey": farmer_public_key, "blockIndex": block_index }),
+            )
+            .await?;
+        response.into_xdr()
+    }
+
+    async fn submit_harvest(&self, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        self.post("/api/harvest/submit", json!({ "signedXdr": signed_tx_xdr }))
+            .await
+    }
+}
@@ -0,0 +1,570 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::endpoint::{self, RpcEndpoint};
+use crate::rpc::FeeEscalation;
+
+/// Config file read from the working directory when no path is given
+pub const DEFAULT_CONFIG_FILE: &str = "kale.toml";
+
+pub const TESTNET_RPC_URL: &str = "https://soroban-testnet.stellar.org";
+pub const TESTNET_CONTRACT_ID: &str = "CDSWUUXGPWDZG76ISK6SUCVPZJMD5YUV66J2FXFXFGDX25XKZJIEITAO";
+pub const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+pub const TESTNET_KALE_ISSUER: &str = "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB";
+
+/// The server port unless configured otherwise
+pub const DEFAULT_SERVER_PORT: u16 = 3737;
+
+/// A Stellar network the server can farm on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Testnet,
+    Mainnet,
+    Futurenet,
+    /// A local quickstart container on port 8000
+    Local,
+}
+
+/// Well-known endpoints and deployments of a network; None where there's nothing public
+#[derive(Debug)]
+pub struct NetworkDefaults {
+    pub passphrase: &'static str,
+    pub rpc_url: Option<&'static str>,
+    pub horizon_url: &'static str,
+    pub friendbot_url: Option<&'static str>,
+    /// Block explorer base URL; append `/tx/<hash>`, `/account/<G...>` or `/contract/<C...>`
+    pub explorer_url: Option<&'static str>,
+    pub contract_id: Option<&'static str>,
+    pub kale_issuer: Option<&'static str>,
+}
+
+const TESTNET: NetworkDefaults = NetworkDefaults {
+    passphrase: TESTNET_PASSPHRASE,
+    rpc_url: Some(TESTNET_RPC_URL),
+    horizon_url: "https://horizon-testnet.stellar.org",
+    friendbot_url: Some("https://friendbot.stellar.org"),
+    explorer_url: Some("https://stellar.expert/explorer/testnet"),
+    contract_id: Some(TESTNET_CONTRACT_ID),
+    kale_issuer: Some(TESTNET_KALE_ISSUER),
+};
+
+// SDF runs no public mainnet RPC, so mainnet needs an rpc_url from a provider
+const MAINNET: NetworkDefaults = NetworkDefaults {
+    passphrase: "Public Global Stellar Network ; September 2015",
+    rpc_url: None,
+    horizon_url: "https://horizon.stellar.org",
+    friendbot_url: None,
+    explorer_url: Some("https://stellar.expert/explorer/public"),
+    contract_id: Some("CB23WRDQWGSP6YPMY4UV5C4OW5CBTXKYN3XEATG7KJEZCXMJBYEHOUOV"),
+    kale_issuer: Some("GBDVX4VELCDSQ54KQJYTNHXAHFLBCA77ZY2USQBM4CSHTTV7DME7KALE"),
+};
+
+const FUTURENET: NetworkDefaults = NetworkDefaults {
+    passphrase: "Test SDF Future Network ; October 2022",
+    rpc_url: Some("https://rpc-futurenet.stellar.org"),
+    horizon_url: "https://horizon-futurenet.stellar.org",
+    friendbot_url: Some("https://friendbot-futurenet.stellar.org"),
+    explorer_url: None,
+    contract_id: None,
+    kale_issuer: None,
+};
+
+const LOCAL: NetworkDefaults = NetworkDefaults {
+    passphrase: "Standalone Network ; February 2017",
+    rpc_url: Some("http://localhost:8000/soroban/rpc"),
+    horizon_url: "http://localhost:8000",
+    friendbot_url: Some("http://localhost:8000/friendbot"),
+    explorer_url: None,
+    contract_id: None,
+    kale_issuer: None,
+};
+
+impl Network {
+    pub fn name(self) -> &'static str {
+        match self {
+            Network::Testnet => "testnet",
+            Network::Mainnet => "mainnet",
+            Network::Futurenet => "futurenet",
+            Network::Local => "local",
+        }
+    }
+
+    pub fn defaults(self) -> &'static NetworkDefaults {
+        match self {
+            Network::Testnet => &TESTNET,
+            Network::Mainnet => &MAINNET,
+            Network::Futurenet => &FUTURENET,
+            Network::Local => &LOCAL,
+        }
+    }
+}
+
+impl std::str::FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "testnet" => Ok(Network::Testnet),
+            "mainnet" | "public" => Ok(Network::Mainnet),
+            "futurenet" => Ok(Network::Futurenet),
+            "local" | "standalone" => Ok(Network::Local),
+            _ => anyhow::bail!(
+                "Unknown network {:?}: expected testnet, mainnet, futurenet or local",
+                s
+            ),
+        }
+    }
+}
+
+/// One layer of settings: the config file, `KALE_*` variables or command-line flags
+///
+/// Unset settings fall through to the next layer down, and finally to the network's
+/// defaults, so `network = "mainnet"` alone switches every endpoint at once.
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub network: Option<Network>,
+    pub rpc_url: Option<String>,
+    /// RPC endpoints to fail over to, in order, when `rpc_url` is unreachable or overloaded
+    pub rpc_fallback_urls: Option<Vec<String>>,
+    /// Archival RPC endpoint for events and transactions older than `rpc_url` retains
+    pub history_rpc_url: Option<String>,
+    /// Extra HTTP headers for the RPC provider, e.g. an API key header
+    pub rpc_headers: Option<BTreeMap<String, String>>,
+    /// Extra query parameters for the RPC provider
+    pub rpc_query: Option<BTreeMap<String, String>>,
+    pub contract_id: Option<String>,
+    pub network_passphrase: Option<String>,
+    pub kale_issuer: Option<String>,
+    pub horizon_url: Option<String>,
+    pub friendbot_url: Option<String>,
+    pub server_port: Option<u16>,
+    /// Percent more inclusion fee each retry of a transaction bids; see [`FeeEscalation`]
+    pub fee_escalation_percent: Option<u32>,
+    /// Most inclusion fee a retry bids, in stroops
+    pub max_inclusion_fee: Option<u32>,
+}
+
+impl Settings {
+    /// Parse a TOML config file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Settings from `KALE_NETWORK`, `KALE_RPC_URL`, `KALE_RPC_FALLBACK_URLS` (separated by
+    /// ","), `KALE_HISTORY_RPC_URL`, `KALE_RPC_HEADERS`, `KALE_RPC_QUERY`, `KALE_CONTRACT_ID`, `KALE_NETWORK_PASSPHRASE`, `KALE_ISSUER`, `KALE_HORIZON_URL`,
+    /// `KALE_FRIENDBOT_URL`, `KALE_PORT`, `KALE_FEE_ESCALATION_PERCENT` and
+    /// `KALE_MAX_INCLUSION_FEE`
+    pub fn from_env() -> Result<Self> {
+        let var = |name| std::env::var(name).ok();
+
+        Ok(Self {
+            network: var("KALE_NETWORK")
+                .map(|value| value.parse())
+                .transpose()
+                .context("Invalid KALE_NETWORK")?,
+            rpc_url: var("KALE_RPC_URL"),
+            rpc_fallback_urls: var("KALE_RPC_FALLBACK_URLS").map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|url| !url.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            history_rpc_url: var("KALE_HISTORY_RPC_URL"),
+            rpc_headers: var("KALE_RPC_HEADERS")
+                .map(|value| endpoint::parse_pairs(&value, ';', ':'))
+                .transpose()
+                .context("KALE_RPC_HEADERS must be \"Name: value\" pairs separated by \";\"")?
+                .map(|pairs| pairs.into_iter().collect()),
+            rpc_query: var("KALE_RPC_QUERY")
+                .map(|value| endpoint::parse_pairs(&value, '&', '='))
+                .transpose()
+                .context("KALE_RPC_QUERY must be \"name=value\" pairs separated by \"&\"")?
+                .map(|pairs| pairs.into_iter().collect()),
+            contract_id: var("KALE_CONTRACT_ID"),
+            network_passphrase: var("KALE_NETWORK_PASSPHRASE"),
+            kale_issuer: var("KALE_ISSUER"),
+            horizon_url: var("KALE_HORIZON_URL"),
+            friendbot_url: var("KALE_FRIENDBOT_URL"),
+            server_port: var("KALE_PORT")
+                .map(|value| value.parse())
+                .transpose()
+                .context("KALE_PORT must be a port number")?,
+            fee_escalation_percent: var("KALE_FEE_ESCALATION_PERCENT")
+                .map(|value| value.parse())
+                .transpose()
+                .context("KALE_FEE_ESCALATION_PERCENT must be a whole percentage")?,
+            max_inclusion_fee: var("KALE_MAX_INCLUSION_FEE")
+                .map(|value| value.parse())
+                .transpose()
+                .context("KALE_MAX_INCLUSION_FEE must be a number of stroops")?,
+        })
+    }
+
+    /// The config file's settings with `KALE_*` environment variables and `overrides` on top
+    ///
+    /// With no `path`, `kale.toml` is read if it exists; an explicit path must exist.
+    pub fn load(path: Option<&Path>, overrides: Settings) -> Result<Self> {
+        let mut settings = match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Self::default(),
+        };
+
+        settings.merge(Self::from_env()?);
+        settings.merge(overrides);
+        Ok(settings)
+    }
+
+    /// Take every setting `over` sets, keeping the rest
+    pub fn merge(&mut self, over: Settings) {
+        self.network = over.network.or(self.network);
+        self.rpc_url = over.rpc_url.or(self.rpc_url.take());
+        self.rpc_fallback_urls = over.rpc_fallback_urls.or(self.rpc_fallback_urls.take());
+        self.history_rpc_url = over.history_rpc_url.or(self.history_rpc_url.take());
+        self.rpc_headers = over.rpc_headers.or(self.rpc_headers.take());
+        self.rpc_query = over.rpc_query.or(self.rpc_query.take());
+        self.contract_id = over.contract_id.or(self.contract_id.take());
+        self.network_passphrase = over.network_passphrase.or(self.network_passphrase.take());
+        self.kale_issuer = over.kale_issuer.or(self.kale_issuer.take());
+        self.horizon_url = over.horizon_url.or(self.horizon_url.take());
+        self.friendbot_url = over.friendbot_url.or(self.friendbot_url.take());
+        self.server_port = over.server_port.or(self.server_port);
+        self.fee_escalation_percent = over.fee_escalation_percent.or(self.fee_escalation_percent);
+        self.max_inclusion_fee = over.max_inclusion_fee.or(self.max_inclusion_fee);
+    }
+}
+
+/// Where a server's config came from, so it can be read again on reload
+#[derive(Clone, Default)]
+pub struct ConfigSource {
+    /// The config file, or None for `kale.toml` if it exists
+    pub path: Option<PathBuf>,
+    /// Command-line settings, which stay on top of the file and environment
+    pub overrides: Settings,
+}
+
+impl ConfigSource {
+    /// The settings from file and environment, with the overrides applied
+    pub fn settings(&self) -> Result<Settings> {
+        Settings::load(self.path.as_deref(), self.overrides.clone())
+    }
+
+    /// Read the config file again and resolve it like at startup
+    pub fn load(&self) -> Result<Config> {
+        Config::resolve(self.settings()?)
+    }
+}
+
+/// Where to find the network and the KALE contract, and where to serve
+///
+/// Each setting comes from, in increasing priority: the network's defaults, the TOML
+/// config file, `KALE_*` environment variables, and finally command-line overrides.
+#[derive(Clone)]
+pub struct Config {
+    pub network: Network,
+    pub rpc_url: String,
+    /// Tried in order after `rpc_url`; they get no headers or query parameters, which are
+    /// usually one provider's credentials
+    pub rpc_fallback_urls: Vec<String>,
+    /// Asked for events and transactions the others no longer keep; like the fallbacks, it
+    /// gets no headers or query parameters
+    pub history_rpc_url: Option<String>,
+    pub rpc_headers: BTreeMap<String, String>,
+    pub rpc_query: BTreeMap<String, String>,
+    pub contract_id: String,
+    pub network_passphrase: String,
+    /// Issuer of the KALE classic asset (trustlines and balances)
+    pub kale_issuer: String,
+    pub horizon_url: String,
+    /// None on mainnet, where accounts have to be funded with real XLM
+    pub friendbot_url: Option<String>,
+    pub server_port: u16,
+    /// How much more inclusion fee the daemon's retries and sponsors' fee bumps bid
+    pub fee_escalation: FeeEscalation,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::resolve(Settings::default()).expect("testnet has every default")
+    }
+}
+
+impl Config {
+    /// Load the config file, then apply `KALE_*` environment variables and `overrides`
+    ///
+    /// With no `path`, `kale.toml` is read if it exists; an explicit path must exist.
+    pub fn load(path: Option<&Path>, overrides: Settings) -> Result<Self> {
+        Self::resolve(Settings::load(path, overrides)?)
+    }
+
+    /// Fill in whatever `settings` leaves out from the network's defaults
+    ///
+    /// Fails if the network has no default for a setting that's left out, such as the
+    /// contract on futurenet, or if a faucet is configured for mainnet.
+    pub fn resolve(settings: Settings) -> Result<Self> {
+        let network = settings.network.unwrap_or_default();
+        let defaults = network.defaults();
+        let required = |value: Option<String>, default: Option<&str>, key: &str, var: &str| {
+            value.or(default.map(str::to_string)).with_context(|| {
+                format!(
+                    "{} has no default {}; set {} in {} or {}",
+                    network.name(),
+                    key,
+                    key,
+                    DEFAULT_CONFIG_FILE,
+                    var
+                )
+            })
+        };
+
+        if network == Network::Mainnet && settings.friendbot_url.is_some() {
+            anyhow::bail!("mainnet has no friendbot; remove friendbot_url");
+        }
+
+        Ok(Self {
+            network,
+            rpc_url: required(
+                settings.rpc_url,
+                defaults.rpc_url,
+                "rpc_url",
+                "KALE_RPC_URL",
+            )?,
+            rpc_fallback_urls: settings.rpc_fallback_urls.unwrap_or_default(),
+            history_rpc_url: settings.history_rpc_url,
+            rpc_headers: settings.rpc_headers.unwrap_or_default(),
+            rpc_query: settings.rpc_query.unwrap_or_default(),
+            contract_id: required(
+                settings.contract_id,
+                defaults.contract_id,
+                "contract_id",
+                "KALE_CONTRACT_ID",
+            )?,
+            network_passphrase: settings
+                .network_passphrase
+                .unwrap_or_else(|| defaults.passphrase.to_string()),
+            kale_issuer: required(
+                settings.kale_issuer,
+                defaults.kale_issuer,
+                "kale_issuer",
+                "KALE_ISSUER",
+            )?,
+            horizon_url: settings
+                .horizon_url
+                .unwrap_or_else(|| defaults.horizon_url.to_string()),
+            friendbot_url: settings
+                .friendbot_url
+                .or(defaults.friendbot_url.map(str::to_string)),
+            server_port: settings.server_port.unwrap_or(DEFAULT_SERVER_PORT),
+            fee_escalation: FeeEscalation {
+                percent_per_retry: settings
+                    .fee_escalation_percent
+                    .unwrap_or(FeeEscalation::default().percent_per_retry),
+                max_inclusion_fee: settings
+                    .max_inclusion_fee
+                    .unwrap_or(FeeEscalation::default().max_inclusion_fee),
+            },
+        })
+    }
+
+    /// The settings `next` changes that a running server can't switch to
+    ///
+    /// Only the RPC endpoints (`rpc_url`, `rpc_fallback_urls`, `history_rpc_url`,
+    /// `rpc_headers`, `rpc_query`) are reloaded; the rest are fixed into clients, signed
+    /// transactions and the listening socket until a restart.
+    pub fn restart_required(&self, next: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.network != next.network {
+            changed.push("network");
+        }
+        if self.contract_id != next.contract_id {
+            changed.push("contract_id");
+        }
+        if self.network_passphrase != next.network_passphrase {
+            changed.push("network_passphrase");
+        }
+        if self.kale_issuer != next.kale_issuer {
+            changed.push("kale_issuer");
+        }
+        if self.horizon_url != next.horizon_url {
+            changed.push("horizon_url");
+        }
+        if self.friendbot_url != next.friendbot_url {
+            changed.push("friendbot_url");
+        }
+        if self.server_port != next.server_port {
+            changed.push("server_port");
+        }
+        if self.fee_escalation != next.fee_escalation {
+            changed.push("fee_escalation_percent/max_inclusion_fee");
+        }
+        changed
+    }
+
+    /// The block explorer for the network, if it has one
+    pub fn explorer_url(&self) -> Option<&'static str> {
+        self.network.defaults().explorer_url
+    }
+
+    /// The RPC endpoint with its provider credentials
+    pub fn endpoint(&self) -> RpcEndpoint {
+        RpcEndpoint {
+            url: self.rpc_url.clone(),
+            headers: self.rpc_headers.clone().into_iter().collect(),
+            query: self.rpc_query.clone().into_iter().collect(),
+        }
+    }
+
+    /// The RPC endpoint followed by its fallbacks
+    pub fn endpoints(&self) -> Vec<RpcEndpoint> {
+        let mut endpoints = vec![self.endpoint()];
+        endpoints.extend(
+            self.rpc_fallback_urls
+                .iter()
+                .map(|url| RpcEndpoint::new(url)),
+        );
+        endpoints
+    }
+}
+
+// Header and query values are usually credentials, so they're redacted like the endpoint's
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field("network", &self.network)
+            .field("rpc", &self.endpoint())
+            .field("rpc_fallback_urls", &self.rpc_fallback_urls)
+            .field("history_rpc_url", &self.history_rpc_url)
+            .field("contract_id", &self.contract_id)
+            .field("network_passphrase", &self.network_passphrase)
+            .field("kale_issuer", &self.kale_issuer)
+            .field("horizon_url", &self.horizon_url)
+            .field("friendbot_url", &self.friendbot_url)
+            .field("server_port", &self.server_port)
+            .field("fee_escalation", &self.fee_escalation)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_overrides_defaults() {
+        let settings: Settings = toml::from_str(
+            r#"
+            rpc_url = "https://rpc.example.com"
+            rpc_fallback_urls = ["https://backup.example.com"]
+            server_port = 8080
+            fee_escalation_percent = 20
+
+            [rpc_headers]
+            X-Api-Key = "abc123"
+            "#,
+        )
+        .unwrap();
+        let config = Config::resolve(settings).unwrap();
+
+        assert_eq!(config.rpc_url, "https://rpc.example.com");
+        assert_eq!(config.server_port, 8080);
+        assert_eq!(config.fee_escalation.percent_per_retry, 20);
+        assert_eq!(config.fee_escalation.max_inclusion_fee, u32::MAX);
+        assert_eq!(config.contract_id, TESTNET_CONTRACT_ID);
+        assert_eq!(
+            config.endpoint().headers,
+            vec![("X-Api-Key".to_string(), "abc123".to_string())]
+        );
+        // Fallbacks don't get the primary provider's credentials
+        let endpoints = config.endpoints();
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints[1].url, "https://backup.example.com");
+        assert!(endpoints[1].headers.is_empty());
+
+        assert!(toml::from_str::<Settings>("rpc_ulr = \"typo\"").is_err());
+    }
+
+    #[test]
+    fn test_reload_applies_only_endpoints() {
+        let path = std::env::temp_dir().join(format!("kale-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "rpc_url = \"https://rpc.example.com\"\n").unwrap();
+        let source = ConfigSource {
+            path: Some(path.clone()),
+            overrides: Settings {
+                server_port: Some(8080),
+                ..Default::default()
+            },
+        };
+        let started = source.load().unwrap();
+
+        std::fs::write(
+            &path,
+            "rpc_url = \"https://other.example.com\"\n\
+             rpc_fallback_urls = [\"https://backup.example.com\"]\n\
+             server_port = 9090\n",
+        )
+        .unwrap();
+        let reloaded = source.load().unwrap();
+        assert_eq!(reloaded.endpoints().len(), 2);
+        // The command line still wins over the file
+        assert_eq!(reloaded.server_port, 8080);
+        assert!(started.restart_required(&reloaded).is_empty());
+
+        std::fs::write(
+            &path,
+            "network = \"futurenet\"\ncontract_id = \"C\"\nkale_issuer = \"G\"\n",
+        )
+        .unwrap();
+        let other_network = source.load().unwrap();
+        assert_eq!(
+            started.restart_required(&other_network),
+            vec![
+                "network",
+                "contract_id",
+                "network_passphrase",
+                "kale_issuer",
+                "horizon_url",
+                "friendbot_url"
+            ]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_network_switches_defaults() {
+        let mainnet = Settings {
+            network: Some(Network::Mainnet),
+            rpc_url: Some("https://rpc.example.com".to_string()),
+            ..Default::default()
+        };
+        let config = Config::resolve(mainnet.clone()).unwrap();
+        assert_eq!(config.network_passphrase, MAINNET.passphrase);
+        assert_eq!(config.horizon_url, "https://horizon.stellar.org");
+        assert_eq!(config.friendbot_url, None);
+
+        // No public mainnet RPC, and no faucet
+        let no_rpc = Settings {
+            rpc_url: None,
+            ..mainnet.clone()
+        };
+        assert!(Config::resolve(no_rpc).is_err());
+        let faucet = Settings {
+            friendbot_url: Some("https://friendbot.stellar.org".to_string()),
+            ..mainnet
+        };
+        assert!(Config::resolve(faucet).is_err());
+
+        assert_eq!("public".parse::<Network>().unwrap(), Network::Mainnet);
+    }
+}
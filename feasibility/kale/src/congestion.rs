@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Fee stats cover the last closed ledger, so there's nothing new to fetch sooner
+const STATS_TTL: Duration = Duration::from_secs(5);
+
+/// Share of ledger capacity above which the next ledger is likely to surge
+const CONGESTED_CAPACITY: f64 = 0.9;
+
+/// How long to wait before asking again: one ledger
+const RETRY_AFTER_SECS: u64 = 5;
+
+/// Recent network load, from Horizon's `/fee_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FeeStats {
+    /// The ledger the stats were computed after
+    pub ledger: u32,
+    #[serde(rename = "baseFee")]
+    pub base_fee: i64, // stroops
+    /// Fraction of the last ledger's operation capacity used, 0.0 to 1.0
+    #[serde(rename = "capacityUsage")]
+    pub capacity_usage: f64,
+    /// Median inclusion fee charged in recent ledgers, in stroops
+    #[serde(rename = "feeP50")]
+    pub fee_p50: i64,
+    #[serde(rename = "feeP90")]
+    pub fee_p90: i64,
+}
+
+impl FeeStats {
+    /// Whether transactions are paying more than the base fee to get in
+    pub fn is_surging(&self) -> bool {
+        self.fee_p50 > self.base_fee
+    }
+
+    pub fn is_congested(&self) -> bool {
+        self.capacity_usage >= CONGESTED_CAPACITY || self.is_surging()
+    }
+}
+
+/// How soon a transaction has to land
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    /// Plant and work only count within the current block
+    Urgent,
+    /// Harvests, trustlines and payouts can wait out a busy ledger
+    Deferrable,
+}
+
+impl Urgency {
+    /// The urgency of a transaction kind, or None for an unknown kind
+    pub fn of_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "plant" | "work" => Some(Urgency::Urgent),
+            "harvest" | "trustline" | "payout" => Some(Urgency::Deferrable),
+            _ => None,
+        }
+    }
+}
+
+/// Whether to submit now or wait a ledger, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmitAdvice {
+    /// "submit" or "wait"
+    pub advice: &'static str,
+    pub reason: String,
+    /// Inclusion fee in stroops that should get the transaction into the next ledger
+    #[serde(rename = "suggestedFee")]
+    pub suggested_fee: i64,
+    #[serde(rename = "retryAfterSecs", skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    pub stats: FeeStats,
+}
+
+/// Recommend submitting now or waiting out a congested ledger
+///
+/// Urgent transactions are always submitted, bidding the 90th percentile fee while the
+/// network surges; deferrable ones wait a ledger instead of paying surge prices.
+pub fn advise(stats: FeeStats, urgency: Urgency) -> SubmitAdvice {
+    let surge_fee = stats.fee_p90.max(stats.base_fee);
+
+    if !stats.is_congested() {
+        return SubmitAdvice {
+            advice: "submit",
+            reason: "The network has spare capacity and fees are at the base rate".to_string(),
+            suggested_fee: stats.base_fee,
+            retry_after_secs: None,
+            stats,
+        };
+    }
+
+    let load = format!(
+        "Ledger {} used {:.0}% of capacity and the median fee was {} stroops (base {})",
+        stats.ledger,
+        stats.capacity_usage * 100.0,
+        stats.fee_p50,
+        stats.base_fee
+    );
+    match urgency {
+        Urgency::Urgent => SubmitAdvice {
+            advice: "submit",
+            reason: format!(
+                "{}; submit now with a higher fee to stay in this block",
+                load
+            ),
+            suggested_fee: surge_fee,
+            retry_after_secs: None,
+            stats,
+        },
+        Urgency::Deferrable => SubmitAdvice {
+            advice: "wait",
+            reason: format!("{}; waiting a ledger avoids paying the surge", load),
+            suggested_fee: stats.base_fee,
+            retry_after_secs: Some(RETRY_AFTER_SECS),
+            stats,
+        },
+    }
+}
+
+/// Fetches and caches Horizon fee stats
+pub struct CongestionMonitor {
+    horizon_url: String,
+    http: reqwest::Client,
+    cached: Mutex<Option<(Instant, FeeStats)>>,
+}
+
+impl CongestionMonitor {
+    pub fn new(horizon_url: impl Into<String>) -> Self {
+        Self {
+            horizon_url: horizon_url.into(),
+            http: reqwest::Client::new(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub async fn current(&self) -> Result<FeeStats> {
+        if let Some((fetched, stats)) = *self.cached.lock().unwrap() {
+            if fetched.elapsed() < STATS_TTL {
+                return Ok(stats);
+            }
+        }
+
+        let response: HorizonFeeStats = self
+            .http
+            .get(format!("{}/fee_stats", self.horizon_url))
+            .send()
+            .await
+            .context("Failed to get fee stats from Horizon")?
+            .error_for_status()
+            .context("Horizon returned an error for fee stats")?
+            .json()
+            .await
+            .context("Invalid fee stats from Horizon")?;
+        let stats = response.parse()?;
+
+        *self.cached.lock().unwrap() = Some((Instant::now(), stats));
+        Ok(stats)
+    }
+}
+
+/// Horizon's `/fee_stats` response; every number is a string
+#[derive(Debug, Deserialize)]
+struct HorizonFeeStats {
+    last_ledger: String,
+    last_ledger_base_fee: String,
+    ledger_capacity_usage: String,
+    fee_charged: HorizonFeeDistribution,
+}
+
+#[derive(Debug, Deserialize)]
+struct HorizonFeeDistribution {
+    p50: String,
+    p90: String,
+}
+
+impl HorizonFeeStats {
+    fn parse(&self) -> Result<FeeStats> {
+        Ok(FeeStats {
+            ledger: self.last_ledger.parse().context("Invalid last_ledger")?,
+            base_fee: self
+                .last_ledger_base_fee
+                .parse()
+                .context("Invalid last_ledger_base_fee")?,
+            capacity_usage: self
+                .ledger_capacity_usage
+                .parse()
+                .context("Invalid ledger_capacity_usage")?,
+            fee_p50: self.fee_charged.p50.parse().context("Invalid fee p50")?,
+            fee_p90: self.fee_charged.p90.parse().context("Invalid fee p90")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advise_defers_only_deferrable_kinds() {
+        let stats = FeeStats {
+            ledger: 1000,
+            base_fee: 100,
+            capacity_usage: 0.97,
+            fee_p50: 250,
+            fee_p90: 1200,
+        };
+
+        let harvest = advise(stats, Urgency::of_kind("harvest").unwrap());
+        assert_eq!(harvest.advice, "wait");
+        assert_eq!(harvest.retry_after_secs, Some(RETRY_AFTER_SECS));
+
+        let work = advise(stats, Urgency::of_kind("work").unwrap());
+        assert_eq!(work.advice, "submit");
+        assert_eq!(work.suggested_fee, 1200);
+
+        let quiet = FeeStats {
+            capacity_usage: 0.4,
+            fee_p50: 100,
+            ..stats
+        };
+        assert_eq!(advise(quiet, Urgency::Deferrable).advice, "submit");
+    }
+}
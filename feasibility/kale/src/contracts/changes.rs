@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::kale::FarmerPail;
+
+/// The farm's current block and the pails planted in it, as of one ledger
+#[derive(Debug, Clone)]
+pub struct FarmSnapshot {
+    pub block_index: u32,
+    /// The latest ledger when the snapshot was taken; later snapshots of the same block
+    /// only look for plant events after it
+    pub ledger: u32,
+    pub farmers: Vec<FarmerPail>,
+}
+
+/// A farmer's work improving (or first appearing) between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ZerosImprovement {
+    pub farmer: String,
+    /// Leading zeros before, or None if the farmer hadn't worked yet
+    #[serde(rename = "previousZeros")]
+    pub previous_zeros: Option<u32>,
+    #[serde(rename = "leadingZeros")]
+    pub leading_zeros: u32,
+}
+
+/// What changed in the farm between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FarmChanges {
+    #[serde(rename = "blockIndex")]
+    pub block_index: u32,
+    /// The farm moved to a new block, so every planter in it is new
+    #[serde(rename = "newBlock")]
+    pub new_block: bool,
+    #[serde(rename = "newPlanters")]
+    pub new_planters: Vec<String>,
+    #[serde(rename = "zerosImprovements")]
+    pub zeros_improvements: Vec<ZerosImprovement>,
+}
+
+impl FarmChanges {
+    pub fn is_empty(&self) -> bool {
+        !self.new_block && self.new_planters.is_empty() && self.zeros_improvements.is_empty()
+    }
+}
+
+/// Compare two snapshots of the farm, oldest first
+///
+/// Pails only gain work within a block, so a farmer missing from `newer` (harvested, or
+/// expired from temporary storage) isn't reported.
+pub fn diff(older: &FarmSnapshot, newer: &FarmSnapshot) -> FarmChanges {
+    let new_block = older.block_index != newer.block_index;
+    let before: HashMap<&str, &FarmerPail> = if new_block {
+        HashMap::new()
+    } else {
+        older
+            .farmers
+            .iter()
+            .map(|pail| (pail.farmer_address.as_str(), pail))
+            .collect()
+    };
+
+    let mut new_planters = Vec::new();
+    let mut zeros_improvements = Vec::new();
+    for pail in &newer.farmers {
+        let previous = before.get(pail.farmer_address.as_str());
+        if previous.is_none() {
+            new_planters.push(pail.farmer_address.clone());
+        }
+
+        let previous_zeros = previous
+            .filter(|previous| previous.has_worked)
+            .map(|previous| previous.leading_zeros);
+        if pail.has_worked && previous_zeros.is_none_or(|zeros| pail.leading_zeros > zeros) {
+            zeros_improvements.push(ZerosImprovement {
+                farmer: pail.farmer_address.clone(),
+                previous_zeros,
+                leading_zeros: pail.leading_zeros,
+            });
+        }
+    }
+
+    FarmChanges {
+        block_index: newer.block_index,
+        new_block,
+        new_planters,
+        zeros_improvements,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pail(farmer: &str, has_worked: bool, leading_zeros: u32) -> FarmerPail {
+        FarmerPail {
+            farmer_address: farmer.to_string(),
+            has_worked,
+            leading_zeros,
+        }
+    }
+
+    fn snapshot(block_index: u32, farmers: Vec<FarmerPail>) -> FarmSnapshot {
+        FarmSnapshot {
+            block_index,
+            ledger: 0,
+            farmers,
+        }
+    }
+
+    #[test]
+    fn test_diff_reports_new_planters_and_better_zeros() {
+        let older = snapshot(7, vec![pail("GA", true, 5), pail("GB", false, 0)]);
+        let newer = snapshot(
+            7,
+            vec![
+                pail("GA", true, 5),
+                pail("GB", true, 3),
+                pail("GC", false, 0),
+            ],
+        );
+
+        let changes = diff(&older, &newer);
+        assert!(!changes.new_block);
+        assert_eq!(changes.new_planters, vec!["GC".to_string()]);
+        assert_eq!(
+            changes.zeros_improvements,
+            vec![ZerosImprovement {
+                farmer: "GB".to_string(),
+                previous_zeros: None,
+                leading_zeros: 3,
+            }]
+        );
+        assert!(diff(&newer, &newer).is_empty());
+
+        // In a new block everyone planted anew
+        let next = snapshot(8, vec![pail("GA", true, 2)]);
+        let changes = diff(&newer, &next);
+        assert!(changes.new_block);
+        assert_eq!(changes.new_planters, vec!["GA".to_string()]);
+        assert_eq!(changes.zeros_improvements[0].previous_zeros, None);
+    }
+}
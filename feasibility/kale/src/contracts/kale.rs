@@ -1,12 +1,796 @@
-use anyhow::{Context, Result};
-use stellar_xdr::curr::{Int128Parts, ReadXdr, ScAddress, ScVal, WriteXdr};
-use stellar_strkey::Strkey;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Instant;
+use stellar_xdr::curr::{ReadXdr, ScAddress, ScVal, WriteXdr};
+use thiserror::Error;
+
+use crate::args::args;
+use crate::config::{Config, TESTNET_KALE_ISSUER};
+use crate::contracts::changes::FarmSnapshot;
+use crate::diagnostics::{self, TransactionDiagnostics};
+use crate::endpoint::RpcEndpoint;
+use crate::network::{FeeBreakdown, NetworkLimits, NETWORK_LIMIT_SETTINGS};
+use crate::payout::Payout;
+use crate::rpc::{
+    ConfirmationOptions, ContractEvent, InclusionFeeOptions, InstanceStorage, LedgerCacheOptions,
+    RebroadcastPolicy, RetryPolicy, RpcError, SorobanRpc, SubmittedTransaction, TransactionStatus,
+};
+use crate::spec::ContractSpec;
+use crate::trace::{PrepareTrace, TraceOptions};
+
+/// User-facing farming errors that the API reports (and localizes) specifically
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FarmError {
+    /// The account has no trustline to the KALE asset
+    TrustlineMissing { asset_code: String, issuer: String },
+    /// The requested stake exceeds the account's KALE balance (both in stroops)
+    InsufficientBalance { requested: i128, available: i64 },
+    /// The requested stake is below zero, which the contract rejects (stroops)
+    NegativeStake { requested: i128 },
+    /// The farm moved to a new block after the work was mined
+    BlockAdvanced { mined: u32, current: u32 },
+    /// The farmer hasn't planted in the block they're trying to work
+    NoPail { block_index: u32 },
+    /// The farmer's recorded work already has at least as many zeros as the candidate
+    WorkAlreadyBetter {
+        existing_zeros: u32,
+        candidate_zeros: u32,
+    },
+}
+
+impl std::fmt::Display for FarmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FarmError::TrustlineMissing { asset_code, issuer } => write!(
+                f,
+                "Account does not have a trustline to {}:{}. \
+                Please add the trustline using a Stellar wallet like Albedo, Freighter, or Stellar Laboratory. \
+                Visit https://albedo.link or https://laboratory.stellar.org/#explorer to add the trustline.",
+                asset_code, issuer
+            ),
+            FarmError::InsufficientBalance {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Insufficient KALE balance: tried to stake {} KALE but only {} KALE is available",
+                format_kale(*requested),
+                format_kale(*available as i128)
+            ),
+            FarmError::NegativeStake { requested } => write!(
+                f,
+                "Invalid stake of {} KALE: the stake can't be negative. Plant 0 KALE to farm without staking.",
+                format_kale(*requested)
+            ),
+            FarmError::BlockAdvanced { mined, current } => write!(
+                f,
+                "The farm moved on to block {} while you were mining block {}. \
+                Plant in the new block and mine again.",
+                current, mined
+            ),
+            FarmError::NoPail { block_index } => write!(
+                f,
+                "You haven't planted in block {} yet. Plant first, then submit your work.",
+                block_index
+            ),
+            FarmError::WorkAlreadyBetter {
+                existing_zeros,
+                candidate_zeros,
+            } => write!(
+                f,
+                "Your recorded work already has {} leading zeros; this hash only has {}. \
+                Submitting it would cost fees without improving your harvest.",
+                existing_zeros, candidate_zeros
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FarmError {}
+
+type Result<T> = std::result::Result<T, KaleError>;
+
+/// Errors from the KALE contract client
+#[derive(Debug, Error)]
+pub enum KaleError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    /// A farming action the contract would reject, caught before the farmer pays fees
+    #[error(transparent)]
+    Farm(#[from] FarmError),
+    /// Call arguments that don't match the contract spec
+    #[error("Invalid contract call: {0}")]
+    InvalidArguments(String),
+    /// The prepared transaction exceeds the network's per-transaction limits
+    #[error("{0}")]
+    ResourceLimit(String),
+    /// Nobody has planted in the block yet, so there is no entropy to work against
+    #[error("Cannot calculate work hash - nobody has planted in block {0} yet")]
+    NoEntropy(u32),
+    /// Contract storage, code or network settings that aren't shaped as expected
+    #[error("{0}")]
+    UnexpectedData(String),
+}
+
+impl From<stellar_xdr::curr::Error> for KaleError {
+    fn from(error: stellar_xdr::curr::Error) -> Self {
+        Self::Rpc(RpcError::Xdr(error))
+    }
+}
+
+/// Whether an event's first topic is the `plant` symbol
+fn is_plant_event(event: &ContractEvent) -> bool {
+    matches!(
+        event.topics.first(),
+        Some(ScVal::Symbol(symbol)) if symbol.to_utf8_string_lossy().eq_ignore_ascii_case("plant")
+    )
+}
+
+/// Collect the addresses (G... accounts and C... contracts) in a value, looking inside
+/// vecs and maps
+fn collect_addresses(value: &ScVal, addresses: &mut Vec<String>) {
+    match value {
+        ScVal::Address(address) => addresses.push(SorobanRpc::address_strkey(address)),
+        ScVal::Vec(Some(values)) => {
+            for value in values.iter() {
+                collect_addresses(value, addresses);
+            }
+        }
+        ScVal::Map(Some(map)) => {
+            for entry in map.iter() {
+                collect_addresses(&entry.key, addresses);
+                collect_addresses(&entry.val, addresses);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Parse a farmer's address into a contract Address
+///
+/// Farmers are usually accounts (G...), but the contract takes any `Address`, so smart
+/// wallets (C..., e.g. passkey wallets) can farm too.
+fn farmer_address(farmer_public_key: &str) -> Result<ScAddress> {
+    if let Ok(stellar_strkey::Strkey::Contract(contract)) =
+        stellar_strkey::Strkey::from_string(farmer_public_key)
+    {
+        return Ok(ScAddress::Contract(stellar_xdr::curr::Hash(contract.0)));
+    }
+
+    let key = SorobanRpc::parse_account_key(farmer_public_key, "farmer address")?;
+    Ok(ScAddress::Account(stellar_xdr::curr::AccountId(
+        stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(stellar_xdr::curr::Uint256(key)),
+    )))
+}
+
+/// Whether a farmer is a contract (smart wallet) rather than an account
+fn is_contract_farmer(farmer_public_key: &str) -> bool {
+    matches!(
+        farmer_address(farmer_public_key),
+        Ok(ScAddress::Contract(_))
+    )
+}
+
+/// A farmer's entry for a block in the contract's temporary storage
+///
+/// Created by planting and removed by harvesting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pail {
+    /// The ledger the farmer planted in
+    pub sequence: u32,
+    /// Ledgers between the plant and the work; None until the farmer has worked
+    pub gap: Option<u32>,
+    /// The stake planted, in stroops
+    pub stake: i128,
+    /// Leading zeros of the work; None until the farmer has worked
+    pub zeros: Option<u32>,
+}
+
+impl Pail {
+    /// Decode a Pail ledger entry's XDR (base64 LedgerEntryData)
+    pub fn from_entry_xdr(xdr: &str) -> Result<Self> {
+        let entry_data = stellar_xdr::curr::LedgerEntryData::from_xdr_base64(
+            xdr,
+            stellar_xdr::curr::Limits::none(),
+        )?;
+        let stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) = entry_data else {
+            return Err(KaleError::UnexpectedData(
+                "Ledger entry is not ContractData".to_string(),
+            ));
+        };
+        Self::from_sc_val(&contract_data.val)
+    }
+
+    /// Decode a Pail from the contract's value, a map of its fields by name
+    pub fn from_sc_val(value: &ScVal) -> Result<Self> {
+        let ScVal::Map(Some(map)) = value else {
+            return Err(KaleError::UnexpectedData(
+                "Pail value is not a Map".to_string(),
+            ));
+        };
+        let field = |name: &str| {
+            map.iter().find_map(|entry| match &entry.key {
+                ScVal::Symbol(sym) if sym.to_utf8_string_lossy() == name => Some(&entry.val),
+                _ => None,
+            })
+        };
+        let unexpected = |name: &str, value: Option<&ScVal>| {
+            KaleError::UnexpectedData(format!("Pail {} is {:?}", name, value))
+        };
+        // Option<u32> is Void when None and the bare value when Some; a missing field or a
+        // one-element Vec is accepted too
+        let optional_u32 = |name: &str| match field(name) {
+            None | Some(ScVal::Void) => Ok(None),
+            Some(ScVal::U32(value)) => Ok(Some(*value)),
+            Some(ScVal::Vec(Some(values))) if values.is_empty() => Ok(None),
+            Some(ScVal::Vec(Some(values))) => match values.first() {
+                Some(ScVal::U32(value)) if values.len() == 1 => Ok(Some(*value)),
+                _ => Err(unexpected(name, field(name))),
+            },
+            value => Err(unexpected(name, value)),
+        };
+
+        Ok(Self {
+            sequence: match field("sequence") {
+                Some(ScVal::U32(value)) => *value,
+                value => return Err(unexpected("sequence", value)),
+            },
+            gap: optional_u32("gap")?,
+            stake: match field("stake") {
+                Some(ScVal::I128(value)) => (i128::from(value.hi) << 64) | i128::from(value.lo),
+                value => return Err(unexpected("stake", value)),
+            },
+            zeros: optional_u32("zeros")?,
+        })
+    }
+
+    /// The (has_pail, has_worked, leading_zeros) tuple of [`Kale::get_pail_data`]
+    pub fn data(&self) -> (bool, bool, u32) {
+        (true, self.zeros.is_some(), self.zeros.unwrap_or(0))
+    }
+}
+
+/// Read the `zeros` field (None until the farmer has worked) of a Pail entry's XDR
+pub fn parse_pail_zeros(entry_xdr: &str) -> Result<Option<u32>> {
+    Ok(Pail::from_entry_xdr(entry_xdr)?.zeros)
+}
+
+/// Format a KALE amount in stroops (7 decimal places) for display
+pub fn format_kale(stroops: i128) -> String {
+    let sign = if stroops < 0 { "-" } else { "" };
+    let stroops = stroops.unsigned_abs();
+    let whole = stroops / 10_000_000;
+    let fraction = stroops % 10_000_000;
+
+    if fraction == 0 {
+        format!("{}{}", sign, whole)
+    } else {
+        let fraction = format!("{:07}", fraction);
+        format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+    }
+}
+
+/// The contract's work hash: Keccak-256 of block index, nonce, entropy and farmer key
+///
+/// `farmer` is the last 32 bytes of the farmer's `ScAddress` XDR: an account's ed25519
+/// public key, or a contract's ID.
+pub fn work_hash(block_index: u32, nonce: u64, entropy: &[u8; 32], farmer: &[u8; 32]) -> [u8; 32] {
+    use sha3::{Digest, Keccak256};
+
+    // Build the 76-byte input: block_index (4) + nonce (8) + entropy (32) + farmer (32)
+    let mut hash_input = [0u8; 76];
+    hash_input[0..4].copy_from_slice(&block_index.to_be_bytes());
+    hash_input[4..12].copy_from_slice(&nonce.to_be_bytes());
+    hash_input[12..44].copy_from_slice(entropy);
+    hash_input[44..76].copy_from_slice(farmer);
+
+    Keccak256::digest(hash_input).into()
+}
+
+/// A farm block's entry in the contract's temporary storage
+///
+/// Created by the block's first plant, and updated by every plant and work in it. The
+/// min/max fields are stats over the block's farmers so far, used to normalize rewards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Unix seconds the block started
+    pub timestamp: u64,
+    pub entropy: [u8; 32],
+    /// Fewest and most ledgers between a farmer's plant and work
+    pub min_gap: u32,
+    pub max_gap: u32,
+    /// Lowest and highest stakes, in stroops
+    pub min_stake: i128,
+    pub max_stake: i128,
+    /// Fewest and most leading zeros worked (both 0 until someone works)
+    pub min_zeros: u32,
+    pub max_zeros: u32,
+    /// Sum of the stakes planted, in stroops
+    pub staked_total: i128,
+    /// Sum of the farmers' normalized scores, which their rewards are shares of
+    pub normalized_total: i128,
+}
+
+impl Block {
+    /// Decode a Block ledger entry's XDR (base64 LedgerEntryData)
+    pub fn from_entry_xdr(xdr: &str) -> Result<Self> {
+        let entry_data = stellar_xdr::curr::LedgerEntryData::from_xdr_base64(
+            xdr,
+            stellar_xdr::curr::Limits::none(),
+        )?;
+        let stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) = entry_data else {
+            return Err(KaleError::UnexpectedData(
+                "Ledger entry is not ContractData".to_string(),
+            ));
+        };
+        Self::from_sc_val(&contract_data.val)
+    }
+
+    /// Decode a Block from the contract's value, a map of its fields by name
+    pub fn from_sc_val(value: &ScVal) -> Result<Self> {
+        let ScVal::Map(Some(map)) = value else {
+            return Err(KaleError::UnexpectedData(format!(
+                "Block is not a Map: {:?}",
+                value
+            )));
+        };
+        let field = |name: &str| {
+            map.iter()
+                .find_map(|entry| match &entry.key {
+                    ScVal::Symbol(sym) if sym.to_utf8_string_lossy() == name => Some(&entry.val),
+                    _ => None,
+                })
+                .ok_or_else(|| KaleError::UnexpectedData(format!("Block has no {} field", name)))
+        };
+        let unexpected = |name: &str, value: &ScVal| {
+            KaleError::UnexpectedData(format!("Block {} is {:?}", name, value))
+        };
+        let u32_field = |name: &str| match field(name)? {
+            ScVal::U32(value) => Ok(*value),
+            value => Err(unexpected(name, value)),
+        };
+        let i128_field = |name: &str| match field(name)? {
+            ScVal::I128(value) => Ok((i128::from(value.hi) << 64) | i128::from(value.lo)),
+            value => Err(unexpected(name, value)),
+        };
+
+        Ok(Self {
+            timestamp: match field("timestamp")? {
+                ScVal::U64(value) => *value,
+                value => return Err(unexpected("timestamp", value)),
+            },
+            entropy: match field("entropy")? {
+                ScVal::Bytes(bytes) if bytes.len() == 32 => {
+                    let mut entropy = [0u8; 32];
+                    entropy.copy_from_slice(&bytes[..]);
+                    entropy
+                }
+                value => return Err(unexpected("entropy", value)),
+            },
+            min_gap: u32_field("min_gap")?,
+            max_gap: u32_field("max_gap")?,
+            min_stake: i128_field("min_stake")?,
+            max_stake: i128_field("max_stake")?,
+            min_zeros: u32_field("min_zeros")?,
+            max_zeros: u32_field("max_zeros")?,
+            staked_total: i128_field("staked_total")?,
+            normalized_total: i128_field("normalized_total")?,
+        })
+    }
+
+    /// The fewest and most zeros worked, or None if nobody has worked the block
+    pub fn zeros_range(&self) -> Option<(u32, u32)> {
+        (self.max_zeros > 0 && self.min_zeros <= self.max_zeros)
+            .then_some((self.min_zeros, self.max_zeros))
+    }
+
+    /// A worked pail's score: its stake, gap and zeros each scaled between the block's
+    /// lowest and highest, then summed. None if the pail hasn't been worked
+    pub fn score(&self, pail: &Pail) -> Option<i128> {
+        let (gap, zeros) = (pail.gap?, pail.zeros?);
+        Some(
+            normalize(pail.stake, self.min_stake, self.max_stake)
+                + normalize(gap.into(), self.min_gap.into(), self.max_gap.into())
+                + normalize(zeros.into(), self.min_zeros.into(), self.max_zeros.into()),
+        )
+    }
+
+    /// Estimate what harvesting a worked pail pays, its stake included, in stroops
+    ///
+    /// The pail's share of `normalized_total` times the block's pot: the decayed block
+    /// reward plus everything staked in it. The contract scores each work with the min/max
+    /// at the time and its own fixed-point scale, which [`SCORE_SCALE`] stands in for, so
+    /// this is an estimate. None if the pail hasn't been worked or no score is recorded.
+    pub fn estimate_reward(&self, block_index: u32, pail: &Pail) -> Option<i128> {
+        if self.normalized_total <= 0 {
+            return None;
+        }
+        let score = self.score(pail)?.min(self.normalized_total);
+        let pot = block_reward(block_index).checked_add(self.staked_total)?;
+        Some(pot.checked_mul(score)? / self.normalized_total)
+    }
+}
+
+/// Full score of each reward component (stake, gap and zeros)
+pub const SCORE_SCALE: i128 = 1_000_000;
+
+/// Scale a value within `min..=max` to `0..=SCORE_SCALE`; full marks when all are equal
+pub fn normalize(value: i128, min: i128, max: i128) -> i128 {
+    if max <= min {
+        SCORE_SCALE
+    } else {
+        (value.clamp(min, max) - min) * SCORE_SCALE / (max - min)
+    }
+}
+
+/// Block reward before decay: 501 KALE a minute for a 5 minute block, in stroops
+pub const BLOCK_REWARD: i128 = 501 * 5 * 10_000_000;
+
+/// Blocks in a 30 day month; the reward drops 5% after each
+const BLOCKS_PER_MONTH: u32 = 8_640;
+
+/// First block of the contract's current reward schedule, which decay counts from
+const V2_GENESIS_BLOCK: u32 = 30_558;
+
+/// The minted reward of a block (without the stakes it returns), after monthly decay
+pub fn block_reward(block_index: u32) -> i128 {
+    let months = block_index.saturating_sub(V2_GENESIS_BLOCK) / BLOCKS_PER_MONTH;
+    (0..months).fold(BLOCK_REWARD, |reward, _| reward * 95 / 100)
+}
+
+/// Serialize an optional amount in stroops as a string, like other i128 amounts
+fn optional_amount<S: serde::Serializer>(
+    amount: &Option<i128>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match amount {
+        Some(amount) => serializer.serialize_some(&amount.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Count the leading zeros of a work hash in hex digits, as the contract scores it
+pub fn count_leading_zeros(hash: &[u8]) -> u32 {
+    let mut zeros = 0;
+    for byte in hash {
+        if *byte == 0 {
+            zeros += 2;
+        } else {
+            if *byte < 0x10 {
+                zeros += 1;
+            }
+            break;
+        }
+    }
+    zeros
+}
+
+/// An unsigned transaction ready for the wallet, with how long each preparation stage took
+#[derive(Debug, Clone)]
+pub struct PreparedTransaction {
+    pub xdr: String,
+    pub timings: PrepareTimings,
+    /// True if this is a RestoreFootprint transaction for archived entries the requested
+    /// call needs: submit it with [`Kale::submit_restore_transaction`], then prepare again
+    pub restore: bool,
+}
+
+/// Per-stage durations of preparing a transaction, in milliseconds
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PrepareTimings {
+    #[serde(rename = "accountFetchMs")]
+    pub account_fetch_ms: u64,
+    #[serde(rename = "simulateMs", skip_serializing_if = "Option::is_none")]
+    pub simulate_ms: Option<u64>, // None for classic operations
+    #[serde(rename = "assembleMs")]
+    pub assemble_ms: u64,
+}
+
+/// A farming call, as decoded from a prepared transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FarmInvocation {
+    Plant { amount: i128 },
+    Work { hash: [u8; 32], nonce: u64 },
+    Harvest { block_index: u32 },
+}
+
+impl FarmInvocation {
+    /// The contract function called
+    pub fn function_name(&self) -> &'static str {
+        match self {
+            FarmInvocation::Plant { .. } => "plant",
+            FarmInvocation::Work { .. } => "work",
+            FarmInvocation::Harvest { .. } => "harvest",
+        }
+    }
+}
 
-use crate::rpc::SorobanRpc;
+/// A farming call decoded from a transaction prepared earlier, to be prepared again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedFarmCall {
+    pub farmer: String,
+    /// The account paying the fee, if not the farmer
+    pub fee_source: Option<String>,
+    pub invocation: FarmInvocation,
+}
+
+/// A transaction for a prepare trace: its XDR, with the fee, footprint and authorization
+/// entries pulled out (as XDR) so the stage that changed them is easy to spot
+fn traced_transaction(transaction: &stellar_xdr::curr::Transaction) -> serde_json::Value {
+    use stellar_xdr::curr::{Limits, OperationBody, TransactionExt};
+
+    fn encode(value: &impl WriteXdr) -> String {
+        value
+            .to_xdr_base64(Limits::none())
+            .unwrap_or_else(|e| format!("<unencodable: {}>", e))
+    }
+    let mut traced = serde_json::json!({
+        "xdr": encode(transaction),
+        "fee": transaction.fee,
+        "sequence": transaction.seq_num.0,
+    });
+    if let TransactionExt::V1(soroban_data) = &transaction.ext {
+        let footprint = &soroban_data.resources.footprint;
+        traced["resourceFee"] = soroban_data.resource_fee.into();
+        traced["readOnly"] = footprint.read_only.iter().map(encode).collect();
+        traced["readWrite"] = footprint.read_write.iter().map(encode).collect();
+    }
+    if let Some(OperationBody::InvokeHostFunction(op)) =
+        transaction.operations.first().map(|op| &op.body)
+    {
+        traced["auth"] = op.auth.iter().map(encode).collect();
+    }
+    traced
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+/// Minimum block duration enforced by the contract, in seconds
+const BLOCK_INTERVAL_SECS: u64 = 300;
+
+/// Number of past blocks sampled when estimating inter-block gaps
+const CLOSE_PREDICTION_SAMPLES: u32 = 12;
+
+/// Number of closed blocks sampled when estimating difficulty (an hour of blocks)
+pub const DIFFICULTY_SAMPLES: u32 = 12;
+
+/// Mining target when no recent block has work to learn from
+pub const DEFAULT_TARGET_ZEROS: u32 = 6;
+
+/// How much work recent blocks took, to pick a mining target
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DifficultyEstimate {
+    /// The current block; the estimate covers the closed blocks before it
+    #[serde(rename = "blockIndex")]
+    pub block_index: u32,
+    /// Blocks that had work to learn from
+    #[serde(rename = "sampledBlocks")]
+    pub sampled_blocks: u32,
+    /// Median over the sampled blocks of the fewest leading zeros worked
+    #[serde(rename = "medianMinZeros", skip_serializing_if = "Option::is_none")]
+    pub median_min_zeros: Option<u32>,
+    /// Median over the sampled blocks of the most leading zeros worked
+    #[serde(rename = "medianMaxZeros", skip_serializing_if = "Option::is_none")]
+    pub median_max_zeros: Option<u32>,
+    /// Zeros to mine for: halfway up the typical range, where the zeros part of the reward
+    /// reaches about half its full share ([`DEFAULT_TARGET_ZEROS`] without samples)
+    #[serde(rename = "recommendedZeros")]
+    pub recommended_zeros: u32,
+    /// Mean time between recent blocks, i.e. how long there is to mine
+    #[serde(rename = "meanBlockGapSecs")]
+    pub mean_block_gap_secs: u64,
+    /// Hashes per second to expect the recommended zeros within a typical block
+    #[serde(rename = "requiredHashRate")]
+    pub required_hash_rate: f64,
+}
+
+impl DifficultyEstimate {
+    /// Estimate from each sampled block's (fewest, most) leading zeros worked, and the gaps
+    /// in seconds between consecutive blocks
+    pub fn from_samples(block_index: u32, zeros: &[(u32, u32)], gaps: &[f64]) -> Self {
+        let median = |mut values: Vec<u32>| {
+            values.sort_unstable();
+            values.get(values.len().saturating_sub(1) / 2).copied()
+        };
+        let median_min_zeros = median(zeros.iter().map(|(min, _)| *min).collect());
+        let median_max_zeros = median(zeros.iter().map(|(_, max)| *max).collect());
+        let recommended_zeros = match (median_min_zeros, median_max_zeros) {
+            (Some(min), Some(max)) => (min + max).div_ceil(2),
+            _ => DEFAULT_TARGET_ZEROS,
+        };
+
+        let mean_gap = if gaps.is_empty() {
+            BLOCK_INTERVAL_SECS as f64
+        } else {
+            (gaps.iter().sum::<f64>() / gaps.len() as f64).max(BLOCK_INTERVAL_SECS as f64)
+        };
+        // Each hex zero is 4 bits, so z zeros take 16^z hashes on average
+        let expected_hashes = 16f64.powi(recommended_zeros as i32);
+
+        Self {
+            block_index,
+            sampled_blocks: zeros.len() as u32,
+            median_min_zeros,
+            median_max_zeros,
+            recommended_zeros,
+            mean_block_gap_secs: mean_gap as u64,
+            required_hash_rate: expected_hashes / mean_gap,
+        }
+    }
+}
+
+/// Gaps in seconds between consecutive blocks' (index, start timestamp) pairs, in order
+///
+/// Only consecutive blocks give a real gap; expired entries leave holes.
+fn consecutive_gaps(timestamps: &[(u32, u64)]) -> Vec<f64> {
+    timestamps
+        .windows(2)
+        .filter(|pair| pair[1].0 == pair[0].0 + 1)
+        .map(|pair| pair[1].1.saturating_sub(pair[0].1) as f64)
+        .collect()
+}
+
+/// Predicted closing time of the current block (unix seconds)
+#[derive(Debug, Clone, Copy)]
+pub struct BlockClosePrediction {
+    pub predicted_close_at: u64,
+    /// Lower bound of the ~95% confidence interval
+    pub low: u64,
+    /// Upper bound of the ~95% confidence interval
+    pub high: u64,
+}
+
+/// Average ledger close time, for turning block timestamps into ledger sequences
+const LEDGER_CLOSE_SECS: u64 = 5;
+
+/// Extra ledgers scanned before a block's estimated first ledger, to absorb slow ledgers
+const DISCOVERY_MARGIN_LEDGERS: u32 = 24;
+
+/// getEvents pages read when discovering a block's farmers
+const DISCOVERY_MAX_PAGES: usize = 20;
+
+/// A farmer found to have planted in a block, with their pail
+#[derive(Debug, Clone, Serialize)]
+pub struct FarmerPail {
+    #[serde(rename = "farmerAddress")]
+    pub farmer_address: String,
+    #[serde(rename = "hasWorked")]
+    pub has_worked: bool,
+    #[serde(rename = "leadingZeros")]
+    pub leading_zeros: u32,
+}
+
+/// A past block a farmer planted in and can still harvest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnharvestedBlock {
+    #[serde(rename = "blockIndex")]
+    pub block_index: u32,
+    #[serde(rename = "hasWorked")]
+    pub has_worked: bool,
+    #[serde(rename = "leadingZeros")]
+    pub leading_zeros: u32,
+    /// The last ledger both the pail and the block are live in; harvest before it passes
+    #[serde(rename = "liveUntilLedger", skip_serializing_if = "Option::is_none")]
+    pub live_until_ledger: Option<u32>,
+    /// Ledgers (about 5 seconds each) left until then, as of the scan
+    #[serde(rename = "expiresInLedgers", skip_serializing_if = "Option::is_none")]
+    pub expires_in_ledgers: Option<u32>,
+}
+
+/// Ledgers during which a plant confirmed through this client is trusted over the RPC node
+const READ_YOUR_WRITES_LEDGERS: u32 = 5;
+
+/// Blocks for which submitted transaction hashes are kept for participation proofs (~1 day)
+const PROOF_RETENTION_BLOCKS: u32 = 288;
+
+/// Ledgers a farmer's authorization stays valid when a fee source pays (~10 minutes)
+const AUTH_VALIDITY_LEDGERS: u32 = 120;
+
+/// Everything a third party needs to check a farmer's participation in a block
+/// against any RPC node, without trusting this server
+#[derive(Debug, Serialize)]
+pub struct ParticipationProof {
+    pub farmer: String,
+    #[serde(rename = "blockIndex")]
+    pub block_index: u32,
+    #[serde(rename = "contractId")]
+    pub contract_id: String,
+    #[serde(rename = "networkPassphrase")]
+    pub network_passphrase: String,
+    /// The farmer's Pail(farmer, block) entry; None if it doesn't exist (or has expired)
+    pub pail: Option<ProvenLedgerEntry>,
+    /// The Block(block) entry; None if it doesn't exist (or has expired)
+    pub block: Option<ProvenLedgerEntry>,
+    /// Plant and work transactions for this block that were submitted through this server
+    #[serde(rename = "transactionHashes")]
+    pub transaction_hashes: Vec<String>,
+}
+
+/// A ledger entry as returned by getLedgerEntries, re-fetchable with its key
+#[derive(Debug, Serialize)]
+pub struct ProvenLedgerEntry {
+    /// LedgerKey XDR (base64)
+    pub key: String,
+    /// LedgerEntryData XDR (base64)
+    pub xdr: String,
+    #[serde(rename = "lastModifiedLedger")]
+    pub last_modified_ledger: u32,
+}
+
+/// What the farmers using this server did in one block
+///
+/// Rewards are estimates ([`Block::estimate_reward`]); what a harvest pays is exact.
+#[derive(Debug, Serialize)]
+pub struct BlockReport {
+    #[serde(rename = "blockIndex")]
+    pub block_index: u32,
+    pub farmers: Vec<FarmerBlockSummary>,
+    /// Most leading zeros among this server's farmers (None if nobody worked)
+    #[serde(rename = "bestZeros")]
+    pub best_zeros: Option<u32>,
+    /// Fees charged for all of the block's transactions submitted through this server
+    #[serde(rename = "totalFeesCharged")]
+    pub total_fees_charged: i64, // stroops
+}
+
+/// One farmer's part in a [`BlockReport`]
+///
+/// Pail fields reflect the current ledger: after a harvest the pail is gone.
+#[derive(Debug, Serialize)]
+pub struct FarmerBlockSummary {
+    pub farmer: String,
+    #[serde(rename = "hasPail")]
+    pub has_pail: bool,
+    #[serde(rename = "hasWorked")]
+    pub has_worked: bool,
+    #[serde(rename = "leadingZeros")]
+    pub leading_zeros: u32,
+    #[serde(rename = "transactionHashes")]
+    pub transaction_hashes: Vec<String>,
+    /// Fees charged for this farmer's transactions that have landed
+    #[serde(rename = "feesCharged")]
+    pub fees_charged: i64, // stroops
+    /// What harvesting should pay, stake included (None if not worked, or harvested)
+    #[serde(rename = "estimatedReward", serialize_with = "optional_amount")]
+    pub estimated_reward: Option<i128>, // stroops
+}
+
+/// A plant submitted through this client that the RPC node may not reflect yet
+#[derive(Debug, Clone, Copy)]
+struct PendingPlant {
+    block_index: u32,
+    /// A ledger the plant is known to be applied in or before
+    confirmed_ledger: u32,
+}
+
+/// Mean and standard deviation of observed inter-block gaps, in seconds
+#[derive(Debug, Clone, Copy)]
+struct GapStats {
+    mean: f64,
+    std_dev: f64,
+}
 
 /// KALE contract client
 pub struct Kale {
     rpc: SorobanRpc,
+    spec: tokio::sync::OnceCell<ContractSpec>,
+    network_limits: tokio::sync::OnceCell<NetworkLimits>,
+    // Gap statistics are only recomputed when the block index changes
+    gap_stats: std::sync::Mutex<Option<(u32, GapStats)>>,
+    // Likewise the difficulty estimate, which only samples closed blocks
+    difficulty: std::sync::Mutex<Option<DifficultyEstimate>>,
+    // The highest block index read, so cached ledger reads are dropped when it advances
+    seen_block_index: std::sync::Mutex<u32>,
+    // Recent plants by farmer, so reads right after a submission don't flap
+    pending_plants: std::sync::Mutex<HashMap<String, PendingPlant>>,
+    // Hashes of plant/work transactions submitted through this client, by (farmer, block)
+    farm_transactions: std::sync::Mutex<HashMap<(String, u32), Vec<String>>>,
+    rebroadcast: RebroadcastPolicy,
+    confirmation: ConfirmationOptions,
+    trace: TraceOptions,
+    kale_issuer: String,
 }
 
 impl Kale {
@@ -17,8 +801,115 @@ impl Kale {
     /// * `contract_address` - The KALE contract address
     /// * `network_passphrase` - The network passphrase
     pub fn new(rpc_url: &str, contract_address: &str, network_passphrase: &str) -> Result<Self> {
-        let rpc = SorobanRpc::new(rpc_url, contract_address, network_passphrase)?;
-        Ok(Self { rpc })
+        Self::with_endpoint(
+            &RpcEndpoint::new(rpc_url),
+            contract_address,
+            network_passphrase,
+        )
+    }
+
+    /// Create a KALE contract client for an RPC endpoint that needs credentials
+    pub fn with_endpoint(
+        endpoint: &RpcEndpoint,
+        contract_address: &str,
+        network_passphrase: &str,
+    ) -> Result<Self> {
+        let rpc = SorobanRpc::with_endpoint(endpoint, contract_address, network_passphrase)?;
+        Ok(Self::with_rpc(rpc))
+    }
+
+    fn with_rpc(rpc: SorobanRpc) -> Self {
+        Self {
+            rpc,
+            spec: tokio::sync::OnceCell::new(),
+            network_limits: tokio::sync::OnceCell::new(),
+            gap_stats: std::sync::Mutex::new(None),
+            difficulty: std::sync::Mutex::new(None),
+            seen_block_index: std::sync::Mutex::new(0),
+            pending_plants: std::sync::Mutex::new(HashMap::new()),
+            farm_transactions: std::sync::Mutex::new(HashMap::new()),
+            rebroadcast: RebroadcastPolicy::default(),
+            confirmation: ConfirmationOptions::default(),
+            trace: TraceOptions::default(),
+            kale_issuer: TESTNET_KALE_ISSUER.to_string(),
+        }
+    }
+
+    /// Create a KALE contract client for the network and contract in a [`Config`], failing
+    /// over to its fallback RPC endpoints
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut kale = Self::with_rpc(SorobanRpc::from_config(config)?);
+        kale.kale_issuer = config.kale_issuer.clone();
+        Ok(kale)
+    }
+
+    /// Set how long submit calls wait for their transaction to succeed or fail
+    pub fn set_confirmation_options(&mut self, options: ConfirmationOptions) {
+        self.confirmation = options;
+    }
+
+    /// Set how calls to the RPC node are retried when it's briefly unavailable
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.rpc.set_retry_policy(policy);
+    }
+
+    /// Set which inclusion fee prepared contract calls bid
+    pub fn set_inclusion_fee_options(&mut self, options: InclusionFeeOptions) {
+        self.rpc.set_inclusion_fee_options(options);
+    }
+
+    /// Raise the inclusion fee of a prepared, unsigned transaction for retry `retry` of it,
+    /// per the config's fee escalation
+    pub fn escalate_inclusion_fee(&self, tx_xdr: &str, retry: u32) -> Result<String> {
+        Ok(self.rpc.escalate_inclusion_fee(tx_xdr, retry)?)
+    }
+
+    /// The outer fee for retry `retry` of a sponsor's fee bump of a signed transaction, per
+    /// the config's fee escalation
+    pub fn escalated_fee_bump(&self, inner_signed_xdr: &str, retry: u32) -> Result<i64> {
+        Ok(self.rpc.escalated_fee_bump(inner_signed_xdr, retry)?)
+    }
+
+    /// Set how long contract instance and block reads are reused between API calls
+    pub fn set_ledger_cache_options(&mut self, options: LedgerCacheOptions) {
+        self.rpc.set_ledger_cache_options(options);
+    }
+
+    /// Set where contract call prepares dump their intermediate artifacts for bug reports
+    pub fn set_trace_options(&mut self, options: TraceOptions) {
+        self.trace = options;
+    }
+
+    /// Drop cached ledger reads when the farm has moved to a new block since the last read
+    fn note_block_index(&self, block_index: u32) {
+        let mut seen = self.seen_block_index.lock().unwrap();
+        if block_index > *seen {
+            if *seen != 0 {
+                self.rpc.clear_ledger_cache();
+            }
+            *seen = block_index;
+        }
+    }
+
+    /// Set when submissions watched by [`Kale::watch_submission`] are rebroadcast
+    pub fn set_rebroadcast_policy(&mut self, policy: RebroadcastPolicy) {
+        self.rebroadcast = policy;
+    }
+
+    /// Follow a submitted transaction until it lands, rebroadcasting it if it goes missing
+    ///
+    /// Returns the final status ("SUCCESS" or "FAILED")
+    pub async fn watch_submission(&self, signed_tx_xdr: &str, tx_hash: &str) -> Result<String> {
+        let response = self
+            .rpc
+            .watch_transaction(signed_tx_xdr, tx_hash, &self.rebroadcast)
+            .await?;
+        Ok(response.status)
+    }
+
+    /// Every key and value in the KALE contract's instance storage (FarmIndex, FarmBlock, ...)
+    pub async fn dump_instance_storage(&self) -> Result<InstanceStorage> {
+        Ok(self.rpc.dump_instance_storage().await?)
     }
 
     /// Get the current farm block index from the KALE contract
@@ -29,83 +920,314 @@ impl Kale {
         let value = SorobanRpc::parse_instance_storage_value(&instance, "FarmIndex")?;
 
         if let ScVal::U32(index) = value {
+            self.note_block_index(index);
             Ok(index)
         } else {
-            anyhow::bail!("FarmIndex is not a U32 value: {:?}", value)
+            Err(KaleError::UnexpectedData(format!(
+                "FarmIndex is not a U32 value: {:?}",
+                value
+            )))
         }
     }
 
+    /// Get the contract spec
+    ///
+    /// The spec is read from the contract's WASM on first use and kept for the
+    /// lifetime of the client.
+    async fn contract_spec(&self) -> Result<&ContractSpec> {
+        self.spec
+            .get_or_try_init(|| async {
+                let wasm = self.rpc.get_contract_wasm().await?;
+                ContractSpec::from_wasm(&wasm)
+                    .map_err(|e| KaleError::UnexpectedData(format!("{:#}", e)))
+            })
+            .await
+    }
+
+    /// Validate call arguments against the contract spec before simulating
+    async fn validate_args(&self, function_name: &str, args: &[ScVal]) -> Result<()> {
+        self.contract_spec()
+            .await?
+            .validate_args(function_name, args)
+            .map_err(|e| KaleError::InvalidArguments(e.to_string()))
+    }
+
+    /// Get the network's per-transaction Soroban limits and fee rates
+    ///
+    /// Read from the network's config settings on first use and kept for the lifetime of
+    /// the client; they only change through network upgrades.
+    pub async fn get_network_limits(&self) -> Result<&NetworkLimits> {
+        self.network_limits
+            .get_or_try_init(|| async {
+                let settings = self
+                    .rpc
+                    .get_config_settings(&NETWORK_LIMIT_SETTINGS)
+                    .await?;
+                NetworkLimits::from_config_settings(&settings)
+                    .map_err(|e| KaleError::UnexpectedData(format!("{:#}", e)))
+            })
+            .await
+    }
+
+    /// Break down the fee of a prepared transaction (base64 envelope XDR)
+    pub async fn explain_fee(&self, tx_xdr: &str) -> Result<FeeBreakdown> {
+        let envelope = stellar_xdr::curr::TransactionEnvelope::from_xdr_base64(
+            tx_xdr,
+            stellar_xdr::curr::Limits::none(),
+        )
+        .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+
+        self.get_network_limits()
+            .await?
+            .explain_fee(&envelope)
+            .map_err(|e| KaleError::InvalidArguments(format!("{:#}", e)))
+    }
+
+    /// Update the resources and fee of a prepared transaction after a smart wallet signed
+    /// its authorization, returning the XDR for the fee source to sign
+    ///
+    /// The prepare simulation didn't run the wallet's signature check, so the transaction
+    /// would otherwise run out of instructions verifying it.
+    pub async fn finalize_signed_auth(&self, tx_xdr: &str) -> Result<String> {
+        let transaction = self.rpc.resimulate_signed_auth(tx_xdr).await?;
+
+        if let stellar_xdr::curr::TransactionExt::V1(soroban_data) = &transaction.ext {
+            self.get_network_limits()
+                .await?
+                .check_resources(&soroban_data.resources)
+                .map_err(|e| KaleError::ResourceLimit(e.to_string()))?;
+        }
+
+        Self::envelope_xdr(transaction)
+    }
+
+    /// Fill the caches that the first requests would otherwise fill with cold RPC round trips
+    ///
+    /// Loads the contract spec (instance, code and WASM parsing), the network limits and
+    /// the inter-block gap statistics used for close predictions.
+    pub async fn prime_caches(&self) -> Result<()> {
+        self.contract_spec().await?;
+        self.get_network_limits().await?;
+
+        let block_index = self.get_block_index().await?;
+        self.predict_block_close(block_index).await?;
+
+        Ok(())
+    }
+
+    /// Whether the contract spec has been loaded, by priming or by a first request
+    pub fn is_primed(&self) -> bool {
+        self.spec.initialized()
+    }
+
     /// Build, simulate, and prepare a plant transaction
     ///
     /// # Arguments
     /// * `farmer_public_key` - The farmer's Stellar public key
     /// * `amount` - The amount of KALE to stake (in stroops, 7 decimal places)
+    /// * `fee_source` - Account paying the fee and supplying the sequence number, if not the farmer
     ///
-    /// Returns the transaction XDR (base64) ready for signing
+    /// Returns the transaction XDR (base64) ready for signing, with stage timings
     pub async fn prepare_plant_transaction(
         &self,
         farmer_public_key: &str,
         amount: i128,
-    ) -> Result<String> {
+        fee_source: Option<&str>,
+    ) -> Result<PreparedTransaction> {
         // KALE token details (from the contract)
         const KALE_ASSET_CODE: &str = "KALE";
-        const KALE_ISSUER: &str = "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB";
 
-        // Check if the farmer has a trustline to the KALE token
-        let (has_trustline, _balance) = self
-            .rpc
-            .check_trustline_and_balance(farmer_public_key, KALE_ASSET_CODE, KALE_ISSUER)
-            .await?;
+        if amount < 0 {
+            return Err(FarmError::NegativeStake { requested: amount }.into());
+        }
 
-        if !has_trustline {
-            anyhow::bail!(
-                "Account does not have a trustline to {}:{}. \
-                Please add the trustline using a Stellar wallet like Albedo, Freighter, or Stellar Laboratory. \
-                Visit https://albedo.link or https://laboratory.stellar.org/#explorer to add the trustline.",
-                KALE_ASSET_CODE,
-                KALE_ISSUER
-            );
+        // Contract farmers hold KALE as a token contract balance rather than a trustline,
+        // so for them simulation is what catches a stake they can't cover
+        if !is_contract_farmer(farmer_public_key) {
+            // Check if the farmer has a trustline to the KALE token
+            let (has_trustline, balance) = self
+                .rpc
+                .check_trustline_and_balance(farmer_public_key, KALE_ASSET_CODE, &self.kale_issuer)
+                .await?;
+
+            if !has_trustline {
+                return Err(FarmError::TrustlineMissing {
+                    asset_code: KALE_ASSET_CODE.to_string(),
+                    issuer: self.kale_issuer.clone(),
+                }
+                .into());
+            }
+
+            // The contract burns the stake, so it can't exceed the farmer's balance
+            if amount > balance as i128 {
+                return Err(FarmError::InsufficientBalance {
+                    requested: amount,
+                    available: balance,
+                }
+                .into());
+            }
         }
+
         // Parse farmer address to ScAddress
-        let farmer_strkey = Strkey::from_string(farmer_public_key)?;
-        let farmer_address = match farmer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                ScAddress::Account(stellar_xdr::curr::AccountId(
-                    stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(
-                        stellar_xdr::curr::Uint256(pk.0)
-                    )
-                ))
-            }
-            _ => anyhow::bail!("Invalid farmer public key type"),
-        };
+        let farmer_address = farmer_address(farmer_public_key)?;
 
         // Build function arguments for plant(farmer: Address, amount: i128)
-        let args = vec![
-            ScVal::Address(farmer_address),
-            ScVal::I128(Int128Parts {
-                hi: (amount >> 64) as i64,
-                lo: (amount & 0xFFFFFFFFFFFFFFFF) as u64,
-            }),
-        ];
+        let args = args().address(farmer_address).i128(amount).build()?;
 
-        // Build the transaction
-        let mut transaction = self.rpc
-            .build_invoke_transaction(farmer_public_key, "plant", args)
+        // Catch malformed arguments before paying for a simulation round trip
+        self.validate_args("plant", &args).await?;
+
+        self.assemble_invocation(farmer_public_key, fee_source, "plant", args)
+            .await
+    }
+
+    /// Build, simulate and assemble a contract invocation, timing each stage
+    ///
+    /// With a distinct fee source, the transaction is that account's and the farmer only
+    /// authorizes the call: the farmer signs the authorization entries (valid for
+    /// [`AUTH_VALIDITY_LEDGERS`]), and the fee source signs the envelope.
+    ///
+    /// With [`TraceOptions`] set, every stage's output is written to a bundle file.
+    async fn assemble_invocation(
+        &self,
+        farmer_public_key: &str,
+        fee_source: Option<&str>,
+        function_name: &str,
+        args: Vec<ScVal>,
+    ) -> Result<PreparedTransaction> {
+        let mut trace = PrepareTrace::new(&self.trace, function_name);
+        trace.context("farmer", farmer_public_key);
+        trace.context("feeSource", fee_source);
+        trace.context("contract", self.rpc.contract_id().to_string());
+        trace.context("networkPassphrase", self.rpc.network_passphrase());
+        trace.context("endpoint", self.rpc.active_endpoint());
+
+        let prepared = self
+            .assemble_traced(
+                farmer_public_key,
+                fee_source,
+                function_name,
+                args,
+                &mut trace,
+            )
+            .await;
+        trace.finish(prepared.as_ref().err(), |text| self.rpc.redact(text));
+        prepared
+    }
+
+    /// [`Kale::assemble_invocation`], recording each stage's output in `trace`
+    async fn assemble_traced(
+        &self,
+        farmer_public_key: &str,
+        fee_source: Option<&str>,
+        function_name: &str,
+        args: Vec<ScVal>,
+        trace: &mut PrepareTrace,
+    ) -> Result<PreparedTransaction> {
+        // Only accounts can be transaction sources, so a contract farmer needs a fee source
+        if fee_source.is_none() && is_contract_farmer(farmer_public_key) {
+            return Err(KaleError::InvalidArguments(format!(
+                "Contract farmer {} can't sign a transaction; prepare it with a fee source account",
+                farmer_public_key
+            )));
+        }
+        let source_public_key = fee_source.unwrap_or(farmer_public_key);
+
+        // Build the transaction (fetches the source account's sequence number)
+        let started = Instant::now();
+        let transaction = self
+            .rpc
+            .build_invoke_transaction(source_public_key, function_name, args)
             .await?;
+        let account_fetch_ms = elapsed_ms(started);
+        if trace.is_enabled() {
+            trace.step("unsigned", traced_transaction(&transaction));
+        }
 
         // Simulate to get fees and footprint
-        let simulation = self.rpc.simulate_transaction(&transaction).await?;
+        let started = Instant::now();
+        let simulation = self
+            .rpc
+            .simulate_transaction(&transaction)
+            .await
+            .map_err(|e| {
+                self.rpc.release_sequence(&transaction);
+                e
+            })?;
+        let simulate_ms = elapsed_ms(started);
+        trace.step("simulation", &simulation);
+
+        // Archived entries have to be restored before the call can succeed, so hand back
+        // the restore transaction instead
+        if let Some(preamble) = &simulation.restore_preamble {
+            let started = Instant::now();
+            self.rpc.release_sequence(&transaction);
+            let transaction = self
+                .rpc
+                .build_restore_transaction(source_public_key, preamble)
+                .await?;
+            let xdr = Self::envelope_xdr(transaction)?;
+            trace.step("restore", serde_json::json!({ "xdr": xdr }));
+
+            return Ok(PreparedTransaction {
+                xdr,
+                timings: PrepareTimings {
+                    account_fetch_ms,
+                    simulate_ms: Some(simulate_ms),
+                    assemble_ms: elapsed_ms(started),
+                },
+                restore: true,
+            });
+        }
 
         // Check for simulation errors
         if let Some(error) = &simulation.error {
-            anyhow::bail!("Transaction simulation failed: {}", error);
+            self.rpc.release_sequence(&transaction);
+            return Err(RpcError::SimulationFailed(error.to_string()).into());
         }
 
-        // Apply simulation results
-        transaction = self.rpc.apply_simulation_to_transaction(transaction, &simulation)?;
+        // Apply simulation results and encode for signing
+        let started = Instant::now();
+        let mut transaction = self
+            .rpc
+            .apply_simulation_to_transaction(transaction, &simulation)
+            .await?;
+        if source_public_key != farmer_public_key {
+            let valid_until = self.rpc.get_latest_ledger().await? + AUTH_VALIDITY_LEDGERS;
+            transaction = self.rpc.set_auth_expiration(transaction, valid_until)?;
+        }
+        if trace.is_enabled() {
+            trace.step("assembled", traced_transaction(&transaction));
+        }
+
+        // The network rejects transactions over its per-transaction limits outright
+        if let stellar_xdr::curr::TransactionExt::V1(soroban_data) = &transaction.ext {
+            self.get_network_limits()
+                .await?
+                .check_resources(&soroban_data.resources)
+                .map_err(|e| {
+                    self.rpc.release_sequence(&transaction);
+                    KaleError::ResourceLimit(e.to_string())
+                })?;
+        }
+        let xdr = Self::envelope_xdr(transaction)?;
+        trace.step("envelope", serde_json::json!({ "xdr": xdr }));
+
+        Ok(PreparedTransaction {
+            xdr,
+            timings: PrepareTimings {
+                account_fetch_ms,
+                simulate_ms: Some(simulate_ms),
+                assemble_ms: elapsed_ms(started),
+            },
+            restore: false,
+        })
+    }
 
-        // Wrap the transaction in a TransactionV1Envelope (required for signing)
-        // Albedo expects a TransactionEnvelope, not a raw Transaction
+    /// Wrap a transaction in an unsigned TransactionV1Envelope and encode it as base64 XDR
+    ///
+    /// Wallets like Albedo expect a TransactionEnvelope, not a raw Transaction.
+    fn envelope_xdr(transaction: stellar_xdr::curr::Transaction) -> Result<String> {
         let tx_envelope = stellar_xdr::curr::TransactionEnvelope::Tx(
             stellar_xdr::curr::TransactionV1Envelope {
                 tx: transaction,
@@ -113,10 +1235,7 @@ impl Kale {
             },
         );
 
-        // Convert the envelope to XDR base64 for signing
-        let tx_xdr = tx_envelope.to_xdr_base64(stellar_xdr::curr::Limits::none())?;
-
-        Ok(tx_xdr)
+        Ok(tx_envelope.to_xdr_base64(stellar_xdr::curr::Limits::none())?)
     }
 
     /// Submit a signed plant transaction
@@ -124,9 +1243,89 @@ impl Kale {
     /// # Arguments
     /// * `signed_tx_xdr` - The signed transaction XDR (base64)
     ///
-    /// Returns the transaction hash
-    pub async fn submit_plant_transaction(&self, signed_tx_xdr: &str) -> Result<String> {
-        self.rpc.submit_transaction(signed_tx_xdr).await
+    /// Returns the transaction hash and, unless the wait times out, its final status and result
+    pub async fn submit_plant_transaction(
+        &self,
+        signed_tx_xdr: &str,
+    ) -> Result<SubmittedTransaction> {
+        let tx_hash = self.rpc.submit_transaction(signed_tx_xdr).await?;
+        let submitted = self
+            .rpc
+            .confirm_transaction(&tx_hash, &self.confirmation)
+            .await?;
+
+        // Remember a plant that landed so reads don't report "not planted" while another
+        // RPC node lags. A failed, rejected or still pending plant isn't remembered. This is
+        // best-effort: the plant itself already succeeded.
+        if submitted.status == TransactionStatus::Success {
+            if let Err(e) = self.record_pending_plant(signed_tx_xdr, &tx_hash).await {
+                eprintln!("Warning: Failed to record pending plant: {}", e);
+            }
+        }
+
+        Ok(submitted)
+    }
+
+    /// Record a successful plant for read-your-writes consistency and participation proofs
+    ///
+    /// getTransaction doesn't report the ledger a transaction was applied in, so the latest
+    /// ledger of the node that just confirmed it stands in: the plant is in it or earlier.
+    async fn record_pending_plant(&self, signed_tx_xdr: &str, tx_hash: &str) -> Result<()> {
+        let farmer = SorobanRpc::transaction_farmer(signed_tx_xdr)?;
+        let block_index = self.get_block_index().await?;
+        let confirmed_ledger = self.rpc.get_latest_ledger().await?;
+
+        self.record_farm_transaction(&farmer, block_index, tx_hash);
+        self.pending_plants.lock().unwrap().insert(
+            farmer,
+            PendingPlant {
+                block_index,
+                confirmed_ledger,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Remember a plant or work transaction hash for the block it was submitted in
+    fn record_farm_transaction(&self, farmer: &str, block_index: u32, tx_hash: &str) {
+        let mut farm_transactions = self.farm_transactions.lock().unwrap();
+        farm_transactions.retain(|(_, index), _| index + PROOF_RETENTION_BLOCKS > block_index);
+        farm_transactions
+            .entry((farmer.to_string(), block_index))
+            .or_default()
+            .push(tx_hash.to_string());
+    }
+
+    /// Check whether a plant for this block was submitted but isn't visible on the RPC node yet
+    ///
+    /// Once the RPC node's latest ledger is past the confirmation window, the record is
+    /// dropped and the on-chain state is authoritative again.
+    async fn is_plant_pending(&self, farmer_public_key: &str, block_index: u32) -> Result<bool> {
+        let pending = self
+            .pending_plants
+            .lock()
+            .unwrap()
+            .get(farmer_public_key)
+            .copied();
+        let Some(pending) = pending else {
+            return Ok(false);
+        };
+
+        if pending.block_index != block_index {
+            return Ok(false);
+        }
+
+        let latest_ledger = self.rpc.get_latest_ledger().await?;
+        if latest_ledger < pending.confirmed_ledger + READ_YOUR_WRITES_LEDGERS {
+            return Ok(true);
+        }
+
+        self.pending_plants
+            .lock()
+            .unwrap()
+            .remove(farmer_public_key);
+        Ok(false)
     }
 
     /// Get the network passphrase (needed for Albedo signing)
@@ -134,6 +1333,26 @@ impl Kale {
         self.rpc.network_passphrase()
     }
 
+    /// Check that the RPC endpoints serve the network of the configured passphrase
+    pub async fn verify_network(&self) -> Result<()> {
+        Ok(self.rpc.verify_network().await?)
+    }
+
+    /// Switch to the RPC endpoints of a reloaded `config`, once they're checked to serve
+    /// the same network; calls under way finish on the old ones
+    pub async fn reload_endpoints(&self, config: &Config) -> Result<()> {
+        let history = config.history_rpc_url.as_deref().map(RpcEndpoint::new);
+        Ok(self
+            .rpc
+            .replace_endpoints(&config.endpoints(), history.as_ref())
+            .await?)
+    }
+
+    /// The RPC endpoint calls currently go to, redacted for logs
+    pub fn active_endpoint(&self) -> String {
+        self.rpc.active_endpoint()
+    }
+
     /// Check if a farmer has planted in the current block
     ///
     /// # Arguments
@@ -144,34 +1363,34 @@ impl Kale {
         let block_index = self.get_block_index().await?;
 
         // Parse farmer address
-        let farmer_strkey = Strkey::from_string(farmer_public_key)?;
-        let farmer_address = match farmer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                ScAddress::Account(stellar_xdr::curr::AccountId(
-                    stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(
-                        stellar_xdr::curr::Uint256(pk.0)
-                    )
-                ))
-            }
-            _ => anyhow::bail!("Invalid farmer public key type"),
-        };
+        let farmer_address = farmer_address(farmer_public_key)?;
 
         // Build the Pail storage key: Pail(farmer, block_index)
-        let pail_key = stellar_xdr::curr::LedgerKey::ContractData(
-            stellar_xdr::curr::LedgerKeyContractData {
-                contract: ScAddress::Contract(stellar_xdr::curr::Hash(self.rpc.contract_id().0.clone())),
-                key: ScVal::Vec(Some(stellar_xdr::curr::ScVec(vec![
-                    ScVal::Symbol(stellar_xdr::curr::ScSymbol("Pail".try_into()?)),
-                    ScVal::Address(farmer_address),
-                    ScVal::U32(block_index),
-                ].try_into()?))),
+        let pail_key =
+            stellar_xdr::curr::LedgerKey::ContractData(stellar_xdr::curr::LedgerKeyContractData {
+                contract: ScAddress::Contract(stellar_xdr::curr::Hash(
+                    self.rpc.contract_id().0.clone(),
+                )),
+                key: args()
+                    .symbol("Pail")
+                    .address(farmer_address)
+                    .u32(block_index)
+                    .build_vec()?,
                 durability: stellar_xdr::curr::ContractDataDurability::Temporary,
-            },
-        );
+            });
 
         // Try to fetch the Pail entry
         let entry = self.rpc.get_ledger_entry(pail_key).await?;
-        Ok(entry.is_some())
+        if entry.is_some() {
+            self.pending_plants
+                .lock()
+                .unwrap()
+                .remove(farmer_public_key);
+            return Ok(true);
+        }
+
+        // The RPC node may not have caught up with a plant we just submitted
+        self.is_plant_pending(farmer_public_key, block_index).await
     }
 
     /// Get the current block information (index and entropy)
@@ -179,291 +1398,619 @@ impl Kale {
     /// Returns (block_index, Option<block_entropy>)
     /// Entropy is None if nobody has planted in the current block yet
     pub async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)> {
+        let (block_index, entropy, _) = self.get_block_info_versioned().await?;
+        Ok((block_index, entropy))
+    }
+
+    /// Get the current block information, plus the last ledger in which it changed
+    ///
+    /// Returns (block_index, Option<block_entropy>, changed_ledger): the latest ledger that
+    /// modified the contract instance or the current Block entry. A client that has seen
+    /// that ledger has seen this block info.
+    pub async fn get_block_info_versioned(&self) -> Result<(u32, Option<[u8; 32]>, u32)> {
         // Get the farm index from instance storage (this always exists)
         let instance = self.rpc.get_contract_instance().await?;
+        let mut changed_ledger = instance.last_modified_ledger;
         let index_value = SorobanRpc::parse_instance_storage_value(&instance, "FarmIndex")?;
         let block_index = if let ScVal::U32(index) = index_value {
+            self.note_block_index(index);
             index
         } else {
-            anyhow::bail!("FarmIndex is not a U32 value: {:?}", index_value)
+            return Err(KaleError::UnexpectedData(format!(
+                "FarmIndex is not a U32 value: {:?}",
+                index_value
+            )));
         };
 
         // IMPORTANT: Get the Block at the specific index from temporary storage, NOT FarmBlock!
         // The contract's work function uses: get_block(&env, index).entropy
         // Note: The Block entry is only created when the first person plants in the block
-        let block_key = stellar_xdr::curr::LedgerKey::ContractData(
-            stellar_xdr::curr::LedgerKeyContractData {
-                contract: ScAddress::Contract(stellar_xdr::curr::Hash(self.rpc.contract_id().0.clone())),
-                key: ScVal::Vec(Some(stellar_xdr::curr::ScVec(vec![
-                    ScVal::Symbol(stellar_xdr::curr::ScSymbol("Block".try_into()?)),
-                    ScVal::U32(block_index),
-                ].try_into()?))),
-                durability: stellar_xdr::curr::ContractDataDurability::Temporary,
-            },
-        );
-
-        let block_entry = self.rpc.get_ledger_entry(block_key).await?;
+        let block_entry = self
+            .rpc
+            .get_ledger_entry(self.block_key(block_index)?)
+            .await?;
 
         // If the Block entry doesn't exist, nobody has planted yet
         let entropy = match block_entry {
             None => None,
             Some(entry) => {
-                // Decode the LedgerEntryData from base64 XDR
-                let entry_data = stellar_xdr::curr::LedgerEntryData::from_xdr_base64(
-                    &entry.xdr,
-                    stellar_xdr::curr::Limits::none(),
-                )?;
-
-                // Parse the Block struct to extract entropy
-                if let stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) = entry_data {
-                    if let ScVal::Map(Some(map)) = contract_data.val {
-                        let mut entropy_bytes = None;
-                        for entry in map.iter() {
-                            if let ScVal::Symbol(ref sym) = entry.key {
-                                if sym.to_utf8_string_lossy() == "entropy" {
-                                    if let ScVal::Bytes(ref bytes) = entry.val {
-                                        if bytes.len() == 32 {
-                                            let mut arr = [0u8; 32];
-                                            arr.copy_from_slice(&bytes[..]);
-                                            entropy_bytes = Some(arr);
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        entropy_bytes
-                    } else {
-                        anyhow::bail!("Block is not a Map: {:?}", contract_data.val)
-                    }
-                } else {
-                    anyhow::bail!("Ledger entry is not ContractData")
-                }
+                changed_ledger = changed_ledger.max(entry.last_modified_ledger);
+                Some(Block::from_entry_xdr(&entry.xdr)?.entropy)
             }
         };
 
-        Ok((block_index, entropy))
+        Ok((block_index, entropy, changed_ledger))
+    }
+
+    /// Get any block's Block entry, current or past
+    ///
+    /// Returns None if nobody has planted in the block yet or the entry has expired from
+    /// temporary storage.
+    pub async fn get_block(&self, block_index: u32) -> Result<Option<Block>> {
+        let block_key = self.block_key(block_index)?;
+
+        match self.rpc.get_ledger_entry(block_key).await? {
+            Some(entry) => Ok(Some(Block::from_entry_xdr(&entry.xdr)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the timestamp (unix seconds) at which a block started
+    ///
+    /// Returns None if the Block entry doesn't exist or has expired from temporary storage.
+    async fn get_block_timestamp(&self, block_index: u32) -> Result<Option<u64>> {
+        Ok(self
+            .get_block(block_index)
+            .await?
+            .map(|block| block.timestamp))
+    }
+
+    /// The lowest and highest stakes planted in a block so far, from its Block entry
+    ///
+    /// These are the block's stats, not limits: the contract takes any stake from zero up
+    /// to the farmer's balance. Returns None if nobody has planted in the block yet or the
+    /// entry has expired from temporary storage.
+    pub async fn get_block_stakes(&self, block_index: u32) -> Result<Option<(i128, i128)>> {
+        let block = self.get_block(block_index).await?;
+        Ok(block.map(|block| (block.min_stake, block.max_stake)))
+    }
+
+    /// Estimate the zeros worth mining for from the last [`DIFFICULTY_SAMPLES`] closed
+    /// blocks' Block stats (fewest and most zeros worked) and the gaps between them
+    ///
+    /// Blocks nobody worked, or whose entries have expired, are skipped. The estimate is
+    /// only recomputed when the block index changes.
+    pub async fn estimate_difficulty(&self) -> Result<DifficultyEstimate> {
+        let current = self.get_block_index().await?;
+        if let Some(estimate) = self.difficulty.lock().unwrap().clone() {
+            if estimate.block_index == current {
+                return Ok(estimate);
+            }
+        }
+
+        let blocks: Vec<u32> = (current.saturating_sub(DIFFICULTY_SAMPLES)..current).collect();
+        let keys = blocks
+            .iter()
+            .map(|&block_index| self.block_key(block_index))
+            .collect::<Result<Vec<_>>>()?;
+        let mut batch = self.rpc.get_ledger_entries_batch(keys.clone()).await?;
+        if !batch.is_complete() {
+            return Err(batch.errors.swap_remove(0).into());
+        }
+
+        let mut zeros = Vec::new();
+        let mut timestamps = Vec::new();
+        for (block_index, key) in blocks.into_iter().zip(&keys) {
+            let Some(entry) = batch.entries.get(key) else {
+                continue;
+            };
+            let block = Block::from_entry_xdr(&entry.xdr)?;
+            timestamps.push((block_index, block.timestamp));
+            zeros.extend(block.zeros_range());
+        }
+
+        let estimate =
+            DifficultyEstimate::from_samples(current, &zeros, &consecutive_gaps(&timestamps));
+        *self.difficulty.lock().unwrap() = Some(estimate.clone());
+        Ok(estimate)
+    }
+
+    /// Estimate inter-block gaps from the timestamps of recent blocks
+    ///
+    /// Blocks close on the first plant after the 5 minute interval, so real gaps are
+    /// usually longer than the interval and depend on farming activity. There is no
+    /// history to query: the timestamps come from the Block entries still live in the
+    /// contract's temporary storage, read in one batch, and blocks whose entries have
+    /// expired are skipped.
+    async fn sample_block_gaps(&self, block_index: u32) -> Result<GapStats> {
+        let blocks: Vec<u32> =
+            (block_index.saturating_sub(CLOSE_PREDICTION_SAMPLES)..=block_index).collect();
+        let keys = blocks
+            .iter()
+            .map(|&index| self.block_key(index))
+            .collect::<Result<Vec<_>>>()?;
+        let mut batch = self.rpc.get_ledger_entries_batch(keys.clone()).await?;
+        if !batch.is_complete() {
+            return Err(batch.errors.swap_remove(0).into());
+        }
+
+        let mut timestamps = Vec::new();
+        for (index, key) in blocks.into_iter().zip(&keys) {
+            if let Some(entry) = batch.entries.get(key) {
+                timestamps.push((index, Block::from_entry_xdr(&entry.xdr)?.timestamp));
+            }
+        }
+
+        let gaps = consecutive_gaps(&timestamps);
+
+        if gaps.is_empty() {
+            return Ok(GapStats {
+                mean: BLOCK_INTERVAL_SECS as f64,
+                std_dev: 0.0,
+            });
+        }
+
+        let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        let variance = gaps.iter().map(|gap| (gap - mean).powi(2)).sum::<f64>() / gaps.len() as f64;
+
+        Ok(GapStats {
+            mean,
+            std_dev: variance.sqrt(),
+        })
+    }
+
+    /// Predict when a block will close, based on recently observed block gaps
+    ///
+    /// Returns None if the block's start time is unknown (no Block entry yet).
+    pub async fn predict_block_close(
+        &self,
+        block_index: u32,
+    ) -> Result<Option<BlockClosePrediction>> {
+        let Some(started_at) = self.get_block_timestamp(block_index).await? else {
+            return Ok(None);
+        };
+
+        let cached = *self.gap_stats.lock().unwrap();
+        let stats = match cached {
+            Some((index, stats)) if index == block_index => stats,
+            _ => {
+                let stats = self.sample_block_gaps(block_index).await?;
+                *self.gap_stats.lock().unwrap() = Some((block_index, stats));
+                stats
+            }
+        };
+
+        // A block can never close before the contract's interval has passed
+        let interval = BLOCK_INTERVAL_SECS as f64;
+        let expected = stats.mean.max(interval);
+        let low = (stats.mean - 2.0 * stats.std_dev).max(interval);
+        let high = expected + 2.0 * stats.std_dev;
+
+        Ok(Some(BlockClosePrediction {
+            predicted_close_at: started_at + expected as u64,
+            low: started_at + low as u64,
+            high: started_at + high as u64,
+        }))
     }
 
     /// Calculate the work hash for a given nonce
     ///
     /// # Arguments
     /// * `farmer_public_key` - The farmer's Stellar public key
-    /// * `nonce` - The nonce to use for hash calculation
+    /// * `nonce` - The nonce to use for hash calculation
+    ///
+    /// Returns the calculated hash
+    pub async fn calculate_work_hash(
+        &self,
+        farmer_public_key: &str,
+        nonce: u64,
+    ) -> Result<[u8; 32]> {
+        let (block_index, entropy_opt) = self.get_block_info().await?;
+        let entropy = entropy_opt.ok_or(KaleError::NoEntropy(block_index))?;
+
+        // Parse farmer address to get raw bytes for XDR encoding
+        let farmer_address_scval = farmer_address(farmer_public_key)?;
+
+        // Encode ScAddress to XDR and get last 32 bytes (matches contract logic)
+        // NOTE: The contract encodes Address directly (which is ScAddress in XDR), NOT wrapped in ScVal
+        use stellar_xdr::curr::WriteXdr;
+        let farmer_xdr = farmer_address_scval.to_xdr(stellar_xdr::curr::Limits::none())?;
+        let farmer_bytes: [u8; 32] = farmer_xdr[farmer_xdr.len() - 32..]
+            .try_into()
+            .expect("32 bytes");
+
+        Ok(work_hash(block_index, nonce, &entropy, &farmer_bytes))
+    }
+
+    /// Build, simulate, and prepare a work transaction
+    ///
+    /// # Arguments
+    /// * `farmer_public_key` - The farmer's Stellar public key
+    /// * `nonce` - The nonce used to generate the hash
+    /// * `mined_block_index` - The block the nonce was mined for, if known
+    /// * `fee_source` - Account paying the fee and supplying the sequence number, if not the farmer
+    ///
+    /// Returns the transaction XDR (base64) ready for signing, with stage timings
+    pub async fn prepare_work_transaction(
+        &self,
+        farmer_public_key: &str,
+        nonce: u64,
+        mined_block_index: Option<u32>,
+        fee_source: Option<&str>,
+    ) -> Result<PreparedTransaction> {
+        let current = self.get_block_index().await?;
+
+        // A nonce mined against an older block is worthless in the current one
+        if let Some(mined) = mined_block_index {
+            if current != mined {
+                return Err(FarmError::BlockAdvanced { mined, current }.into());
+            }
+        }
+
+        // Calculate the hash using current block info
+        let hash = self.calculate_work_hash(farmer_public_key, nonce).await?;
+
+        // Work without a Pail fails on-chain, and work with fewer zeros than the
+        // recorded entry would downgrade it, so catch both before the farmer pays fees
+        let (has_pail, has_worked, existing_zeros) =
+            self.get_pail_data(farmer_public_key, current).await?;
+        if !has_pail {
+            return Err(FarmError::NoPail {
+                block_index: current,
+            }
+            .into());
+        }
+        let candidate_zeros = count_leading_zeros(&hash);
+        if has_worked && existing_zeros >= candidate_zeros {
+            return Err(FarmError::WorkAlreadyBetter {
+                existing_zeros,
+                candidate_zeros,
+            }
+            .into());
+        }
+        // Parse farmer address to ScAddress
+        let farmer_address = farmer_address(farmer_public_key)?;
+
+        // Build function arguments for work(farmer: Address, hash: BytesN<32>, nonce: u64)
+        let args = args()
+            .address(farmer_address)
+            .bytes_n(&hash)
+            .u64(nonce)
+            .build()?;
+
+        // Catch malformed arguments before paying for a simulation round trip
+        self.validate_args("work", &args).await?;
+
+        self.assemble_invocation(farmer_public_key, fee_source, "work", args)
+            .await
+    }
+
+    /// Submit a signed work transaction
+    ///
+    /// # Arguments
+    /// * `signed_tx_xdr` - The signed transaction XDR (base64)
+    ///
+    /// Returns the transaction hash and, unless the wait times out, its final status and result
+    pub async fn submit_work_transaction(
+        &self,
+        signed_tx_xdr: &str,
+    ) -> Result<SubmittedTransaction> {
+        let tx_hash = self.rpc.submit_transaction(signed_tx_xdr).await?;
+
+        // Best-effort, like the pending plant record: the submission already succeeded
+        let farmer = SorobanRpc::transaction_farmer(signed_tx_xdr).map_err(KaleError::from);
+        match (farmer, self.get_block_index().await) {
+            (Ok(farmer), Ok(block_index)) => {
+                self.record_farm_transaction(&farmer, block_index, &tx_hash)
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Warning: Failed to record work transaction: {}", e)
+            }
+        }
+
+        Ok(self
+            .rpc
+            .confirm_transaction(&tx_hash, &self.confirmation)
+            .await?)
+    }
+
+    /// Get a farmer's Pail entry for a block
+    ///
+    /// Returns None if the farmer hasn't planted in the block (as far as the ledger shows),
+    /// has harvested it, or the entry has expired from temporary storage.
+    pub async fn get_pail(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<Option<Pail>> {
+        let pail_key = self.pail_key(farmer_public_key, block_index)?;
+        Ok(self.read_pail(pail_key).await?.map(|(pail, _)| pail))
+    }
+
+    /// Read a Pail entry, with the ledger it last changed in
+    async fn read_pail(
+        &self,
+        pail_key: stellar_xdr::curr::LedgerKey,
+    ) -> Result<Option<(Pail, u32)>> {
+        match self.rpc.get_ledger_entry(pail_key).await? {
+            Some(entry) => Ok(Some((
+                Pail::from_entry_xdr(&entry.xdr)?,
+                entry.last_modified_ledger,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the Pail data for a farmer in a specific block
+    ///
+    /// # Arguments
+    /// * `farmer_public_key` - The farmer's Stellar public key
+    /// * `block_index` - The block index to query
     ///
-    /// Returns the calculated hash
-    pub async fn calculate_work_hash(
+    /// Returns (has_pail, has_worked, leading_zeros) tuple; see [`Kale::get_pail`] for the
+    /// whole entry. Unlike it, a plant this client just submitted counts as a pail.
+    pub async fn get_pail_data(
         &self,
         farmer_public_key: &str,
-        nonce: u64,
-    ) -> Result<[u8; 32]> {
-        use sha3::{Digest, Keccak256};
-
-        let (block_index, entropy_opt) = self.get_block_info().await?;
-        let entropy = entropy_opt
-            .context("Cannot calculate work hash - nobody has planted in this block yet")?;
+        block_index: u32,
+    ) -> Result<(bool, bool, u32)> {
+        Ok(self
+            .get_pail_data_versioned(farmer_public_key, block_index)
+            .await?
+            .0)
+    }
 
-        // Parse farmer address to get raw bytes for XDR encoding
-        let farmer_strkey = Strkey::from_string(farmer_public_key)?;
-        let farmer_address_scval = match farmer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                ScAddress::Account(stellar_xdr::curr::AccountId(
-                    stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(
-                        stellar_xdr::curr::Uint256(pk.0)
-                    )
-                ))
+    /// Get the Pail data for a farmer in a specific block, plus the last ledger it changed in
+    ///
+    /// The ledger is the Pail entry's last modification, or 0 if there is no entry yet.
+    /// It is None while the pail is only known from a plant this client just submitted,
+    /// since that can still change without a ledger recording it.
+    pub async fn get_pail_data_versioned(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<((bool, bool, u32), Option<u32>)> {
+        let pail_key = self.pail_key(farmer_public_key, block_index)?;
+
+        match self.read_pail(pail_key).await? {
+            None => {
+                // A plant we just submitted counts even if the RPC node hasn't caught up
+                let has_pail = self
+                    .is_plant_pending(farmer_public_key, block_index)
+                    .await?;
+                let changed_ledger = if has_pail { None } else { Some(0) };
+                Ok(((has_pail, false, 0), changed_ledger))
             }
-            _ => anyhow::bail!("Invalid farmer public key type"),
-        };
+            Some((pail, changed_ledger)) => Ok((pail.data(), Some(changed_ledger))),
+        }
+    }
 
-        // Encode ScAddress to XDR and get last 32 bytes (matches contract logic)
-        // NOTE: The contract encodes Address directly (which is ScAddress in XDR), NOT wrapped in ScVal
-        use stellar_xdr::curr::WriteXdr;
-        let farmer_xdr = farmer_address_scval.to_xdr(stellar_xdr::curr::Limits::none())?;
-        let farmer_bytes = &farmer_xdr[farmer_xdr.len() - 32..];
+    /// Get the pail data of many farmers in one block
+    ///
+    /// Fetches all the Pail entries with batched getLedgerEntries requests instead of one
+    /// request per farmer. Returns a (has_pail, has_worked, leading_zeros) result for each
+    /// farmer, in order; a farmer's result is an error if their key or entry is invalid, or
+    /// if the request that should have read their entry failed.
+    pub async fn get_pails_for_block(
+        &self,
+        farmer_public_keys: &[String],
+        block_index: u32,
+    ) -> Result<Vec<Result<(bool, bool, u32)>>> {
+        let pail_keys: Vec<Result<stellar_xdr::curr::LedgerKey>> = farmer_public_keys
+            .iter()
+            .map(|farmer| self.pail_key(farmer, block_index))
+            .collect();
+
+        let valid_keys = pail_keys
+            .iter()
+            .filter_map(|key| key.as_ref().ok())
+            .cloned()
+            .collect();
+        let batch = self.rpc.get_ledger_entries_batch(valid_keys).await?;
+
+        let mut pails = Vec::with_capacity(farmer_public_keys.len());
+        for (farmer, pail_key) in farmer_public_keys.iter().zip(pail_keys) {
+            let pail = match pail_key {
+                Err(e) => Err(e),
+                Ok(pail_key) => match batch.entries.get(&pail_key) {
+                    Some(entry) => Pail::from_entry_xdr(&entry.xdr).map(|pail| pail.data()),
+                    // Unknown rather than absent: don't report "not planted"
+                    None => match batch.failure(&pail_key) {
+                        Some(error) => Err(error.into()),
+                        None => self
+                            .is_plant_pending(farmer, block_index)
+                            .await
+                            .map(|has_pail| (has_pail, false, 0)),
+                    },
+                },
+            };
+            pails.push(pail);
+        }
 
-        // Build the 76-byte input: block_index (4) + nonce (8) + entropy (32) + farmer (32)
-        let mut hash_input = [0u8; 76];
-        hash_input[0..4].copy_from_slice(&block_index.to_be_bytes());
-        hash_input[4..12].copy_from_slice(&nonce.to_be_bytes());
-        hash_input[12..44].copy_from_slice(&entropy);
-        hash_input[44..76].copy_from_slice(farmer_bytes);
+        Ok(pails)
+    }
 
-        // Calculate Keccak256
-        let mut hasher = Keccak256::new();
-        hasher.update(&hash_input);
-        let result = hasher.finalize();
+    /// Find every farmer that planted in a block, without being told who to look for
+    ///
+    /// Scans the contract's plant events from around the block's start and collects the
+    /// account addresses they mention. Events don't say which block they were for, so each
+    /// candidate is then checked against its Pail entry; only farmers with a pail in this
+    /// block are returned. Blocks older than the RPC node's event retention can't be scanned.
+    pub async fn discover_block_farmers(&self, block_index: u32) -> Result<Vec<FarmerPail>> {
+        let Some(started_at) = self.get_block_timestamp(block_index).await? else {
+            // Nobody has planted yet, or the block has expired from temporary storage
+            return Ok(Vec::new());
+        };
 
-        let mut hash = [0u8; 32];
-        hash.copy_from_slice(&result);
-        Ok(hash)
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(started_at);
+        let ledgers_ago = (now.saturating_sub(started_at) / LEDGER_CLOSE_SECS) as u32;
+        let latest_ledger = self.rpc.get_latest_ledger().await?;
+        let start_ledger = latest_ledger
+            .saturating_sub(ledgers_ago + DISCOVERY_MARGIN_LEDGERS)
+            .max(1);
+
+        let candidates = self.plant_event_addresses(start_ledger).await?;
+        self.planted_pails(candidates, block_index).await
     }
 
-    /// Build, simulate, and prepare a work transaction
-    ///
-    /// # Arguments
-    /// * `farmer_public_key` - The farmer's Stellar public key
-    /// * `nonce` - The nonce used to generate the hash
+    /// Take a snapshot of the current block's pails, for [`crate::contracts::changes::diff`]
     ///
-    /// Returns the transaction XDR (base64) ready for signing
-    pub async fn prepare_work_transaction(
-        &self,
-        farmer_public_key: &str,
-        nonce: u64,
-    ) -> Result<String> {
-        // Calculate the hash using current block info
-        let hash = self.calculate_work_hash(farmer_public_key, nonce).await?;
-        // Parse farmer address to ScAddress
-        let farmer_strkey = Strkey::from_string(farmer_public_key)?;
-        let farmer_address = match farmer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                ScAddress::Account(stellar_xdr::curr::AccountId(
-                    stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(
-                        stellar_xdr::curr::Uint256(pk.0)
-                    )
-                ))
+    /// Given the previous snapshot of the same block, only plant events since it are read,
+    /// and its farmers are re-read in the same batch, instead of scanning the whole block.
+    pub async fn snapshot_farm(&self, previous: Option<&FarmSnapshot>) -> Result<FarmSnapshot> {
+        let block_index = self.get_block_index().await?;
+        let ledger = self.rpc.get_latest_ledger().await?;
+
+        let farmers = match previous.filter(|previous| previous.block_index == block_index) {
+            Some(previous) => {
+                let mut candidates = self.plant_event_addresses(previous.ledger).await?;
+                candidates.extend(
+                    previous
+                        .farmers
+                        .iter()
+                        .map(|pail| pail.farmer_address.clone()),
+                );
+                self.planted_pails(candidates, block_index).await?
             }
-            _ => anyhow::bail!("Invalid farmer public key type"),
+            None => self.discover_block_farmers(block_index).await?,
         };
 
-        // Build function arguments for work(farmer: Address, hash: BytesN<32>, nonce: u64)
-        // Note: BytesN<32> is represented as ScVal::Bytes with ScBytes containing the fixed 32 bytes
-        let args = vec![
-            ScVal::Address(farmer_address),
-            ScVal::Bytes(stellar_xdr::curr::ScBytes(hash.to_vec().try_into()?)),
-            ScVal::U64(nonce),
-        ];
+        Ok(FarmSnapshot {
+            block_index,
+            ledger,
+            farmers,
+        })
+    }
 
-        // Build the transaction
-        let mut transaction = self.rpc
-            .build_invoke_transaction(farmer_public_key, "work", args)
+    /// Addresses mentioned in plant events from `start_ledger` on
+    async fn plant_event_addresses(&self, start_ledger: u32) -> Result<Vec<String>> {
+        let events = self
+            .rpc
+            .get_contract_events(start_ledger, DISCOVERY_MAX_PAGES)
             .await?;
 
-        // Simulate to get fees and footprint
-        let simulation = self.rpc.simulate_transaction(&transaction).await?;
-
-        // Check for simulation errors
-        if let Some(error) = &simulation.error {
-            anyhow::bail!("Transaction simulation failed: {}", error);
+        let mut addresses = Vec::new();
+        for event in events.iter().filter(|event| is_plant_event(event)) {
+            for value in event.topics.iter().chain(std::iter::once(&event.value)) {
+                collect_addresses(value, &mut addresses);
+            }
         }
+        Ok(addresses)
+    }
 
-        // Apply simulation results
-        transaction = self.rpc.apply_simulation_to_transaction(transaction, &simulation)?;
-
-        // Wrap the transaction in a TransactionV1Envelope (required for signing)
-        let tx_envelope = stellar_xdr::curr::TransactionEnvelope::Tx(
-            stellar_xdr::curr::TransactionV1Envelope {
-                tx: transaction,
-                signatures: stellar_xdr::curr::VecM::default(),
-            },
-        );
-
-        // Convert the envelope to XDR base64 for signing
-        let tx_xdr = tx_envelope.to_xdr_base64(stellar_xdr::curr::Limits::none())?;
+    /// The pails of whichever `candidates` planted in the block
+    async fn planted_pails(
+        &self,
+        mut candidates: Vec<String>,
+        block_index: u32,
+    ) -> Result<Vec<FarmerPail>> {
+        candidates.sort();
+        candidates.dedup();
+
+        let pails = self.get_pails_for_block(&candidates, block_index).await?;
+        let mut farmers = Vec::new();
+        for (farmer_address, pail) in candidates.into_iter().zip(pails) {
+            if let Ok((true, has_worked, leading_zeros)) = pail {
+                farmers.push(FarmerPail {
+                    farmer_address,
+                    has_worked,
+                    leading_zeros,
+                });
+            }
+        }
 
-        Ok(tx_xdr)
+        Ok(farmers)
     }
 
-    /// Submit a signed work transaction
+    /// Find the blocks among the `lookback` before the current one that a farmer can still
+    /// harvest, oldest first
     ///
-    /// # Arguments
-    /// * `signed_tx_xdr` - The signed transaction XDR (base64)
-    ///
-    /// Returns the transaction hash
-    pub async fn submit_work_transaction(&self, signed_tx_xdr: &str) -> Result<String> {
-        self.rpc.submit_transaction(signed_tx_xdr).await
-    }
+    /// Harvesting removes the farmer's Pail entry, so a pail in a past block hasn't been
+    /// harvested yet. Pail and Block entries live in temporary storage, and harvesting needs
+    /// both, so blocks where either has expired are left out. All the entries are read in
+    /// one batch; if any request fails, so does the scan, rather than miss a block.
+    pub async fn find_unharvested_blocks(
+        &self,
+        farmer_public_key: &str,
+        lookback: u32,
+    ) -> Result<Vec<UnharvestedBlock>> {
+        let current = self.get_block_index().await?;
+        let blocks: Vec<u32> = (current.saturating_sub(lookback)..current).collect();
+
+        let mut keys = Vec::with_capacity(blocks.len() * 2);
+        for &block_index in &blocks {
+            keys.push(self.pail_key(farmer_public_key, block_index)?);
+            keys.push(self.block_key(block_index)?);
+        }
+        let mut batch = self.rpc.get_ledger_entries_batch(keys.clone()).await?;
+        if !batch.is_complete() {
+            return Err(batch.errors.swap_remove(0).into());
+        }
+        let latest_ledger = self.rpc.get_latest_ledger().await?;
+
+        let mut unharvested = Vec::new();
+        for (block_index, keys) in blocks.into_iter().zip(keys.chunks(2)) {
+            let pail = batch.entries.get(&keys[0]);
+            let block = batch.entries.get(&keys[1]);
+            let (Some(pail), Some(block)) = (pail, block) else {
+                continue;
+            };
+            let zeros = Pail::from_entry_xdr(&pail.xdr)?.zeros;
+            let live_until_ledger = match (
+                pail.live_until_ledger_seq_ledger_seq,
+                block.live_until_ledger_seq_ledger_seq,
+            ) {
+                (Some(pail), Some(block)) => Some(pail.min(block)),
+                (pail, block) => pail.or(block),
+            };
+            unharvested.push(UnharvestedBlock {
+                block_index,
+                has_worked: zeros.is_some(),
+                leading_zeros: zeros.unwrap_or(0),
+                live_until_ledger,
+                expires_in_ledgers: live_until_ledger
+                    .map(|live_until| live_until.saturating_sub(latest_ledger)),
+            });
+        }
 
-    /// Get the Pail data for a farmer in a specific block
-    ///
-    /// # Arguments
-    /// * `farmer_public_key` - The farmer's Stellar public key
-    /// * `block_index` - The block index to query
-    ///
-    /// Returns (has_pail, has_worked, leading_zeros) tuple
-    pub async fn get_pail_data(&self, farmer_public_key: &str, block_index: u32) -> Result<(bool, bool, u32)> {
-        // Parse farmer address
-        let farmer_strkey = Strkey::from_string(farmer_public_key)?;
-        let farmer_address = match farmer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                ScAddress::Account(stellar_xdr::curr::AccountId(
-                    stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(
-                        stellar_xdr::curr::Uint256(pk.0)
-                    )
-                ))
-            }
-            _ => anyhow::bail!("Invalid farmer public key type"),
-        };
+        Ok(unharvested)
+    }
 
-        // Build the Pail storage key
-        let pail_key = stellar_xdr::curr::LedgerKey::ContractData(
+    /// The ledger key of a block's Block entry (temporary storage, created by its first plant)
+    fn block_key(&self, block_index: u32) -> Result<stellar_xdr::curr::LedgerKey> {
+        Ok(stellar_xdr::curr::LedgerKey::ContractData(
             stellar_xdr::curr::LedgerKeyContractData {
-                contract: ScAddress::Contract(stellar_xdr::curr::Hash(self.rpc.contract_id().0.clone())),
-                key: ScVal::Vec(Some(stellar_xdr::curr::ScVec(vec![
-                    ScVal::Symbol(stellar_xdr::curr::ScSymbol("Pail".try_into()?)),
-                    ScVal::Address(farmer_address),
-                    ScVal::U32(block_index),
-                ].try_into()?))),
+                contract: ScAddress::Contract(stellar_xdr::curr::Hash(self.rpc.contract_id().0)),
+                key: args().symbol("Block").u32(block_index).build_vec()?,
                 durability: stellar_xdr::curr::ContractDataDurability::Temporary,
             },
-        );
+        ))
+    }
 
-        // Try to fetch the Pail entry
-        let entry = self.rpc.get_ledger_entry(pail_key).await?;
+    /// The ledger key of a farmer's Pail entry for a block
+    fn pail_key(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<stellar_xdr::curr::LedgerKey> {
+        let farmer_address = farmer_address(farmer_public_key)?;
 
-        match entry {
-            None => Ok((false, false, 0)),
-            Some(ledger_entry_result) => {
-                // Decode the LedgerEntryData from base64 XDR
-                let entry_data = stellar_xdr::curr::LedgerEntryData::from_xdr_base64(
-                    &ledger_entry_result.xdr,
-                    stellar_xdr::curr::Limits::none(),
-                )?;
-
-                // Parse the Pail struct
-                if let stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) = entry_data {
-                    if let ScVal::Map(Some(map)) = contract_data.val {
-                        // Extract zeros field (Option<u32>)
-                        let mut zeros_value = None;
-
-                        for entry in map.iter() {
-                            if let ScVal::Symbol(ref sym) = entry.key {
-                                if sym.to_utf8_string_lossy() == "zeros" {
-                                    // In the Pail struct, zeros is Option<u32>
-                                    // When Some(value), Soroban serializes it directly as U32(value)
-                                    // When None, the field might not be present or be represented differently
-                                    match &entry.val {
-                                        ScVal::U32(zeros) => {
-                                            zeros_value = Some(*zeros);
-                                        }
-                                        ScVal::Vec(Some(vec)) if vec.is_empty() => {
-                                            // None case - empty Vec represents None
-                                            zeros_value = None;
-                                        }
-                                        ScVal::Vec(Some(vec)) => {
-                                            // Vec with one element
-                                            if let Some(ScVal::U32(zeros)) = vec.first() {
-                                                zeros_value = Some(*zeros);
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-
-                        let has_worked = zeros_value.is_some();
-                        let zeros = zeros_value.unwrap_or(0);
-                        Ok((true, has_worked, zeros))
-                    } else {
-                        anyhow::bail!("Pail value is not a Map")
-                    }
-                } else {
-                    anyhow::bail!("Ledger entry is not ContractData")
-                }
-            }
-        }
+        Ok(stellar_xdr::curr::LedgerKey::ContractData(
+            stellar_xdr::curr::LedgerKeyContractData {
+                contract: ScAddress::Contract(stellar_xdr::curr::Hash(
+                    self.rpc.contract_id().0.clone(),
+                )),
+                key: args()
+                    .symbol("Pail")
+                    .address(farmer_address)
+                    .u32(block_index)
+                    .build_vec()?,
+                durability: stellar_xdr::curr::ContractDataDurability::Temporary,
+            },
+        ))
     }
 
     /// Build, simulate, and prepare a harvest transaction
@@ -471,77 +2018,284 @@ impl Kale {
     /// # Arguments
     /// * `farmer_public_key` - The farmer's Stellar public key
     /// * `block_index` - The block index to harvest from
+    /// * `fee_source` - Account paying the fee and supplying the sequence number, if not the farmer
     ///
-    /// Returns the transaction XDR (base64) ready for signing
+    /// Returns the transaction XDR (base64) ready for signing, with stage timings
     pub async fn prepare_harvest_transaction(
         &self,
         farmer_public_key: &str,
         block_index: u32,
-    ) -> Result<String> {
+        fee_source: Option<&str>,
+    ) -> Result<PreparedTransaction> {
         // Parse farmer address to ScAddress
-        let farmer_strkey = Strkey::from_string(farmer_public_key)?;
-        let farmer_address = match farmer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                ScAddress::Account(stellar_xdr::curr::AccountId(
-                    stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(
-                        stellar_xdr::curr::Uint256(pk.0)
-                    )
-                ))
-            }
-            _ => anyhow::bail!("Invalid farmer public key type"),
-        };
+        let farmer_address = farmer_address(farmer_public_key)?;
 
         // Build function arguments for harvest(farmer: Address, index: u32)
-        let args = vec![
-            ScVal::Address(farmer_address),
-            ScVal::U32(block_index),
-        ];
+        let args = args().address(farmer_address).u32(block_index).build()?;
 
-        // Build the transaction
-        let mut transaction = self.rpc
-            .build_invoke_transaction(farmer_public_key, "harvest", args)
-            .await?;
+        // Catch malformed arguments before paying for a simulation round trip
+        self.validate_args("harvest", &args).await?;
 
-        // Simulate to get fees and footprint
-        let simulation = self.rpc.simulate_transaction(&transaction).await?;
+        self.assemble_invocation(farmer_public_key, fee_source, "harvest", args)
+            .await
+    }
 
-        // Check for simulation errors
-        if let Some(error) = &simulation.error {
-            anyhow::bail!("Transaction simulation failed: {}", error);
-        }
+    /// Submit a signed harvest transaction
+    ///
+    /// # Arguments
+    /// * `signed_tx_xdr` - The signed transaction XDR (base64)
+    ///
+    /// Returns the transaction hash and, unless the wait times out, its final status and result
+    pub async fn submit_harvest_transaction(
+        &self,
+        signed_tx_xdr: &str,
+    ) -> Result<SubmittedTransaction> {
+        let tx_hash = self.rpc.submit_transaction(signed_tx_xdr).await?;
+        Ok(self
+            .rpc
+            .confirm_transaction(&tx_hash, &self.confirmation)
+            .await?)
+    }
 
-        // Apply simulation results
-        transaction = self.rpc.apply_simulation_to_transaction(transaction, &simulation)?;
+    /// Decode a plant, work or harvest transaction prepared earlier (e.g. one that expired
+    /// before it was signed)
+    pub fn decode_farm_call(&self, tx_xdr: &str) -> Result<PreparedFarmCall> {
+        let not_farm_call = || {
+            KaleError::InvalidArguments(
+                "only prepared plant, work and harvest calls can be refreshed".to_string(),
+            )
+        };
 
-        // Wrap the transaction in a TransactionV1Envelope (required for signing)
-        let tx_envelope = stellar_xdr::curr::TransactionEnvelope::Tx(
-            stellar_xdr::curr::TransactionV1Envelope {
-                tx: transaction,
-                signatures: stellar_xdr::curr::VecM::default(),
+        let envelope = stellar_xdr::curr::TransactionEnvelope::from_xdr_base64(
+            tx_xdr,
+            stellar_xdr::curr::Limits::none(),
+        )
+        .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+        let stellar_xdr::curr::TransactionEnvelope::Tx(envelope) = envelope else {
+            return Err(not_farm_call());
+        };
+        let [operation] = envelope.tx.operations.as_slice() else {
+            return Err(not_farm_call());
+        };
+        let stellar_xdr::curr::OperationBody::InvokeHostFunction(invoke_op) = &operation.body
+        else {
+            return Err(not_farm_call());
+        };
+        let stellar_xdr::curr::HostFunction::InvokeContract(call) = &invoke_op.host_function else {
+            return Err(not_farm_call());
+        };
+        let contract = ScAddress::Contract(stellar_xdr::curr::Hash(self.rpc.contract_id().0));
+        if call.contract_address != contract {
+            return Err(KaleError::InvalidArguments(
+                "the transaction calls another contract".to_string(),
+            ));
+        }
+
+        let Some((ScVal::Address(farmer), args)) = call.args.split_first() else {
+            return Err(not_farm_call());
+        };
+        let invocation = match (call.function_name.to_utf8_string_lossy().as_str(), args) {
+            ("plant", [ScVal::I128(amount)]) => FarmInvocation::Plant {
+                amount: (i128::from(amount.hi) << 64) | i128::from(amount.lo),
             },
-        );
+            ("work", [ScVal::Bytes(hash), ScVal::U64(nonce)]) => FarmInvocation::Work {
+                hash: hash[..].try_into().map_err(|_| not_farm_call())?,
+                nonce: *nonce,
+            },
+            ("harvest", [ScVal::U32(block_index)]) => FarmInvocation::Harvest {
+                block_index: *block_index,
+            },
+            _ => return Err(not_farm_call()),
+        };
 
-        // Convert the envelope to XDR base64 for signing
-        let tx_xdr = tx_envelope.to_xdr_base64(stellar_xdr::curr::Limits::none())?;
+        let farmer = SorobanRpc::address_strkey(farmer);
+        let source = SorobanRpc::transaction_source_account(tx_xdr)?;
+        Ok(PreparedFarmCall {
+            fee_source: (source != farmer).then_some(source),
+            farmer,
+            invocation,
+        })
+    }
 
-        Ok(tx_xdr)
+    /// Prepare a decoded farming call again, with a fresh sequence number, fees and footprint
+    ///
+    /// It goes through the same checks as the original prepare. Work is refused once the
+    /// farm has moved to another block, since its hash no longer matches.
+    pub async fn refresh_farm_call(&self, call: &PreparedFarmCall) -> Result<PreparedTransaction> {
+        let fee_source = call.fee_source.as_deref();
+        match call.invocation {
+            FarmInvocation::Plant { amount } => {
+                self.prepare_plant_transaction(&call.farmer, amount, fee_source)
+                    .await
+            }
+            FarmInvocation::Work { hash, nonce } => {
+                if self.calculate_work_hash(&call.farmer, nonce).await? != hash {
+                    return Err(KaleError::InvalidArguments(
+                        "the work was mined for an earlier block; mine again".to_string(),
+                    ));
+                }
+                self.prepare_work_transaction(&call.farmer, nonce, None, fee_source)
+                    .await
+            }
+            FarmInvocation::Harvest { block_index } => {
+                self.prepare_harvest_transaction(&call.farmer, block_index, fee_source)
+                    .await
+            }
+        }
     }
 
-    /// Submit a signed harvest transaction
+    /// Submit a signed RestoreFootprint transaction
     ///
     /// # Arguments
     /// * `signed_tx_xdr` - The signed transaction XDR (base64)
     ///
-    /// Returns the transaction hash
-    pub async fn submit_harvest_transaction(&self, signed_tx_xdr: &str) -> Result<String> {
-        self.rpc.submit_transaction(signed_tx_xdr).await
+    /// Returns the transaction hash and, unless the wait times out, its final status and result
+    pub async fn submit_restore_transaction(
+        &self,
+        signed_tx_xdr: &str,
+    ) -> Result<SubmittedTransaction> {
+        let tx_hash = self.rpc.submit_transaction(signed_tx_xdr).await?;
+        Ok(self
+            .rpc
+            .confirm_transaction(&tx_hash, &self.confirmation)
+            .await?)
+    }
+
+    /// Explain the outcome of a submitted transaction
+    ///
+    /// # Arguments
+    /// * `tx_hash` - The hex-encoded transaction hash
+    pub async fn get_transaction_diagnostics(
+        &self,
+        tx_hash: &str,
+    ) -> Result<TransactionDiagnostics> {
+        let response = self.rpc.get_historical_transaction(tx_hash).await?;
+        Ok(diagnostics::diagnose(tx_hash, &response))
+    }
+
+    /// Bundle the ledger entries proving a farmer's participation in a block
+    ///
+    /// The Pail and Block entries live in temporary storage, so proofs can only be
+    /// built while they are still live on the network.
+    pub async fn get_participation_proof(
+        &self,
+        farmer_public_key: &str,
+        block_index: u32,
+    ) -> Result<ParticipationProof> {
+        let pail = self
+            .get_proven_entry(self.pail_key(farmer_public_key, block_index)?)
+            .await?;
+        let block = self.get_proven_entry(self.block_key(block_index)?).await?;
+
+        let transaction_hashes = self
+            .farm_transactions
+            .lock()
+            .unwrap()
+            .get(&(farmer_public_key.to_string(), block_index))
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(ParticipationProof {
+            farmer: farmer_public_key.to_string(),
+            block_index,
+            contract_id: self.rpc.contract_id().to_string(),
+            network_passphrase: self.rpc.network_passphrase().to_string(),
+            pail,
+            block,
+            transaction_hashes,
+        })
+    }
+
+    /// Summarize a block for the farmers who planted or worked through this server
+    ///
+    /// Built from the transactions this server submitted (kept for the last
+    /// `PROOF_RETENTION_BLOCKS` blocks), the farmers' current pails and the Block entry.
+    pub async fn get_block_report(&self, block_index: u32) -> Result<BlockReport> {
+        let mut farmer_transactions: Vec<(String, Vec<String>)> = self
+            .farm_transactions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((_, index), _)| *index == block_index)
+            .map(|((farmer, _), hashes)| (farmer.clone(), hashes.clone()))
+            .collect();
+        farmer_transactions.sort();
+        let block = if farmer_transactions.is_empty() {
+            None
+        } else {
+            self.get_block(block_index).await?
+        };
+
+        let mut farmers = Vec::new();
+        for (farmer, transaction_hashes) in farmer_transactions {
+            let pail = self.get_pail(&farmer, block_index).await?;
+            let (has_pail, has_worked, leading_zeros) =
+                pail.as_ref().map_or((false, false, 0), Pail::data);
+            let estimated_reward = pail
+                .zip(block.as_ref())
+                .and_then(|(pail, block)| block.estimate_reward(block_index, &pail));
+
+            // Transactions still pending (or expired from the RPC node's history) have no fee yet
+            let mut fees_charged = 0;
+            for hash in &transaction_hashes {
+                if let Ok(response) = self.rpc.get_historical_transaction(hash).await {
+                    fees_charged += response.result.map_or(0, |result| result.fee_charged);
+                }
+            }
+
+            farmers.push(FarmerBlockSummary {
+                farmer,
+                has_pail,
+                has_worked,
+                leading_zeros,
+                transaction_hashes,
+                fees_charged,
+                estimated_reward,
+            });
+        }
+
+        Ok(BlockReport {
+            block_index,
+            best_zeros: farmers
+                .iter()
+                .filter(|farmer| farmer.has_worked)
+                .map(|farmer| farmer.leading_zeros)
+                .max(),
+            total_fees_charged: farmers.iter().map(|farmer| farmer.fees_charged).sum(),
+            farmers,
+        })
+    }
+
+    /// Fetch a ledger entry along with its encoded key
+    async fn get_proven_entry(
+        &self,
+        key: stellar_xdr::curr::LedgerKey,
+    ) -> Result<Option<ProvenLedgerEntry>> {
+        let encoded_key = key.to_xdr_base64(stellar_xdr::curr::Limits::none())?;
+
+        Ok(self
+            .rpc
+            .get_ledger_entry(key)
+            .await?
+            .map(|entry| ProvenLedgerEntry {
+                key: encoded_key,
+                xdr: entry.xdr,
+                last_modified_ledger: entry.last_modified_ledger,
+            }))
     }
 
     /// Get the XLM balance of an account
     ///
     /// Returns the balance in stroops, or None if the account doesn't exist
     pub async fn get_xlm_balance(&self, account_address: &str) -> Result<Option<i64>> {
-        self.rpc.get_xlm_balance(account_address).await
+        Ok(self.rpc.get_xlm_balance(account_address).await?)
+    }
+
+    /// Get the XLM an account can spend, above its reserve and offers
+    ///
+    /// Returns the amount in stroops, or None if the account doesn't exist
+    pub async fn get_spendable_xlm(&self, account_address: &str) -> Result<Option<i64>> {
+        Ok(self.rpc.get_spendable_xlm(account_address).await?)
     }
 
     /// Check if an account has a KALE trustline
@@ -549,40 +2303,50 @@ impl Kale {
     /// Returns (has_trustline, balance in stroops)
     pub async fn check_kale_trustline(&self, account_address: &str) -> Result<(bool, i64)> {
         const KALE_ASSET_CODE: &str = "KALE";
-        const KALE_ISSUER: &str = "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB";
 
-        self.rpc
-            .check_trustline_and_balance(account_address, KALE_ASSET_CODE, KALE_ISSUER)
-            .await
+        Ok(self
+            .rpc
+            .check_trustline_and_balance(account_address, KALE_ASSET_CODE, &self.kale_issuer)
+            .await?)
     }
 
     /// Build and prepare a trustline transaction for KALE
     ///
-    /// Returns the transaction XDR (base64) ready for signing
+    /// With a `fee_source`, that account pays and both accounts sign the envelope.
+    ///
+    /// Returns the transaction XDR (base64) ready for signing, with stage timings
     pub async fn prepare_add_kale_trustline_transaction(
         &self,
         account_address: &str,
-    ) -> Result<String> {
+        fee_source: Option<&str>,
+    ) -> Result<PreparedTransaction> {
         const KALE_ASSET_CODE: &str = "KALE";
-        const KALE_ISSUER: &str = "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB";
 
-        // Build the trustline transaction
-        let transaction = self.rpc
-            .build_add_trustline_transaction(account_address, KALE_ASSET_CODE, KALE_ISSUER)
+        // Build the trustline transaction (a classic operation, so no simulation)
+        let started = Instant::now();
+        let transaction = self
+            .rpc
+            .build_add_trustline_transaction(
+                account_address,
+                KALE_ASSET_CODE,
+                &self.kale_issuer,
+                fee_source,
+            )
             .await?;
+        let account_fetch_ms = elapsed_ms(started);
 
-        // Wrap the transaction in a TransactionV1Envelope (required for signing)
-        let tx_envelope = stellar_xdr::curr::TransactionEnvelope::Tx(
-            stellar_xdr::curr::TransactionV1Envelope {
-                tx: transaction,
-                signatures: stellar_xdr::curr::VecM::default(),
-            },
-        );
-
-        // Convert the envelope to XDR base64 for signing
-        let tx_xdr = tx_envelope.to_xdr_base64(stellar_xdr::curr::Limits::none())?;
+        let started = Instant::now();
+        let xdr = Self::envelope_xdr(transaction)?;
 
-        Ok(tx_xdr)
+        Ok(PreparedTransaction {
+            xdr,
+            timings: PrepareTimings {
+                account_fetch_ms,
+                simulate_ms: None,
+                assemble_ms: elapsed_ms(started),
+            },
+            restore: false,
+        })
     }
 
     /// Submit a signed trustline transaction
@@ -590,9 +2354,68 @@ impl Kale {
     /// # Arguments
     /// * `signed_tx_xdr` - The signed transaction XDR (base64)
     ///
-    /// Returns the transaction hash
-    pub async fn submit_trustline_transaction(&self, signed_tx_xdr: &str) -> Result<String> {
-        self.rpc.submit_transaction(signed_tx_xdr).await
+    /// Returns the transaction hash and, unless the wait times out, its final status and result
+    pub async fn submit_trustline_transaction(
+        &self,
+        signed_tx_xdr: &str,
+    ) -> Result<SubmittedTransaction> {
+        let tx_hash = self.rpc.submit_transaction(signed_tx_xdr).await?;
+        Ok(self
+            .rpc
+            .confirm_transaction(&tx_hash, &self.confirmation)
+            .await?)
+    }
+
+    /// Build a transaction paying pool rewards in KALE from the pool's account
+    ///
+    /// With a `fee_source`, that account pays and both accounts sign the envelope.
+    ///
+    /// Returns the transaction XDR (base64) for the pool operator to review and sign
+    pub async fn prepare_kale_payouts_transaction(
+        &self,
+        pool_public_key: &str,
+        payouts: &[Payout],
+        fee_source: Option<&str>,
+    ) -> Result<PreparedTransaction> {
+        const KALE_ASSET_CODE: &str = "KALE";
+
+        let payments = payouts
+            .iter()
+            .map(|payout| {
+                let amount = i64::try_from(payout.amount).map_err(|_| {
+                    let message = format!("Payout to {} is too large", payout.worker);
+                    KaleError::InvalidArguments(message)
+                })?;
+                Ok((payout.worker.clone(), amount))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Payments are classic operations, so no simulation
+        let started = Instant::now();
+        let transaction = self
+            .rpc
+            .build_payments_transaction(
+                pool_public_key,
+                KALE_ASSET_CODE,
+                &self.kale_issuer,
+                &payments,
+                fee_source,
+            )
+            .await?;
+        let account_fetch_ms = elapsed_ms(started);
+
+        let started = Instant::now();
+        let xdr = Self::envelope_xdr(transaction)?;
+
+        Ok(PreparedTransaction {
+            xdr,
+            timings: PrepareTimings {
+                account_fetch_ms,
+                simulate_ms: None,
+                assemble_ms: elapsed_ms(started),
+            },
+            restore: false,
+        })
     }
 }
 
@@ -604,6 +2427,92 @@ mod tests {
     const TESTNET_CONTRACT: &str = "CDSWUUXGPWDZG76ISK6SUCVPZJMD5YUV66J2FXFXFGDX25XKZJIEITAO";
     const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
 
+    #[test]
+    fn test_farmer_address_accepts_accounts_and_contracts() {
+        assert!(matches!(
+            farmer_address("GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6"),
+            Ok(ScAddress::Account(_))
+        ));
+        assert!(matches!(
+            farmer_address(TESTNET_CONTRACT),
+            Ok(ScAddress::Contract(_))
+        ));
+        assert!(is_contract_farmer(TESTNET_CONTRACT));
+        assert!(farmer_address("not an address").is_err());
+    }
+
+    #[test]
+    fn test_count_leading_zeros() {
+        let mut hash = [0xffu8; 32];
+        assert_eq!(count_leading_zeros(&hash), 0);
+
+        hash[0] = 0x0f;
+        assert_eq!(count_leading_zeros(&hash), 1);
+
+        hash[0] = 0x00;
+        hash[1] = 0x00;
+        hash[2] = 0x01;
+        assert_eq!(count_leading_zeros(&hash), 5);
+
+        assert_eq!(count_leading_zeros(&[0u8; 32]), 64);
+    }
+
+    #[test]
+    fn test_decode_farm_call() -> Result<()> {
+        use stellar_xdr::curr::{
+            HostFunction, InvokeContractArgs, InvokeHostFunctionOp, Memo, MuxedAccount, Operation,
+            OperationBody, Preconditions, SequenceNumber, Transaction, TransactionEnvelope,
+            TransactionExt, TransactionV1Envelope, Uint256,
+        };
+
+        let kale = Kale::new(TESTNET_RPC, TESTNET_CONTRACT, TESTNET_PASSPHRASE)?;
+        let farmer = "GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6";
+        let fee_source = "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB";
+        let harvest = |source: &str| -> Result<String> {
+            let source = SorobanRpc::parse_account_key(source, "source")?;
+            let invoke_args = InvokeContractArgs {
+                contract_address: farmer_address(TESTNET_CONTRACT)?,
+                function_name: "harvest".try_into()?,
+                args: args()
+                    .address(farmer_address(farmer)?)
+                    .u32(42)
+                    .build()?
+                    .try_into()?,
+            };
+            let tx = Transaction {
+                source_account: MuxedAccount::Ed25519(Uint256(source)),
+                fee: 100,
+                seq_num: SequenceNumber(1),
+                cond: Preconditions::None,
+                memo: Memo::None,
+                operations: vec![Operation {
+                    source_account: None,
+                    body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                        host_function: HostFunction::InvokeContract(invoke_args),
+                        auth: Default::default(),
+                    }),
+                }]
+                .try_into()?,
+                ext: TransactionExt::V0,
+            };
+            let envelope = TransactionEnvelope::Tx(TransactionV1Envelope {
+                tx,
+                signatures: Default::default(),
+            });
+            Ok(envelope.to_xdr_base64(stellar_xdr::curr::Limits::none())?)
+        };
+
+        let call = kale.decode_farm_call(&harvest(farmer)?)?;
+        assert_eq!(call.farmer, farmer);
+        assert_eq!(call.fee_source, None);
+        assert_eq!(call.invocation, FarmInvocation::Harvest { block_index: 42 });
+        let call = kale.decode_farm_call(&harvest(fee_source)?)?;
+        assert_eq!(call.fee_source.as_deref(), Some(fee_source));
+
+        assert!(kale.decode_farm_call("not xdr").is_err());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_get_block_index() -> Result<()> {
         let kale = Kale::new(TESTNET_RPC, TESTNET_CONTRACT, TESTNET_PASSPHRASE)?;
@@ -616,4 +2525,202 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[ignore = "calls testnet"]
+    async fn test_get_pails_for_block() -> Result<()> {
+        let kale = Kale::new(TESTNET_RPC, TESTNET_CONTRACT, TESTNET_PASSPHRASE)?;
+        let block_index = kale.get_block_index().await?;
+        let farmers = vec![
+            "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB".to_string(),
+            "not a public key".to_string(),
+        ];
+
+        let pails = kale.get_pails_for_block(&farmers, block_index).await?;
+
+        assert_eq!(pails.len(), 2);
+        assert_eq!(
+            pails[0].as_ref().ok().copied(),
+            kale.get_pail_data(&farmers[0], block_index).await.ok()
+        );
+        assert!(pails[1].is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_from_sc_val() -> Result<()> {
+        use stellar_xdr::curr::{Int128Parts, ScMap, ScMapEntry, ScSymbol};
+
+        let i128_val = |value: i128| {
+            ScVal::I128(Int128Parts {
+                hi: (value >> 64) as i64,
+                lo: value as u64,
+            })
+        };
+        let mut fields = vec![
+            ("entropy", ScVal::Bytes(vec![7u8; 32].try_into()?)),
+            ("max_gap", ScVal::U32(40)),
+            ("max_stake", i128_val(50_000_000)),
+            ("max_zeros", ScVal::U32(9)),
+            ("min_gap", ScVal::U32(2)),
+            ("min_stake", i128_val(0)),
+            ("min_zeros", ScVal::U32(4)),
+            ("normalized_total", i128_val(3_000_000)),
+            ("staked_total", i128_val(60_000_000)),
+            ("timestamp", ScVal::U64(1_700_000_000)),
+        ];
+        let map = |fields: &[(&str, ScVal)]| -> Result<ScVal> {
+            let entries = fields
+                .iter()
+                .map(|(name, val)| {
+                    Ok(ScMapEntry {
+                        key: ScVal::Symbol(ScSymbol((*name).try_into()?)),
+                        val: val.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(ScVal::Map(Some(ScMap(entries.try_into()?))))
+        };
+
+        let block = Block::from_sc_val(&map(&fields)?)?;
+        assert_eq!(block.timestamp, 1_700_000_000);
+        assert_eq!(block.entropy, [7; 32]);
+        assert_eq!((block.min_gap, block.max_gap), (2, 40));
+        assert_eq!((block.min_stake, block.max_stake), (0, 50_000_000));
+        assert_eq!(block.zeros_range(), Some((4, 9)));
+        assert_eq!(block.staked_total, 60_000_000);
+        assert_eq!(block.normalized_total, 3_000_000);
+
+        // Nobody has worked a freshly planted block
+        fields[3].1 = ScVal::U32(0);
+        fields[6].1 = ScVal::U32(0);
+        assert_eq!(Block::from_sc_val(&map(&fields)?)?.zeros_range(), None);
+
+        fields.pop();
+        assert!(matches!(
+            Block::from_sc_val(&map(&fields)?),
+            Err(KaleError::UnexpectedData(message)) if message.contains("timestamp")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_reward() {
+        assert_eq!(block_reward(100), BLOCK_REWARD);
+        assert_eq!(
+            block_reward(V2_GENESIS_BLOCK + 2 * BLOCKS_PER_MONTH),
+            BLOCK_REWARD * 95 / 100 * 95 / 100
+        );
+
+        // Two workers: one top on every component, one bottom on all but stake
+        let block = Block {
+            timestamp: 0,
+            entropy: [0; 32],
+            min_gap: 2,
+            max_gap: 40,
+            min_stake: 100,
+            max_stake: 100,
+            min_zeros: 4,
+            max_zeros: 9,
+            staked_total: 200,
+            normalized_total: 4 * SCORE_SCALE,
+        };
+        let pail = |gap, zeros| Pail {
+            sequence: 1,
+            gap,
+            stake: 100,
+            zeros,
+        };
+        let pot = BLOCK_REWARD + 200;
+        assert_eq!(block.score(&pail(Some(40), Some(9))), Some(3 * SCORE_SCALE));
+        assert_eq!(
+            block.estimate_reward(100, &pail(Some(40), Some(9))),
+            Some(pot * 3 / 4)
+        );
+        assert_eq!(
+            block.estimate_reward(100, &pail(Some(2), Some(4))),
+            Some(pot / 4)
+        );
+        assert_eq!(block.estimate_reward(100, &pail(None, None)), None);
+    }
+
+    #[test]
+    fn test_pail_from_sc_val() -> Result<()> {
+        use stellar_xdr::curr::{Int128Parts, ScSymbol};
+
+        let pail = |gap: ScVal, zeros: ScVal| -> Result<ScVal> {
+            let field =
+                |name: &str| -> Result<ScVal> { Ok(ScVal::Symbol(ScSymbol(name.try_into()?))) };
+            let stake = ScVal::I128(Int128Parts {
+                hi: 0,
+                lo: 10_000_000,
+            });
+            Ok(args()
+                .map([
+                    (field("gap")?, gap),
+                    (field("sequence")?, ScVal::U32(1_000_000)),
+                    (field("stake")?, stake),
+                    (field("zeros")?, zeros),
+                ])
+                .build()?
+                .remove(0))
+        };
+
+        let worked = Pail::from_sc_val(&pail(ScVal::U32(12), ScVal::U32(7))?)?;
+        assert_eq!(
+            worked,
+            Pail {
+                sequence: 1_000_000,
+                gap: Some(12),
+                stake: 10_000_000,
+                zeros: Some(7),
+            }
+        );
+        assert_eq!(worked.data(), (true, true, 7));
+
+        let planted = Pail::from_sc_val(&pail(ScVal::Void, ScVal::Void)?)?;
+        assert_eq!((planted.gap, planted.zeros), (None, None));
+        assert_eq!(planted.data(), (true, false, 0));
+
+        assert!(Pail::from_sc_val(&pail(ScVal::Void, ScVal::Bool(true))?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_difficulty_from_samples() {
+        let estimate =
+            DifficultyEstimate::from_samples(100, &[(3, 8), (4, 9), (2, 7)], &[600.0, 300.0]);
+        assert_eq!(estimate.sampled_blocks, 3);
+        assert_eq!(estimate.median_min_zeros, Some(3));
+        assert_eq!(estimate.median_max_zeros, Some(8));
+        assert_eq!(estimate.recommended_zeros, 6);
+        assert_eq!(estimate.mean_block_gap_secs, 450);
+        assert_eq!(estimate.required_hash_rate, 16f64.powi(6) / 450.0);
+
+        // Nothing to learn from: the default target, and blocks no faster than the contract allows
+        let empty = DifficultyEstimate::from_samples(100, &[], &[120.0]);
+        assert_eq!(empty.median_max_zeros, None);
+        assert_eq!(empty.recommended_zeros, DEFAULT_TARGET_ZEROS);
+        assert_eq!(empty.mean_block_gap_secs, BLOCK_INTERVAL_SECS);
+    }
+
+    #[tokio::test]
+    async fn test_negative_stake_rejected_before_rpc() -> Result<()> {
+        // No request is made, so an unreachable node doesn't matter
+        let kale = Kale::new("http://127.0.0.1:9", TESTNET_CONTRACT, TESTNET_PASSPHRASE)?;
+        let result = kale
+            .prepare_plant_transaction(
+                "GCHPTWXMT3HYF4RLZHWBNRF4MPXLTJ76ISHMSYIWCCDXWUYOQG5MR2AB",
+                -1,
+                None,
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(KaleError::Farm(FarmError::NegativeStake { requested: -1 }))
+        ));
+        Ok(())
+    }
 }
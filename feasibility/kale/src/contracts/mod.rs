@@ -1 +1,2 @@
+pub mod changes;
 pub mod kale;
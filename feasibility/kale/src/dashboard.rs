@@ -0,0 +1,308 @@
+use crate::access::DenialStats;
+use crate::congestion::FeeStats;
+use crate::farmer::MiningReport;
+use crate::pause::Subsystem;
+
+/// How often the dashboard page reloads itself
+const REFRESH_SECS: u32 = 10;
+
+/// What the operator dashboard shows, gathered fresh for each page load
+pub struct DashboardMetrics {
+    pub network: &'static str,
+    /// Startup cache priming has finished
+    pub ready: bool,
+    /// Round trip of a contract read through the RPC node
+    pub rpc_latency_ms: u128,
+    /// Why the contract read failed, if it did
+    pub rpc_error: Option<String>,
+    pub block_index: Option<u32>,
+    pub has_entropy: bool,
+    /// Seconds until the current block is predicted to close; negative once overdue
+    pub block_closes_in: Option<i64>,
+    pub fees: Option<FeeStats>,
+    /// Connected WebSocket clients
+    pub live_clients: usize,
+    pub denials: DenialStats,
+    /// Background subsystems paused at /api/admin/pause
+    pub paused: Vec<Subsystem>,
+    /// The farmers of the fleet file, in its order
+    pub fleet: Vec<FarmerMetrics>,
+}
+
+/// A fleet farmer's harvest backlog and, if its daemon reports it, its mining
+pub struct FarmerMetrics {
+    pub account: String,
+    /// Blocks worked but not yet harvested; None if they couldn't be read
+    pub unharvested: Option<usize>,
+    /// The latest report from the farmer's `farm --status-file`
+    pub mining: Option<MiningReport>,
+}
+
+/// A self-contained page that reloads every few seconds, for a quick look at the server
+pub fn render(metrics: &DashboardMetrics) -> String {
+    let rpc = match &metrics.rpc_error {
+        None => format!("ok, {} ms", metrics.rpc_latency_ms),
+        Some(error) => format!(
+            "<span class=\"bad\">failing after {} ms</span>: {}",
+            metrics.rpc_latency_ms,
+            escape(error)
+        ),
+    };
+    let readiness = if metrics.ready { "ready" } else { "warming up" };
+
+    let block = match metrics.block_index {
+        Some(index) if metrics.has_entropy => index.to_string(),
+        Some(index) => format!("{} (nobody has planted yet)", index),
+        None => "unavailable".to_string(),
+    };
+    let countdown = match metrics.block_closes_in {
+        Some(secs) if secs >= 0 => format!("in about {}:{:02}", secs / 60, secs % 60),
+        Some(secs) => format!("overdue by {}s", -secs),
+        None => "unknown".to_string(),
+    };
+
+    let fees = match &metrics.fees {
+        Some(fees) => format!(
+            "ledger {}: {:.0}% full, median fee {} stroops (base {}){}",
+            fees.ledger,
+            fees.capacity_usage * 100.0,
+            fees.fee_p50,
+            fees.base_fee,
+            if fees.is_congested() {
+                " <span class=\"bad\">congested</span>"
+            } else {
+                ""
+            }
+        ),
+        None => "unavailable".to_string(),
+    };
+
+    let paused = if metrics.paused.is_empty() {
+        "nothing".to_string()
+    } else {
+        let names: Vec<_> = metrics
+            .paused
+            .iter()
+            .map(|subsystem| subsystem.name())
+            .collect();
+        format!("<span class=\"bad\">{}</span>", names.join(", "))
+    };
+
+    let backlog = if metrics.fleet.is_empty() {
+        "no fleet file".to_string()
+    } else {
+        let unharvested: usize = metrics
+            .fleet
+            .iter()
+            .filter_map(|farmer| farmer.unharvested)
+            .sum();
+        let unreadable = metrics
+            .fleet
+            .iter()
+            .filter(|farmer| farmer.unharvested.is_none())
+            .count();
+        let mut backlog = format!(
+            "{} blocks across {} farmers",
+            unharvested,
+            metrics.fleet.len()
+        );
+        if unreadable > 0 {
+            backlog.push_str(&format!(
+                " <span class=\"bad\">{} unreadable</span>",
+                unreadable
+            ));
+        }
+        backlog
+    };
+    let reports: Vec<_> = metrics
+        .fleet
+        .iter()
+        .filter_map(|farmer| farmer.mining.as_ref())
+        .collect();
+    let hash_rate = if reports.is_empty() {
+        "not reported".to_string()
+    } else {
+        format!(
+            "{} ({}/{} farmers reporting)",
+            format_hash_rate(reports.iter().map(|report| report.hash_rate()).sum()),
+            reports.len(),
+            metrics.fleet.len()
+        )
+    };
+    let farmers: String = metrics
+        .fleet
+        .iter()
+        .map(|farmer| {
+            let unharvested = match farmer.unharvested {
+                Some(blocks) => blocks.to_string(),
+                None => "<span class=\"bad\">unreadable</span>".to_string(),
+            };
+            let mining = match &farmer.mining {
+                Some(report) => format!(
+                    "{} (block {})",
+                    format_hash_rate(report.hash_rate()),
+                    report.block_index
+                ),
+                None => "not reported".to_string(),
+            };
+            format!(
+                "\n    <tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&farmer.account),
+                unharvested,
+                mining
+            )
+        })
+        .collect();
+    let fleet = if farmers.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n  <h2>Fleet</h2>\n  <table>\n    \
+             <tr><th>Farmer</th><th>Unharvested blocks</th><th>Hashrate</th></tr>{}\n  </table>",
+            farmers
+        )
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <meta http-equiv="refresh" content="{refresh}">
+  <title>Galactic Playground dashboard</title>
+  <style>
+    body {{ font-family: sans-serif; max-width: 720px; margin: 40px auto; color: #222; }}
+    td, th {{ padding: 4px 12px 4px 0; vertical-align: top; text-align: left; }}
+    .bad {{ color: #b00020; font-weight: bold; }}
+  </style>
+</head>
+<body>
+  <h1>Galactic Playground dashboard</h1>
+  <table>
+    <tr><td>Network</td><td>{network}</td></tr>
+    <tr><td>Server</td><td>{readiness}</td></tr>
+    <tr><td>RPC</td><td>{rpc}</td></tr>
+    <tr><td>Farm block</td><td>{block}</td></tr>
+    <tr><td>Block closes</td><td>{countdown}</td></tr>
+    <tr><td>Network load</td><td>{fees}</td></tr>
+    <tr><td>Live clients</td><td>{live_clients}</td></tr>
+    <tr><td>Refused requests</td><td>{denied} denied, {not_allowed} not allowed</td></tr>
+    <tr><td>Paused</td><td>{paused}</td></tr>
+    <tr><td>Harvest backlog</td><td>{backlog}</td></tr>
+    <tr><td>Hashrate</td><td>{hash_rate}</td></tr>
+  </table>{fleet}
+  <p>Reloads every {refresh} seconds.</p>
+</body>
+</html>
+"#,
+        refresh = REFRESH_SECS,
+        network = metrics.network,
+        readiness = readiness,
+        rpc = rpc,
+        block = block,
+        countdown = countdown,
+        fees = fees,
+        live_clients = metrics.live_clients,
+        denied = metrics.denials.denied,
+        not_allowed = metrics.denials.not_allowed,
+        paused = paused,
+        backlog = backlog,
+        hash_rate = hash_rate,
+        fleet = fleet,
+    )
+}
+
+/// A hashrate with a unit prefix, e.g. "1.5 MH/s"
+fn format_hash_rate(rate: f64) -> String {
+    let mut rate = rate;
+    for unit in ["H/s", "kH/s", "MH/s"] {
+        if rate < 1000.0 {
+            return format!("{:.1} {}", rate, unit);
+        }
+        rate /= 1000.0;
+    }
+    format!("{:.1} GH/s", rate)
+}
+
+/// Escape text for an HTML body
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(fleet: Vec<FarmerMetrics>) -> DashboardMetrics {
+        DashboardMetrics {
+            network: "testnet",
+            ready: true,
+            rpc_latency_ms: 120,
+            rpc_error: None,
+            block_index: Some(42),
+            has_entropy: true,
+            block_closes_in: Some(125),
+            fees: None,
+            live_clients: 3,
+            denials: DenialStats {
+                denied: 1,
+                not_allowed: 2,
+            },
+            paused: Vec::new(),
+            fleet,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let page = render(&metrics(Vec::new()));
+        assert!(page.contains("<tr><td>RPC</td><td>ok, 120 ms</td></tr>"));
+        assert!(page.contains("<tr><td>Farm block</td><td>42</td></tr>"));
+        assert!(page.contains("<tr><td>Block closes</td><td>in about 2:05</td></tr>"));
+        assert!(page.contains("<td>1 denied, 2 not allowed</td>"));
+        assert!(page.contains("<tr><td>Harvest backlog</td><td>no fleet file</td></tr>"));
+        assert!(page.contains("<tr><td>Hashrate</td><td>not reported</td></tr>"));
+        assert!(!page.contains("<h2>Fleet</h2>"));
+
+        let mut failing = metrics(vec![
+            FarmerMetrics {
+                account: "GA1".to_string(),
+                unharvested: Some(2),
+                mining: Some(MiningReport {
+                    block_index: 41,
+                    hashes: 3_000_000,
+                    mining_secs: 2.0,
+                    reported_at: 1_700_000_000,
+                }),
+            },
+            FarmerMetrics {
+                account: "GA2".to_string(),
+                unharvested: None,
+                mining: None,
+            },
+        ]);
+        failing.rpc_error = Some("<timeout>".to_string());
+        failing.paused = vec![Subsystem::Notifications];
+        let page = render(&failing);
+        assert!(page.contains("failing after 120 ms</span>: &lt;timeout&gt;"));
+        assert!(page.contains("<span class=\"bad\">notifications</span>"));
+        assert!(page.contains(
+            "<td>2 blocks across 2 farmers <span class=\"bad\">1 unreadable</span></td>"
+        ));
+        assert!(page.contains("<td>1.5 MH/s (1/2 farmers reporting)</td>"));
+        assert!(page.contains("<tr><td>GA1</td><td>2</td><td>1.5 MH/s (block 41)</td></tr>"));
+        assert!(page.contains(
+            "<tr><td>GA2</td><td><span class=\"bad\">unreadable</span></td><td>not reported</td></tr>"
+        ));
+    }
+
+    #[test]
+    fn test_format_hash_rate() {
+        assert_eq!(format_hash_rate(0.0), "0.0 H/s");
+        assert_eq!(format_hash_rate(999.0), "999.0 H/s");
+        assert_eq!(format_hash_rate(1_500.0), "1.5 kH/s");
+        assert_eq!(format_hash_rate(2_000_000_000_000.0), "2000.0 GH/s");
+    }
+}
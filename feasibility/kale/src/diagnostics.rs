@@ -0,0 +1,409 @@
+use serde::Serialize;
+use stellar_rpc_client::GetTransactionResponse;
+use stellar_xdr::curr::{
+    ContractEventBody, DiagnosticEvent, LedgerKey, OperationResult, OperationResultTr, ScAddress,
+    ScError, ScVal, TransactionEnvelope, TransactionExt, TransactionMeta,
+};
+
+use crate::scval_json::to_json;
+
+/// Decoded explanation of what happened to a submitted transaction
+#[derive(Debug, Serialize)]
+pub struct TransactionDiagnostics {
+    pub hash: String,
+    pub status: String,
+    #[serde(rename = "resultCode", skip_serializing_if = "Option::is_none")]
+    pub result_code: Option<String>,
+    #[serde(rename = "operationResults")]
+    pub operation_results: Vec<String>,
+    #[serde(rename = "feeCharged", skip_serializing_if = "Option::is_none")]
+    pub fee_charged: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceUsage>,
+    #[serde(rename = "diagnosticEvents")]
+    pub diagnostic_events: Vec<String>,
+    /// What the contract call returned, as JSON (see [`to_json`])
+    #[serde(rename = "returnValue", skip_serializing_if = "Option::is_none")]
+    pub return_value: Option<serde_json::Value>,
+    pub explanation: String,
+}
+
+impl TransactionDiagnostics {
+    /// The outcome without the resource and event detail, for pushing to clients
+    pub fn receipt(&self) -> TransactionReceipt {
+        TransactionReceipt {
+            result_code: self.result_code.clone(),
+            operation_results: self.operation_results.clone(),
+            fee_charged: self.fee_charged,
+            return_value: self.return_value.clone(),
+            explanation: self.explanation.clone(),
+        }
+    }
+}
+
+/// The decoded result of a transaction that reached a ledger
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionReceipt {
+    #[serde(rename = "resultCode", skip_serializing_if = "Option::is_none")]
+    pub result_code: Option<String>,
+    #[serde(rename = "operationResults")]
+    pub operation_results: Vec<String>,
+    #[serde(rename = "feeCharged", skip_serializing_if = "Option::is_none")]
+    pub fee_charged: Option<i64>,
+    #[serde(rename = "returnValue", skip_serializing_if = "Option::is_none")]
+    pub return_value: Option<serde_json::Value>,
+    pub explanation: String,
+}
+
+/// Soroban resources declared in the transaction vs. consumed during execution
+#[derive(Debug, Serialize)]
+pub struct ResourceUsage {
+    #[serde(rename = "declaredInstructions")]
+    pub declared_instructions: u32,
+    #[serde(
+        rename = "consumedInstructions",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consumed_instructions: Option<u64>,
+    #[serde(rename = "declaredReadBytes")]
+    pub declared_read_bytes: u32,
+    #[serde(rename = "consumedReadBytes", skip_serializing_if = "Option::is_none")]
+    pub consumed_read_bytes: Option<u64>,
+    #[serde(rename = "declaredWriteBytes")]
+    pub declared_write_bytes: u32,
+    #[serde(rename = "consumedWriteBytes", skip_serializing_if = "Option::is_none")]
+    pub consumed_write_bytes: Option<u64>,
+    #[serde(rename = "footprintReadOnly")]
+    pub footprint_read_only: Vec<String>,
+    #[serde(rename = "footprintReadWrite")]
+    pub footprint_read_write: Vec<String>,
+}
+
+/// Build diagnostics from a getTransaction response
+pub fn diagnose(hash: &str, response: &GetTransactionResponse) -> TransactionDiagnostics {
+    let result_code = response
+        .result
+        .as_ref()
+        .map(|result| result.result.name().to_string());
+
+    let operation_results = response
+        .result
+        .as_ref()
+        .map(|result| match &result.result {
+            stellar_xdr::curr::TransactionResultResult::TxSuccess(results)
+            | stellar_xdr::curr::TransactionResultResult::TxFailed(results) => {
+                results.iter().map(operation_result_name).collect()
+            }
+            _ => Vec::new(),
+        })
+        .unwrap_or_default();
+
+    let events = diagnostic_events(response.result_meta.as_ref());
+    let resources = response
+        .envelope
+        .as_ref()
+        .and_then(|envelope| resource_usage(envelope, &events));
+
+    let explanation = explain(
+        &response.status,
+        result_code.as_deref(),
+        &operation_results,
+        resources.as_ref(),
+        &events,
+    );
+
+    TransactionDiagnostics {
+        hash: hash.to_string(),
+        status: response.status.clone(),
+        result_code,
+        operation_results,
+        fee_charged: response.result.as_ref().map(|result| result.fee_charged),
+        resources,
+        diagnostic_events: events.iter().map(describe_event).collect(),
+        return_value: return_value(response.result_meta.as_ref()).map(to_json),
+        explanation,
+    }
+}
+
+/// Name of an operation result, down to the invoke host function result code
+fn operation_result_name(result: &OperationResult) -> String {
+    match result {
+        OperationResult::OpInner(OperationResultTr::InvokeHostFunction(invoke_result)) => {
+            invoke_result.name().to_string()
+        }
+        OperationResult::OpInner(inner) => inner.name().to_string(),
+        other => other.name().to_string(),
+    }
+}
+
+/// Extract the diagnostic events from Soroban transaction meta
+fn diagnostic_events(meta: Option<&TransactionMeta>) -> Vec<DiagnosticEvent> {
+    match meta {
+        Some(TransactionMeta::V3(meta)) => meta
+            .soroban_meta
+            .as_ref()
+            .map(|soroban_meta| soroban_meta.diagnostic_events.to_vec())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// The value a Soroban transaction's contract call returned, unless it returned nothing
+fn return_value(meta: Option<&TransactionMeta>) -> Option<&ScVal> {
+    match meta {
+        Some(TransactionMeta::V3(meta)) => meta
+            .soroban_meta
+            .as_ref()
+            .map(|soroban_meta| &soroban_meta.return_value)
+            .filter(|value| !matches!(value, ScVal::Void)),
+        _ => None,
+    }
+}
+
+/// Compare the declared resources with the `core_metrics` events emitted by the host
+fn resource_usage(
+    envelope: &TransactionEnvelope,
+    events: &[DiagnosticEvent],
+) -> Option<ResourceUsage> {
+    let TransactionEnvelope::Tx(envelope) = envelope else {
+        return None;
+    };
+    let TransactionExt::V1(soroban_data) = &envelope.tx.ext else {
+        return None;
+    };
+
+    let resources = &soroban_data.resources;
+    Some(ResourceUsage {
+        declared_instructions: resources.instructions,
+        consumed_instructions: core_metric(events, "cpu_insn"),
+        declared_read_bytes: resources.read_bytes,
+        consumed_read_bytes: core_metric(events, "ledger_read_byte"),
+        declared_write_bytes: resources.write_bytes,
+        consumed_write_bytes: core_metric(events, "ledger_write_byte"),
+        footprint_read_only: resources
+            .footprint
+            .read_only
+            .iter()
+            .map(describe_key)
+            .collect(),
+        footprint_read_write: resources
+            .footprint
+            .read_write
+            .iter()
+            .map(describe_key)
+            .collect(),
+    })
+}
+
+/// Read a `core_metrics` diagnostic event value, e.g. ("core_metrics", "cpu_insn") -> 1234
+fn core_metric(events: &[DiagnosticEvent], metric: &str) -> Option<u64> {
+    events.iter().find_map(|event| {
+        let ContractEventBody::V0(body) = &event.event.body;
+        match (body.topics.first(), body.topics.get(1), &body.data) {
+            (Some(ScVal::Symbol(kind)), Some(ScVal::Symbol(name)), ScVal::U64(value))
+                if kind.to_utf8_string_lossy() == "core_metrics"
+                    && name.to_utf8_string_lossy() == metric =>
+            {
+                Some(*value)
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Collect the host error messages (and their arguments) from diagnostic events
+fn error_messages(events: &[DiagnosticEvent]) -> Vec<(Option<ScError>, String)> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let ContractEventBody::V0(body) = &event.event.body;
+            match body.topics.first() {
+                Some(ScVal::Symbol(kind)) if kind.to_utf8_string_lossy() == "error" => {
+                    let error = body.topics.iter().find_map(|topic| match topic {
+                        ScVal::Error(error) => Some(error.clone()),
+                        _ => None,
+                    });
+                    Some((error, describe_value(&body.data)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Pick the most likely root cause and phrase it for humans
+fn explain(
+    status: &str,
+    result_code: Option<&str>,
+    operation_results: &[String],
+    resources: Option<&ResourceUsage>,
+    events: &[DiagnosticEvent],
+) -> String {
+    match status {
+        "SUCCESS" => return "Transaction succeeded".to_string(),
+        "NOT_FOUND" => {
+            return "Transaction not found: it is still pending, was never accepted, \
+                or is older than the RPC node's retention window"
+                .to_string()
+        }
+        _ => {}
+    }
+
+    match result_code {
+        Some("TxBadSeq") => {
+            return "Bad sequence number: another transaction from this account was \
+                submitted after this one was prepared. Prepare it again."
+                .to_string()
+        }
+        Some("TxTooLate") => {
+            return "Transaction expired before it was included. Prepare it again.".to_string()
+        }
+        Some("TxInsufficientBalance") => {
+            return "The source account doesn't have enough XLM to pay the fee".to_string()
+        }
+        Some("TxInsufficientFee") => {
+            return "The fee was too low to be included in a ledger".to_string()
+        }
+        _ => {}
+    }
+
+    let errors = error_messages(events);
+
+    // Accessing an entry outside the footprint means state changed between simulate and submit
+    if let Some((_, message)) = errors
+        .iter()
+        .find(|(_, message)| message.contains("footprint"))
+    {
+        return format!(
+            "Footprint missing a ledger entry — state changed between simulate and submit ({})",
+            message
+        );
+    }
+
+    match operation_results.first().map(String::as_str) {
+        Some("ResourceLimitExceeded") => {
+            if let Some(resources) = resources {
+                let checks = [
+                    (
+                        "CPU instructions",
+                        resources.consumed_instructions,
+                        resources.declared_instructions,
+                    ),
+                    (
+                        "read bytes",
+                        resources.consumed_read_bytes,
+                        resources.declared_read_bytes,
+                    ),
+                    (
+                        "write bytes",
+                        resources.consumed_write_bytes,
+                        resources.declared_write_bytes,
+                    ),
+                ];
+                for (name, consumed, declared) in checks {
+                    if let Some(consumed) = consumed {
+                        if consumed > declared as u64 {
+                            return format!(
+                                "Resource limit exceeded: {} consumed {} > declared {}",
+                                name, consumed, declared
+                            );
+                        }
+                    }
+                }
+            }
+            "Resource limit exceeded: the transaction used more resources than simulation declared"
+                .to_string()
+        }
+        Some("EntryArchived") => {
+            "A ledger entry in the footprint is archived and must be restored first".to_string()
+        }
+        Some("InsufficientRefundableFee") => {
+            "The refundable resource fee was too low (rent or events cost more than simulated)"
+                .to_string()
+        }
+        Some("Trapped") => match errors.iter().find_map(|(error, message)| match error {
+            Some(ScError::Contract(code)) => Some((*code, message)),
+            _ => None,
+        }) {
+            Some((code, message)) => {
+                format!("Contract returned error #{}: {}", code, message)
+            }
+            None => match errors.first() {
+                Some((_, message)) => format!("Contract execution trapped: {}", message),
+                None => "Contract execution trapped (no diagnostic events available)".to_string(),
+            },
+        },
+        _ => format!(
+            "Transaction failed with {}",
+            result_code.unwrap_or("an unknown result")
+        ),
+    }
+}
+
+/// Render a diagnostic event as "topic, topic: data"
+fn describe_event(event: &DiagnosticEvent) -> String {
+    let ContractEventBody::V0(body) = &event.event.body;
+    let topics: Vec<String> = body.topics.iter().map(describe_value).collect();
+    format!("{}: {}", topics.join(", "), describe_value(&body.data))
+}
+
+/// Render a footprint ledger key compactly, e.g. "Block(1234)" for contract data
+fn describe_key(key: &LedgerKey) -> String {
+    match key {
+        LedgerKey::ContractData(data) => describe_value(&data.key),
+        LedgerKey::ContractCode(_) => "ContractCode".to_string(),
+        LedgerKey::Account(account) => format!(
+            "Account({})",
+            describe_address(&ScAddress::Account(account.account_id.clone()))
+        ),
+        LedgerKey::Trustline(trustline) => format!(
+            "Trustline({})",
+            describe_address(&ScAddress::Account(trustline.account_id.clone()))
+        ),
+        other => other.name().to_string(),
+    }
+}
+
+/// Render an ScVal compactly; storage keys like Vec[Symbol("Block"), U32(1234)] become "Block(1234)"
+pub(crate) fn describe_value(value: &ScVal) -> String {
+    match value {
+        ScVal::Vec(Some(items)) => match items.split_first() {
+            Some((ScVal::Symbol(name), args)) => format!(
+                "{}({})",
+                name.to_utf8_string_lossy(),
+                args.iter()
+                    .map(describe_value)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(describe_value)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        ScVal::Symbol(symbol) => symbol.to_utf8_string_lossy(),
+        ScVal::String(string) => string.to_utf8_string_lossy(),
+        ScVal::U32(value) => value.to_string(),
+        ScVal::I32(value) => value.to_string(),
+        ScVal::U64(value) => value.to_string(),
+        ScVal::I64(value) => value.to_string(),
+        ScVal::I128(value) => ((i128::from(value.hi) << 64) | i128::from(value.lo)).to_string(),
+        ScVal::Address(address) => describe_address(address),
+        ScVal::LedgerKeyContractInstance => "Instance".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Render an address as its strkey (G... or C...)
+fn describe_address(address: &ScAddress) -> String {
+    match address {
+        ScAddress::Account(stellar_xdr::curr::AccountId(
+            stellar_xdr::curr::PublicKey::PublicKeyTypeEd25519(key),
+        )) => stellar_strkey::ed25519::PublicKey(key.0).to_string(),
+        ScAddress::Contract(hash) => stellar_strkey::Contract(hash.0).to_string(),
+    }
+}
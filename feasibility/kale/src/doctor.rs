@@ -0,0 +1,245 @@
+use anyhow::Result;
+use galacticplayground::config::{Config, Settings};
+use galacticplayground::contracts::kale::Kale;
+use galacticplayground::endpoint::RpcEndpoint;
+use std::time::Duration;
+use stellar_rpc_client::Client;
+
+/// Newest protocol whose XDR this build decodes (that of its stellar-xdr version)
+///
+/// Futurenet trials protocols before testnet gets them; its ledgers can then hold entries,
+/// results and meta in XDR this build can't read.
+const SUPPORTED_PROTOCOL: u32 = 22;
+
+/// Longest to wait for Horizon or friendbot to answer
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The config to check, from settings that may leave out the contract and KALE issuer
+///
+/// Futurenet and local have no KALE deployment to default to. The network checks don't
+/// need one, so a contract or issuer the network has no default for is left empty, and the
+/// contract checks are skipped.
+pub fn config(mut settings: Settings) -> Result<Config> {
+    let defaults = settings.network.unwrap_or_default().defaults();
+    if defaults.contract_id.is_none() {
+        settings.contract_id.get_or_insert_with(String::new);
+    }
+    if defaults.kale_issuer.is_none() {
+        settings.kale_issuer.get_or_insert_with(String::new);
+    }
+    Config::resolve(settings)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    /// Works now, but something may break, e.g. a protocol newer than this build
+    Warn,
+    Fail,
+    /// Not applicable to this network
+    Skip,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+            Status::Skip => "SKIP",
+        }
+    }
+}
+
+/// The checks run so far, printed as they finish
+struct Report {
+    /// Strips the RPC provider's credentials from error messages
+    endpoint: RpcEndpoint,
+    failed: usize,
+    warned: usize,
+}
+
+impl Report {
+    fn record(&mut self, check: &str, outcome: Result<(Status, String), impl std::fmt::Display>) {
+        let (status, detail) =
+            outcome.unwrap_or_else(|e| (Status::Fail, self.endpoint.redact(&e.to_string())));
+        match status {
+            Status::Fail => self.failed += 1,
+            Status::Warn => self.warned += 1,
+            Status::Pass | Status::Skip => {}
+        }
+        println!("{}  {:<18} {}", status.label(), check, detail);
+    }
+
+    fn skip(&mut self, check: &str, reason: &str) {
+        self.record(check, Ok::<_, String>((Status::Skip, reason.to_string())));
+    }
+}
+
+/// Whether the network's protocol is one this build decodes
+fn protocol_status(version: u32) -> (Status, String) {
+    if version > SUPPORTED_PROTOCOL {
+        let detail = format!(
+            "protocol {} is newer than the {} this build decodes; new XDR may fail to parse",
+            version, SUPPORTED_PROTOCOL
+        );
+        (Status::Warn, detail)
+    } else {
+        (Status::Pass, format!("protocol {}", version))
+    }
+}
+
+/// Whether an HTTP service answers; friendbot answers 400 when asked to fund no one
+async fn reachable(url: &str) -> Result<(Status, String), reqwest::Error> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .timeout(HTTP_TIMEOUT)
+        .send()
+        .await?;
+    let status = if response.status().is_server_error() {
+        Status::Fail
+    } else {
+        Status::Pass
+    };
+    Ok((status, format!("{} answered {}", url, response.status())))
+}
+
+/// Self-test the configured network, e.g. futurenet before trialling a protocol on it
+///
+/// Checks the passphrase, the protocol version against the XDR this build decodes, that
+/// the network's config settings and the contract's instance storage and current Block
+/// entry decode, and that Horizon and friendbot answer. Prints a line per check and fails if any
+/// check failed; warnings don't fail it.
+pub async fn run(config: &Config) -> Result<()> {
+    let endpoint = config.endpoint();
+    println!(
+        "Checking {} via {}\n",
+        config.network.name(),
+        endpoint.redacted()
+    );
+    let client = endpoint
+        .header_map()
+        .and_then(|headers| Ok(Client::new_with_headers(&endpoint.full_url(), headers)?));
+    let info = match client {
+        Ok(client) => client.get_network().await.map_err(anyhow::Error::from),
+        Err(e) => Err(e),
+    };
+    let mut report = Report {
+        endpoint,
+        failed: 0,
+        warned: 0,
+    };
+    match info {
+        Ok(info) => {
+            let passphrase = if info.passphrase == config.network_passphrase {
+                Ok((Status::Pass, info.passphrase))
+            } else {
+                Err(format!(
+                    "RPC reports '{}', expected '{}'",
+                    info.passphrase, config.network_passphrase
+                ))
+            };
+            report.record("passphrase", passphrase);
+            report.record(
+                "protocol",
+                Ok::<_, String>(protocol_status(info.protocol_version)),
+            );
+        }
+        Err(e) => {
+            report.record("passphrase", Err(e));
+            report.skip("protocol", "network info unavailable");
+        }
+    }
+
+    if config.contract_id.is_empty() {
+        let reason = "no KALE deployment to default to; set KALE_CONTRACT_ID";
+        report.skip("network limits", reason);
+        report.skip("instance storage", reason);
+        report.skip("block entry", reason);
+    } else {
+        let kale = Kale::from_config(config)?;
+        let limits = kale.get_network_limits().await;
+        report.record(
+            "network limits",
+            limits.map(|_| (Status::Pass, "config settings decode".to_string())),
+        );
+        let storage = kale.dump_instance_storage().await;
+        report.record(
+            "instance storage",
+            storage.map(|storage| {
+                let keys = storage.entries.keys().cloned().collect::<Vec<_>>();
+                (Status::Pass, format!("decodes: {}", keys.join(", ")))
+            }),
+        );
+        match kale.get_block_index().await {
+            Ok(block_index) => {
+                let block = kale.get_block(block_index).await;
+                report.record(
+                    "block entry",
+                    block.map(|block| match block {
+                        Some(_) => (Status::Pass, format!("block {} decodes", block_index)),
+                        None => (
+                            Status::Pass,
+                            format!("block {} has no entry until someone plants", block_index),
+                        ),
+                    }),
+                );
+            }
+            Err(e) => report.record("block entry", Err(e)),
+        }
+    }
+
+    report.record("horizon", reachable(&config.horizon_url).await);
+    match &config.friendbot_url {
+        Some(url) => report.record("friendbot", reachable(url).await),
+        None => report.skip("friendbot", "none on this network"),
+    }
+
+    println!();
+    if report.failed > 0 {
+        anyhow::bail!(
+            "{} of the checks failed on {}",
+            report.failed,
+            config.network.name()
+        );
+    }
+    println!(
+        "✓ {} is usable ({} warnings)",
+        config.network.name(),
+        report.warned
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galacticplayground::config::Network;
+
+    #[test]
+    fn test_protocol_status() {
+        assert_eq!(protocol_status(SUPPORTED_PROTOCOL).0, Status::Pass);
+        assert_eq!(protocol_status(SUPPORTED_PROTOCOL - 1).0, Status::Pass);
+        let (status, detail) = protocol_status(SUPPORTED_PROTOCOL + 1);
+        assert_eq!(status, Status::Warn);
+        assert!(detail.contains("newer"));
+    }
+
+    #[test]
+    fn test_config_without_deployment() {
+        let futurenet = Settings {
+            network: Some(Network::Futurenet),
+            ..Default::default()
+        };
+        assert!(Config::resolve(futurenet.clone()).is_err());
+        let resolved = config(futurenet).unwrap();
+        assert_eq!(
+            resolved.network_passphrase,
+            "Test SDF Future Network ; October 2022"
+        );
+        assert!(resolved.contract_id.is_empty());
+
+        // Networks with a deployment keep their defaults
+        assert!(!config(Settings::default()).unwrap().contract_id.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// Placeholder printed instead of credential values
+const REDACTED: &str = "***";
+
+/// A Soroban RPC endpoint, with the credentials a commercial provider may require
+///
+/// Header and query parameter values are treated as secrets: [`RpcEndpoint::redacted`]
+/// and the `Debug` output never include them.
+#[derive(Clone)]
+pub struct RpcEndpoint {
+    pub url: String,
+    /// Extra HTTP headers sent with every request (e.g. an API key header)
+    pub headers: Vec<(String, String)>,
+    /// Extra query parameters appended to the URL (e.g. `apikey`)
+    pub query: Vec<(String, String)>,
+}
+
+impl RpcEndpoint {
+    /// An endpoint without credentials
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: Vec::new(),
+            query: Vec::new(),
+        }
+    }
+
+    /// Load the endpoint from `KALE_RPC_URL` (falling back to `default_url`),
+    /// `KALE_RPC_HEADERS` ("Name: value" pairs separated by ";") and
+    /// `KALE_RPC_QUERY` ("name=value" pairs separated by "&")
+    pub fn from_env(default_url: &str) -> Result<Self> {
+        let url = std::env::var("KALE_RPC_URL").unwrap_or_else(|_| default_url.to_string());
+
+        let headers = match std::env::var("KALE_RPC_HEADERS") {
+            Ok(value) => parse_pairs(&value, ';', ':')
+                .context("KALE_RPC_HEADERS must be \"Name: value\" pairs separated by \";\"")?,
+            Err(_) => Vec::new(),
+        };
+
+        let query = match std::env::var("KALE_RPC_QUERY") {
+            Ok(value) => parse_pairs(&value, '&', '=')
+                .context("KALE_RPC_QUERY must be \"name=value\" pairs separated by \"&\"")?,
+            Err(_) => Vec::new(),
+        };
+
+        Ok(Self {
+            url,
+            headers,
+            query,
+        })
+    }
+
+    /// The URL to connect to, including query parameters
+    pub fn full_url(&self) -> String {
+        self.url_with(|value| value)
+    }
+
+    /// A description safe for logs: header names and query names only
+    pub fn redacted(&self) -> String {
+        let url = self.url_with(|_| REDACTED);
+        if self.headers.is_empty() {
+            return url;
+        }
+
+        let headers: Vec<String> = self
+            .headers
+            .iter()
+            .map(|(name, _)| format!("{}: {}", name, REDACTED))
+            .collect();
+        format!("{} (headers: {})", url, headers.join(", "))
+    }
+
+    /// Replace every header and query parameter value in `text`, e.g. an error message
+    /// that may echo the URL
+    pub fn redact(&self, text: &str) -> String {
+        self.headers
+            .iter()
+            .chain(&self.query)
+            .map(|(_, value)| value)
+            .filter(|value| !value.is_empty())
+            .fold(text.to_string(), |text, value| {
+                text.replace(value.as_str(), REDACTED)
+            })
+    }
+
+    /// The extra headers as an HTTP header map
+    pub fn header_map(&self) -> Result<HeaderMap> {
+        let mut map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid RPC header name '{}'", name))?;
+            // Don't echo the value: it's usually a credential
+            let header_value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for RPC header '{}'", name))?;
+            map.insert(header_name, header_value);
+        }
+
+        Ok(map)
+    }
+
+    fn url_with<'a>(&'a self, value: impl Fn(&'a str) -> &'a str) -> String {
+        if self.query.is_empty() {
+            return self.url.clone();
+        }
+
+        let query: Vec<String> = self
+            .query
+            .iter()
+            .map(|(name, v)| format!("{}={}", name, value(v)))
+            .collect();
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        format!("{}{}{}", self.url, separator, query.join("&"))
+    }
+}
+
+impl std::fmt::Debug for RpcEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RpcEndpoint({})", self.redacted())
+    }
+}
+
+/// Split "a<sep>b<sep>..." into trimmed (name, value) pairs at the first `assign`
+pub(crate) fn parse_pairs(
+    input: &str,
+    separator: char,
+    assign: char,
+) -> Result<Vec<(String, String)>> {
+    input
+        .split(separator)
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once(assign)
+                .with_context(|| format!("Missing '{}' in pair", assign))?;
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted() {
+        let endpoint = RpcEndpoint {
+            url: "https://rpc.example.com".to_string(),
+            headers: parse_pairs(
+                "X-Api-Key: secret1; Authorization: Bearer secret2",
+                ';',
+                ':',
+            )
+            .unwrap(),
+            query: parse_pairs("apikey=secret3", '&', '=').unwrap(),
+        };
+
+        assert_eq!(
+            endpoint.full_url(),
+            "https://rpc.example.com?apikey=secret3"
+        );
+        assert_eq!(endpoint.header_map().unwrap().len(), 2);
+
+        let redacted = format!("{} {:?}", endpoint.redacted(), endpoint);
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("X-Api-Key"));
+
+        let message = format!("error sending request for url ({})", endpoint.full_url());
+        assert_eq!(
+            endpoint.redact(&message),
+            "error sending request for url (https://rpc.example.com?apikey=***)"
+        );
+    }
+}
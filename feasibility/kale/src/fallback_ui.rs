@@ -0,0 +1,131 @@
+use std::path::Path;
+
+/// Where the built frontend is served from
+pub const FRONTEND_DIR: &str = "frontend/dist";
+
+/// Read-only endpoints listed (and linked) on the status page: (path, description)
+const GET_ENDPOINTS: &[(&str, &str)] = &[
+    ("/readyz", "Readiness (cache priming done)"),
+    (
+        "/api/server_info",
+        "Version, network and maintenance status",
+    ),
+    ("/dashboard", "Operator dashboard (allowed hosts only)"),
+    ("/api/network", "Network, contract and explorer"),
+    ("/api/block_info", "Current block index and entropy"),
+    ("/api/network_limits", "Soroban limits and fee rates"),
+    ("/api/price", "KALE/USD price, if a source is configured"),
+    (
+        "/api/submit_advice?kind=harvest",
+        "Submit now or wait a ledger",
+    ),
+    (
+        "/api/harvestable?publicKey={pubkey}",
+        "Past blocks a farmer can still harvest",
+    ),
+    ("/api/difficulty", "Leading zeros worth mining for"),
+    ("/api/block/{blockIndex}", "A past or current block's stats"),
+    ("/api/contract_state", "The contract's instance storage"),
+    ("/api/tx/{hash}/diagnostics", "Explain a transaction"),
+    ("/api/proof/{pubkey}/{blockIndex}", "Participation proof"),
+    ("/api/signing/{jobId}/status", "External signing progress"),
+    ("/static/kale-signing.js", "Signing helper script"),
+];
+
+/// JSON POST endpoints listed on the status page: (path, description)
+const POST_ENDPOINTS: &[(&str, &str)] = &[
+    ("/api/check_planted", "Whether a farmer planted this block"),
+    ("/api/pail_data", "A farmer's pail in a block"),
+    ("/api/account_status", "Balances and KALE trustline"),
+    ("/api/plant/prepare", "Build a plant transaction"),
+    ("/api/work/prepare", "Build a work transaction"),
+    ("/api/harvest/prepare", "Build a harvest transaction"),
+    ("/api/trustline/prepare", "Build a trustline transaction"),
+    (
+        "/api/refresh_xdr",
+        "Rebuild an expired plant, work or harvest transaction",
+    ),
+    (
+        "/api/feebump/prepare",
+        "Wrap a signed transaction in a sponsor's fee bump",
+    ),
+    (
+        "/api/passkey/challenge",
+        "WebAuthn challenge for a smart wallet's authorization",
+    ),
+    (
+        "/api/passkey/attach",
+        "Add a passkey signature to a prepared transaction",
+    ),
+    ("/api/restore/submit", "Submit a signed restore transaction"),
+    (
+        "/api/fee_explain",
+        "Break down a prepared transaction's fee",
+    ),
+];
+
+/// Whether the frontend bundle has been built
+///
+/// The app page loads `/bundle.js` from the frontend directory, so without it the
+/// page would render blank.
+pub fn frontend_built() -> bool {
+    Path::new(FRONTEND_DIR).join("bundle.js").is_file()
+}
+
+/// A minimal self-contained status page for installs without a built frontend
+///
+/// `block_index` is None if the contract couldn't be read.
+pub fn render_status_page(block_index: Option<u32>, ready: bool) -> String {
+    let block = match block_index {
+        Some(index) => index.to_string(),
+        None => "unavailable".to_string(),
+    };
+    let readiness = if ready { "ready" } else { "warming up" };
+
+    let gets = GET_ENDPOINTS.iter().map(|(path, description)| {
+        // Paths with parameters can't be followed as-is
+        let link = if path.contains('{') {
+            path.to_string()
+        } else {
+            format!("<a href=\"{0}\">{0}</a>", path)
+        };
+        ("GET", link, description)
+    });
+    let posts = POST_ENDPOINTS
+        .iter()
+        .map(|(path, description)| ("POST", path.to_string(), description));
+    let endpoints: String = gets
+        .chain(posts)
+        .map(|(method, path, description)| {
+            format!(
+                "<tr><td>{}</td><td><code>{}</code></td><td>{}</td></tr>\n",
+                method, path, description
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>Galactic Playground</title>
+  <style>
+    body {{ font-family: sans-serif; max-width: 720px; margin: 40px auto; color: #222; }}
+    td {{ padding: 4px 12px 4px 0; vertical-align: top; }}
+  </style>
+</head>
+<body>
+  <h1>Galactic Playground</h1>
+  <p>The web interface has not been built, so this server is running headless.
+  Run <code>npm install &amp;&amp; npm run build</code> in <code>frontend/</code> and restart to get it.</p>
+  <h2>Status</h2>
+  <p>Current farm block: <strong>{block}</strong><br>Server: {readiness}</p>
+  <h2>API</h2>
+  <table>
+{endpoints}  </table>
+</body>
+</html>
+"#
+    )
+}
@@ -0,0 +1,765 @@
+use crate::headless::{check_success, FarmCall, HeadlessFarmer};
+use crate::hooks::{BlockDecision, Hooks, NewBlock, WorkFound};
+use crate::pause::{Pauses, Subsystem};
+use crate::sign_agent::Keys;
+use anyhow::Result;
+use async_trait::async_trait;
+use galacticplayground::config::Config;
+use galacticplayground::contracts::kale::{
+    count_leading_zeros, work_hash, DifficultyEstimate, KaleError, UnharvestedBlock,
+    DEFAULT_TARGET_ZEROS,
+};
+use galacticplayground::rpc::SorobanRpc;
+use galacticplayground::watcher::BlockWatcher;
+use serde::{Deserialize, Serialize};
+use std::fmt::Arguments;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+
+/// How often to check whether the farm has moved to a new block
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Attempts per phase before giving up on it until the next block
+const PHASE_ATTEMPTS: u32 = 3;
+
+/// Delay before a phase's first retry; doubles after each failed attempt
+const PHASE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Past blocks checked for unharvested work each block (about two hours)
+const HARVEST_LOOKBACK: u32 = 24;
+
+/// Nonces each mining thread tries between checks of the clock and the other threads
+const MINING_BATCH: u64 = 10_000;
+
+/// What the daemon does each block
+pub struct FarmOptions {
+    /// Stake in stroops
+    pub stake: i128,
+    /// Stop mining once a hash has this many leading zeros; estimated each block if `None`
+    pub target_zeros: Option<u32>,
+    /// Longest to mine each block before submitting the best nonce found
+    pub mining_budget: Duration,
+    /// Mining threads; every core if `None`
+    pub mining_threads: Option<usize>,
+    /// User script run at points in each block; see [`Hooks`]
+    pub hooks: Option<Hooks>,
+    /// File naming the subsystems to pause (`harvester`, `miner`, `blockWatcher`), reread
+    /// at every poll; see [`Pauses::load_file`]
+    pub pause_file: Option<PathBuf>,
+    /// File the latest [`MiningReport`] is written to after each block's mining
+    pub status_file: Option<PathBuf>,
+}
+
+/// How the daemon's latest mining went, written to its status file for the server's
+/// dashboard to show the hashrate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MiningReport {
+    pub block_index: u32,
+    /// Hashes tried across every mining thread
+    pub hashes: u64,
+    pub mining_secs: f64,
+    /// Unix seconds
+    pub reported_at: u64,
+}
+
+impl MiningReport {
+    /// Read a daemon's status file
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Hashes per second; 0 if mining took no measurable time
+    pub fn hash_rate(&self) -> f64 {
+        if self.mining_secs > 0.0 {
+            self.hashes as f64 / self.mining_secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The farm as the daemon sees it: reads, and farming calls signed for one farmer
+///
+/// Implemented by [`HeadlessFarmer`] against the network, and by the simulated farm in
+/// tests so the daemon's block cycle can be checked without testnet timing.
+#[async_trait]
+pub trait FarmBackend: Send + Sync {
+    /// The farmer's address (G...)
+    fn farmer(&self) -> String;
+
+    /// Current block index and entropy (None if nobody has planted yet)
+    async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)>;
+
+    /// (has_pail, has_worked, leading_zeros) for the farmer in a block
+    async fn get_pail_data(&self, block_index: u32) -> Result<(bool, bool, u32)>;
+
+    async fn find_unharvested_blocks(&self, lookback: u32) -> Result<Vec<UnharvestedBlock>>;
+
+    /// The zeros recent blocks suggest mining for
+    async fn estimate_difficulty(&self) -> Result<DifficultyEstimate>;
+
+    /// Run a farming call, failing unless it succeeds; returns the transaction hash
+    ///
+    /// `retry` counts earlier tries of the call, which bid more inclusion fee each.
+    async fn execute(&self, call: &FarmCall, retry: u32) -> Result<String>;
+}
+
+#[async_trait]
+impl FarmBackend for HeadlessFarmer {
+    fn farmer(&self) -> String {
+        HeadlessFarmer::farmer(self)
+    }
+
+    async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)> {
+        Ok(self.kale().get_block_info().await?)
+    }
+
+    async fn get_pail_data(&self, block_index: u32) -> Result<(bool, bool, u32)> {
+        let farmer = HeadlessFarmer::farmer(self);
+        Ok(self.kale().get_pail_data(&farmer, block_index).await?)
+    }
+
+    async fn find_unharvested_blocks(&self, lookback: u32) -> Result<Vec<UnharvestedBlock>> {
+        let farmer = HeadlessFarmer::farmer(self);
+        Ok(self
+            .kale()
+            .find_unharvested_blocks(&farmer, lookback)
+            .await?)
+    }
+
+    async fn estimate_difficulty(&self) -> Result<DifficultyEstimate> {
+        Ok(self.kale().estimate_difficulty().await?)
+    }
+
+    async fn execute(&self, call: &FarmCall, retry: u32) -> Result<String> {
+        let submitted = HeadlessFarmer::execute(self, call, retry).await?;
+        check_success(call.name(), &submitted)?;
+        Ok(submitted.hash)
+    }
+}
+
+/// Farm every block until the process is stopped
+///
+/// Logs are one `key=value` line per phase outcome; see [`Daemon::farm_block`].
+pub async fn run(config: &Config, keys: Keys, options: FarmOptions) -> Result<()> {
+    let farmer = HeadlessFarmer::new(config, keys).await?;
+    log(
+        "start",
+        0,
+        format_args!(
+            "farmer={} network={} stake={} target_zeros={}",
+            farmer.farmer(),
+            config.network.name(),
+            options.stake,
+            options
+                .target_zeros
+                .map_or("auto".to_string(), |zeros| zeros.to_string())
+        ),
+    );
+
+    let pause_file = options.pause_file.clone();
+    let daemon = Daemon::new(farmer, options)?;
+    let watcher = BlockWatcher::new(BLOCK_POLL_INTERVAL);
+    let mut updates = watcher.subscribe();
+    // Read before the first poll, so a paused daemon never starts a block
+    if let Some(path) = &pause_file {
+        apply_pause_file(&daemon.pauses, &watcher, path);
+    }
+    let pausing = async {
+        let Some(path) = &pause_file else {
+            return std::future::pending().await;
+        };
+        let mut interval = tokio::time::interval(BLOCK_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            apply_pause_file(&daemon.pauses, &watcher, path);
+        }
+    };
+    let farming = async {
+        loop {
+            let update = match updates.recv().await {
+                Ok(update) => update,
+                // Only the latest block matters, and the check below finds it
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            };
+            // Skip blocks that went by while the previous one was being farmed
+            let current = watcher.current().map(|current| current.block_index);
+            if update.is_new_block() && current == Some(update.block_index) {
+                log("block", update.block_index, format_args!("result=new"));
+                daemon.farm_block(update.block_index).await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = watcher.run(daemon.backend.kale()) => {}
+        _ = farming => {}
+        _ = pausing => {}
+    }
+    Ok(())
+}
+
+/// Pause and resume per the pause file, logging each change; a bad file changes nothing
+fn apply_pause_file(pauses: &Pauses, watcher: &BlockWatcher, path: &Path) {
+    match pauses.load_file(path) {
+        Ok(changes) => {
+            for (subsystem, paused) in changes {
+                if subsystem == Subsystem::BlockWatcher {
+                    watcher.set_paused(paused);
+                }
+                log(
+                    "pause",
+                    0,
+                    format_args!(
+                        "result={} subsystem={}",
+                        if paused { "paused" } else { "resumed" },
+                        subsystem.name()
+                    ),
+                );
+            }
+        }
+        Err(e) => log(
+            "pause",
+            0,
+            format_args!("result=error error={:?}", format!("{:#}", e)),
+        ),
+    }
+}
+
+/// A farmer and what it does each block
+pub struct Daemon<B> {
+    backend: B,
+    options: FarmOptions,
+    farmer_key: [u8; 32],
+    pauses: Pauses,
+}
+
+impl<B: FarmBackend> Daemon<B> {
+    pub fn new(backend: B, options: FarmOptions) -> Result<Self> {
+        let farmer_key = SorobanRpc::parse_account_key(&backend.farmer(), "farmer")?;
+        Ok(Self {
+            backend,
+            options,
+            farmer_key,
+            pauses: Pauses::default(),
+        })
+    }
+
+    /// Farm a block the farm just moved to
+    ///
+    /// Harvests past blocks the farmer worked, plants with the stake, mines a nonce and
+    /// submits the work. A phase that keeps failing is logged and left until the next
+    /// block rather than stopping the daemon. The hooks may skip the block or change its
+    /// stake, and may hold back the work. A paused harvester or miner skips its part.
+    pub async fn farm_block(&self, block_index: u32) {
+        let stake = self.on_new_block(block_index);
+        if self.pauses.is_paused(Subsystem::Harvester) {
+            log(
+                "harvest",
+                block_index,
+                format_args!("result=skipped reason=paused"),
+            );
+        } else {
+            self.harvest(block_index).await;
+        }
+        let Some(stake) = stake else {
+            return;
+        };
+        if self.pauses.is_paused(Subsystem::Miner) {
+            log(
+                "plant",
+                block_index,
+                format_args!("result=skipped reason=paused"),
+            );
+            return;
+        }
+        if self.plant(block_index, stake).await {
+            self.work(block_index).await;
+        }
+    }
+
+    /// The stake to plant in the block, or `None` if the hooks skip it
+    fn on_new_block(&self, block_index: u32) -> Option<i128> {
+        let stake = self.options.stake;
+        let Some(hooks) = &self.options.hooks else {
+            return Some(stake);
+        };
+        let farmer = self.backend.farmer();
+        let block = NewBlock {
+            block_index,
+            farmer: &farmer,
+            stake,
+        };
+        match hooks.on_new_block(&block) {
+            Ok(BlockDecision::Plant(stake)) => Some(stake),
+            Ok(BlockDecision::Skip) => {
+                log(
+                    "block",
+                    block_index,
+                    format_args!("result=skipped reason=hook"),
+                );
+                None
+            }
+            Err(e) => {
+                hook_failed(block_index, e);
+                Some(stake)
+            }
+        }
+    }
+
+    /// Harvest every recent block the farmer worked but hasn't harvested yet
+    async fn harvest(&self, block_index: u32) {
+        let found = phase("scan", block_index, || {
+            self.backend.find_unharvested_blocks(HARVEST_LOOKBACK)
+        })
+        .await;
+        let Some(blocks) = found else {
+            return;
+        };
+        for block in blocks.into_iter().filter(|block| block.has_worked) {
+            let harvested = self
+                .submit(block.block_index, &FarmCall::Harvest(block.block_index))
+                .await;
+            if let (true, Some(hooks)) = (harvested, &self.options.hooks) {
+                let farmer = self.backend.farmer();
+                if let Err(e) = hooks.on_harvest(block.block_index, &farmer) {
+                    hook_failed(block.block_index, e);
+                }
+            }
+        }
+    }
+
+    /// Plant in the block unless already planted, e.g. before a restart
+    ///
+    /// Returns whether the farmer has a pail in the block to work.
+    async fn plant(&self, block_index: u32, stake: i128) -> bool {
+        let pail = phase("pail", block_index, || {
+            self.backend.get_pail_data(block_index)
+        })
+        .await;
+        match pail {
+            Some((true, _, _)) => {
+                log(
+                    "plant",
+                    block_index,
+                    format_args!("result=skipped reason=planted"),
+                );
+                true
+            }
+            Some((false, _, _)) => self.submit(block_index, &FarmCall::Plant(stake)).await,
+            None => false,
+        }
+    }
+
+    /// Mine a nonce for the block and submit it as work
+    async fn work(&self, block_index: u32) {
+        // Planting creates the block's entropy, so read it afresh
+        let info = phase("entropy", block_index, || self.backend.get_block_info()).await;
+        let entropy = match info {
+            Some((current, Some(entropy))) if current == block_index => entropy,
+            Some((current, _)) => {
+                log(
+                    "mine",
+                    block_index,
+                    format_args!("result=skipped reason=no_entropy current={}", current),
+                );
+                return;
+            }
+            None => return,
+        };
+
+        let started = Instant::now();
+        let farmer_key = self.farmer_key;
+        let target_zeros = match self.options.target_zeros {
+            Some(zeros) => zeros,
+            None => self.target_zeros(block_index).await,
+        };
+        let budget = self.options.mining_budget;
+        let threads = self
+            .options
+            .mining_threads
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let mined = tokio::task::spawn_blocking(move || {
+            mine(
+                block_index,
+                &entropy,
+                &farmer_key,
+                target_zeros,
+                budget,
+                threads,
+            )
+        })
+        .await;
+        let (nonce, zeros, hashes) = match mined {
+            Ok(mined) => mined,
+            Err(e) => {
+                log(
+                    "mine",
+                    block_index,
+                    format_args!("result=error error={:?}", e.to_string()),
+                );
+                return;
+            }
+        };
+        log(
+            "mine",
+            block_index,
+            format_args!(
+                "result=ok nonce={} zeros={} target={} secs={} hashes={}",
+                nonce,
+                zeros,
+                target_zeros,
+                started.elapsed().as_secs(),
+                hashes
+            ),
+        );
+        if let Some(path) = &self.options.status_file {
+            let report = MiningReport {
+                block_index,
+                hashes,
+                mining_secs: started.elapsed().as_secs_f64(),
+                reported_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs()),
+            };
+            let json = serde_json::to_string(&report).expect("mining reports always serialize");
+            if let Err(e) = std::fs::write(path, json) {
+                log(
+                    "status",
+                    block_index,
+                    format_args!("result=error error={:?}", e.to_string()),
+                );
+            }
+        }
+
+        if let Some(hooks) = &self.options.hooks {
+            let farmer = self.backend.farmer();
+            let work = WorkFound {
+                block_index,
+                farmer: &farmer,
+                nonce,
+                zeros,
+                target_zeros,
+            };
+            match hooks.on_work_found(&work) {
+                Ok(true) => {}
+                Ok(false) => {
+                    log(
+                        "work",
+                        block_index,
+                        format_args!("result=skipped reason=hook"),
+                    );
+                    return;
+                }
+                Err(e) => hook_failed(block_index, e),
+            }
+        }
+
+        self.submit(block_index, &FarmCall::Work(nonce, Some(block_index)))
+            .await;
+    }
+
+    /// The zeros recent blocks suggest, or the default if they can't be read
+    async fn target_zeros(&self, block_index: u32) -> u32 {
+        let estimate = phase("difficulty", block_index, || {
+            self.backend.estimate_difficulty()
+        })
+        .await;
+        match estimate {
+            Some(estimate) => {
+                log(
+                    "difficulty",
+                    block_index,
+                    format_args!(
+                        "result=ok target={} sampled={}",
+                        estimate.recommended_zeros, estimate.sampled_blocks
+                    ),
+                );
+                estimate.recommended_zeros
+            }
+            None => DEFAULT_TARGET_ZEROS,
+        }
+    }
+
+    /// Run a farming call with retries, logging the outcome; returns whether it succeeded
+    ///
+    /// Each retry bids more inclusion fee, per the config's fee escalation.
+    async fn submit(&self, block_index: u32, call: &FarmCall) -> bool {
+        let mut retry = 0;
+        let hash = phase(call.name(), block_index, || {
+            retry += 1;
+            self.backend.execute(call, retry - 1)
+        })
+        .await;
+        match hash {
+            Some(hash) => {
+                log(
+                    call.name(),
+                    block_index,
+                    format_args!("result=ok tx={}", hash),
+                );
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Run one phase, retrying failures with backoff; logs and returns `None` if it gives up
+///
+/// Farming errors (a missing pail, better work already recorded, ...) won't change on a
+/// retry, so they end the phase straight away.
+async fn phase<T, E, F, Fut>(name: &str, block_index: u32, mut attempt: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: Into<anyhow::Error>,
+{
+    let mut delay = PHASE_RETRY_DELAY;
+    for attempt_number in 1..=PHASE_ATTEMPTS {
+        let e: anyhow::Error = match attempt().await {
+            Ok(value) => return Some(value),
+            Err(e) => e.into(),
+        };
+        let permanent = matches!(e.downcast_ref::<KaleError>(), Some(KaleError::Farm(_)));
+        if permanent || attempt_number == PHASE_ATTEMPTS {
+            log(
+                name,
+                block_index,
+                format_args!(
+                    "result=failed attempts={} error={:?}",
+                    attempt_number,
+                    e.to_string()
+                ),
+            );
+            return None;
+        }
+        log(
+            name,
+            block_index,
+            format_args!(
+                "result=retry attempt={} delay_secs={} error={:?}",
+                attempt_number,
+                delay.as_secs(),
+                e.to_string()
+            ),
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    None
+}
+
+/// Log a hook that failed; the daemon carries on as if the script didn't define it
+fn hook_failed(block_index: u32, e: anyhow::Error) {
+    log(
+        "hook",
+        block_index,
+        format_args!("result=failed error={:?}", format!("{:#}", e)),
+    );
+}
+
+/// Search nonces on `threads` threads until a hash has `target_zeros` leading zeros or
+/// `budget` runs out, returning the best nonce found, its zeros and the hashes tried
+pub fn mine(
+    block_index: u32,
+    entropy: &[u8; 32],
+    farmer: &[u8; 32],
+    target_zeros: u32,
+    budget: Duration,
+    threads: usize,
+) -> (u64, u32, u64) {
+    let threads = threads.max(1) as u64;
+    let deadline = Instant::now() + budget;
+    let found = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        let miners: Vec<_> = (0..threads)
+            .map(|thread| {
+                let found = &found;
+                scope.spawn(move || {
+                    // Each thread takes every `threads`th nonce so none are tried twice
+                    let mut best = (thread, 0);
+                    let mut nonce = thread;
+                    let mut tried = 0;
+                    loop {
+                        for _ in 0..MINING_BATCH {
+                            let zeros = count_leading_zeros(&work_hash(
+                                block_index,
+                                nonce,
+                                entropy,
+                                farmer,
+                            ));
+                            if zeros > best.1 {
+                                best = (nonce, zeros);
+                            }
+                            nonce += threads;
+                        }
+                        tried += MINING_BATCH;
+                        if best.1 >= target_zeros {
+                            found.store(true, Ordering::Relaxed);
+                        }
+                        if found.load(Ordering::Relaxed) || Instant::now() >= deadline {
+                            return (best, tried);
+                        }
+                    }
+                })
+            })
+            .collect();
+        let results: Vec<_> = miners
+            .into_iter()
+            .map(|miner| miner.join().expect("mining thread panicked"))
+            .collect();
+        let hashes = results.iter().map(|&(_, tried)| tried).sum();
+        let (nonce, zeros) = results
+            .into_iter()
+            .map(|(best, _)| best)
+            .max_by_key(|&(_, zeros)| zeros)
+            .unwrap_or((0, 0));
+        (nonce, zeros, hashes)
+    })
+}
+
+/// Print a structured log line, e.g. `ts=1700000000 phase=plant block=42 result=ok tx=...`
+fn log(phase: &str, block_index: u32, fields: Arguments) {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    println!("ts={} phase={} block={} {}", ts, phase, block_index, fields);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{address, SimulatedFarm};
+
+    fn options(stake: i128) -> FarmOptions {
+        FarmOptions {
+            stake,
+            target_zeros: Some(2),
+            mining_budget: Duration::from_secs(60),
+            mining_threads: Some(1),
+            hooks: None,
+            pause_file: None,
+            status_file: None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_daemon_farms_block_cycle() {
+        let farm = SimulatedFarm::new(1_000_000);
+        let (farmer, rival) = (address(1), address(2));
+        farm.fund(&farmer, 1_000);
+        farm.fund(&rival, 1_000);
+        let daemon = Daemon::new(farm.farmer(&farmer), options(100)).unwrap();
+
+        // Plants, mines to the target and works in block 1
+        daemon.farm_block(1).await;
+        let (has_pail, has_worked, zeros) = daemon.backend.get_pail_data(1).await.unwrap();
+        assert!(has_pail && has_worked && zeros >= 2);
+
+        farm.plant(&rival, 300).unwrap();
+        farm.advance_ledgers(20);
+        farm.work(&rival, farm.find_nonce(&rival, 1)).unwrap();
+        let reward = farm.reward(&farmer, 1).unwrap();
+        assert!(
+            reward > 100 && reward < 1_000_100,
+            "the rival shares the block"
+        );
+
+        // Harvests block 1 and plants again in block 2
+        farm.next_block();
+        daemon.farm_block(2).await;
+        assert_eq!(farm.reward(&farmer, 1), None);
+        assert_eq!(farm.balance(&farmer), 1_000 - 100 + reward - 100);
+        assert!(daemon.backend.get_pail_data(2).await.unwrap().1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_daemon_retries_phases() {
+        let farm = SimulatedFarm::new(1_000_000);
+        let farmer = address(1);
+        farm.fund(&farmer, 1_000);
+        let daemon = Daemon::new(farm.farmer(&farmer), options(100)).unwrap();
+
+        // Two dropped calls are retried within the phase
+        farm.fail_next_calls(2);
+        daemon.farm_block(1).await;
+        assert!(daemon.backend.get_pail_data(1).await.unwrap().0);
+
+        // A phase that keeps failing waits for the next block
+        farm.next_block();
+        farm.fail_next_calls(PHASE_ATTEMPTS * 2);
+        daemon.farm_block(2).await;
+        assert!(!daemon.backend.get_pail_data(2).await.unwrap().0);
+        assert!(farm.reward(&farmer, 1).is_some(), "the harvest gave up too");
+
+        farm.next_block();
+        daemon.farm_block(3).await;
+        assert_eq!(farm.reward(&farmer, 1), None);
+        assert!(daemon.backend.get_pail_data(3).await.unwrap().1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_daemon_runs_hooks() {
+        let farm = SimulatedFarm::new(1_000_000);
+        let farmer = address(1);
+        farm.fund(&farmer, 1_000);
+        let mut options = options(100);
+        let script = "fn on_new_block(block) { if block.index == 1 { false } else { 250 } }";
+        options.hooks = Some(Hooks::compile(script).unwrap());
+        let daemon = Daemon::new(farm.farmer(&farmer), options).unwrap();
+
+        daemon.farm_block(1).await;
+        assert!(!daemon.backend.get_pail_data(1).await.unwrap().0);
+
+        farm.next_block();
+        daemon.farm_block(2).await;
+        assert!(daemon.backend.get_pail_data(2).await.unwrap().1);
+        assert_eq!(farm.balance(&farmer), 1_000 - 250);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_daemon_skips_paused_subsystems() {
+        let farm = SimulatedFarm::new(1_000_000);
+        let farmer = address(1);
+        farm.fund(&farmer, 1_000);
+        let daemon = Daemon::new(farm.farmer(&farmer), options(100)).unwrap();
+        daemon.farm_block(1).await;
+
+        // Harvests block 1, but doesn't plant in block 2
+        farm.next_block();
+        daemon.pauses.set(Subsystem::Miner, true);
+        daemon.farm_block(2).await;
+        assert_eq!(farm.reward(&farmer, 1), None);
+        assert!(!daemon.backend.get_pail_data(2).await.unwrap().0);
+
+        // Plants and works block 3 again, but its harvest waits for the harvester
+        farm.next_block();
+        daemon.pauses.set(Subsystem::Miner, false);
+        daemon.pauses.set(Subsystem::Harvester, true);
+        daemon.farm_block(3).await;
+        assert!(daemon.backend.get_pail_data(3).await.unwrap().1);
+        farm.next_block();
+        daemon.farm_block(4).await;
+        assert!(farm.reward(&farmer, 3).is_some());
+    }
+
+    #[test]
+    fn test_mine_reaches_target() {
+        let entropy = [7u8; 32];
+        let farmer = [9u8; 32];
+        let (nonce, zeros, hashes) = mine(42, &entropy, &farmer, 3, Duration::from_secs(60), 2);
+        assert!(zeros >= 3);
+        assert!(hashes >= 2 * MINING_BATCH);
+        assert_eq!(
+            count_leading_zeros(&work_hash(42, nonce, &entropy, &farmer)),
+            zeros
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use crate::sign_agent::{KeyRole, Keys, SignAgent};
+use anyhow::{Context, Result};
+use galacticplayground::contracts::kale::{format_kale, Kale};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use stellar_xdr::curr::{
+    Limits, Memo, MuxedAccount, Preconditions, SequenceNumber, Transaction, TransactionEnvelope,
+    TransactionExt, TransactionV1Envelope, Uint256, WriteXdr,
+};
+
+/// Spendable XLM a fleet key needs to keep paying fees: 5 XLM
+const DEFAULT_MIN_SPENDABLE_XLM: i64 = 50_000_000;
+/// How often each fleet key is checked
+const DEFAULT_CHECK_SECS: u64 = 600;
+
+/// A fleet file (TOML): the farmer accounts to keep checked, and who hears of problems
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FleetFile {
+    /// Address emailed when a key fails a check
+    notify: Option<String>,
+    #[serde(default = "default_min_spendable_xlm")]
+    min_spendable_xlm: i64,
+    #[serde(default, rename = "farmer")]
+    farmers: Vec<FleetKey>,
+}
+
+fn default_min_spendable_xlm() -> i64 {
+    DEFAULT_MIN_SPENDABLE_XLM
+}
+
+/// One farmer of the fleet
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FleetKey {
+    pub account: String,
+    /// The key file its `farm` daemon uses; checked for signing when set
+    pub key_file: Option<PathBuf>,
+    /// The `--status-file` its `farm` daemon writes, for its hashrate on the dashboard
+    pub status_file: Option<PathBuf>,
+}
+
+/// The outcome of a key's last check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyHealth {
+    /// Unix seconds
+    pub checked_at: u64,
+    pub spendable_xlm: Option<i64>,
+    pub kale_balance: Option<i64>,
+    /// What would make the key miss blocks; empty when it's healthy
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetKeyStatus {
+    pub account: String,
+    /// Absent until the key's first check
+    #[serde(flatten)]
+    pub last_check: Option<KeyHealth>,
+}
+
+/// The fleet's keys and how their last checks went; empty unless `KALE_FLEET_FILE` is set
+///
+/// Keys are checked one at a time, spread over the check interval, so a large fleet doesn't
+/// burst the RPC node. Results are kept in memory.
+pub struct Fleet {
+    keys: Vec<FleetKey>,
+    notify: Option<String>,
+    min_spendable_xlm: i64,
+    check_interval: Duration,
+    health: Mutex<HashMap<String, KeyHealth>>,
+}
+
+impl Fleet {
+    /// Load `KALE_FLEET_FILE`, checking each key every `KALE_FLEET_CHECK_SECS` (default 600)
+    pub fn from_env() -> Result<Self> {
+        let check_interval = match std::env::var("KALE_FLEET_CHECK_SECS") {
+            Ok(secs) => Duration::from_secs(
+                secs.parse()
+                    .context("KALE_FLEET_CHECK_SECS must be a number of seconds")?,
+            ),
+            Err(_) => Duration::from_secs(DEFAULT_CHECK_SECS),
+        };
+        match std::env::var("KALE_FLEET_FILE") {
+            Ok(path) => Self::load(Path::new(&path), check_interval),
+            Err(_) => Ok(Self::new(
+                Vec::new(),
+                None,
+                DEFAULT_MIN_SPENDABLE_XLM,
+                check_interval,
+            )),
+        }
+    }
+
+    pub fn load(path: &Path, check_interval: Duration) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fleet file {}", path.display()))?;
+        let file: FleetFile = toml::from_str(&contents)
+            .with_context(|| format!("Invalid fleet file {}", path.display()))?;
+        for key in &file.farmers {
+            stellar_strkey::ed25519::PublicKey::from_string(&key.account)
+                .map_err(|_| anyhow::anyhow!("Invalid fleet account {:?}", key.account))?;
+        }
+        Ok(Self::new(
+            file.farmers,
+            file.notify,
+            file.min_spendable_xlm,
+            check_interval,
+        ))
+    }
+
+    fn new(
+        keys: Vec<FleetKey>,
+        notify: Option<String>,
+        min_spendable_xlm: i64,
+        check_interval: Duration,
+    ) -> Self {
+        Self {
+            keys,
+            notify,
+            min_spendable_xlm,
+            check_interval,
+            health: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn keys(&self) -> &[FleetKey] {
+        &self.keys
+    }
+
+    pub fn notify(&self) -> Option<&str> {
+        self.notify.as_deref()
+    }
+
+    pub fn min_spendable_xlm(&self) -> i64 {
+        self.min_spendable_xlm
+    }
+
+    /// The wait between two checks, so each key is checked once per interval
+    pub fn check_spacing(&self) -> Duration {
+        (self.check_interval / self.keys.len().max(1) as u32).max(Duration::from_secs(1))
+    }
+
+    /// Keep a key's check, returning whether the key just became unhealthy
+    ///
+    /// A key that stays unhealthy isn't reported again until it passes a check.
+    pub fn record(&self, account: &str, health: KeyHealth) -> bool {
+        let failing = !health.problems.is_empty();
+        let was_failing = self
+            .health
+            .lock()
+            .unwrap()
+            .insert(account.to_string(), health)
+            .is_some_and(|last| !last.problems.is_empty());
+        failing && !was_failing
+    }
+
+    /// Every key with its last check, in fleet file order
+    pub fn status(&self) -> Vec<FleetKeyStatus> {
+        let health = self.health.lock().unwrap();
+        self.keys
+            .iter()
+            .map(|key| FleetKeyStatus {
+                account: key.account.clone(),
+                last_check: health.get(&key.account).cloned(),
+            })
+            .collect()
+    }
+}
+
+/// Check that a fleet key can farm the next block: its key file can sign for it, the
+/// account exists with enough spendable XLM for fees, and it has a KALE trustline
+pub async fn check(kale: &Kale, key: &FleetKey, min_spendable_xlm: i64) -> KeyHealth {
+    let mut problems = Vec::new();
+
+    if let Some(key_file) = key.key_file.clone() {
+        let account = key.account.clone();
+        let passphrase = kale.network_passphrase().to_string();
+        let shown = key_file.display().to_string();
+        // Starting the agent and waiting for it blocks
+        let signed = tokio::task::spawn_blocking(move || {
+            can_sign(&open_key(&key_file)?, &account, &passphrase)
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+        if let Err(e) = signed {
+            problems.push(format!("Key file {} can't sign: {:#}", shown, e));
+        }
+    }
+
+    let spendable_xlm = match kale.get_spendable_xlm(&key.account).await {
+        Ok(Some(spendable_xlm)) => {
+            if spendable_xlm < min_spendable_xlm {
+                problems.push(format!(
+                    "Spendable XLM {} is below {}",
+                    format_kale(i128::from(spendable_xlm)),
+                    format_kale(i128::from(min_spendable_xlm))
+                ));
+            }
+            Some(spendable_xlm)
+        }
+        Ok(None) => {
+            problems.push("Account doesn't exist; fund it with XLM".to_string());
+            None
+        }
+        Err(e) => {
+            problems.push(format!("Failed to read the XLM balance: {:#}", e));
+            None
+        }
+    };
+
+    let kale_balance = match kale.check_kale_trustline(&key.account).await {
+        Ok((true, balance)) => Some(balance),
+        Ok((false, _)) => {
+            if spendable_xlm.is_some() {
+                problems.push("No KALE trustline; harvests will fail".to_string());
+            }
+            None
+        }
+        Err(e) => {
+            problems.push(format!("Failed to read the KALE trustline: {:#}", e));
+            None
+        }
+    };
+
+    KeyHealth {
+        checked_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default(),
+        spendable_xlm,
+        kale_balance,
+        problems,
+    }
+}
+
+/// Open a key file as the daemon's `--isolate-signer` would, so on Linux the server never
+/// holds the seed
+fn open_key(key_file: &Path) -> Result<Keys> {
+    if cfg!(target_os = "linux") {
+        return Ok(Keys::Agent(SignAgent::spawn(Some(key_file), None, None)?));
+    }
+    let secret_key = std::fs::read_to_string(key_file)
+        .with_context(|| format!("Failed to read {}", key_file.display()))?;
+    Keys::local(secret_key.trim(), None)
+}
+
+/// Whether `keys` are for `account` and sign a transaction from it
+fn can_sign(keys: &Keys, account: &str, network_passphrase: &str) -> Result<()> {
+    anyhow::ensure!(
+        keys.farmer() == account,
+        "it holds the key of {}",
+        keys.farmer()
+    );
+    let public_key = stellar_strkey::ed25519::PublicKey::from_string(account)
+        .map_err(|_| anyhow::anyhow!("Invalid account {}", account))?;
+    // Never submitted: no operations, and sequence number zero
+    let probe = TransactionEnvelope::Tx(TransactionV1Envelope {
+        tx: Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256(public_key.0)),
+            fee: 100,
+            seq_num: SequenceNumber(0),
+            cond: Preconditions::None,
+            memo: Memo::None,
+            operations: Default::default(),
+            ext: TransactionExt::V0,
+        },
+        signatures: Default::default(),
+    })
+    .to_xdr_base64(Limits::none())?;
+    keys.sign(KeyRole::Farmer, &probe, network_passphrase)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "SBGWSG6BTNCKCOB3DIFBGCVMUPQFYPA2G4O34RMTB343OYPXU5DJDVMN";
+    const ACCOUNT: &str = "GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6";
+
+    fn health(problems: &[&str]) -> KeyHealth {
+        KeyHealth {
+            checked_at: 1_700_000_000,
+            spendable_xlm: Some(0),
+            kale_balance: None,
+            problems: problems.iter().map(|problem| problem.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_fleet_file() {
+        let path = std::env::temp_dir().join(format!("kale-fleet-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            format!(
+                "notify = \"ops@example.com\"\n\n[[farmer]]\naccount = \"{}\"\n\
+                 key_file = \"/etc/kale/farmer.key\"\nstatus_file = \"/run/kale/farmer.json\"\n\n\
+                 [[farmer]]\naccount = \"{}\"\n",
+                ACCOUNT, ACCOUNT
+            ),
+        )
+        .unwrap();
+        let fleet = Fleet::load(&path, Duration::from_secs(600)).unwrap();
+        assert_eq!(fleet.keys().len(), 2);
+        assert_eq!(
+            fleet.keys()[0].status_file.as_deref(),
+            Some(Path::new("/run/kale/farmer.json"))
+        );
+        assert_eq!(fleet.notify(), Some("ops@example.com"));
+        assert_eq!(fleet.min_spendable_xlm(), DEFAULT_MIN_SPENDABLE_XLM);
+        assert_eq!(fleet.check_spacing(), Duration::from_secs(300));
+
+        std::fs::write(&path, "[[farmer]]\naccount = \"GA\"\n").unwrap();
+        assert!(Fleet::load(&path, Duration::from_secs(600)).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unhealthy_key_is_reported_once() {
+        let fleet = Fleet::new(
+            vec![FleetKey {
+                account: ACCOUNT.to_string(),
+                key_file: None,
+                status_file: None,
+            }],
+            None,
+            DEFAULT_MIN_SPENDABLE_XLM,
+            Duration::from_secs(600),
+        );
+        assert!(fleet.status()[0].last_check.is_none());
+
+        assert!(!fleet.record(ACCOUNT, health(&[])));
+        assert!(fleet.record(ACCOUNT, health(&["No KALE trustline"])));
+        assert!(!fleet.record(ACCOUNT, health(&["No KALE trustline"])));
+        assert!(!fleet.record(ACCOUNT, health(&[])));
+        assert!(fleet.record(ACCOUNT, health(&["Account doesn't exist"])));
+        assert_eq!(
+            fleet.status()[0].last_check,
+            Some(health(&["Account doesn't exist"]))
+        );
+    }
+
+    #[test]
+    fn test_can_sign() {
+        let keys = Keys::local(SECRET, None).unwrap();
+        let passphrase = "Test SDF Network ; September 2015";
+        assert!(can_sign(&keys, ACCOUNT, passphrase).is_ok());
+
+        let other = "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF";
+        let error = can_sign(&keys, other, passphrase).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("it holds the key of {}", ACCOUNT)
+        );
+    }
+}
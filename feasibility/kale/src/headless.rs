@@ -0,0 +1,202 @@
+use crate::sign_agent::{KeyRole, Keys};
+use anyhow::Result;
+use galacticplayground::config::Config;
+use galacticplayground::contracts::kale::{Kale, PreparedTransaction};
+use galacticplayground::rpc::{
+    ConfirmationOptions, InclusionFeeOptions, LedgerCacheOptions, RebroadcastPolicy, RetryPolicy,
+    SubmittedTransaction, TransactionStatus,
+};
+use galacticplayground::trace::TraceOptions;
+
+/// A farming call run from the command line, signed with a local secret key
+pub enum FarmCall {
+    /// Stake in stroops
+    Plant(i128),
+    /// Nonce, and the block it was mined for if known
+    Work(u64, Option<u32>),
+    /// Block index
+    Harvest(u32),
+}
+
+impl FarmCall {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FarmCall::Plant(_) => "plant",
+            FarmCall::Work(..) => "work",
+            FarmCall::Harvest(_) => "harvest",
+        }
+    }
+
+    async fn prepare(
+        &self,
+        kale: &Kale,
+        farmer: &str,
+        fee_source: Option<&str>,
+    ) -> Result<PreparedTransaction> {
+        Ok(match *self {
+            FarmCall::Plant(amount) => {
+                kale.prepare_plant_transaction(farmer, amount, fee_source)
+                    .await?
+            }
+            FarmCall::Work(nonce, block_index) => {
+                kale.prepare_work_transaction(farmer, nonce, block_index, fee_source)
+                    .await?
+            }
+            FarmCall::Harvest(block_index) => {
+                kale.prepare_harvest_transaction(farmer, block_index, fee_source)
+                    .await?
+            }
+        })
+    }
+
+    async fn submit(&self, kale: &Kale, signed_tx_xdr: &str) -> Result<SubmittedTransaction> {
+        Ok(match self {
+            FarmCall::Plant(_) => kale.submit_plant_transaction(signed_tx_xdr).await?,
+            FarmCall::Work(..) => kale.submit_work_transaction(signed_tx_xdr).await?,
+            FarmCall::Harvest(_) => kale.submit_harvest_transaction(signed_tx_xdr).await?,
+        })
+    }
+}
+
+/// A KALE client that signs farming calls with local keys instead of a browser wallet
+///
+/// With a fee source key, that account pays and signs each transaction, and the farmer
+/// only authorizes the call.
+pub struct HeadlessFarmer {
+    kale: Kale,
+    keys: Keys,
+    passphrase: String,
+}
+
+impl HeadlessFarmer {
+    /// Connect to the configured network, with the RPC options from the environment
+    ///
+    /// Fails if the RPC endpoints are on a different network than the configured passphrase.
+    pub async fn new(config: &Config, keys: Keys) -> Result<Self> {
+        let mut kale = Kale::from_config(config)?;
+        kale.set_retry_policy(RetryPolicy::from_env()?);
+        kale.set_rebroadcast_policy(RebroadcastPolicy::from_env()?);
+        kale.set_confirmation_options(ConfirmationOptions::from_env()?);
+        kale.set_inclusion_fee_options(InclusionFeeOptions::from_env()?);
+        kale.set_ledger_cache_options(LedgerCacheOptions::from_env()?);
+        kale.set_trace_options(TraceOptions::from_env()?);
+        kale.verify_network().await?;
+        let passphrase = kale.network_passphrase().to_string();
+
+        Ok(Self {
+            kale,
+            keys,
+            passphrase,
+        })
+    }
+
+    pub fn kale(&self) -> &Kale {
+        &self.kale
+    }
+
+    pub fn farmer(&self) -> String {
+        self.keys.farmer()
+    }
+
+    pub fn fee_source(&self) -> Option<String> {
+        self.keys.fee_source()
+    }
+
+    /// Prepare, sign and submit a farming call, restoring archived contract entries it
+    /// needs first
+    ///
+    /// `retry` counts earlier tries of the same call; each bids more inclusion fee per the
+    /// config's fee escalation. A submission not confirmed in time is watched until it
+    /// lands, rebroadcast per the rebroadcast policy if the network loses it. The returned
+    /// transaction may still have failed; see [`check_success`].
+    pub async fn execute(&self, call: &FarmCall, retry: u32) -> Result<SubmittedTransaction> {
+        let farmer = self.farmer();
+        let fee_source = self.fee_source();
+        let payer = self.keys.payer();
+
+        let mut prepared = call
+            .prepare(&self.kale, &farmer, fee_source.as_deref())
+            .await?;
+        if prepared.restore {
+            println!("Restoring archived contract entries first...");
+            let signed = self.keys.sign(payer, &prepared.xdr, &self.passphrase)?;
+            let restored = self.kale.submit_restore_transaction(&signed).await?;
+            check_success("restore", &self.settle(&signed, restored).await?)?;
+            prepared = call
+                .prepare(&self.kale, &farmer, fee_source.as_deref())
+                .await?;
+        }
+
+        let mut xdr = prepared.xdr;
+        if retry > 0 {
+            xdr = self.kale.escalate_inclusion_fee(&xdr, retry)?;
+        }
+        if payer == KeyRole::FeeSource {
+            xdr = self.keys.sign_auth_entries(&xdr, &self.passphrase)?;
+        }
+        let signed = self.keys.sign(payer, &xdr, &self.passphrase)?;
+        let submitted = call.submit(&self.kale, &signed).await?;
+        self.settle(&signed, submitted).await
+    }
+
+    /// Follow a submission that was still pending until it succeeds or fails
+    async fn settle(
+        &self,
+        signed_tx_xdr: &str,
+        mut submitted: SubmittedTransaction,
+    ) -> Result<SubmittedTransaction> {
+        if submitted.status == TransactionStatus::Pending {
+            println!("Waiting for transaction {}...", submitted.hash);
+            let status = self
+                .kale
+                .watch_submission(signed_tx_xdr, &submitted.hash)
+                .await?;
+            submitted.status = if status == "SUCCESS" {
+                TransactionStatus::Success
+            } else {
+                TransactionStatus::Failed
+            };
+        }
+        Ok(submitted)
+    }
+}
+
+/// Prepare, sign and submit a farming call without a browser or wallet
+///
+/// Fails unless the transaction succeeds, so scripts can rely on the exit status.
+pub async fn run(config: &Config, keys: Keys, call: FarmCall) -> Result<()> {
+    let farmer = HeadlessFarmer::new(config, keys).await?;
+
+    println!("Farmer: {}", farmer.farmer());
+    if let Some(fee_source) = farmer.fee_source() {
+        println!("Fee source: {}", fee_source);
+    }
+    println!("Preparing {} transaction...", call.name());
+    let submitted = farmer.execute(&call, 0).await?;
+    println!("Transaction hash: {}", submitted.hash);
+    if let Some(explorer_url) = config.explorer_url() {
+        println!("{}/tx/{}", explorer_url, submitted.hash);
+    }
+    check_success(call.name(), &submitted)
+}
+
+/// Fail unless a submitted transaction succeeded
+pub fn check_success(name: &str, submitted: &SubmittedTransaction) -> Result<()> {
+    match submitted.status {
+        TransactionStatus::Success => {
+            println!("✓ {} transaction succeeded", name);
+            Ok(())
+        }
+        TransactionStatus::Failed => anyhow::bail!(
+            "{} transaction {} failed (result {})",
+            name,
+            submitted.hash,
+            submitted.result_xdr.as_deref().unwrap_or("unknown")
+        ),
+        TransactionStatus::Pending => anyhow::bail!(
+            "{} transaction {} is still pending; check it later",
+            name,
+            submitted.hash
+        ),
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::path::Path;
+
+/// Most operations one hook call may run before it is stopped
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A block the daemon is about to farm
+pub struct NewBlock<'a> {
+    pub block_index: u32,
+    pub farmer: &'a str,
+    pub stake: i128,
+}
+
+/// A nonce the daemon mined and is about to submit as work
+pub struct WorkFound<'a> {
+    pub block_index: u32,
+    pub farmer: &'a str,
+    pub nonce: u64,
+    pub zeros: u32,
+    pub target_zeros: u32,
+}
+
+/// What `on_new_block` asked of the daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockDecision {
+    /// Plant with the stake given
+    Plant(i128),
+    /// Sit the block out: no plant or work (past blocks are still harvested)
+    Skip,
+}
+
+/// A user's Rhai script, run at points in the farm daemon's block cycle
+///
+/// A script defines any of `on_new_block(block)`, `on_work_found(work)` and
+/// `on_harvest(harvest)`; each gets a map of the event. Scripts can't sign, reach the network
+/// or the filesystem, or import modules: they steer the daemon through what they return, and
+/// [`MAX_OPERATIONS`] stops one that runs away. A hook that fails is logged by the caller and
+/// the daemon goes on as if the script didn't define it.
+pub struct Hooks {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Hooks {
+    /// Compile the script at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::compile(&source).with_context(|| format!("Invalid hook script {}", path.display()))
+    }
+
+    pub fn compile(source: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(10_000);
+        engine.set_max_array_size(1_000);
+        engine.set_max_map_size(1_000);
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        let ast = engine.compile(source)?;
+        Ok(Self { engine, ast })
+    }
+
+    /// `on_new_block`: return `false` to skip the block or a number to stake instead
+    pub fn on_new_block(&self, block: &NewBlock) -> Result<BlockDecision> {
+        let mut event = Map::new();
+        event.insert("index".into(), Dynamic::from(i64::from(block.block_index)));
+        event.insert("farmer".into(), Dynamic::from(block.farmer.to_string()));
+        event.insert("stake".into(), Dynamic::from(stake_int(block.stake)?));
+
+        let Some(result) = self.call("on_new_block", event)? else {
+            return Ok(BlockDecision::Plant(block.stake));
+        };
+        if result.is_unit() || result.as_bool() == Ok(true) {
+            Ok(BlockDecision::Plant(block.stake))
+        } else if result.as_bool() == Ok(false) {
+            Ok(BlockDecision::Skip)
+        } else if let Ok(stake) = result.as_int() {
+            anyhow::ensure!(
+                stake >= 0,
+                "on_new_block returned a negative stake {}",
+                stake
+            );
+            Ok(BlockDecision::Plant(i128::from(stake)))
+        } else {
+            anyhow::bail!(
+                "on_new_block returned a {}, not a bool or stake",
+                result.type_name()
+            )
+        }
+    }
+
+    /// `on_work_found`: return `false` to keep the work from being submitted
+    pub fn on_work_found(&self, work: &WorkFound) -> Result<bool> {
+        let mut event = Map::new();
+        event.insert("block".into(), Dynamic::from(i64::from(work.block_index)));
+        event.insert("farmer".into(), Dynamic::from(work.farmer.to_string()));
+        // Rhai integers are signed; nonces past i64::MAX are out of reach of the miner anyway
+        event.insert("nonce".into(), Dynamic::from(work.nonce as i64));
+        event.insert("zeros".into(), Dynamic::from(i64::from(work.zeros)));
+        event.insert("target".into(), Dynamic::from(i64::from(work.target_zeros)));
+
+        match self.call("on_work_found", event)? {
+            Some(result) if !result.is_unit() => result
+                .as_bool()
+                .map_err(|kind| anyhow::anyhow!("on_work_found returned a {}, not a bool", kind)),
+            _ => Ok(true),
+        }
+    }
+
+    /// `on_harvest`: told of a block harvested; what it returns is ignored
+    pub fn on_harvest(&self, block_index: u32, farmer: &str) -> Result<()> {
+        let mut event = Map::new();
+        event.insert("block".into(), Dynamic::from(i64::from(block_index)));
+        event.insert("farmer".into(), Dynamic::from(farmer.to_string()));
+        self.call("on_harvest", event)?;
+        Ok(())
+    }
+
+    /// Call a hook with its event, or `None` if the script doesn't define it
+    fn call(&self, name: &str, event: Map) -> Result<Option<Dynamic>> {
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == 1);
+        if !defined {
+            return Ok(None);
+        }
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, (event,))
+            .map_err(|e| anyhow::anyhow!("{} failed: {}", name, e))?;
+        Ok(Some(result))
+    }
+}
+
+/// A stake as a Rhai integer
+fn stake_int(stake: i128) -> Result<i64> {
+    i64::try_from(stake).context("Stake doesn't fit a script integer")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FARMER: &str = "GA";
+
+    fn block(block_index: u32) -> NewBlock<'static> {
+        NewBlock {
+            block_index,
+            farmer: FARMER,
+            stake: 100,
+        }
+    }
+
+    #[test]
+    fn test_hooks_steer_the_daemon() {
+        let hooks = Hooks::compile(
+            r#"
+            fn on_new_block(block) {
+                if block.index % 2 == 1 { return false; }
+                if block.index > 10 { block.stake * 2 }
+            }
+            fn on_work_found(work) { work.zeros >= work.target }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(hooks.on_new_block(&block(3)).unwrap(), BlockDecision::Skip);
+        assert_eq!(
+            hooks.on_new_block(&block(4)).unwrap(),
+            BlockDecision::Plant(100)
+        );
+        assert_eq!(
+            hooks.on_new_block(&block(12)).unwrap(),
+            BlockDecision::Plant(200)
+        );
+
+        let work = WorkFound {
+            block_index: 4,
+            farmer: FARMER,
+            nonce: 7,
+            zeros: 5,
+            target_zeros: 6,
+        };
+        assert!(!hooks.on_work_found(&work).unwrap());
+        // Hooks the script leaves out don't change anything
+        hooks.on_harvest(4, FARMER).unwrap();
+    }
+
+    #[test]
+    fn test_hooks_are_sandboxed() {
+        let hooks = Hooks::compile("fn on_new_block(block) { loop {} }").unwrap();
+        assert!(hooks.on_new_block(&block(1)).is_err());
+        assert!(Hooks::compile(r#"fn on_harvest(h) { eval("1") }"#).is_err());
+    }
+}
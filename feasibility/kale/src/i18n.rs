@@ -0,0 +1,148 @@
+use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap};
+
+use galacticplayground::contracts::kale::{format_kale, FarmError};
+
+/// Languages the user-facing API errors are translated into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Es,
+    De,
+}
+
+impl Locale {
+    /// Pick the best supported locale from the request's `Accept-Language` header
+    ///
+    /// Falls back to English if the header is missing or lists no supported language.
+    pub fn negotiate(headers: &HeaderMap) -> Self {
+        headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(Self::from_accept_language)
+            .unwrap_or(Locale::En)
+    }
+
+    /// Parse an `Accept-Language` value like "de-CH,de;q=0.9,en;q=0.8"
+    fn from_accept_language(value: &str) -> Self {
+        let mut best: Option<(Locale, f32)> = None;
+
+        for item in value.split(',') {
+            let mut parts = item.trim().split(';');
+            let tag = parts.next().unwrap_or("").trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            // Only the primary subtag matters ("es-AR" -> "es")
+            let primary = tag.split('-').next().unwrap_or("").to_ascii_lowercase();
+            let locale = match primary.as_str() {
+                "en" => Locale::En,
+                "es" => Locale::Es,
+                "de" => Locale::De,
+                _ => continue,
+            };
+
+            if quality > 0.0 && !matches!(best, Some((_, q)) if q >= quality) {
+                best = Some((locale, quality));
+            }
+        }
+
+        best.map(|(locale, _)| locale).unwrap_or(Locale::En)
+    }
+}
+
+/// Translate a farming error into an actionable message for the given locale
+pub fn farm_error_message(locale: Locale, error: &FarmError) -> String {
+    match (locale, error) {
+        (Locale::En, _) => error.to_string(),
+
+        (Locale::Es, FarmError::TrustlineMissing { asset_code, issuer }) => format!(
+            "La cuenta no tiene una trustline para {}:{}. \
+            Añádela con una billetera Stellar como Albedo, Freighter o Stellar Laboratory. \
+            Visita https://albedo.link o https://laboratory.stellar.org/#explorer para añadirla.",
+            asset_code, issuer
+        ),
+        (Locale::Es, FarmError::InsufficientBalance { requested, available }) => format!(
+            "Saldo de KALE insuficiente: intentaste plantar {} KALE pero solo hay {} KALE disponibles",
+            format_kale(*requested),
+            format_kale(*available as i128)
+        ),
+        (Locale::Es, FarmError::NegativeStake { requested }) => format!(
+            "Apuesta no válida de {} KALE: no puede ser negativa. Planta 0 KALE para cultivar sin apostar.",
+            format_kale(*requested)
+        ),
+        (Locale::Es, FarmError::BlockAdvanced { mined, current }) => format!(
+            "La granja avanzó al bloque {} mientras minabas el bloque {}. \
+            Planta en el nuevo bloque y vuelve a minar.",
+            current, mined
+        ),
+        (Locale::Es, FarmError::NoPail { block_index }) => format!(
+            "Todavía no has plantado en el bloque {}. Planta primero y luego envía tu trabajo.",
+            block_index
+        ),
+        (
+            Locale::Es,
+            FarmError::WorkAlreadyBetter {
+                existing_zeros,
+                candidate_zeros,
+            },
+        ) => format!(
+            "Tu trabajo registrado ya tiene {} ceros iniciales; este hash solo tiene {}. \
+            Enviarlo costaría comisiones sin mejorar tu cosecha.",
+            existing_zeros, candidate_zeros
+        ),
+
+        (Locale::De, FarmError::TrustlineMissing { asset_code, issuer }) => format!(
+            "Das Konto hat keine Trustline für {}:{}. \
+            Füge die Trustline mit einer Stellar-Wallet wie Albedo, Freighter oder Stellar Laboratory hinzu. \
+            Besuche dazu https://albedo.link oder https://laboratory.stellar.org/#explorer.",
+            asset_code, issuer
+        ),
+        (Locale::De, FarmError::InsufficientBalance { requested, available }) => format!(
+            "Unzureichendes KALE-Guthaben: {} KALE sollten gepflanzt werden, verfügbar sind aber nur {} KALE",
+            format_kale(*requested),
+            format_kale(*available as i128)
+        ),
+        (Locale::De, FarmError::NegativeStake { requested }) => format!(
+            "Ungültiger Einsatz von {} KALE: Der Einsatz darf nicht negativ sein. Pflanze 0 KALE, um ohne Einsatz zu farmen.",
+            format_kale(*requested)
+        ),
+        (Locale::De, FarmError::BlockAdvanced { mined, current }) => format!(
+            "Die Farm ist zu Block {} weitergezogen, während du Block {} gemint hast. \
+            Pflanze im neuen Block und mine erneut.",
+            current, mined
+        ),
+        (Locale::De, FarmError::NoPail { block_index }) => format!(
+            "Du hast in Block {} noch nicht gepflanzt. Pflanze zuerst und reiche dann deine Arbeit ein.",
+            block_index
+        ),
+        (
+            Locale::De,
+            FarmError::WorkAlreadyBetter {
+                existing_zeros,
+                candidate_zeros,
+            },
+        ) => format!(
+            "Deine gespeicherte Arbeit hat bereits {} führende Nullen, dieser Hash nur {}. \
+            Das Einreichen würde Gebühren kosten, ohne deine Ernte zu verbessern.",
+            existing_zeros, candidate_zeros
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_locale() {
+        assert_eq!(Locale::from_accept_language("es-AR,es;q=0.9"), Locale::Es);
+        assert_eq!(
+            Locale::from_accept_language("fr-FR,de;q=0.7,en;q=0.8"),
+            Locale::En
+        );
+        assert_eq!(Locale::from_accept_language("de;q=0"), Locale::En);
+        assert_eq!(Locale::from_accept_language("*"), Locale::En);
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::http::{HeaderValue, StatusCode};
+use galacticplayground::rpc::SorobanRpc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use sha3::{Digest, Sha3_256};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header clients set to make a mutating request safe to retry
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header set on responses replayed from an earlier request with the same key
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Default time a response stays available for replay
+const DEFAULT_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Where the store is kept unless `KALE_IDEMPOTENCY_DB` says otherwise
+const DEFAULT_DB_FILE: &str = "kale-idempotency.sqlite";
+
+/// A stored response, replayed verbatim on retries
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub content_type: Option<HeaderValue>,
+    pub body: Bytes,
+}
+
+/// What to do with a request carrying an idempotency key
+#[derive(Debug)]
+pub enum Claim {
+    /// First request with this key: run it, then [`IdempotencyStore::complete`] or
+    /// [`IdempotencyStore::release`] the key
+    New,
+    /// The key has a stored response for the same request
+    Replay(StoredResponse),
+    /// The key is still being processed by another request
+    InProgress,
+    /// The key was already used for a request with a different body
+    Mismatch,
+}
+
+/// Who an idempotent request is from, so one client's key never replays another's response
+///
+/// The account in the body's `publicKey`, or else the source account of the transaction
+/// in its `signedXdr` or `xdr`; None if the body names neither.
+pub fn caller(request_body: &[u8]) -> Option<String> {
+    let body: Value = serde_json::from_slice(request_body).ok()?;
+    if let Some(public_key) = body.get("publicKey").and_then(Value::as_str) {
+        return Some(public_key.to_string());
+    }
+    let xdr = body
+        .get("signedXdr")
+        .or_else(|| body.get("xdr"))
+        .and_then(Value::as_str)?;
+    SorobanRpc::transaction_source_account(xdr).ok()
+}
+
+/// First responses to mutating requests, keyed by endpoint, caller and `Idempotency-Key`
+///
+/// Kept in a SQLite database, so a client retrying across a server restart still gets the
+/// first response instead of a second friendbot call or submission.
+pub struct IdempotencyStore {
+    ttl: Duration,
+    db: Mutex<Connection>,
+}
+
+impl IdempotencyStore {
+    /// Open the store at `path`, creating it if needed
+    ///
+    /// Keys a previous run left in progress are dropped: their requests ended with it, so
+    /// retries run them again.
+    pub fn open(path: impl AsRef<Path>, ttl: Duration) -> Result<Self> {
+        let path = path.as_ref();
+        let db = Connection::open(path)
+            .with_context(|| format!("Failed to open idempotency database {}", path.display()))?;
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS responses (
+                key TEXT PRIMARY KEY,
+                created INTEGER NOT NULL,
+                request_hash BLOB NOT NULL,
+                status INTEGER,
+                content_type BLOB,
+                body BLOB
+            );
+            DELETE FROM responses WHERE status IS NULL;",
+        )
+        .with_context(|| format!("Invalid idempotency database {}", path.display()))?;
+
+        Ok(Self {
+            ttl,
+            db: Mutex::new(db),
+        })
+    }
+
+    /// Open the store at `KALE_IDEMPOTENCY_DB` (default `kale-idempotency.sqlite`), with
+    /// the TTL from `KALE_IDEMPOTENCY_TTL_SECS` (default 24 hours)
+    pub fn from_env() -> Result<Self> {
+        let ttl_secs = std::env::var("KALE_IDEMPOTENCY_TTL_SECS")
+            .ok()
+            .map(|value| value.parse())
+            .transpose()
+            .context("KALE_IDEMPOTENCY_TTL_SECS must be a number of seconds")?
+            .unwrap_or(DEFAULT_TTL_SECS);
+        let path =
+            std::env::var("KALE_IDEMPOTENCY_DB").unwrap_or_else(|_| DEFAULT_DB_FILE.to_string());
+
+        Self::open(path, Duration::from_secs(ttl_secs))
+    }
+
+    /// Claim a key for a request, or find the response to replay
+    pub fn claim(&self, key: &str, request_body: &[u8]) -> Result<Claim> {
+        let request_hash: [u8; 32] = Sha3_256::digest(request_body).into();
+        let now = unix_now();
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "DELETE FROM responses WHERE created <= ?1",
+            params![now - self.ttl.as_secs() as i64],
+        )?;
+
+        let entry = db
+            .query_row(
+                "SELECT request_hash, status, content_type, body FROM responses WHERE key = ?1",
+                params![key],
+                |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, Option<u16>>(1)?,
+                        row.get::<_, Option<Vec<u8>>>(2)?,
+                        row.get::<_, Option<Vec<u8>>>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        Ok(match entry {
+            Some((hash, ..)) if hash != request_hash => Claim::Mismatch,
+            Some((_, Some(status), content_type, body)) => Claim::Replay(StoredResponse {
+                status: StatusCode::from_u16(status)?,
+                content_type: content_type
+                    .map(|value| HeaderValue::from_bytes(&value))
+                    .transpose()?,
+                body: Bytes::from(body.unwrap_or_default()),
+            }),
+            Some(_) => Claim::InProgress,
+            None => {
+                db.execute(
+                    "INSERT INTO responses (key, created, request_hash) VALUES (?1, ?2, ?3)",
+                    params![key, now, &request_hash[..]],
+                )?;
+                Claim::New
+            }
+        })
+    }
+
+    /// Store the response of a claimed key for replay
+    pub fn complete(&self, key: &str, response: StoredResponse) -> Result<()> {
+        self.db.lock().unwrap().execute(
+            "UPDATE responses SET status = ?2, content_type = ?3, body = ?4 WHERE key = ?1",
+            params![
+                key,
+                response.status.as_u16(),
+                response.content_type.as_ref().map(HeaderValue::as_bytes),
+                &response.body[..]
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Give up a claimed key so a retry runs the request again
+    pub fn release(&self, key: &str) -> Result<()> {
+        self.db
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM responses WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "kale-idempotency-{}-{}.sqlite",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_claim_and_replay() {
+        let path = temp_db("claim");
+        let store = IdempotencyStore::open(&path, Duration::from_secs(60)).unwrap();
+
+        assert!(matches!(store.claim("a", b"{}").unwrap(), Claim::New));
+        assert!(matches!(
+            store.claim("a", b"{}").unwrap(),
+            Claim::InProgress
+        ));
+
+        store
+            .complete(
+                "a",
+                StoredResponse {
+                    status: StatusCode::OK,
+                    content_type: Some(HeaderValue::from_static("application/json")),
+                    body: Bytes::from_static(b"{\"hash\":\"abc\"}"),
+                },
+            )
+            .unwrap();
+        match store.claim("a", b"{}").unwrap() {
+            Claim::Replay(response) => {
+                assert_eq!(response.status, StatusCode::OK);
+                assert_eq!(response.content_type.unwrap(), "application/json");
+                assert_eq!(&response.body[..], b"{\"hash\":\"abc\"}");
+            }
+            other => panic!("expected replay, got {:?}", other),
+        }
+        assert!(matches!(
+            store.claim("a", b"{\"x\":1}").unwrap(),
+            Claim::Mismatch
+        ));
+
+        assert!(matches!(store.claim("b", b"{}").unwrap(), Claim::New));
+        store.release("b").unwrap();
+        assert!(matches!(store.claim("b", b"{}").unwrap(), Claim::New));
+
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_survives_restart() {
+        let path = temp_db("restart");
+        let store = IdempotencyStore::open(&path, Duration::from_secs(60)).unwrap();
+        assert!(matches!(store.claim("done", b"{}").unwrap(), Claim::New));
+        store
+            .complete(
+                "done",
+                StoredResponse {
+                    status: StatusCode::CREATED,
+                    content_type: None,
+                    body: Bytes::from_static(b"funded"),
+                },
+            )
+            .unwrap();
+        assert!(matches!(store.claim("running", b"{}").unwrap(), Claim::New));
+        drop(store);
+
+        let store = IdempotencyStore::open(&path, Duration::from_secs(60)).unwrap();
+        match store.claim("done", b"{}").unwrap() {
+            Claim::Replay(response) => assert_eq!(&response.body[..], b"funded"),
+            other => panic!("expected replay, got {:?}", other),
+        }
+        // The request holding this key ended with the old process
+        assert!(matches!(store.claim("running", b"{}").unwrap(), Claim::New));
+
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_caller() {
+        let public_key = "GAAZI4TCR3TY5OJHCTJC2A4QSY6CJWJH5IAJTGKIN2ER7LBNVKOCCWN7";
+        assert_eq!(
+            caller(format!("{{\"publicKey\":\"{}\"}}", public_key).as_bytes()).as_deref(),
+            Some(public_key)
+        );
+        assert_eq!(caller(b"{\"signedXdr\":\"not xdr\"}"), None);
+        assert_eq!(caller(b"not json"), None);
+    }
+}
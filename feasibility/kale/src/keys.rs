@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Context, Result};
+use bip39::Mnemonic;
+use ed25519_dalek::SigningKey;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+/// Hardened index offset for SLIP-10 derivation (ed25519 only supports hardened children)
+const HARDENED: u32 = 0x8000_0000;
+
+/// The Stellar SEP-5 derivation path for an account index
+pub fn derivation_path(index: u32) -> String {
+    format!("m/44'/148'/{}'", index)
+}
+
+/// Derive a farmer keypair from a BIP-39 mnemonic, following SEP-5
+///
+/// Uses the path `m/44'/148'/{index}'`, so the same phrase and index give the same
+/// account as other SEP-5 wallets. `passphrase` is the optional BIP-39 passphrase
+/// (empty for none).
+pub fn derive_signing_key(mnemonic: &str, passphrase: &str, index: u32) -> Result<SigningKey> {
+    if index >= HARDENED {
+        anyhow::bail!("Account index must be below {}", HARDENED);
+    }
+
+    let mnemonic = Mnemonic::parse_normalized(mnemonic).context("Invalid mnemonic phrase")?;
+    let seed = mnemonic.to_seed(passphrase);
+
+    // SLIP-10: master key from the seed, then hardened children along the path
+    let (mut key, mut chain_code) = hmac_sha512(b"ed25519 seed", &[&seed])?;
+    for child in [44, 148, index] {
+        let hardened = (child | HARDENED).to_be_bytes();
+        (key, chain_code) = hmac_sha512(&chain_code, &[&[0], &key, &hardened])?;
+    }
+
+    Ok(SigningKey::from_bytes(&key))
+}
+
+/// The public key (G...) of a signing key
+pub fn public_key_strkey(signing_key: &SigningKey) -> String {
+    stellar_strkey::ed25519::PublicKey(signing_key.verifying_key().to_bytes()).to_string()
+}
+
+/// The secret seed (S...) of a signing key
+pub fn secret_seed_strkey(signing_key: &SigningKey) -> String {
+    stellar_strkey::ed25519::PrivateKey(signing_key.to_bytes()).to_string()
+}
+
+/// HMAC-SHA512 split into (key, chain code) halves
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> Result<([u8; 32], [u8; 32])> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).map_err(|e| anyhow!("{}", e))?;
+    for part in data {
+        mac.update(part);
+    }
+    let output = mac.finalize().into_bytes();
+
+    let mut left = [0u8; 32];
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&output[..32]);
+    right.copy_from_slice(&output[32..]);
+    Ok((left, right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sep5_vector() {
+        // Test 1 from SEP-5
+        let mnemonic = "illness spike retreat truth genius clock brain pass fit cave bargain toe";
+
+        let key = derive_signing_key(mnemonic, "", 0).unwrap();
+        assert_eq!(
+            public_key_strkey(&key),
+            "GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6"
+        );
+        assert_eq!(
+            secret_seed_strkey(&key),
+            "SBGWSG6BTNCKCOB3DIFBGCVMUPQFYPA2G4O34RMTB343OYPXU5DJDVMN"
+        );
+
+        assert!(derive_signing_key("not a valid phrase", "", 0).is_err());
+    }
+}
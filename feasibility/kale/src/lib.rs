@@ -0,0 +1,21 @@
+//! KALE farming on Stellar: a Soroban RPC client for the KALE contract, plus a
+//! client for a remote galactic-playground server behind the same [`api::KaleApi`] trait.
+
+pub mod api;
+pub mod args;
+pub mod client;
+pub mod config;
+pub mod contracts;
+pub mod diagnostics;
+pub mod endpoint;
+pub mod keys;
+pub mod network;
+pub mod passkey;
+pub mod payout;
+pub mod rpc;
+pub mod scval_json;
+pub mod sequence;
+pub mod signer;
+pub mod spec;
+pub mod trace;
+pub mod watcher;
@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Operator-configured per-farmer limits for shared deployments
+///
+/// A limit of None means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpendingLimits {
+    /// Maximum KALE stake (in stroops) a farmer may plant in one block
+    pub max_stake_per_block: Option<i128>,
+    /// Maximum USD value of a farmer's stake in one block, at the current KALE price
+    pub max_stake_usd: Option<f64>,
+    /// Maximum number of transactions a farmer may submit per UTC day
+    pub max_transactions_per_day: Option<u32>,
+}
+
+impl SpendingLimits {
+    /// Load limits from `KALE_MAX_STAKE_PER_BLOCK` (stroops), `KALE_MAX_STAKE_USD` and
+    /// `KALE_MAX_TX_PER_DAY`
+    pub fn from_env() -> Result<Self> {
+        let max_stake_per_block = std::env::var("KALE_MAX_STAKE_PER_BLOCK")
+            .ok()
+            .map(|value| value.parse())
+            .transpose()
+            .context("KALE_MAX_STAKE_PER_BLOCK must be an integer amount in stroops")?;
+
+        let max_stake_usd = std::env::var("KALE_MAX_STAKE_USD")
+            .ok()
+            .map(|value| value.parse())
+            .transpose()
+            .context("KALE_MAX_STAKE_USD must be a number")?;
+
+        let max_transactions_per_day = std::env::var("KALE_MAX_TX_PER_DAY")
+            .ok()
+            .map(|value| value.parse())
+            .transpose()
+            .context("KALE_MAX_TX_PER_DAY must be a non-negative integer")?;
+
+        Ok(Self {
+            max_stake_per_block,
+            max_stake_usd,
+            max_transactions_per_day,
+        })
+    }
+}
+
+/// A request that would exceed one of the farmer's limits
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "limit")]
+pub enum LimitExceeded {
+    #[serde(rename = "maxStakePerBlock")]
+    StakePerBlock {
+        limit: String,     // i128 as string
+        requested: String, // i128 as string
+    },
+    #[serde(rename = "maxStakeUsd")]
+    StakeUsd { limit: f64, requested: f64 },
+    #[serde(rename = "maxTransactionsPerDay")]
+    TransactionsPerDay { limit: u32, used: u32 },
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::StakePerBlock { limit, requested } => write!(
+                f,
+                "Stake of {} stroops exceeds this server's limit of {} stroops per block",
+                requested, limit
+            ),
+            LimitExceeded::StakeUsd { limit, requested } => write!(
+                f,
+                "Stake worth ${:.2} exceeds this server's limit of ${:.2} per block",
+                requested, limit
+            ),
+            LimitExceeded::TransactionsPerDay { limit, used } => write!(
+                f,
+                "Daily transaction limit reached ({} of {} used); try again tomorrow (UTC)",
+                used, limit
+            ),
+        }
+    }
+}
+
+/// Tracks per-farmer usage against the spending limits
+pub struct LimitTracker {
+    limits: SpendingLimits,
+    // farmer -> (UTC day number, transactions submitted that day)
+    daily_transactions: Mutex<HashMap<String, (u64, u32)>>,
+}
+
+impl LimitTracker {
+    pub fn new(limits: SpendingLimits) -> Self {
+        Self {
+            limits,
+            daily_transactions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a plant stake against the per-block limit
+    ///
+    /// A farmer can only plant once per block, so the per-block limit applies to each plant.
+    pub fn check_stake(&self, amount: i128) -> Result<(), LimitExceeded> {
+        match self.limits.max_stake_per_block {
+            Some(limit) if amount > limit => Err(LimitExceeded::StakePerBlock {
+                limit: limit.to_string(),
+                requested: amount.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The USD stake limit, if one is configured (checking it needs a price)
+    pub fn max_stake_usd(&self) -> Option<f64> {
+        self.limits.max_stake_usd
+    }
+
+    /// Check a plant stake's USD value against the per-block USD limit
+    pub fn check_stake_usd(&self, stake_usd: f64) -> Result<(), LimitExceeded> {
+        match self.limits.max_stake_usd {
+            Some(limit) if stake_usd > limit => Err(LimitExceeded::StakeUsd {
+                limit,
+                requested: stake_usd,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check whether the farmer may prepare another transaction today
+    pub fn check_transaction(&self, farmer: &str) -> Result<(), LimitExceeded> {
+        let Some(limit) = self.limits.max_transactions_per_day else {
+            return Ok(());
+        };
+
+        let used = match self.daily_transactions.lock().unwrap().get(farmer) {
+            Some((day, count)) if *day == current_day() => *count,
+            _ => 0,
+        };
+
+        if used >= limit {
+            return Err(LimitExceeded::TransactionsPerDay { limit, used });
+        }
+
+        Ok(())
+    }
+
+    /// Count a submitted transaction against the farmer's daily limit
+    ///
+    /// Counting on submission (not preparation) means retries after a dismissed
+    /// wallet popup don't use up the allowance.
+    pub fn record_transaction(&self, farmer: &str) {
+        let today = current_day();
+        let mut daily_transactions = self.daily_transactions.lock().unwrap();
+        let entry = daily_transactions
+            .entry(farmer.to_string())
+            .or_insert((today, 0));
+
+        if entry.0 != today {
+            *entry = (today, 0);
+        }
+        entry.1 += 1;
+    }
+}
+
+/// Days since the unix epoch (UTC)
+fn current_day() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or(0)
+}
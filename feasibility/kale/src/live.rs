@@ -0,0 +1,361 @@
+use anyhow::Context;
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use std::io::{LineWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use galacticplayground::contracts::changes::FarmChanges;
+use galacticplayground::diagnostics::TransactionReceipt;
+use galacticplayground::rpc::TransactionStatus;
+
+use crate::albedo::AccountStatusResponse;
+use crate::signing::SigningState;
+
+/// Events a slow client may fall behind by before it starts missing some
+const CHANNEL_CAPACITY: usize = 64;
+
+/// An update pushed to WebSocket clients as a JSON text message
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum LiveEvent {
+    /// The farm moved to a new block, or the current block got its entropy
+    #[serde(rename = "block")]
+    Block {
+        #[serde(rename = "blockIndex")]
+        block_index: u32,
+        entropy: Option<String>, // hex, None until someone plants
+    },
+    /// Planters joined the current block or farmers' work improved, for leaderboard deltas
+    #[serde(rename = "farm")]
+    Farm {
+        #[serde(flatten)]
+        changes: FarmChanges,
+    },
+    /// A transaction submitted through this server reached a ledger
+    #[serde(rename = "receipt")]
+    Receipt {
+        #[serde(rename = "publicKey")]
+        public_key: String,
+        kind: &'static str, // plant, work, harvest, trustline or restore
+        hash: String,
+        status: TransactionStatus,
+        /// None if the transaction couldn't be read back to decode it
+        result: Option<TransactionReceipt>,
+    },
+    /// An account's balances after one of its transactions reached a ledger
+    #[serde(rename = "account")]
+    Account {
+        #[serde(rename = "publicKey")]
+        public_key: String,
+        #[serde(flatten)]
+        status: AccountStatusResponse,
+    },
+    /// An external signer reported progress on one of the account's signing jobs
+    #[serde(rename = "signing")]
+    Signing {
+        #[serde(rename = "publicKey")]
+        public_key: String,
+        #[serde(rename = "jobId")]
+        job_id: String,
+        kind: &'static str,
+        state: SigningState,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+}
+
+impl LiveEvent {
+    /// The account the event is about, if it's about one
+    fn public_key(&self) -> Option<&str> {
+        match self {
+            LiveEvent::Block { .. } | LiveEvent::Farm { .. } => None,
+            LiveEvent::Receipt { public_key, .. }
+            | LiveEvent::Account { public_key, .. }
+            | LiveEvent::Signing { public_key, .. } => Some(public_key),
+        }
+    }
+}
+
+/// An event as clients get it: its JSON, and the account it's about if any
+///
+/// Recordings hold frames rather than [`LiveEvent`]s, so they replay exactly as they went out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    #[serde(rename = "publicKey")]
+    public_key: Option<String>,
+    event: serde_json::Value,
+}
+
+impl Frame {
+    fn is_block(&self) -> bool {
+        self.event["type"] == "block"
+    }
+}
+
+impl From<&LiveEvent> for Frame {
+    fn from(event: &LiveEvent) -> Self {
+        Self {
+            public_key: event.public_key().map(str::to_string),
+            event: serde_json::to_value(event).expect("live events always serialize"),
+        }
+    }
+}
+
+/// A line of a recording: a frame and when it went out, in milliseconds since recording began
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedFrame {
+    ms: u64,
+    #[serde(flatten)]
+    frame: Frame,
+}
+
+/// Where live events are recorded to, or replayed from instead of the network
+#[derive(Debug, Clone)]
+pub struct LiveOptions {
+    /// JSON Lines file every published event is appended to
+    pub record: Option<PathBuf>,
+    /// A recording to send clients on a loop instead of live events
+    pub replay: Option<PathBuf>,
+    /// How many times faster than recorded to replay
+    pub replay_speed: f64,
+}
+
+impl Default for LiveOptions {
+    fn default() -> Self {
+        Self {
+            record: None,
+            replay: None,
+            replay_speed: 1.0,
+        }
+    }
+}
+
+impl LiveOptions {
+    /// Load the options from `KALE_LIVE_RECORD`, `KALE_LIVE_REPLAY` and
+    /// `KALE_LIVE_REPLAY_SPEED`, using the defaults for unset values
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = Self {
+            record: std::env::var_os("KALE_LIVE_RECORD").map(PathBuf::from),
+            replay: std::env::var_os("KALE_LIVE_REPLAY").map(PathBuf::from),
+            ..Self::default()
+        };
+        if let Ok(value) = std::env::var("KALE_LIVE_REPLAY_SPEED") {
+            options.replay_speed = value
+                .parse()
+                .ok()
+                .filter(|speed: &f64| speed.is_finite() && *speed > 0.0)
+                .context("KALE_LIVE_REPLAY_SPEED must be a positive number, e.g. 2 or 0.5")?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Fan-out of live updates to connected WebSocket clients
+pub struct LiveUpdates {
+    sender: broadcast::Sender<Frame>,
+    /// The latest block event, sent to clients as soon as they connect
+    current_block: Mutex<Option<Frame>>,
+    /// The recording being written, and when it began
+    recording: Option<Mutex<(Instant, LineWriter<std::fs::File>)>>,
+    /// The recording being replayed instead of live events, and how fast
+    replay: Option<(Vec<RecordedFrame>, f64)>,
+}
+
+impl Default for LiveUpdates {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            current_block: Mutex::new(None),
+            recording: None,
+            replay: None,
+        }
+    }
+}
+
+impl LiveUpdates {
+    /// Open the recording to write or replay, if any
+    pub fn new(options: LiveOptions) -> anyhow::Result<Self> {
+        let mut live = Self::default();
+        if let Some(path) = &options.record {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open {} to record to", path.display()))?;
+            live.recording = Some(Mutex::new((Instant::now(), LineWriter::new(file))));
+        }
+        if let Some(path) = &options.replay {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read recording {}", path.display()))?;
+            let frames = contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(number, line)| {
+                    serde_json::from_str(line).with_context(|| {
+                        format!("Invalid frame on line {} of {}", number + 1, path.display())
+                    })
+                })
+                .collect::<anyhow::Result<Vec<RecordedFrame>>>()?;
+            anyhow::ensure!(!frames.is_empty(), "{} has no events", path.display());
+            live.replay = Some((frames, options.replay_speed));
+        }
+        Ok(live)
+    }
+
+    /// Whether clients get a recording instead of live events
+    pub fn replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Send an event to every connected client; dropped while replaying a recording
+    pub fn publish(&self, event: LiveEvent) {
+        if self.replaying() {
+            return;
+        }
+        self.send(Frame::from(&event));
+    }
+
+    fn send(&self, frame: Frame) {
+        if let Some(recording) = &self.recording {
+            let mut recording = recording.lock().unwrap();
+            let line = RecordedFrame {
+                ms: recording.0.elapsed().as_millis() as u64,
+                frame: frame.clone(),
+            };
+            let line = serde_json::to_string(&line).expect("frames always serialize");
+            if let Err(e) = writeln!(recording.1, "{}", line) {
+                eprintln!("Warning: Failed to record live event: {}", e);
+            }
+        }
+        if frame.is_block() {
+            *self.current_block.lock().unwrap() = Some(frame.clone());
+        }
+        // No receivers just means nobody is connected
+        let _ = self.sender.send(frame);
+    }
+
+    /// Send the recording to clients at its recorded pace (scaled by the replay speed),
+    /// starting over at the end; returns straight away if there's nothing to replay
+    pub async fn replay(&self) {
+        let Some((frames, speed)) = &self.replay else {
+            return;
+        };
+        loop {
+            let start = Instant::now();
+            for recorded in frames {
+                let at = Duration::from_millis(recorded.ms).div_f64(*speed);
+                tokio::time::sleep_until(start + at).await;
+                self.send(recorded.frame.clone());
+            }
+        }
+    }
+
+    /// How many WebSocket clients are connected
+    pub fn client_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Stream events to a client until it disconnects
+    ///
+    /// Block and farm events go to everyone; receipt, account and signing events only to
+    /// clients that connected with the matching public key.
+    pub async fn serve(&self, mut socket: WebSocket, public_key: Option<String>) {
+        let mut receiver = self.sender.subscribe();
+        let current_block = self.current_block.lock().unwrap().clone();
+        if let Some(event) = current_block {
+            if send(&mut socket, &event).await.is_err() {
+                return;
+            }
+        }
+
+        loop {
+            tokio::select! {
+                event = receiver.recv() => {
+                    let event = match event {
+                        Ok(event) => event,
+                        // The client will catch up with the next block or account event
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    };
+                    if event.public_key.is_some() && event.public_key != public_key {
+                        continue;
+                    }
+                    if send(&mut socket, &event).await.is_err() {
+                        return;
+                    }
+                }
+                message = socket.recv() => match message {
+                    // Clients have nothing to say; anything but a close is ignored
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, frame: &Frame) -> Result<(), axum::Error> {
+    socket.send(Message::Text(frame.event.to_string())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("kale-live-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let recorder = LiveUpdates::new(LiveOptions {
+            record: Some(path.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        for block_index in [7, 8] {
+            recorder.publish(LiveEvent::Block {
+                block_index,
+                entropy: None,
+            });
+            tokio::time::advance(Duration::from_secs(60)).await;
+        }
+        drop(recorder);
+
+        let player = LiveUpdates::new(LiveOptions {
+            replay: Some(path.clone()),
+            replay_speed: 60.0,
+            ..Default::default()
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut receiver = player.sender.subscribe();
+        // Live events don't get through while replaying
+        player.publish(LiveEvent::Block {
+            block_index: 99,
+            entropy: None,
+        });
+
+        let started = Instant::now();
+        let replay = async {
+            player.replay().await;
+        };
+        let received = async {
+            let first = receiver.recv().await.unwrap();
+            let second = receiver.recv().await.unwrap();
+            (first, second, started.elapsed())
+        };
+        let (first, second, elapsed) = tokio::select! {
+            _ = replay => unreachable!("replays loop"),
+            received = received => received,
+        };
+        assert_eq!(first.event["blockIndex"], 7);
+        assert_eq!(second.event["blockIndex"], 8);
+        // A minute apart when recorded, a second at 60x
+        assert_eq!(elapsed.as_secs(), 1);
+    }
+}
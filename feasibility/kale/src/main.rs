@@ -1,37 +1,563 @@
+mod access;
 mod albedo;
-mod contracts;
-mod rpc;
+mod alerts;
+mod congestion;
+mod dashboard;
+mod doctor;
+mod fallback_ui;
+mod farmer;
+mod fleet;
+mod headless;
+mod hooks;
+mod i18n;
+mod idempotency;
+mod limits;
+mod live;
+mod maintenance;
+mod notify;
+mod ownership;
+mod pause;
+mod preferences;
+mod price;
+mod prompt;
+mod script;
+mod sign_agent;
+mod signing;
+#[cfg(test)]
+mod simulation;
+mod systemd;
 
-use contracts::kale::Kale;
+use access::AccessPolicy;
+use anyhow::Context;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use farmer::FarmOptions;
+use fleet::Fleet;
+use galacticplayground::config::{Config, ConfigSource, Network, Settings, DEFAULT_CONFIG_FILE};
+use galacticplayground::contracts::kale::{format_kale, Kale};
+use galacticplayground::keys;
+use galacticplayground::rpc::{
+    ConfirmationOptions, InclusionFeeOptions, LedgerCacheOptions, RebroadcastPolicy, RetryPolicy,
+};
+use galacticplayground::trace::TraceOptions;
+use headless::FarmCall;
+use idempotency::IdempotencyStore;
+use limits::{LimitTracker, SpendingLimits};
+use live::{LiveOptions, LiveUpdates};
+use maintenance::Maintenance;
+use notify::Notifier;
+use preferences::PreferenceStore;
+use price::{PriceFeed, PriceSource};
+use sign_agent::{Keys, SignAgent};
+
+#[derive(Parser)]
+#[command(about = "KALE farming playground on Stellar")]
+struct Cli {
+    /// Run a utility command instead of starting the server
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// TOML config file (default: kale.toml, if it exists)
+    #[arg(long, env = "KALE_CONFIG", global = true)]
+    config: Option<std::path::PathBuf>,
+    /// testnet, mainnet, futurenet or local; picks the defaults for every setting below
+    #[arg(long, global = true)]
+    network: Option<Network>,
+    /// Soroban RPC URL, overriding the config file and KALE_RPC_URL
+    #[arg(long, global = true)]
+    rpc_url: Option<String>,
+    /// KALE contract address, overriding the config file and KALE_CONTRACT_ID
+    #[arg(long, global = true)]
+    contract_id: Option<String>,
+    /// Network passphrase, overriding the config file and KALE_NETWORK_PASSPHRASE
+    #[arg(long, global = true)]
+    network_passphrase: Option<String>,
+    /// KALE asset issuer, overriding the config file and KALE_ISSUER
+    #[arg(long, global = true)]
+    kale_issuer: Option<String>,
+    /// Server port, overriding the config file and KALE_PORT
+    #[arg(long, global = true)]
+    port: Option<u16>,
+}
+
+impl Cli {
+    /// The config file and the command-line overrides to apply on top of it
+    fn config_source(&self) -> ConfigSource {
+        ConfigSource {
+            path: self.config.clone(),
+            overrides: Settings {
+                network: self.network,
+                rpc_url: self.rpc_url.clone(),
+                contract_id: self.contract_id.clone(),
+                network_passphrase: self.network_passphrase.clone(),
+                kale_issuer: self.kale_issuer.clone(),
+                server_port: self.port,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The settings from file and environment, with command-line overrides applied
+    fn settings(&self) -> anyhow::Result<Settings> {
+        self.config_source().settings()
+    }
+
+    /// The config from file and environment, with command-line overrides applied
+    fn config(&self) -> anyhow::Result<Config> {
+        Config::resolve(self.settings()?)
+    }
+
+    /// Whether a flag, `KALE_NETWORK` or the config file picks the network, rather than
+    /// it falling back to testnet
+    fn network_chosen(&self) -> anyhow::Result<bool> {
+        if self.network.is_some() || std::env::var("KALE_NETWORK").is_ok() {
+            return Ok(true);
+        }
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None if std::path::Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                DEFAULT_CONFIG_FILE.into()
+            }
+            None => return Ok(false),
+        };
+        Ok(Settings::from_file(&path)?.network.is_some())
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Farmer key utilities
+    Key {
+        #[command(subcommand)]
+        command: KeyCommand,
+    },
+    /// Plant in the current block without a browser, signing with a local secret key
+    Plant {
+        /// Stake in stroops; asked for if left out at a terminal
+        #[arg(long)]
+        amount: Option<i128>,
+        /// Plant without confirming the stake at a terminal
+        #[arg(long, short)]
+        yes: bool,
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+    /// Submit work for a nonce mined elsewhere, signing with a local secret key
+    Work {
+        #[arg(long)]
+        nonce: u64,
+        /// The block the nonce was mined for
+        #[arg(long)]
+        block: Option<u32>,
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+    /// Harvest a block without a browser, signing with a local secret key
+    Harvest {
+        #[arg(long)]
+        block: u32,
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+    /// Farm every block until stopped: harvest, plant, mine and submit work
+    Farm {
+        /// File holding the farmer's secret seed (S...), readable only by the farming user
+        #[arg(long)]
+        key_file: std::path::PathBuf,
+        /// Stake in stroops for each block
+        #[arg(long, default_value_t = 0)]
+        stake: i128,
+        /// Stop mining a block once a hash has this many leading zeros; defaults to an
+        /// estimate from recent blocks
+        #[arg(long)]
+        target_zeros: Option<u32>,
+        /// Longest to mine each block before submitting the best nonce found
+        #[arg(long, default_value_t = 60)]
+        mining_secs: u64,
+        /// Mining threads; defaults to every core
+        #[arg(long)]
+        mining_threads: Option<usize>,
+        /// Rhai script defining on_new_block, on_work_found and/or on_harvest hooks
+        #[arg(long)]
+        hooks: Option<std::path::PathBuf>,
+        /// Secret seed of an account that pays the fees instead, so the farmer needs no spare XLM
+        #[arg(long, env = "KALE_FEE_SOURCE_SECRET_KEY", hide_env_values = true)]
+        fee_source_secret_key: Option<String>,
+        /// File naming subsystems to pause, one per line: harvester, miner, blockWatcher;
+        /// checked at every poll, so editing it pauses and resumes the running daemon
+        #[arg(long, env = "KALE_FARM_PAUSE_FILE")]
+        pause_file: Option<std::path::PathBuf>,
+        /// File to write the latest mining round's hashrate to, for the server's dashboard
+        /// (name it as the farmer's `status_file` in the fleet file)
+        #[arg(long, env = "KALE_FARM_STATUS_FILE")]
+        status_file: Option<std::path::PathBuf>,
+        /// Hold the keys in a sandboxed signing process that reads the key file itself, so
+        /// the seed never enters the daemon (Linux only)
+        #[arg(long, env = "KALE_ISOLATE_SIGNER")]
+        isolate_signer: bool,
+    },
+    /// Recorded farming scripts
+    Script {
+        #[command(subcommand)]
+        command: ScriptCommand,
+    },
+    /// Self-test a network: passphrase, protocol version, contract reads and friendbot,
+    /// e.g. `galacticplayground doctor --network futurenet`
+    Doctor,
+    /// Write systemd unit files for running the server as a socket-activated service
+    InstallService {
+        /// Directory to write the .service and .socket units to
+        #[arg(long, default_value = "/etc/systemd/system")]
+        dir: std::path::PathBuf,
+        /// Unit name
+        #[arg(long, default_value = "galacticplayground")]
+        name: String,
+    },
+    /// Print a shell completion script, e.g. `galacticplayground completions bash`
+    Completions { shell: clap_complete::Shell },
+}
+
+impl Command {
+    /// Whether the command sends transactions, so the network matters
+    fn farms(&self) -> bool {
+        matches!(
+            self,
+            Command::Plant { .. }
+                | Command::Work { .. }
+                | Command::Harvest { .. }
+                | Command::Farm { .. }
+                | Command::Script { .. }
+        )
+    }
+}
+
+#[derive(Args)]
+struct SignerArgs {
+    /// Farmer secret seed (S...); prefer KALE_SECRET_KEY to keep it out of shell history.
+    /// Asked for if left out at a terminal
+    #[arg(long, env = "KALE_SECRET_KEY", hide_env_values = true)]
+    secret_key: Option<String>,
+    /// Secret seed of an account that pays the fees instead, so the farmer needs no spare XLM
+    #[arg(long, env = "KALE_FEE_SOURCE_SECRET_KEY", hide_env_values = true)]
+    fee_source_secret_key: Option<String>,
+    /// Append each call that succeeds to this YAML script, to replay with `script run`
+    #[arg(long, env = "KALE_RECORD")]
+    record: Option<std::path::PathBuf>,
+    /// Hold the keys in a sandboxed signing process instead of this one (Linux only)
+    #[arg(long, env = "KALE_ISOLATE_SIGNER")]
+    isolate_signer: bool,
+}
+
+impl SignerArgs {
+    /// The farmer's secret seed, asked for at a terminal if not given
+    fn secret_key(&self) -> anyhow::Result<String> {
+        match &self.secret_key {
+            Some(secret_key) => Ok(secret_key.clone()),
+            None if prompt::interactive() => prompt::secret_key("Farmer"),
+            None => anyhow::bail!("Set --secret-key or KALE_SECRET_KEY"),
+        }
+    }
+
+    /// The farmer's and fee source's keys, held by a signing agent with --isolate-signer
+    fn keys(&self) -> anyhow::Result<Keys> {
+        let secret_key = self.secret_key()?;
+        let fee_source = self.fee_source_secret_key.as_deref();
+        if self.isolate_signer {
+            Ok(Keys::Agent(SignAgent::spawn(
+                None,
+                Some(&secret_key),
+                fee_source,
+            )?))
+        } else {
+            Keys::local(&secret_key, fee_source)
+        }
+    }
+
+    /// Prepare, sign and submit a farming call with these keys
+    async fn run(&self, config: &Config, call: FarmCall) -> anyhow::Result<()> {
+        let step = script::Step::from_call(&call)?;
+        headless::run(config, self.keys()?, call).await?;
+        if let Some(path) = &self.record {
+            script::Script::record(path, step)?;
+            println!("Recorded to {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand)]
+enum ScriptCommand {
+    /// Replay a YAML script of plant, mine, work and harvest steps on the chosen network,
+    /// signing with a local secret key
+    Run {
+        file: std::path::PathBuf,
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyCommand {
+    /// Derive a farmer keypair from a BIP-39 mnemonic (SEP-5 path m/44'/148'/N')
+    Derive {
+        /// The mnemonic phrase (prefer KALE_MNEMONIC to keep it out of shell history)
+        #[arg(long, env = "KALE_MNEMONIC", hide_env_values = true)]
+        mnemonic: String,
+        /// Optional BIP-39 passphrase
+        #[arg(
+            long,
+            env = "KALE_MNEMONIC_PASSPHRASE",
+            hide_env_values = true,
+            default_value = ""
+        )]
+        passphrase: String,
+        /// Account index N
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        /// Also print the secret seed (S...)
+        #[arg(long)]
+        show_secret: bool,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    // The signing agent locks itself down while it is still a single thread, before the
+    // runtime starts any
+    if std::env::args().nth(1).as_deref() == Some(sign_agent::COMMAND) {
+        return sign_agent::main();
+    }
+    run()
+}
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    println!("=== Galactic Playground - KALE Plant Transaction ===\n");
+async fn run() -> anyhow::Result<()> {
+    let mut cli = Cli::parse();
+    if let Some(Command::Completions { shell }) = cli.command {
+        clap_complete::generate(
+            shell,
+            &mut Cli::command(),
+            "galacticplayground",
+            &mut std::io::stdout(),
+        );
+        return Ok(());
+    }
+    // Farming on the wrong network by default could spend real funds, so ask at a terminal
+    if cli.command.as_ref().is_some_and(Command::farms)
+        && prompt::interactive()
+        && !cli.network_chosen()?
+    {
+        cli.network = Some(prompt::network()?);
+    }
+    // Network, contract and port: network defaults < kale.toml < KALE_* env < CLI flags
+    let config_source = cli.config_source();
+    let config = match cli.command {
+        // The doctor also checks networks that have no KALE deployment to default to
+        Some(Command::Doctor) => doctor::config(cli.settings()?)?,
+        _ => cli.config()?,
+    };
+    if let Some(command) = cli.command {
+        return run_command(command, &config).await;
+    }
 
-    // Testnet configuration
-    const TESTNET_RPC: &str = "https://soroban-testnet.stellar.org";
-    const TESTNET_CONTRACT: &str = "CDSWUUXGPWDZG76ISK6SUCVPZJMD5YUV66J2FXFXFGDX25XKZJIEITAO";
-    const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+    println!("=== Galactic Playground - KALE Plant Transaction ===\n");
+    println!("Network: {}", config.network.name());
 
     // Create KALE contract client
-    println!("Connecting to KALE contract on testnet...");
-    let kale = Kale::new(TESTNET_RPC, TESTNET_CONTRACT, TESTNET_PASSPHRASE)?;
-    println!("✓ Connected to KALE contract: {}\n", TESTNET_CONTRACT);
+    // RPC endpoint, with provider API keys or headers if configured
+    println!(
+        "Connecting to KALE contract via {}...",
+        config.endpoint().redacted()
+    );
+    if !config.rpc_fallback_urls.is_empty() {
+        println!("Failing over to: {}", config.rpc_fallback_urls.join(", "));
+    }
+    if let Some(url) = &config.history_rpc_url {
+        println!("Reading old events and transactions from: {}", url);
+    }
+    let mut kale = Kale::from_config(&config)?;
+    kale.set_retry_policy(RetryPolicy::from_env()?);
+    kale.set_rebroadcast_policy(RebroadcastPolicy::from_env()?);
+    kale.set_confirmation_options(ConfirmationOptions::from_env()?);
+    kale.set_inclusion_fee_options(InclusionFeeOptions::from_env()?);
+    kale.set_ledger_cache_options(LedgerCacheOptions::from_env()?);
+    kale.set_trace_options(TraceOptions::from_env()?);
+    kale.verify_network().await?;
+    println!("✓ Connected to KALE contract: {}\n", config.contract_id);
 
     // Get current block index
     println!("Fetching current farm block...");
     let block_index = kale.get_block_index().await?;
     println!("✓ Current block index: {}\n", block_index);
 
+    // Per-farmer spending limits for shared deployments (unlimited unless configured)
+    let limits = SpendingLimits::from_env()?;
+
+    // Which hosts may call submit endpoints, and which proxies to trust for client IPs
+    let access = AccessPolicy::from_env()?;
+
+    // Optional KALE/USD price source for USD-denominated limits and display
+    let price = PriceFeed::new(PriceSource::from_env()?);
+
+    // Responses for Idempotency-Key retries, kept in SQLite so they survive restarts
+    let idempotency = IdempotencyStore::from_env()?;
+
+    // Farmers' UI preferences, saved to a JSON file so they survive restarts
+    let preferences = PreferenceStore::from_env()?;
+
+    // Live WebSocket events, recorded with KALE_LIVE_RECORD or replayed with KALE_LIVE_REPLAY
+    let live = LiveUpdates::new(LiveOptions::from_env()?)?;
+
+    // Emails farmers per their notification preferences once KALE_SMTP_URL is set
+    let notifier = Notifier::from_env()?;
+
+    // Starts in maintenance mode with KALE_MAINTENANCE_MESSAGE; switched at /api/admin/maintenance
+    let maintenance = Maintenance::from_env();
+
+    // Farmer accounts from KALE_FLEET_FILE, health-checked in turn and shown at /api/fleet/status
+    let fleet = Fleet::from_env()?;
+
     // Start the authentication and plant transaction flow
     println!("Starting authentication and plant transaction flow...");
-    let (public_key, tx_hash) = albedo::authenticate_and_plant(kale).await?;
+    let (public_key, tx_hash) = albedo::authenticate_and_plant(
+        config.clone(),
+        config_source,
+        kale,
+        LimitTracker::new(limits),
+        access,
+        price,
+        idempotency,
+        preferences,
+        live,
+        notifier,
+        maintenance,
+        fleet,
+    )
+    .await?;
 
     println!("\n=== Transaction Complete ===");
     println!("Public key: {}", public_key);
     println!("Transaction hash: {}", tx_hash);
-    println!("\nYou can view the transaction on Stellar Expert:");
-    println!("https://stellar.expert/explorer/testnet/tx/{}", tx_hash);
+    if let Some(explorer_url) = config.explorer_url() {
+        println!("\nYou can view the transaction on Stellar Expert:");
+        println!("{}/tx/{}", explorer_url, tx_hash);
+    }
+
+    Ok(())
+}
+
+async fn run_command(command: Command, config: &Config) -> anyhow::Result<()> {
+    match command {
+        Command::Key {
+            command:
+                KeyCommand::Derive {
+                    mnemonic,
+                    passphrase,
+                    index,
+                    show_secret,
+                },
+        } => {
+            let signing_key = keys::derive_signing_key(&mnemonic, &passphrase, index)?;
+            println!("Path:       {}", keys::derivation_path(index));
+            println!("Public key: {}", keys::public_key_strkey(&signing_key));
+            if show_secret {
+                println!("Secret:     {}", keys::secret_seed_strkey(&signing_key));
+            }
+        }
+        Command::Plant {
+            amount,
+            yes,
+            signer,
+        } => {
+            let amount = match amount {
+                Some(amount) => amount,
+                None if prompt::interactive() => prompt::stake()?,
+                None => anyhow::bail!("Set --amount (stroops) to plant"),
+            };
+            if !yes && prompt::interactive() {
+                let question = format!(
+                    "Plant with a stake of {} KALE on {}?",
+                    format_kale(amount),
+                    config.network.name()
+                );
+                if !prompt::confirm(&question)? {
+                    println!("Not planting");
+                    return Ok(());
+                }
+            }
+            signer.run(config, FarmCall::Plant(amount)).await?;
+        }
+        Command::Work {
+            nonce,
+            block,
+            signer,
+        } => {
+            signer.run(config, FarmCall::Work(nonce, block)).await?;
+        }
+        Command::Harvest { block, signer } => {
+            signer.run(config, FarmCall::Harvest(block)).await?;
+        }
+        Command::Farm {
+            key_file,
+            stake,
+            target_zeros,
+            mining_secs,
+            mining_threads,
+            hooks,
+            fee_source_secret_key,
+            pause_file,
+            status_file,
+            isolate_signer,
+        } => {
+            let fee_source = fee_source_secret_key.as_deref();
+            let keys = if isolate_signer {
+                Keys::Agent(SignAgent::spawn(Some(&key_file), None, fee_source)?)
+            } else {
+                let secret_key = std::fs::read_to_string(&key_file)
+                    .with_context(|| format!("Failed to read {}", key_file.display()))?;
+                Keys::local(secret_key.trim(), fee_source)?
+            };
+            let options = FarmOptions {
+                stake,
+                target_zeros,
+                mining_budget: std::time::Duration::from_secs(mining_secs),
+                mining_threads,
+                hooks: hooks.as_deref().map(hooks::Hooks::load).transpose()?,
+                pause_file,
+                status_file,
+            };
+            farmer::run(config, keys, options).await?;
+        }
+        Command::Script {
+            command: ScriptCommand::Run { file, signer },
+        } => {
+            let script = script::Script::from_file(&file)?;
+            script::run(config, signer.keys()?, &script, signer.record.as_deref()).await?;
+        }
+        Command::Doctor => doctor::run(config).await?,
+        Command::InstallService { dir, name } => {
+            // The server serves the frontend and keeps state relative to where it runs
+            let exe = std::env::current_exe()?;
+            let working_dir = std::env::current_dir()?;
+
+            let service_path = dir.join(format!("{}.service", name));
+            let socket_path = dir.join(format!("{}.socket", name));
+            std::fs::write(
+                &service_path,
+                systemd::service_unit(&name, &exe, &working_dir),
+            )
+            .with_context(|| format!("Failed to write {}", service_path.display()))?;
+            std::fs::write(
+                &socket_path,
+                systemd::socket_unit(&name, config.server_port),
+            )
+            .with_context(|| format!("Failed to write {}", socket_path.display()))?;
+
+            println!("Wrote {}", service_path.display());
+            println!("Wrote {}", socket_path.display());
+            println!("\nPut KALE_* settings in /etc/default/{}, then run:", name);
+            println!("  systemctl daemon-reload");
+            println!("  systemctl enable --now {}.socket", name);
+        }
+        Command::Completions { .. } => unreachable!("handled before loading the config"),
+    }
 
     Ok(())
 }
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Banner shown while in maintenance mode if the operator didn't write one
+const DEFAULT_BANNER: &str =
+    "The server is down for maintenance; signing and submitting are paused. Try again later.";
+
+/// Longest banner or schedule message accepted
+const MAX_MESSAGE_LEN: usize = 500;
+
+/// Maintenance announced ahead of time, e.g. an RPC migration or contract upgrade
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledMaintenance {
+    /// Unix seconds
+    #[serde(rename = "startsAt")]
+    pub starts_at: u64,
+    #[serde(rename = "endsAt", default, skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// The operator's maintenance switch, banner and schedule
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    /// While true, endpoints that prepare or submit transactions return 503
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled: Option<ScheduledMaintenance>,
+}
+
+impl MaintenanceStatus {
+    /// Check values the types don't constrain
+    pub fn validate(&self) -> Result<()> {
+        let messages = self
+            .message
+            .iter()
+            .chain(self.scheduled.iter().filter_map(|s| s.message.as_ref()));
+        for message in messages {
+            anyhow::ensure!(
+                message.len() <= MAX_MESSAGE_LEN,
+                "Messages can be at most {} bytes",
+                MAX_MESSAGE_LEN
+            );
+        }
+        if let Some(ScheduledMaintenance {
+            starts_at,
+            ends_at: Some(ends_at),
+            ..
+        }) = &self.scheduled
+        {
+            anyhow::ensure!(ends_at >= starts_at, "endsAt can't be before startsAt");
+        }
+        Ok(())
+    }
+}
+
+/// Maintenance mode, switched at runtime by the operator
+///
+/// Kept in memory: a restart ends maintenance unless `KALE_MAINTENANCE_MESSAGE` is set.
+#[derive(Default)]
+pub struct Maintenance {
+    status: Mutex<MaintenanceStatus>,
+}
+
+impl Maintenance {
+    /// Start in maintenance mode if `KALE_MAINTENANCE_MESSAGE` is set, with it as the banner
+    pub fn from_env() -> Self {
+        let status = match std::env::var("KALE_MAINTENANCE_MESSAGE") {
+            Ok(message) => MaintenanceStatus {
+                active: true,
+                message: Some(message).filter(|message| !message.is_empty()),
+                scheduled: None,
+            },
+            Err(_) => MaintenanceStatus::default(),
+        };
+        Self {
+            status: Mutex::new(status),
+        }
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Replace the status, returning the previous one
+    pub fn set(&self, status: MaintenanceStatus) -> Result<MaintenanceStatus> {
+        status.validate()?;
+        Ok(std::mem::replace(&mut *self.status.lock().unwrap(), status))
+    }
+
+    /// The banner to refuse mutating requests with, while in maintenance mode
+    pub fn banner(&self) -> Option<String> {
+        let status = self.status.lock().unwrap();
+        status.active.then(|| {
+            status
+                .message
+                .clone()
+                .unwrap_or_else(|| DEFAULT_BANNER.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_banner_only_while_active() {
+        let maintenance = Maintenance::default();
+        assert_eq!(maintenance.banner(), None);
+
+        let scheduled = MaintenanceStatus {
+            scheduled: Some(ScheduledMaintenance {
+                starts_at: 2_000,
+                ends_at: Some(3_000),
+                message: Some("Contract upgrade".to_string()),
+            }),
+            ..Default::default()
+        };
+        maintenance.set(scheduled.clone()).unwrap();
+        assert_eq!(maintenance.banner(), None);
+
+        let previous = maintenance
+            .set(MaintenanceStatus {
+                active: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(previous, scheduled);
+        assert_eq!(maintenance.banner().as_deref(), Some(DEFAULT_BANNER));
+
+        let backwards = MaintenanceStatus {
+            scheduled: Some(ScheduledMaintenance {
+                starts_at: 3_000,
+                ends_at: Some(2_000),
+                message: None,
+            }),
+            ..Default::default()
+        };
+        assert!(maintenance.set(backwards).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use stellar_xdr::curr::{
+    ConfigSettingEntry, ConfigSettingId, Limits, SorobanResources, TransactionEnvelope,
+    TransactionExt, WriteXdr,
+};
+
+use crate::contracts::kale::format_kale;
+
+/// Config settings that make up [`NetworkLimits`]
+pub const NETWORK_LIMIT_SETTINGS: [ConfigSettingId; 4] = [
+    ConfigSettingId::ContractComputeV0,
+    ConfigSettingId::ContractLedgerCostV0,
+    ConfigSettingId::ContractBandwidthV0,
+    ConfigSettingId::ContractEventsV0,
+];
+
+/// Per-transaction Soroban resource limits and fee rates configured on the network
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkLimits {
+    #[serde(rename = "txMaxInstructions")]
+    pub tx_max_instructions: i64,
+    #[serde(rename = "txMemoryLimit")]
+    pub tx_memory_limit: u32,
+    #[serde(rename = "txMaxReadLedgerEntries")]
+    pub tx_max_read_ledger_entries: u32,
+    #[serde(rename = "txMaxReadBytes")]
+    pub tx_max_read_bytes: u32,
+    #[serde(rename = "txMaxWriteLedgerEntries")]
+    pub tx_max_write_ledger_entries: u32,
+    #[serde(rename = "txMaxWriteBytes")]
+    pub tx_max_write_bytes: u32,
+    #[serde(rename = "txMaxSizeBytes")]
+    pub tx_max_size_bytes: u32,
+    #[serde(rename = "txMaxContractEventsSizeBytes")]
+    pub tx_max_contract_events_size_bytes: u32,
+    /// Fee (in stroops) per 10,000 instructions
+    #[serde(rename = "feePerInstructionsIncrement")]
+    pub fee_per_instructions_increment: i64,
+    #[serde(rename = "feeReadLedgerEntry")]
+    pub fee_read_ledger_entry: i64,
+    #[serde(rename = "feeWriteLedgerEntry")]
+    pub fee_write_ledger_entry: i64,
+    #[serde(rename = "feeRead1Kb")]
+    pub fee_read_1kb: i64,
+    #[serde(rename = "feeTxSize1Kb")]
+    pub fee_tx_size_1kb: i64,
+    #[serde(rename = "feeContractEvents1Kb")]
+    pub fee_contract_events_1kb: i64,
+}
+
+/// A fee amount, in stroops and in XLM
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeAmount {
+    pub stroops: i64,
+    pub xlm: String,
+}
+
+impl From<i64> for FeeAmount {
+    fn from(stroops: i64) -> Self {
+        Self {
+            stroops,
+            xlm: format_kale(stroops as i128), // XLM has the same 7 decimal places
+        }
+    }
+}
+
+/// How a Soroban transaction's fee splits between inclusion and resources
+///
+/// The resource components are computed from the transaction's declared resources and
+/// the network's fee rates. Write bytes, rent and events are priced from ledger state
+/// this server doesn't track, so they make up `other`: the part of the resource fee the
+/// listed components don't account for.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeBreakdown {
+    /// The maximum fee the transaction pays
+    pub total: FeeAmount,
+    /// The bid for inclusion in a ledger (total minus resource fee)
+    pub inclusion: FeeAmount,
+    /// The resource fee declared in the transaction's Soroban data
+    pub resource: FeeAmount,
+    /// CPU instructions
+    pub cpu: FeeAmount,
+    /// Ledger entry reads and read bytes
+    pub read: FeeAmount,
+    /// Ledger entry writes
+    pub write: FeeAmount,
+    /// Transaction size
+    pub bandwidth: FeeAmount,
+    /// Write bytes, rent, events and simulation headroom
+    pub other: FeeAmount,
+}
+
+/// Fee for `amount` units at `rate` stroops per `per` units, rounded up like the network
+fn resource_fee(amount: i64, rate: i64, per: i64) -> i64 {
+    (amount * rate + per - 1) / per
+}
+
+impl NetworkLimits {
+    /// Build the limits from the entries of [`NETWORK_LIMIT_SETTINGS`]
+    pub fn from_config_settings(settings: &[ConfigSettingEntry]) -> Result<Self> {
+        let (mut compute, mut ledger, mut bandwidth, mut events) = (None, None, None, None);
+        for setting in settings {
+            match setting {
+                ConfigSettingEntry::ContractComputeV0(setting) => compute = Some(setting),
+                ConfigSettingEntry::ContractLedgerCostV0(setting) => ledger = Some(setting),
+                ConfigSettingEntry::ContractBandwidthV0(setting) => bandwidth = Some(setting),
+                ConfigSettingEntry::ContractEventsV0(setting) => events = Some(setting),
+                _ => {}
+            }
+        }
+
+        let compute = compute.context("Missing ContractComputeV0 config setting")?;
+        let ledger = ledger.context("Missing ContractLedgerCostV0 config setting")?;
+        let bandwidth = bandwidth.context("Missing ContractBandwidthV0 config setting")?;
+        let events = events.context("Missing ContractEventsV0 config setting")?;
+
+        Ok(Self {
+            tx_max_instructions: compute.tx_max_instructions,
+            tx_memory_limit: compute.tx_memory_limit,
+            tx_max_read_ledger_entries: ledger.tx_max_read_ledger_entries,
+            tx_max_read_bytes: ledger.tx_max_read_bytes,
+            tx_max_write_ledger_entries: ledger.tx_max_write_ledger_entries,
+            tx_max_write_bytes: ledger.tx_max_write_bytes,
+            tx_max_size_bytes: bandwidth.tx_max_size_bytes,
+            tx_max_contract_events_size_bytes: events.tx_max_contract_events_size_bytes,
+            fee_per_instructions_increment: compute.fee_rate_per_instructions_increment,
+            fee_read_ledger_entry: ledger.fee_read_ledger_entry,
+            fee_write_ledger_entry: ledger.fee_write_ledger_entry,
+            fee_read_1kb: ledger.fee_read1_kb,
+            fee_tx_size_1kb: bandwidth.fee_tx_size1_kb,
+            fee_contract_events_1kb: events.fee_contract_events1_kb,
+        })
+    }
+
+    /// Break down the fee of a prepared Soroban transaction
+    ///
+    /// The bandwidth component counts the envelope as given; each signature added to an
+    /// unsigned envelope makes it 72 bytes bigger.
+    pub fn explain_fee(&self, envelope: &TransactionEnvelope) -> Result<FeeBreakdown> {
+        let TransactionEnvelope::Tx(v1) = envelope else {
+            anyhow::bail!("Only v1 transaction envelopes can be explained");
+        };
+        let TransactionExt::V1(soroban_data) = &v1.tx.ext else {
+            anyhow::bail!("Transaction has no Soroban data; it isn't a contract transaction");
+        };
+
+        let resources = &soroban_data.resources;
+        let read_entries =
+            resources.footprint.read_only.len() + resources.footprint.read_write.len();
+        let write_entries = resources.footprint.read_write.len();
+        let size = envelope.to_xdr(Limits::none())?.len();
+
+        let total = v1.tx.fee as i64;
+        let resource = soroban_data.resource_fee;
+        let cpu = resource_fee(
+            resources.instructions as i64,
+            self.fee_per_instructions_increment,
+            10_000,
+        );
+        let read = read_entries as i64 * self.fee_read_ledger_entry
+            + resource_fee(resources.read_bytes as i64, self.fee_read_1kb, 1024);
+        let write = write_entries as i64 * self.fee_write_ledger_entry;
+        let bandwidth = resource_fee(size as i64, self.fee_tx_size_1kb, 1024);
+
+        Ok(FeeBreakdown {
+            total: total.into(),
+            inclusion: (total - resource).into(),
+            resource: resource.into(),
+            cpu: cpu.into(),
+            read: read.into(),
+            write: write.into(),
+            bandwidth: bandwidth.into(),
+            other: (resource - cpu - read - write - bandwidth).into(),
+        })
+    }
+
+    /// Check simulated resources against the per-transaction maxima
+    ///
+    /// A transaction over any of these limits is rejected by the network, so catching it
+    /// here saves the farmer a signature and a failed submission.
+    pub fn check_resources(&self, resources: &SorobanResources) -> Result<()> {
+        let read_entries =
+            resources.footprint.read_only.len() + resources.footprint.read_write.len();
+        let write_entries = resources.footprint.read_write.len();
+
+        let checks = [
+            (
+                "CPU instructions",
+                resources.instructions as i64,
+                self.tx_max_instructions,
+            ),
+            (
+                "ledger read bytes",
+                resources.read_bytes as i64,
+                self.tx_max_read_bytes as i64,
+            ),
+            (
+                "ledger write bytes",
+                resources.write_bytes as i64,
+                self.tx_max_write_bytes as i64,
+            ),
+            (
+                "ledger entry reads",
+                read_entries as i64,
+                self.tx_max_read_ledger_entries as i64,
+            ),
+            (
+                "ledger entry writes",
+                write_entries as i64,
+                self.tx_max_write_ledger_entries as i64,
+            ),
+        ];
+
+        for (name, needed, limit) in checks {
+            if needed > limit {
+                anyhow::bail!(
+                    "Transaction needs {} {}, but the network allows at most {} per transaction",
+                    needed,
+                    name,
+                    limit
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
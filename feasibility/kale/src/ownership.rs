@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a farmer has to sign a challenge
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(300);
+
+/// Prefix of signed messages (SEP-53), so a signature can't pass for a transaction's
+const SIGNED_MESSAGE_PREFIX: &str = "Stellar Signed Message:\n";
+
+/// A challenge not yet answered
+struct Challenge {
+    message: String,
+    issued: Instant,
+}
+
+/// One-time messages a farmer signs with their account key to prove they hold it
+///
+/// Wallets sign them as SEP-53 messages (`signMessage`): an ed25519 signature over
+/// `sha256("Stellar Signed Message:\n" + message)`. A challenge is used up by the first
+/// attempt to answer it, right or wrong. Challenges live in memory, one per public key.
+#[derive(Default)]
+pub struct KeyChallenges {
+    challenges: Mutex<HashMap<String, Challenge>>,
+}
+
+impl KeyChallenges {
+    /// A new message for `public_key` to sign, replacing any earlier one
+    pub fn issue(&self, public_key: &str, purpose: &str) -> Result<String> {
+        let mut nonce = [0u8; 16];
+        getrandom::getrandom(&mut nonce).context("Failed to generate a challenge")?;
+        let message = format!(
+            "galacticplayground {} for {}: {}",
+            purpose,
+            public_key,
+            hex::encode(nonce)
+        );
+
+        let mut challenges = self.challenges.lock().unwrap();
+        challenges.retain(|_, challenge| challenge.issued.elapsed() < CHALLENGE_TTL);
+        challenges.insert(
+            public_key.to_string(),
+            Challenge {
+                message: message.clone(),
+                issued: Instant::now(),
+            },
+        );
+        Ok(message)
+    }
+
+    /// Check `signature` (hex) over the challenge last issued to `public_key`
+    pub fn verify(&self, public_key: &str, signature: &str) -> Result<()> {
+        let challenge = self
+            .challenges
+            .lock()
+            .unwrap()
+            .remove(public_key)
+            .filter(|challenge| challenge.issued.elapsed() < CHALLENGE_TTL)
+            .context("No challenge pending for this public key; request a new one")?;
+        verify_message(public_key, &challenge.message, signature)
+    }
+}
+
+/// Check a SEP-53 signature (hex) of `message` by the account `public_key`
+pub fn verify_message(public_key: &str, message: &str, signature: &str) -> Result<()> {
+    let key = stellar_strkey::ed25519::PublicKey::from_string(public_key)
+        .context("publicKey must be a G... account")?;
+    let key = VerifyingKey::from_bytes(&key.0).context("publicKey is not a valid ed25519 key")?;
+    let signature = hex::decode(signature).context("signature must be hex")?;
+    let signature = Signature::from_slice(&signature).context("signature must be 64 bytes")?;
+
+    let hash = Sha256::digest(format!("{}{}", SIGNED_MESSAGE_PREFIX, message));
+    key.verify_strict(&hash, &signature)
+        .context("signature doesn't match the challenge and public key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_challenge_proves_key_ownership() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key =
+            stellar_strkey::ed25519::PublicKey(key.verifying_key().to_bytes()).to_string();
+        let sign = |message: &str| {
+            let hash = Sha256::digest(format!("{}{}", SIGNED_MESSAGE_PREFIX, message));
+            hex::encode(key.sign(&hash).to_bytes())
+        };
+
+        let challenges = KeyChallenges::default();
+        let message = challenges.issue(&public_key, "preferences").unwrap();
+        assert!(message.contains(&public_key));
+        challenges.verify(&public_key, &sign(&message)).unwrap();
+        // Used up by the first answer
+        assert!(challenges.verify(&public_key, &sign(&message)).is_err());
+
+        // A signature over anything else, or by another key, fails and uses it up too
+        let message = challenges.issue(&public_key, "preferences").unwrap();
+        assert!(challenges.verify(&public_key, &sign("other")).is_err());
+        assert!(challenges.verify(&public_key, &sign(&message)).is_err());
+        let other = SigningKey::from_bytes(&[8u8; 32]);
+        let other_key =
+            stellar_strkey::ed25519::PublicKey(other.verifying_key().to_bytes()).to_string();
+        let message = challenges.issue(&other_key, "preferences").unwrap();
+        assert!(challenges.verify(&other_key, &sign(&message)).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use stellar_xdr::curr::{
+    Hash, Limits, OperationBody, ReadXdr, ScAddress, ScBytes, ScMap, ScMapEntry, ScSymbol, ScVal,
+    ScVec, SorobanCredentials, TransactionEnvelope, TransactionV1Envelope, WriteXdr,
+};
+
+use crate::signer::authorization_payload;
+
+/// Order of the P-256 (secp256r1) curve, big-endian
+const P256_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+
+/// A WebAuthn assertion from `navigator.credentials.get`, made over an authorization challenge
+pub struct PasskeyAssertion {
+    /// The credential (passkey) ID, as the smart wallet registered it as a signer
+    pub key_id: Vec<u8>,
+    pub authenticator_data: Vec<u8>,
+    pub client_data_json: Vec<u8>,
+    /// ASN.1 DER encoded ECDSA signature, as WebAuthn returns it
+    pub signature: Vec<u8>,
+}
+
+/// The WebAuthn challenge for a smart wallet's authorization in a prepared transaction
+///
+/// The passkey signs it as-is (the browser puts it base64url encoded into the client data),
+/// and the wallet's `__check_auth` compares it with the authorization payload. Transactions
+/// for contract farmers are prepared with a fee source, which sets the expiration ledger
+/// the payload covers.
+pub fn auth_challenge(tx_xdr: &str, wallet: &str, network_passphrase: &str) -> Result<[u8; 32]> {
+    let (envelope, operation, entry) = wallet_authorization(tx_xdr, wallet)?;
+    let OperationBody::InvokeHostFunction(invoke_op) = &envelope.tx.operations[operation].body
+    else {
+        unreachable!("wallet_authorization only finds invocations");
+    };
+    let entry = &invoke_op.auth[entry];
+    let SorobanCredentials::Address(credentials) = &entry.credentials else {
+        unreachable!("wallet_authorization only finds address credentials");
+    };
+
+    authorization_payload(credentials, &entry.root_invocation, network_passphrase)
+}
+
+/// Put a passkey assertion into the smart wallet's authorization, returning the updated
+/// (still unsigned) XDR
+///
+/// The signature is encoded the way passkey-kit smart wallets expect it:
+/// `Signatures({SignerKey::Secp256r1(key_id): Signature::Secp256r1({authenticator_data,
+/// client_data_json, signature})})`. The fee source signs the envelope afterwards.
+pub fn attach_assertion(
+    tx_xdr: &str,
+    wallet: &str,
+    assertion: &PasskeyAssertion,
+) -> Result<String> {
+    let signature = compact_signature(&assertion.signature)?;
+    let (mut envelope, operation, entry) = wallet_authorization(tx_xdr, wallet)?;
+
+    let secp256r1 = |value: ScVal| -> Result<ScVal> {
+        Ok(ScVal::Vec(Some(ScVec(
+            vec![ScVal::Symbol(ScSymbol("Secp256r1".try_into()?)), value].try_into()?,
+        ))))
+    };
+    let bytes = |bytes: &[u8]| -> Result<ScVal> { Ok(ScVal::Bytes(ScBytes(bytes.try_into()?))) };
+    let field = |name: &str, val: ScVal| -> Result<ScMapEntry> {
+        Ok(ScMapEntry {
+            key: ScVal::Symbol(ScSymbol(name.try_into()?)),
+            val,
+        })
+    };
+
+    // Struct fields and map keys are sorted, as the contract's host encodes them
+    let secp256r1_signature = ScVal::Map(Some(ScMap(
+        vec![
+            field("authenticator_data", bytes(&assertion.authenticator_data)?)?,
+            field("client_data_json", bytes(&assertion.client_data_json)?)?,
+            field("signature", bytes(&signature)?)?,
+        ]
+        .try_into()?,
+    )));
+    let signatures = ScVal::Map(Some(ScMap(
+        vec![ScMapEntry {
+            key: secp256r1(bytes(&assertion.key_id)?)?,
+            val: secp256r1(secp256r1_signature)?,
+        }]
+        .try_into()?,
+    )));
+
+    let mut operations = envelope.tx.operations.to_vec();
+    let OperationBody::InvokeHostFunction(invoke_op) = &mut operations[operation].body else {
+        unreachable!("wallet_authorization only finds invocations");
+    };
+    let mut auth = invoke_op.auth.to_vec();
+    if let SorobanCredentials::Address(credentials) = &mut auth[entry].credentials {
+        credentials.signature = ScVal::Vec(Some(ScVec(vec![signatures].try_into()?)));
+    }
+    invoke_op.auth = auth.try_into()?;
+    envelope.tx.operations = operations.try_into()?;
+
+    Ok(TransactionEnvelope::Tx(envelope).to_xdr_base64(Limits::none())?)
+}
+
+/// Decode a prepared transaction and find the one authorization `wallet` (C...) gives in it,
+/// as (operation index, authorization index)
+fn wallet_authorization(
+    tx_xdr: &str,
+    wallet: &str,
+) -> Result<(TransactionV1Envelope, usize, usize)> {
+    let contract = stellar_strkey::Contract::from_string(wallet)
+        .map_err(|_| anyhow::anyhow!("Invalid smart wallet address {}; expected C...", wallet))?;
+    let address = ScAddress::Contract(Hash(contract.0));
+
+    let envelope = TransactionEnvelope::from_xdr_base64(tx_xdr, Limits::none())
+        .context("Invalid transaction XDR")?;
+    let TransactionEnvelope::Tx(envelope) = envelope else {
+        anyhow::bail!("Only v1 transaction envelopes can be signed");
+    };
+
+    let mut found = Vec::new();
+    for (operation_index, operation) in envelope.tx.operations.iter().enumerate() {
+        let OperationBody::InvokeHostFunction(invoke_op) = &operation.body else {
+            continue;
+        };
+        for (entry_index, entry) in invoke_op.auth.iter().enumerate() {
+            if let SorobanCredentials::Address(credentials) = &entry.credentials {
+                if credentials.address == address {
+                    found.push((operation_index, entry_index));
+                }
+            }
+        }
+    }
+
+    match found[..] {
+        [(operation, entry)] => Ok((envelope, operation, entry)),
+        [] => anyhow::bail!("Transaction has no authorizations for {}", wallet),
+        _ => anyhow::bail!(
+            "Transaction asks {} for {} authorizations; a passkey signs one at a time",
+            wallet,
+            found.len()
+        ),
+    }
+}
+
+/// Convert a DER encoded ECDSA signature to the 64-byte `r || s` form Soroban verifies,
+/// with `s` in the lower half of the curve order (the host rejects high `s`)
+pub fn compact_signature(der: &[u8]) -> Result<[u8; 64]> {
+    let invalid = || anyhow::anyhow!("Invalid DER signature");
+
+    // SEQUENCE { INTEGER r, INTEGER s }, each integer at most 33 bytes (a sign byte)
+    let body = match der {
+        [0x30, length, body @ ..] if usize::from(*length) == body.len() => body,
+        _ => return Err(invalid()),
+    };
+    let integer = |input: &[u8]| -> Result<([u8; 32], usize)> {
+        let [0x02, length, rest @ ..] = input else {
+            return Err(invalid());
+        };
+        let length = usize::from(*length);
+        let value = rest.get(..length).ok_or_else(invalid)?;
+        let start = value
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(value.len());
+        let value = &value[start..];
+        if value.len() > 32 {
+            return Err(invalid());
+        }
+        let mut padded = [0u8; 32];
+        padded[32 - value.len()..].copy_from_slice(value);
+        Ok((padded, 2 + length))
+    };
+    let (r, r_length) = integer(body)?;
+    let (mut s, s_length) = integer(&body[r_length..])?;
+    if r_length + s_length != body.len() {
+        return Err(invalid());
+    }
+
+    let mut half_order = [0u8; 32];
+    let mut carry = 0;
+    for (half, byte) in half_order.iter_mut().zip(P256_ORDER) {
+        *half = (carry << 7) | (byte >> 1);
+        carry = byte & 1;
+    }
+    if s > half_order {
+        // s = n - s
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let difference = i16::from(P256_ORDER[i]) - i16::from(s[i]) - borrow;
+            borrow = i16::from(difference < 0);
+            s[i] = difference.rem_euclid(256) as u8;
+        }
+    }
+
+    let mut compact = [0u8; 64];
+    compact[..32].copy_from_slice(&r);
+    compact[32..].copy_from_slice(&s);
+    Ok(compact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_signature_normalizes_high_s() {
+        // r with a sign byte, s = n - 1 (high, becomes 1)
+        let mut der = vec![0x30, 0x46, 0x02, 0x21, 0x00];
+        der.extend([0x80; 32]);
+        der.extend([0x02, 0x21, 0x00]);
+        let mut high_s = P256_ORDER;
+        high_s[31] -= 1;
+        der.extend(high_s);
+
+        let compact = compact_signature(&der).unwrap();
+        assert_eq!(compact[..32], [0x80; 32]);
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(compact[32..], one);
+
+        // Short integers are left-padded, and low s is kept
+        let compact = compact_signature(&[0x30, 0x06, 0x02, 0x01, 0x05, 0x02, 0x01, 0x07]).unwrap();
+        assert_eq!((compact[31], compact[63]), (5, 7));
+        assert!(compact_signature(&[0x30, 0x06, 0x02, 0x01, 0x05]).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Background work an operator can pause without stopping the process, e.g. while
+/// debugging against a rate-limited RPC node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Subsystem {
+    /// The block poller; while it's paused nothing downstream of it sees new blocks
+    BlockWatcher,
+    /// The server's farm snapshots behind /ws farm events (an events scan and a pail batch
+    /// per poll)
+    Indexer,
+    /// The server's notification emails and balance alerts
+    Notifications,
+    /// The farm daemon's harvests of past blocks
+    Harvester,
+    /// The farm daemon's plant, mining and work in each new block
+    Miner,
+}
+
+impl Subsystem {
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::BlockWatcher => "blockWatcher",
+            Subsystem::Indexer => "indexer",
+            Subsystem::Notifications => "notifications",
+            Subsystem::Harvester => "harvester",
+            Subsystem::Miner => "miner",
+        }
+    }
+}
+
+impl std::str::FromStr for Subsystem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blockWatcher" => Ok(Subsystem::BlockWatcher),
+            "indexer" => Ok(Subsystem::Indexer),
+            "notifications" => Ok(Subsystem::Notifications),
+            "harvester" => Ok(Subsystem::Harvester),
+            "miner" => Ok(Subsystem::Miner),
+            _ => anyhow::bail!(
+                "Unknown subsystem {:?}: expected blockWatcher, indexer, notifications, \
+                 harvester or miner",
+                s
+            ),
+        }
+    }
+}
+
+/// Which subsystems are paused; everything runs until paused
+///
+/// Kept in memory, so a restart resumes everything.
+#[derive(Default)]
+pub struct Pauses {
+    paused: Mutex<BTreeSet<Subsystem>>,
+}
+
+impl Pauses {
+    /// Pause or resume a subsystem, returning whether that changed anything
+    pub fn set(&self, subsystem: Subsystem, paused: bool) -> bool {
+        let mut current = self.paused.lock().unwrap();
+        if paused {
+            current.insert(subsystem)
+        } else {
+            current.remove(&subsystem)
+        }
+    }
+
+    pub fn is_paused(&self, subsystem: Subsystem) -> bool {
+        self.paused.lock().unwrap().contains(&subsystem)
+    }
+
+    /// The paused subsystems, in a stable order
+    pub fn paused(&self) -> Vec<Subsystem> {
+        self.paused.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Pause exactly the subsystems named in `path`, one per line (`#` starts a comment);
+    /// a missing file resumes everything
+    ///
+    /// Returns the subsystems that were paused or resumed by it.
+    pub fn load_file(&self, path: &Path) -> Result<Vec<(Subsystem, bool)>> {
+        let wanted = match std::fs::read_to_string(path) {
+            Ok(contents) => contents
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or_default().trim())
+                .filter(|line| !line.is_empty())
+                .map(str::parse)
+                .collect::<Result<BTreeSet<Subsystem>>>()
+                .with_context(|| format!("Invalid pause file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read pause file {}", path.display()))
+            }
+        };
+
+        let mut paused = self.paused.lock().unwrap();
+        let changes = paused
+            .symmetric_difference(&wanted)
+            .map(|&subsystem| (subsystem, wanted.contains(&subsystem)))
+            .collect();
+        *paused = wanted;
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_file() {
+        let path = std::env::temp_dir().join(format!("kale-pause-{}", std::process::id()));
+        let pauses = Pauses::default();
+        assert!(pauses.set(Subsystem::Indexer, true));
+        assert!(!pauses.set(Subsystem::Indexer, true));
+
+        std::fs::write(
+            &path,
+            "miner\n# harvester\nblockWatcher # while the RPC recovers\n",
+        )
+        .unwrap();
+        let changes = pauses.load_file(&path).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                (Subsystem::BlockWatcher, true),
+                (Subsystem::Indexer, false),
+                (Subsystem::Miner, true)
+            ]
+        );
+        assert_eq!(
+            pauses.paused(),
+            vec![Subsystem::BlockWatcher, Subsystem::Miner]
+        );
+
+        std::fs::write(&path, "minr\n").unwrap();
+        assert!(pauses.load_file(&path).is_err());
+        assert!(pauses.is_paused(Subsystem::Miner));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(pauses.load_file(&path).unwrap().len(), 2);
+        assert!(pauses.paused().is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A worker's verified shares for a pool that farms into a single account
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkerContribution {
+    pub worker: String,
+    /// Leading hex zeros of each verified hash the worker submitted to the coordinator
+    #[serde(rename = "shareZeros")]
+    pub share_zeros: Vec<u32>,
+}
+
+/// A worker's proportional share of a reward
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Payout {
+    pub worker: String,
+    #[serde(serialize_with = "as_string")]
+    pub amount: i128, // stroops
+    /// Expected hashes represented by the worker's shares
+    #[serde(serialize_with = "as_string")]
+    pub work: u128,
+}
+
+fn as_string<T: ToString, S: serde::Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+/// Expected number of hashes needed to find a share with this many leading hex zeros
+///
+/// Each hex zero is 4 bits, so a share with `z` zeros takes 16^z hashes on average.
+/// Capped at 31 zeros to fit in a u128.
+pub fn share_work(zeros: u32) -> u128 {
+    1u128 << (4 * zeros.min(31))
+}
+
+/// Largest reward that can be split: the KALE supply fits in an i64 of stroops
+pub const MAX_TOTAL_REWARD: i128 = i64::MAX as i128;
+
+/// Split a reward between workers in proportion to their verified work
+///
+/// Each share is `total_reward * work / total_work` in integer math, and the stroops left
+/// by rounding down (fewer than the number of workers) go to the largest remainders, so the
+/// payouts always add up to exactly `total_reward`. Workers with no shares get nothing.
+/// Fails if the reward is above [`MAX_TOTAL_REWARD`] or the product overflows a u128.
+pub fn split_rewards(
+    total_reward: i128,
+    contributions: &[WorkerContribution],
+) -> Result<Vec<Payout>> {
+    anyhow::ensure!(
+        total_reward <= MAX_TOTAL_REWARD,
+        "Total reward is more than the KALE supply ({} stroops)",
+        MAX_TOTAL_REWARD
+    );
+    let works: Vec<u128> = contributions
+        .iter()
+        .map(|contribution| {
+            contribution
+                .share_zeros
+                .iter()
+                .map(|zeros| share_work(*zeros))
+                .fold(0u128, u128::saturating_add)
+        })
+        .collect();
+    let total_work = works
+        .iter()
+        .fold(0u128, |sum, work| sum.saturating_add(*work));
+
+    if total_work == 0 || total_reward <= 0 {
+        return Ok(Vec::new());
+    }
+    let reward = total_reward as u128;
+
+    // Floor of each exact share, remembering what rounding down left over
+    let mut payouts = Vec::with_capacity(contributions.len());
+    let mut remainders = Vec::with_capacity(contributions.len());
+    for (contribution, work) in contributions.iter().zip(&works) {
+        let scaled = reward
+            .checked_mul(*work)
+            .context("Total reward times the verified work is too large to split")?;
+        payouts.push(Payout {
+            worker: contribution.worker.clone(),
+            amount: (scaled / total_work) as i128,
+            work: *work,
+        });
+        remainders.push(scaled % total_work);
+    }
+
+    let distributed: i128 = payouts.iter().map(|payout| payout.amount).sum();
+    let remainder = (total_reward - distributed) as usize;
+
+    let mut by_remainder: Vec<usize> = (0..payouts.len()).collect();
+    by_remainder.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]));
+    for index in by_remainder.into_iter().take(remainder) {
+        payouts[index].amount += 1;
+    }
+
+    Ok(payouts
+        .into_iter()
+        .filter(|payout| payout.amount > 0)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_rewards() {
+        let contributions = vec![
+            WorkerContribution {
+                worker: "A".to_string(),
+                share_zeros: vec![5, 5],
+            },
+            WorkerContribution {
+                worker: "B".to_string(),
+                share_zeros: vec![6],
+            },
+            WorkerContribution {
+                worker: "C".to_string(),
+                share_zeros: vec![],
+            },
+        ];
+
+        // A did 2 * 16^5 expected hashes, B 16^6 = 8 * A's share
+        let payouts = split_rewards(1_000_000_001, &contributions).unwrap();
+        assert_eq!(payouts.len(), 2);
+        assert_eq!(
+            payouts.iter().map(|payout| payout.amount).sum::<i128>(),
+            1_000_000_001
+        );
+        assert_eq!(payouts[0].worker, "A");
+        assert_eq!(payouts[0].amount, 111_111_111);
+        assert_eq!(payouts[1].amount, 888_888_890);
+
+        assert!(split_rewards(100, &contributions[2..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_split_huge_rewards() {
+        let contributions: Vec<_> = ["A", "B", "C"]
+            .iter()
+            .map(|worker| WorkerContribution {
+                worker: worker.to_string(),
+                share_zeros: vec![7],
+            })
+            .collect();
+
+        let payouts = split_rewards(MAX_TOTAL_REWARD, &contributions).unwrap();
+        assert_eq!(
+            payouts.iter().map(|payout| payout.amount).sum::<i128>(),
+            MAX_TOTAL_REWARD
+        );
+        assert!(payouts[0].amount - payouts[2].amount <= 1);
+
+        assert!(split_rewards(MAX_TOTAL_REWARD + 1, &contributions).is_err());
+        assert!(split_rewards(10i128.pow(38), &contributions).is_err());
+        // 16^30 hashes per share times the reward doesn't fit a u128
+        let heavy = vec![WorkerContribution {
+            worker: "A".to_string(),
+            share_zeros: vec![30],
+        }];
+        assert!(split_rewards(MAX_TOTAL_REWARD, &heavy).is_err());
+    }
+}
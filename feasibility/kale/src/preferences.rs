@@ -0,0 +1,504 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Where preferences are kept unless `KALE_PREFERENCES_FILE` says otherwise
+const DEFAULT_PREFERENCES_FILE: &str = "kale-preferences.json";
+
+/// Wrong verification codes after which the address has to be set again
+const MAX_VERIFY_ATTEMPTS: u32 = 5;
+
+/// Balance alerts a farmer can keep
+const MAX_ALERTS: usize = 10;
+
+/// Longest note a farmer can attach to an alert
+const MAX_ALERT_MESSAGE_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    System,
+}
+
+/// How hard the in-browser miner works
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MiningPowerMode {
+    Low,
+    Balanced,
+    Max,
+}
+
+/// An email a farmer can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationKind {
+    /// A block the farmer worked has closed and can be harvested
+    HarvestAvailable,
+    /// A work transaction submitted through the server failed
+    WorkFailed,
+    /// Once a day, the blocks still waiting to be harvested
+    DailySummary,
+    /// At each block rollover, what the farmer did in the block that closed
+    BlockReport,
+}
+
+/// Which balance an alert watches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertBalance {
+    /// XLM above the account's reserve and open offers, i.e. what fees can be paid from
+    SpendableXlm,
+    /// The KALE trustline balance
+    Kale,
+}
+
+/// When an alert fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertCondition {
+    /// The balance drops under the threshold; a missing account or KALE trustline counts
+    Below,
+    /// The balance rises over the threshold
+    Above,
+}
+
+/// An email to send when a balance crosses a threshold, e.g. "spendable XLM below 2"
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BalanceAlert {
+    pub balance: AlertBalance,
+    pub condition: AlertCondition,
+    pub threshold: String, // stroops, as a string to avoid JSON number precision loss
+    /// The farmer's own reminder, put in the email (e.g. "sweep to cold storage")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl BalanceAlert {
+    /// The threshold in stroops; valid once [`Preferences::validate`] passed
+    pub fn threshold(&self) -> i64 {
+        self.threshold.parse().unwrap_or_default()
+    }
+
+    /// Whether a balance (None: no account or trustline) meets the alert's condition
+    pub fn is_met(&self, balance: Option<i64>) -> bool {
+        match (self.condition, balance) {
+            (AlertCondition::Below, None) => true,
+            (AlertCondition::Above, None) => false,
+            (AlertCondition::Below, Some(balance)) => balance < self.threshold(),
+            (AlertCondition::Above, Some(balance)) => balance > self.threshold(),
+        }
+    }
+}
+
+/// A farmer's UI preferences; unset fields fall back to the frontend's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preferences {
+    #[serde(
+        rename = "defaultStake",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_stake: Option<String>, // stroops, as a string to avoid JSON number precision loss
+    #[serde(
+        rename = "preferredWallet",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub preferred_wallet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    #[serde(
+        rename = "miningPowerMode",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mining_power_mode: Option<MiningPowerMode>,
+    /// Where notification emails should go; never returned, and only used once the
+    /// farmer enters the code sent there (see [`PreferenceStore::set_email`])
+    #[serde(default, skip_serializing)]
+    pub email: Option<String>,
+    /// Which emails to send; none unless set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<Vec<NotificationKind>>,
+    /// Balance thresholds to email about, checked at every watcher update
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<Vec<BalanceAlert>>,
+}
+
+impl Preferences {
+    /// Check values the types don't constrain
+    pub fn validate(&self) -> Result<()> {
+        if let Some(stake) = &self.default_stake {
+            let stake: i128 = stake
+                .parse()
+                .context("defaultStake must be an integer amount in stroops")?;
+            anyhow::ensure!(stake >= 0, "defaultStake can't be negative");
+        }
+        if let Some(wallet) = &self.preferred_wallet {
+            anyhow::ensure!(
+                !wallet.is_empty() && wallet.len() <= 64,
+                "preferredWallet must be 1 to 64 characters"
+            );
+        }
+        if let Some(email) = &self.email {
+            email
+                .parse::<lettre::Address>()
+                .context("email must be an email address")?;
+        }
+        if let Some(alerts) = &self.alerts {
+            anyhow::ensure!(
+                alerts.len() <= MAX_ALERTS,
+                "At most {} alerts can be set",
+                MAX_ALERTS
+            );
+            for alert in alerts {
+                let threshold: i64 = alert
+                    .threshold
+                    .parse()
+                    .context("alert threshold must be an integer amount in stroops")?;
+                anyhow::ensure!(threshold >= 0, "alert threshold can't be negative");
+                if let Some(message) = &alert.message {
+                    anyhow::ensure!(
+                        message.chars().count() <= MAX_ALERT_MESSAGE_LEN,
+                        "alert message must be at most {} characters",
+                        MAX_ALERT_MESSAGE_LEN
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Overwrite the fields set in `update`, keeping the rest
+    pub fn merge(&mut self, update: Preferences) {
+        if update.default_stake.is_some() {
+            self.default_stake = update.default_stake;
+        }
+        if update.preferred_wallet.is_some() {
+            self.preferred_wallet = update.preferred_wallet;
+        }
+        if update.theme.is_some() {
+            self.theme = update.theme;
+        }
+        if update.mining_power_mode.is_some() {
+            self.mining_power_mode = update.mining_power_mode;
+        }
+        if update.notifications.is_some() {
+            self.notifications = update.notifications;
+        }
+        if update.alerts.is_some() {
+            self.alerts = update.alerts;
+        }
+    }
+}
+
+/// A farmer's email address, and the code it has to be confirmed with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EmailAddress {
+    address: String,
+    /// Sent to the address; None once the farmer entered it
+    #[serde(rename = "verificationCode", default)]
+    verification_code: Option<String>,
+    #[serde(rename = "failedAttempts", default)]
+    failed_attempts: u32,
+}
+
+/// What the store keeps per farmer: the preferences and, apart from them, the email
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FarmerRecord {
+    #[serde(flatten)]
+    preferences: Preferences,
+    #[serde(
+        rename = "emailAddress",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    email: Option<EmailAddress>,
+}
+
+/// Farmers' preferences keyed by public key, persisted to a JSON file
+///
+/// The whole file is rewritten on every update; it holds a few small records per farmer.
+/// Email addresses are kept next to the preferences but never handed back, and nothing is
+/// sent to one until it's verified.
+pub struct PreferenceStore {
+    path: PathBuf,
+    preferences: Mutex<HashMap<String, FarmerRecord>>,
+}
+
+impl PreferenceStore {
+    /// Open the store at `path`, starting empty if the file doesn't exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let preferences = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid preferences file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read preferences file {}", path.display()))
+            }
+        };
+
+        Ok(Self {
+            path,
+            preferences: Mutex::new(preferences),
+        })
+    }
+
+    /// Open the store at `KALE_PREFERENCES_FILE` (default `kale-preferences.json`)
+    pub fn from_env() -> Result<Self> {
+        let path = std::env::var("KALE_PREFERENCES_FILE")
+            .unwrap_or_else(|_| DEFAULT_PREFERENCES_FILE.to_string());
+        Self::open(path)
+    }
+
+    /// A farmer's preferences, empty if they never saved any
+    pub fn get(&self, public_key: &str) -> Preferences {
+        self.preferences
+            .lock()
+            .unwrap()
+            .get(public_key)
+            .map(|record| record.preferences.clone())
+            .unwrap_or_default()
+    }
+
+    /// (public key, email) of every farmer with a verified address who asked for an
+    /// email of `kind`
+    pub fn subscribers(&self, kind: NotificationKind) -> Vec<(String, String)> {
+        self.preferences
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, record)| {
+                record
+                    .preferences
+                    .notifications
+                    .as_ref()
+                    .is_some_and(|kinds| kinds.contains(&kind))
+            })
+            .filter_map(|(public_key, record)| {
+                let email = record.email.as_ref()?;
+                email
+                    .verification_code
+                    .is_none()
+                    .then(|| (public_key.clone(), email.address.clone()))
+            })
+            .collect()
+    }
+
+    /// (public key, email, alerts) of every farmer with a verified address and alerts set
+    pub fn alert_subscribers(&self) -> Vec<(String, String, Vec<BalanceAlert>)> {
+        self.preferences
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(public_key, record)| {
+                let alerts = record.preferences.alerts.as_ref()?;
+                let email = record.email.as_ref()?;
+                (!alerts.is_empty() && email.verification_code.is_none())
+                    .then(|| (public_key.clone(), email.address.clone(), alerts.clone()))
+            })
+            .collect()
+    }
+
+    /// Set a farmer's email address, unverified, and return the code to send to it
+    ///
+    /// Until [`Self::verify_email`] gets the code, no notification goes to the new address
+    /// (nor to the old one).
+    pub fn set_email(&self, public_key: &str, address: &str) -> Result<String> {
+        let mut code = [0u8; 4];
+        getrandom::getrandom(&mut code).context("Failed to generate a verification code")?;
+        let code = format!("{:06}", u32::from_le_bytes(code) % 1_000_000);
+
+        self.modify(public_key, |record| {
+            record.email = Some(EmailAddress {
+                address: address.to_string(),
+                verification_code: Some(code.clone()),
+                failed_attempts: 0,
+            });
+        })?;
+        Ok(code)
+    }
+
+    /// Confirm a farmer's address with the code sent to it
+    ///
+    /// Returns whether the code matched. After [`MAX_VERIFY_ATTEMPTS`] wrong codes the
+    /// address is dropped and has to be set again.
+    pub fn verify_email(&self, public_key: &str, code: &str) -> Result<bool> {
+        let mut verified = false;
+        self.modify(public_key, |record| {
+            let Some(email) = &mut record.email else {
+                return;
+            };
+            match email.verification_code.as_deref() {
+                None => verified = true,
+                Some(expected) if expected == code => {
+                    email.verification_code = None;
+                    verified = true;
+                }
+                Some(_) => {
+                    email.failed_attempts += 1;
+                    if email.failed_attempts >= MAX_VERIFY_ATTEMPTS {
+                        record.email = None;
+                    }
+                }
+            }
+        })?;
+        Ok(verified)
+    }
+
+    /// Merge an update into a farmer's preferences and save the file
+    ///
+    /// Returns the preferences after the update.
+    /// The `email` field is left alone; it is set with [`Self::set_email`].
+    pub fn update(&self, public_key: &str, update: Preferences) -> Result<Preferences> {
+        self.modify(public_key, |record| record.preferences.merge(update))
+            .map(|record| record.preferences)
+    }
+
+    /// Change a farmer's record and save the file, returning the record after the change
+    fn modify(
+        &self,
+        public_key: &str,
+        change: impl FnOnce(&mut FarmerRecord),
+    ) -> Result<FarmerRecord> {
+        let mut preferences = self.preferences.lock().unwrap();
+        let mut updated = preferences.get(public_key).cloned().unwrap_or_default();
+        change(&mut updated);
+
+        let mut next = preferences.clone();
+        next.insert(public_key.to_string(), updated.clone());
+        self.save(&next)?;
+        *preferences = next;
+
+        Ok(updated)
+    }
+
+    /// Write to a temporary file and rename it over the old one, so a crash mid-write
+    /// can't leave a truncated file behind
+    fn save(&self, preferences: &HashMap<String, FarmerRecord>) -> Result<()> {
+        let contents = serde_json::to_string_pretty(preferences)?;
+        let temp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&temp_path, contents)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_merges_and_persists() {
+        let path =
+            std::env::temp_dir().join(format!("kale-preferences-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let store = PreferenceStore::open(&path).unwrap();
+        assert_eq!(store.get("GA"), Preferences::default());
+
+        store
+            .update(
+                "GA",
+                Preferences {
+                    default_stake: Some("1000".to_string()),
+                    theme: Some(Theme::Dark),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let updated = store
+            .update(
+                "GA",
+                Preferences {
+                    theme: Some(Theme::Light),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.default_stake.as_deref(), Some("1000"));
+        assert_eq!(updated.theme, Some(Theme::Light));
+
+        let reopened = PreferenceStore::open(&path).unwrap();
+        assert_eq!(reopened.get("GA"), updated);
+
+        // Emails go only to farmers with a verified address who asked for that kind
+        let notifications = Preferences {
+            email: Some("farmer@example.com".to_string()),
+            notifications: Some(vec![NotificationKind::WorkFailed]),
+            ..Default::default()
+        };
+        assert!(notifications.validate().is_ok());
+        reopened.update("GB", notifications).unwrap();
+        assert!(reopened
+            .subscribers(NotificationKind::WorkFailed)
+            .is_empty());
+        let code = reopened.set_email("GB", "farmer@example.com").unwrap();
+        assert!(reopened
+            .subscribers(NotificationKind::WorkFailed)
+            .is_empty());
+        assert!(!reopened.verify_email("GB", "wrong").unwrap());
+        assert!(reopened.verify_email("GB", &code).unwrap());
+        assert_eq!(
+            reopened.subscribers(NotificationKind::WorkFailed),
+            vec![("GB".to_string(), "farmer@example.com".to_string())]
+        );
+        // The address is kept across restarts but never returned
+        let reopened = PreferenceStore::open(&path).unwrap();
+        assert_eq!(reopened.subscribers(NotificationKind::WorkFailed).len(), 1);
+        assert_eq!(reopened.get("GB").email, None);
+        assert!(!serde_json::to_string(&reopened.get("GB"))
+            .unwrap()
+            .contains("example.com"));
+        assert!(reopened
+            .subscribers(NotificationKind::DailySummary)
+            .is_empty());
+        let invalid = Preferences {
+            email: Some("not an address".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+
+        // Alerts go to the same verified address
+        let alert = BalanceAlert {
+            balance: AlertBalance::SpendableXlm,
+            condition: AlertCondition::Below,
+            threshold: "20000000".to_string(),
+            message: None,
+        };
+        let alerts = Preferences {
+            alerts: Some(vec![alert.clone()]),
+            ..Default::default()
+        };
+        assert!(alerts.validate().is_ok());
+        reopened.update("GB", alerts.clone()).unwrap();
+        reopened.update("GC", alerts).unwrap();
+        assert_eq!(
+            reopened.alert_subscribers(),
+            vec![(
+                "GB".to_string(),
+                "farmer@example.com".to_string(),
+                vec![alert.clone()]
+            )]
+        );
+        let invalid = Preferences {
+            alerts: Some(vec![BalanceAlert {
+                threshold: "-1".to_string(),
+                ..alert
+            }]),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
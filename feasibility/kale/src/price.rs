@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a fetched price is reused before asking the source again
+const PRICE_TTL: Duration = Duration::from_secs(60);
+
+/// An HTTP price API returning JSON, and where in the response the KALE/USD price is
+#[derive(Debug, Clone)]
+pub struct PriceSource {
+    pub url: String,
+    /// JSON pointer to the price, e.g. "/kale/usd"; the value may be a number or a string
+    pub pointer: String,
+}
+
+impl PriceSource {
+    /// Load the source from `KALE_PRICE_URL` and `KALE_PRICE_POINTER`
+    ///
+    /// Returns None if no price URL is configured.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(url) = std::env::var("KALE_PRICE_URL") else {
+            return Ok(None);
+        };
+        let pointer = std::env::var("KALE_PRICE_POINTER")
+            .context("KALE_PRICE_POINTER is required when KALE_PRICE_URL is set")?;
+
+        Ok(Some(Self { url, pointer }))
+    }
+}
+
+/// A KALE price quote
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Price {
+    #[serde(rename = "kaleUsd")]
+    pub kale_usd: f64,
+    #[serde(rename = "fetchedAt")]
+    pub fetched_at: u64, // unix seconds
+}
+
+impl Price {
+    /// USD value of an amount of KALE in stroops
+    pub fn usd_value(&self, stroops: i128) -> f64 {
+        stroops as f64 / 10_000_000.0 * self.kale_usd
+    }
+}
+
+/// Fetches and caches the KALE/USD price from the configured source
+pub struct PriceFeed {
+    source: Option<PriceSource>,
+    http: reqwest::Client,
+    cached: Mutex<Option<(Instant, Price)>>,
+}
+
+impl PriceFeed {
+    pub fn new(source: Option<PriceSource>) -> Self {
+        Self {
+            source,
+            http: reqwest::Client::new(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Get the current price, or None if no price source is configured
+    pub async fn current(&self) -> Result<Option<Price>> {
+        let Some(source) = &self.source else {
+            return Ok(None);
+        };
+
+        if let Some((fetched, price)) = *self.cached.lock().unwrap() {
+            if fetched.elapsed() < PRICE_TTL {
+                return Ok(Some(price));
+            }
+        }
+
+        let response: serde_json::Value = self
+            .http
+            .get(&source.url)
+            .send()
+            .await
+            .context("Failed to reach price source")?
+            .error_for_status()
+            .context("Price source returned an error")?
+            .json()
+            .await
+            .context("Price source returned invalid JSON")?;
+
+        let kale_usd = match response.pointer(&source.pointer) {
+            Some(serde_json::Value::Number(number)) => number.as_f64(),
+            Some(serde_json::Value::String(string)) => string.parse().ok(),
+            _ => None,
+        }
+        .filter(|price| price.is_finite() && *price >= 0.0)
+        .with_context(|| format!("No price at '{}' in price source response", source.pointer))?;
+
+        let price = Price {
+            kale_usd,
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+        };
+        *self.cached.lock().unwrap() = Some((Instant::now(), price));
+
+        Ok(Some(price))
+    }
+}
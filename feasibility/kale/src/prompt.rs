@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use dialoguer::{Confirm, Input, Password, Select};
+use std::io::IsTerminal;
+
+use galacticplayground::config::Network;
+
+const NETWORKS: [Network; 4] = [
+    Network::Testnet,
+    Network::Mainnet,
+    Network::Futurenet,
+    Network::Local,
+];
+
+/// Whether someone is at the terminal to answer prompts
+///
+/// Scripts and CI (no TTY) get an error for a missing argument instead of a hang.
+pub fn interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Ask which network to farm on, defaulting to testnet
+pub fn network() -> Result<Network> {
+    let names: Vec<&str> = NETWORKS.iter().map(|network| network.name()).collect();
+    let choice = Select::new()
+        .with_prompt("Network (set --network or KALE_NETWORK to skip this)")
+        .items(&names)
+        .default(0)
+        .interact()
+        .context("No network chosen")?;
+    Ok(NETWORKS[choice])
+}
+
+/// Ask for a secret seed without echoing it
+pub fn secret_key(what: &str) -> Result<String> {
+    Password::new()
+        .with_prompt(format!("{} secret seed (S...)", what))
+        .interact()
+        .context("No secret seed entered")
+}
+
+/// Ask for a stake in stroops
+pub fn stake() -> Result<i128> {
+    Input::new()
+        .with_prompt("Stake in stroops (1 KALE = 10000000; 0 to plant without staking)")
+        .default(0)
+        .validate_with(|amount: &i128| {
+            if *amount < 0 {
+                Err("The stake can't be negative")
+            } else {
+                Ok(())
+            }
+        })
+        .interact_text()
+        .context("No stake entered")
+}
+
+/// Ask a yes/no question, defaulting to no
+pub fn confirm(question: &str) -> Result<bool> {
+    Confirm::new()
+        .with_prompt(question)
+        .default(false)
+        .interact()
+        .context("No answer given")
+}
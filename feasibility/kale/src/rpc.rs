@@ -1,16 +1,736 @@
-use anyhow::{Context, Result};
-use stellar_rpc_client::{Client, LedgerEntryResult, SimulateTransactionResponse};
+use anyhow::Context;
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use stellar_rpc_client::{
+    Client, EventStart, EventType, GetFeeStatsResponse, GetNetworkResponse, GetTransactionResponse,
+    LedgerEntryResult, RestorePreamble, SimulateTransactionResponse,
+};
 use stellar_strkey::{Contract, Strkey};
 use stellar_xdr::curr::{
-    Hash, Limits, MuxedAccount, Operation, OperationBody, Preconditions, PublicKey, ReadXdr,
-    ScAddress, ScVal, SequenceNumber, Transaction, TransactionEnvelope, TransactionExt, Uint256,
+    ConfigSettingEntry, ConfigSettingId, FeeBumpTransaction, FeeBumpTransactionEnvelope,
+    FeeBumpTransactionExt, FeeBumpTransactionInnerTx, Hash, LedgerKey, LedgerKeyConfigSetting,
+    Limits, MuxedAccount, Operation, OperationBody, Preconditions, PublicKey, ReadXdr, ScAddress,
+    ScMap, ScVal, SequenceNumber, SorobanCredentials, Transaction, TransactionEnvelope,
+    TransactionExt, Uint256, WriteXdr,
 };
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::diagnostics::describe_value;
+use crate::endpoint::RpcEndpoint;
+use crate::scval_json::to_json;
+use crate::sequence::{SequenceContention, SequenceManager};
+
+type Result<T> = std::result::Result<T, RpcError>;
+
+/// Errors from the Soroban RPC client
+#[derive(Debug, Error)]
+pub enum RpcError {
+    /// The RPC node couldn't be reached, or it rejected the request
+    #[error("Failed to {action}: {source}")]
+    Transport {
+        action: &'static str,
+        #[source]
+        source: stellar_rpc_client::Error,
+    },
+    /// The endpoint URL or its credentials are malformed (reported redacted)
+    #[error("Invalid RPC endpoint {0}")]
+    InvalidEndpoint(String),
+    /// The endpoint serves a different network than the configured passphrase names
+    #[error(
+        "RPC endpoint {endpoint} is on network \"{actual}\", but the configured passphrase is \"{expected}\""
+    )]
+    NetworkMismatch {
+        endpoint: String,
+        expected: String,
+        actual: String,
+    },
+    /// A Stellar address that doesn't parse, or isn't the expected kind of key
+    #[error("Invalid {what}: {value}")]
+    BadStrkey { what: &'static str, value: String },
+    /// A transaction envelope from the caller that can't be decoded or isn't supported
+    #[error("Invalid transaction XDR: {0}")]
+    BadTransaction(String),
+    /// A transaction hash that isn't 32 bytes of hex
+    #[error("Invalid transaction hash: {0}")]
+    BadHash(String),
+    /// Arguments refused before anything is sent to the network
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("{0} not found")]
+    EntryNotFound(String),
+    /// The simulation response has nothing to assemble the transaction from
+    #[error("Transaction simulation failed: {0}")]
+    SimulationFailed(String),
+    #[error("Unexpected ledger entry: {0}")]
+    UnexpectedEntry(String),
+    /// The batched request that should have read the entry failed, so its state is unknown
+    #[error("Ledger entry unavailable: {0}")]
+    EntryUnavailable(String),
+    /// A watched transaction was still unknown to the network after every rebroadcast
+    #[error(
+        "Transaction {hash} was not found {after_secs}s after its last send ({rebroadcasts} rebroadcasts)"
+    )]
+    TransactionDropped {
+        hash: String,
+        after_secs: u64,
+        rebroadcasts: u32,
+    },
+    /// The simulated resource fee plus the inclusion fee doesn't fit a transaction's u32 fee
+    #[error(
+        "Transaction fee overflows: resource fee {resource_fee} + inclusion fee {inclusion_fee} stroops"
+    )]
+    FeeOverflow {
+        resource_fee: u64,
+        inclusion_fee: u32,
+    },
+    /// Other prepared transactions hold every sequence number the account may take
+    #[error(
+        "Sequence number {} of {} and the ones after it are held by other prepared transactions; submit or abandon them, or retry in {}s",
+        .0.sequence, .0.account, .0.retry_after_secs
+    )]
+    SequenceContention(SequenceContention),
+    #[error("XDR error: {0}")]
+    Xdr(#[from] stellar_xdr::curr::Error),
+}
+
+/// How long ledger entries read by [`SorobanRpc::get_ledger_entry`] and
+/// [`SorobanRpc::get_contract_instance`] are reused
+///
+/// UI sessions poll the contract instance and block for every API call; within a ledger or
+/// two they can't have changed much. The cache is also cleared when this client sees one of
+/// its transactions succeed, and [`crate::contracts::kale::Kale`] clears it when the farm
+/// moves to a new block.
+#[derive(Debug, Clone, Copy)]
+pub struct LedgerCacheOptions {
+    /// Zero disables the cache
+    pub ttl: Duration,
+}
+
+impl Default for LedgerCacheOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(2),
+        }
+    }
+}
+
+impl LedgerCacheOptions {
+    /// Load the options from `KALE_LEDGER_CACHE_MS`, using the default if unset
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        if let Ok(value) = std::env::var("KALE_LEDGER_CACHE_MS") {
+            options.ttl = Duration::from_millis(
+                value
+                    .parse()
+                    .context("KALE_LEDGER_CACHE_MS must be a number of milliseconds")?,
+            );
+        }
+
+        Ok(options)
+    }
+}
+
+/// Simulation cache key: (source account, function name, args XDR, latest ledger)
+type SimulationCacheKey = (String, String, String, u32);
+
+/// How long a simulation is kept, bounding the cache's memory; it is only reused within
+/// the ledger it was simulated on
+const SIMULATION_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Most keys a single getLedgerEntries request may carry
+const MAX_LEDGER_KEYS_PER_REQUEST: usize = 200;
+
+/// Most getLedgerEntries requests of one batch in flight at once
+const LEDGER_ENTRY_REQUEST_CONCURRENCY: usize = 4;
+
+/// Most events requested per getEvents page
+const EVENTS_PAGE_LIMIT: usize = 1000;
+
+/// How often a watched submission is polled with getTransaction
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The network's minimum inclusion fee per operation, in stroops
+const BASE_FEE: u32 = 100;
+
+/// Fee stats only change once a ledger closes
+const FEE_STATS_TTL: Duration = Duration::from_secs(5);
+
+/// XLM an account must keep per entry it owns (and twice for itself), in stroops: the
+/// base reserve of pubnet and testnet
+const BASE_RESERVE: i64 = 5_000_000;
+
+/// Percentiles getFeeStats reports
+const FEE_PERCENTILES: [u8; 11] = [10, 20, 30, 40, 50, 60, 70, 80, 90, 95, 99];
+
+/// An event emitted by the contract, with its topics and value decoded
+#[derive(Debug, Clone)]
+pub struct ContractEvent {
+    pub ledger: u32,
+    pub topics: Vec<ScVal>,
+    pub value: ScVal,
+}
+
+/// How long submit calls wait for a transaction to reach a ledger
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmationOptions {
+    /// Give up waiting (and report PENDING) after this long
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for ConfirmationOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ConfirmationOptions {
+    /// Load the options from `KALE_CONFIRM_TIMEOUT_SECS` and `KALE_CONFIRM_POLL_MS`,
+    /// using the defaults for unset values
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        if let Ok(value) = std::env::var("KALE_CONFIRM_TIMEOUT_SECS") {
+            options.timeout = Duration::from_secs(
+                value
+                    .parse()
+                    .context("KALE_CONFIRM_TIMEOUT_SECS must be a number of seconds")?,
+            );
+        }
+        if let Ok(value) = std::env::var("KALE_CONFIRM_POLL_MS") {
+            options.poll_interval = Duration::from_millis(
+                value
+                    .parse()
+                    .context("KALE_CONFIRM_POLL_MS must be a number of milliseconds")?,
+            );
+        }
+
+        Ok(options)
+    }
+}
+
+/// Which inclusion fee prepared Soroban transactions bid
+#[derive(Debug, Clone, Copy)]
+pub struct InclusionFeeOptions {
+    /// Percentile of recent Soroban inclusion fees to pay (10, 20, ..., 90, 95 or 99);
+    /// never less than the base fee
+    pub percentile: u8,
+}
+
+impl Default for InclusionFeeOptions {
+    fn default() -> Self {
+        Self { percentile: 50 }
+    }
+}
+
+impl InclusionFeeOptions {
+    /// Load the options from `KALE_INCLUSION_FEE_PERCENTILE`, using the default if unset
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        if let Ok(value) = std::env::var("KALE_INCLUSION_FEE_PERCENTILE") {
+            options.percentile = value
+                .parse()
+                .ok()
+                .filter(|percentile| FEE_PERCENTILES.contains(percentile))
+                .context("KALE_INCLUSION_FEE_PERCENTILE must be 10, 20, ..., 90, 95 or 99")?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// How much more inclusion fee each retry of a transaction bids
+///
+/// Set per deployment in `kale.toml` (`fee_escalation_percent`, `max_inclusion_fee`) or the
+/// matching `KALE_*` variables, so a mainnet operator states their fee tolerance once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEscalation {
+    /// Added to the inclusion fee per retry, compounding: 20 bids 100, 120, 144, ... stroops;
+    /// 0 bids the same fee every time
+    pub percent_per_retry: u32,
+    /// Most inclusion fee a retry bids, in stroops; a first bid above it is kept as is
+    pub max_inclusion_fee: u32,
+}
+
+impl Default for FeeEscalation {
+    fn default() -> Self {
+        Self {
+            percent_per_retry: 0,
+            max_inclusion_fee: u32::MAX,
+        }
+    }
+}
+
+impl FeeEscalation {
+    /// The inclusion fee for retry `retry` (0 is the first try) of a transaction that
+    /// first bid `fee`
+    pub fn inclusion_fee(&self, fee: u32, retry: u32) -> u32 {
+        let cap = u64::from(self.max_inclusion_fee.max(fee));
+        let mut escalated = u64::from(fee);
+        for _ in 0..retry {
+            if escalated >= cap {
+                break;
+            }
+            escalated = escalated * (100 + u64::from(self.percent_per_retry)) / 100;
+        }
+        escalated.min(cap) as u32
+    }
+}
+
+/// How long calls stay on a fallback RPC endpoint before the primary is tried again
+pub const PRIMARY_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How RPC calls are retried when the node is briefly unreachable or overloaded
+///
+/// Only transient failures are retried (see [`is_transient_failure`]); a request the node
+/// rejects fails straight away.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Tries per call, including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubling for each one after
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(4),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Load the policy from `KALE_RPC_RETRY_ATTEMPTS` and `KALE_RPC_RETRY_BASE_MS`, using
+    /// the defaults for unset values
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(value) = std::env::var("KALE_RPC_RETRY_ATTEMPTS") {
+            policy.max_attempts = value
+                .parse()
+                .ok()
+                .filter(|attempts| *attempts >= 1)
+                .context("KALE_RPC_RETRY_ATTEMPTS must be a positive integer")?;
+        }
+        if let Ok(value) = std::env::var("KALE_RPC_RETRY_BASE_MS") {
+            policy.base_delay = Duration::from_millis(
+                value
+                    .parse()
+                    .context("KALE_RPC_RETRY_BASE_MS must be a number of milliseconds")?,
+            );
+        }
+
+        Ok(policy)
+    }
+
+    /// How long to wait before retry number `retry` (from 1): exponential, capped, and
+    /// jittered to between half and all of it so clients that failed together spread out
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_delay);
+        // Sub-second clock noise is random enough to spread retries
+        let noise = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or(0);
+        let half = backoff / 2;
+        half + half.mul_f64(f64::from(noise % 1000) / 1000.0)
+    }
+}
+
+/// Whether a failed RPC call is worth retrying, from the client error's message
+///
+/// The node being unreachable, timing out or shedding load (429, 502, 503, 504) is
+/// transient; anything else, such as a malformed request or an unknown account, is not.
+pub fn is_transient_failure(message: &str) -> bool {
+    const TRANSIENT: [&str; 13] = [
+        "429",
+        "502",
+        "503",
+        "504",
+        "too many requests",
+        "bad gateway",
+        "service unavailable",
+        "gateway timeout",
+        "timed out",
+        "timeout",
+        "connection",
+        "reset by peer",
+        "broken pipe",
+    ];
+    let message = message.to_ascii_lowercase();
+    TRANSIENT.iter().any(|pattern| message.contains(pattern))
+}
+
+/// Whether an RPC error says the request reaches further back than the node keeps
+///
+/// Nodes only keep a retention window of recent ledgers, and reject requests before it,
+/// e.g. "startLedger must be between the oldest ledger: 100 and the latest ledger: 200".
+pub fn is_before_retention(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    message.contains("oldest ledger") || message.contains("out of range")
+}
+
+/// Where a submitted transaction stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionStatus {
+    /// Not in a ledger yet (still queued, or the wait timed out)
+    #[serde(rename = "PENDING")]
+    Pending,
+    #[serde(rename = "SUCCESS")]
+    Success,
+    /// Included in a ledger, but failed
+    #[serde(rename = "FAILED")]
+    Failed,
+}
+
+/// A submitted transaction and, once it's in a ledger, its result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmittedTransaction {
+    pub hash: String,
+    pub status: TransactionStatus,
+    /// TransactionResult XDR (base64); None while pending
+    #[serde(rename = "resultXdr")]
+    pub result_xdr: Option<String>,
+}
+
+/// When to rebroadcast a submitted transaction the network still doesn't know about
+///
+/// A sent transaction can be dropped before it reaches a ledger (e.g. the RPC node's
+/// queue was full). Resending the same signed envelope is safe: it has the same hash
+/// and sequence number, so it can be applied at most once.
+#[derive(Debug, Clone)]
+pub struct RebroadcastPolicy {
+    /// How long getTransaction may report NOT_FOUND after a send before resending
+    pub not_found_after: Duration,
+    /// How many times to resend before declaring the submission failed
+    pub max_rebroadcasts: u32,
+    /// JSON-lines file each rebroadcast and how the watch ended are appended to, if any
+    pub audit_log: Option<PathBuf>,
+}
+
+impl Default for RebroadcastPolicy {
+    fn default() -> Self {
+        Self {
+            not_found_after: Duration::from_secs(30),
+            max_rebroadcasts: 3,
+            audit_log: None,
+        }
+    }
+}
+
+/// One line of the rebroadcast audit log
+#[derive(Debug, Serialize)]
+struct RebroadcastRecord<'a> {
+    at: u64, // unix seconds
+    hash: &'a str,
+    /// "rebroadcast", or how the watch ended: the final status or "DROPPED"
+    event: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl RebroadcastPolicy {
+    /// Load the policy from `KALE_REBROADCAST_AFTER_SECS`, `KALE_MAX_REBROADCASTS` and
+    /// `KALE_AUDIT_LOG`, using the defaults for unset values
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(value) = std::env::var("KALE_REBROADCAST_AFTER_SECS") {
+            policy.not_found_after = Duration::from_secs(
+                value
+                    .parse()
+                    .context("KALE_REBROADCAST_AFTER_SECS must be a number of seconds")?,
+            );
+        }
+        if let Ok(value) = std::env::var("KALE_MAX_REBROADCASTS") {
+            policy.max_rebroadcasts = value
+                .parse()
+                .context("KALE_MAX_REBROADCASTS must be a non-negative integer")?;
+        }
+        policy.audit_log = std::env::var_os("KALE_AUDIT_LOG").map(PathBuf::from);
+
+        Ok(policy)
+    }
+
+    /// Append a record to the audit log, if there is one
+    ///
+    /// A record that can't be written is only warned about: auditing never stops a watch.
+    fn audit(&self, record: RebroadcastRecord) {
+        let Some(path) = &self.audit_log else {
+            return;
+        };
+        let mut line = serde_json::to_string(&record).expect("audit records always serialize");
+        line.push('\n');
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        if let Err(e) = written {
+            eprintln!(
+                "Warning: Failed to write audit log {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
 
 /// General-purpose Soroban RPC client for interacting with contracts
 pub struct SorobanRpc {
-    client: Client,
+    // The endpoints new calls go to
+    pool: Mutex<Arc<RpcPool>>,
     contract_id: Contract,
     network_passphrase: String,
+    // Recent successful simulations of contract calls, and when they were made
+    simulation_cache: Mutex<HashMap<SimulationCacheKey, (Instant, SimulateTransactionResponse)>>,
+    ledger_cache_options: LedgerCacheOptions,
+    // Recent single-entry reads by key, and when they were read; None if the entry didn't exist
+    ledger_cache: Mutex<HashMap<LedgerKey, (Instant, Option<LedgerEntryResult>)>>,
+    sequences: SequenceManager,
+    inclusion_fee: InclusionFeeOptions,
+    fee_escalation: FeeEscalation,
+    retry: RetryPolicy,
+    // The last inclusion fee picked from getFeeStats, and when
+    inclusion_fee_cache: Mutex<Option<(Instant, u32)>>,
+}
+
+/// An RPC endpoint's client, and how to name the endpoint in logs
+struct RpcNode {
+    client: Client,
+    redacted: String,
+    /// Kept to redact its credentials from text bound for files, see [`SorobanRpc::redact`]
+    endpoint: RpcEndpoint,
+}
+
+impl RpcNode {
+    fn new(endpoint: &RpcEndpoint) -> Result<Self> {
+        // Client errors may echo the URL, so report the redacted form instead
+        let redacted = endpoint.redacted();
+        let headers = endpoint
+            .header_map()
+            .map_err(|e| RpcError::InvalidEndpoint(format!("{}: {}", redacted, e)))?;
+        let client = Client::new_with_headers(&endpoint.full_url(), headers)
+            .map_err(|_| RpcError::InvalidEndpoint(redacted.clone()))?;
+        Ok(Self {
+            client,
+            redacted,
+            endpoint: endpoint.clone(),
+        })
+    }
+}
+
+/// The endpoints calls go to, swapped whole by [`SorobanRpc::replace_endpoints`]
+///
+/// A call stays on the pool it started on, so a swap doesn't cut it off.
+struct RpcPool {
+    /// The primary endpoint first, then its fallbacks
+    nodes: Vec<RpcNode>,
+    /// An archival node for events and transactions older than the others retain
+    history: Option<RpcNode>,
+    // The node calls go to, and since when
+    active: Mutex<(usize, Instant)>,
+}
+
+impl RpcPool {
+    fn new(endpoints: &[RpcEndpoint], history: Option<&RpcEndpoint>) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(RpcError::InvalidEndpoint("(none given)".to_string()));
+        }
+        Ok(Self {
+            nodes: endpoints
+                .iter()
+                .map(RpcNode::new)
+                .collect::<Result<Vec<_>>>()?,
+            history: history.map(RpcNode::new).transpose()?,
+            active: Mutex::new((0, Instant::now())),
+        })
+    }
+
+    /// Which node to call, moving back to the primary once it's had time to recover
+    fn active_node(&self) -> usize {
+        let mut active = self.active.lock().unwrap();
+        if active.0 != 0 && active.1.elapsed() >= PRIMARY_RETRY_AFTER {
+            *active = (0, Instant::now());
+        }
+        active.0
+    }
+
+    /// Move calls off `failed`, unless a concurrent call already did
+    fn fail_over(&self, failed: usize) -> usize {
+        let next = (failed + 1) % self.nodes.len();
+        let mut active = self.active.lock().unwrap();
+        if active.0 == failed && next != failed {
+            eprintln!(
+                "Warning: RPC endpoint {} is failing; switching to {}",
+                self.nodes[failed].redacted, self.nodes[next].redacted
+            );
+            *active = (next, Instant::now());
+        }
+        next
+    }
+}
+
+/// Ledger entries read in batches by [`SorobanRpc::get_ledger_entries_batch`]
+#[derive(Debug, Default)]
+pub struct LedgerEntriesBatch {
+    /// The entries that exist, keyed by their ledger key
+    pub entries: HashMap<LedgerKey, LedgerEntryResult>,
+    /// Keys whose request failed, with the index of that request's error in `errors`; a
+    /// key in neither map has no live entry
+    pub failed: HashMap<LedgerKey, usize>,
+    /// Why each failed request failed
+    pub errors: Vec<RpcError>,
+}
+
+impl LedgerEntriesBatch {
+    /// Whether every requested key was read
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Why the entry for `key` couldn't be read, if its request failed
+    pub fn failure(&self, key: &LedgerKey) -> Option<RpcError> {
+        let error = &self.errors[*self.failed.get(key)?];
+        Some(RpcError::EntryUnavailable(error.to_string()))
+    }
+}
+
+/// Everything in a contract's instance storage, from [`SorobanRpc::dump_instance_storage`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceStorage {
+    /// Last ledger the contract instance changed in
+    pub last_modified_ledger: u32,
+    /// Values by key name: "FarmIndex" for `Vec[Symbol("FarmIndex")]`, and keys with
+    /// arguments like footprint keys, e.g. "Block(12)"
+    pub entries: BTreeMap<String, ScVal>,
+}
+
+impl InstanceStorage {
+    /// Decode a contract instance entry's XDR (base64 LedgerEntryData)
+    pub fn from_entry_xdr(xdr: &str, last_modified_ledger: u32) -> Result<Self> {
+        let entries = instance_storage_map(xdr)?
+            .iter()
+            .map(|entry| (storage_key_name(&entry.key), entry.val.clone()))
+            .collect();
+        Ok(Self {
+            last_modified_ledger,
+            entries,
+        })
+    }
+
+    /// The values as JSON (see [`to_json`]), by key name
+    pub fn to_json(&self) -> serde_json::Value {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.clone(), to_json(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+/// The storage map of a contract instance entry's XDR
+fn instance_storage_map(xdr: &str) -> Result<ScMap> {
+    let entry_data = stellar_xdr::curr::LedgerEntryData::from_xdr_base64(xdr, Limits::none())?;
+    let stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) = entry_data else {
+        return Err(RpcError::UnexpectedEntry(
+            "expected a contract instance".to_string(),
+        ));
+    };
+    let ScVal::ContractInstance(instance) = contract_data.val else {
+        return Err(RpcError::UnexpectedEntry(
+            "expected a contract instance".to_string(),
+        ));
+    };
+    instance.storage.ok_or_else(|| {
+        RpcError::UnexpectedEntry("contract instance has no storage map".to_string())
+    })
+}
+
+/// Name an instance storage key, dropping the parentheses of argument-less keys
+fn storage_key_name(key: &ScVal) -> String {
+    match key {
+        ScVal::Vec(Some(items)) => match items.as_slice() {
+            [ScVal::Symbol(name)] => name.to_utf8_string_lossy(),
+            _ => describe_value(key),
+        },
+        _ => describe_value(key),
+    }
+}
+
+/// Fail unless an endpoint's network passphrase is the configured one
+fn check_passphrase(endpoint: &str, expected: &str, actual: &str) -> Result<()> {
+    if actual == expected {
+        return Ok(());
+    }
+    Err(RpcError::NetworkMismatch {
+        endpoint: endpoint.to_string(),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+    })
+}
+
+/// A transaction's fee: the simulated resource fee plus the inclusion fee, unless that
+/// doesn't fit the u32 fee field
+fn total_fee(resource_fee: u64, inclusion_fee: u32) -> Result<u32> {
+    u32::try_from(resource_fee)
+        .ok()
+        .and_then(|fee| fee.checked_add(inclusion_fee))
+        .ok_or(RpcError::FeeOverflow {
+            resource_fee,
+            inclusion_fee,
+        })
+}
+
+/// An account's balance less its minimum reserve and selling liabilities, in stroops
+///
+/// The reserve is two base reserves plus one per subentry, with sponsorships moving
+/// entries' reserves between sponsor and sponsored. Never below zero.
+pub fn spendable_xlm(account: &stellar_xdr::curr::AccountEntry) -> i64 {
+    use stellar_xdr::curr::{AccountEntryExt, AccountEntryExtensionV1Ext};
+
+    let (selling, sponsoring, sponsored) = match &account.ext {
+        AccountEntryExt::V0 => (0, 0, 0),
+        AccountEntryExt::V1(v1) => match &v1.ext {
+            AccountEntryExtensionV1Ext::V0 => (v1.liabilities.selling, 0, 0),
+            AccountEntryExtensionV1Ext::V2(v2) => (
+                v1.liabilities.selling,
+                i64::from(v2.num_sponsoring),
+                i64::from(v2.num_sponsored),
+            ),
+        },
+    };
+    let entries = 2 + i64::from(account.num_sub_entries) + sponsoring - sponsored;
+    (account.balance - entries * BASE_RESERVE - selling).max(0)
+}
+
+/// Split keys into getLedgerEntries requests of at most 200, dropping duplicates
+fn ledger_key_chunks(keys: Vec<LedgerKey>) -> Vec<Vec<LedgerKey>> {
+    let mut seen = HashSet::with_capacity(keys.len());
+    let unique: Vec<LedgerKey> = keys
+        .into_iter()
+        .filter(|key| seen.insert(key.clone()))
+        .collect();
+
+    unique
+        .chunks(MAX_LEDGER_KEYS_PER_REQUEST)
+        .map(<[LedgerKey]>::to_vec)
+        .collect()
 }
 
 impl SorobanRpc {
@@ -21,15 +741,326 @@ impl SorobanRpc {
     /// * `contract_address` - The contract address (e.g., "CDSWUUXGPWDZG76ISK6SUCVPZJMD5YUV66J2FXFXFGDX25XKZJIEITAO")
     /// * `network_passphrase` - The network passphrase (e.g., "Test SDF Network ; September 2015" for testnet)
     pub fn new(rpc_url: &str, contract_address: &str, network_passphrase: &str) -> Result<Self> {
-        let client = Client::new(rpc_url)?;
+        Self::with_endpoint(
+            &RpcEndpoint::new(rpc_url),
+            contract_address,
+            network_passphrase,
+        )
+    }
+
+    /// Create a new RPC client for the network and contract in a [`Config`], failing over
+    /// to its fallback endpoints, and reading old history from its history endpoint
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut rpc = Self::with_endpoints(
+            &config.endpoints(),
+            &config.contract_id,
+            &config.network_passphrase,
+        )?;
+        if let Some(url) = &config.history_rpc_url {
+            rpc.set_history_endpoint(&RpcEndpoint::new(url))?;
+        }
+        rpc.fee_escalation = config.fee_escalation;
+        Ok(rpc)
+    }
+
+    /// Create a new RPC client for an endpoint that needs extra headers or query parameters
+    pub fn with_endpoint(
+        endpoint: &RpcEndpoint,
+        contract_address: &str,
+        network_passphrase: &str,
+    ) -> Result<Self> {
+        Self::with_endpoints(
+            std::slice::from_ref(endpoint),
+            contract_address,
+            network_passphrase,
+        )
+    }
+
+    /// Create a new RPC client that fails over between endpoints
+    ///
+    /// Calls go to the first endpoint. One that fails transiently (see
+    /// [`is_transient_failure`]) is swapped for the next, and after [`PRIMARY_RETRY_AFTER`]
+    /// on a fallback the first is tried again.
+    pub fn with_endpoints(
+        endpoints: &[RpcEndpoint],
+        contract_address: &str,
+        network_passphrase: &str,
+    ) -> Result<Self> {
+        let pool = RpcPool::new(endpoints, None)?;
         let contract_id =
-            Contract::from_string(contract_address).context("Failed to parse contract address")?;
+            Contract::from_string(contract_address).map_err(|_| RpcError::BadStrkey {
+                what: "contract address",
+                value: contract_address.to_string(),
+            })?;
 
         Ok(Self {
-            client,
+            pool: Mutex::new(Arc::new(pool)),
             contract_id,
             network_passphrase: network_passphrase.to_string(),
+            simulation_cache: Mutex::new(HashMap::new()),
+            ledger_cache_options: LedgerCacheOptions::default(),
+            ledger_cache: Mutex::new(HashMap::new()),
+            sequences: SequenceManager::default(),
+            inclusion_fee: InclusionFeeOptions::default(),
+            fee_escalation: FeeEscalation::default(),
+            retry: RetryPolicy::default(),
+            inclusion_fee_cache: Mutex::new(None),
+        })
+    }
+
+    /// Set an archival endpoint to read events and transactions from once they're older
+    /// than the other endpoints' retention window
+    pub fn set_history_endpoint(&mut self, endpoint: &RpcEndpoint) -> Result<()> {
+        let pool = self.pool.get_mut().unwrap();
+        let endpoints: Vec<_> = pool
+            .nodes
+            .iter()
+            .map(|node| node.endpoint.clone())
+            .collect();
+        *pool = Arc::new(RpcPool::new(&endpoints, Some(endpoint))?);
+        Ok(())
+    }
+
+    /// Swap in new endpoints and history endpoint, e.g. after the config file changed
+    ///
+    /// The new endpoints are checked like [`verify_network`](Self::verify_network) first,
+    /// and nothing changes if that fails. Calls already under way finish on the old ones.
+    pub async fn replace_endpoints(
+        &self,
+        endpoints: &[RpcEndpoint],
+        history: Option<&RpcEndpoint>,
+    ) -> Result<()> {
+        let pool = RpcPool::new(endpoints, history)?;
+        self.verify_pool(&pool).await?;
+        *self.pool.lock().unwrap() = Arc::new(pool);
+        Ok(())
+    }
+
+    /// The endpoints new calls go to
+    fn pool(&self) -> Arc<RpcPool> {
+        self.pool.lock().unwrap().clone()
+    }
+
+    /// Set which inclusion fee prepared Soroban transactions bid
+    pub fn set_inclusion_fee_options(&mut self, options: InclusionFeeOptions) {
+        self.inclusion_fee = options;
+        *self.inclusion_fee_cache.lock().unwrap() = None;
+    }
+
+    /// Set how much more inclusion fee retried transactions bid
+    pub fn set_fee_escalation(&mut self, escalation: FeeEscalation) {
+        self.fee_escalation = escalation;
+    }
+
+    /// Set how calls to the RPC node are retried
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Set how long single ledger entry reads are reused
+    pub fn set_ledger_cache_options(&mut self, options: LedgerCacheOptions) {
+        self.ledger_cache_options = options;
+        self.clear_ledger_cache();
+    }
+
+    /// Forget every cached ledger entry, e.g. after a write the next reads must see
+    pub fn clear_ledger_cache(&self) {
+        self.ledger_cache.lock().unwrap().clear();
+    }
+
+    /// Read one ledger entry, reusing a read younger than the cache TTL
+    ///
+    /// Failed reads aren't cached; a missing entry is.
+    async fn read_ledger_entry(&self, key: LedgerKey) -> Result<Option<LedgerEntryResult>> {
+        let ttl = self.ledger_cache_options.ttl;
+        if let Some((read_at, entry)) = self.ledger_cache.lock().unwrap().get(&key) {
+            if read_at.elapsed() < ttl {
+                return Ok(entry.clone());
+            }
+        }
+
+        let keys = [key];
+        let response = self
+            .call(&self.pool(), "get ledger entries", |client| {
+                client.get_ledger_entries(&keys)
+            })
+            .await?;
+        let entry = response.entries.and_then(|e| e.into_iter().next());
+
+        if !ttl.is_zero() {
+            let [key] = keys;
+            let mut cache = self.ledger_cache.lock().unwrap();
+            cache.retain(|_, (read_at, _)| read_at.elapsed() < ttl);
+            cache.insert(key, (Instant::now(), entry.clone()));
+        }
+        Ok(entry)
+    }
+
+    /// The endpoint calls currently go to, redacted for logs
+    pub fn active_endpoint(&self) -> String {
+        let pool = self.pool();
+        pool.nodes[pool.active_node()].redacted.clone()
+    }
+
+    /// Replace every endpoint's credentials in `text`, e.g. an error that echoes a URL
+    pub fn redact(&self, text: &str) -> String {
+        let pool = self.pool();
+        pool.nodes
+            .iter()
+            .chain(&pool.history)
+            .fold(text.to_string(), |text, node| node.endpoint.redact(&text))
+    }
+
+    /// Make a call to a node of `pool`, failing over between its endpoints and retrying
+    /// transient failures per the [`RetryPolicy`]
+    ///
+    /// Every endpoint gets a try before the retries run out, and the backoff only applies
+    /// once they've all failed. `action` describes the call in the error if it still fails.
+    async fn call<'a, T, F, Fut>(
+        &self,
+        pool: &'a RpcPool,
+        action: &'static str,
+        mut request: F,
+    ) -> Result<T>
+    where
+        F: FnMut(&'a Client) -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<T, stellar_rpc_client::Error>>,
+    {
+        let first = pool.active_node();
+        let max_attempts = self.retry.max_attempts + pool.nodes.len() as u32 - 1;
+        let mut node = first;
+        let mut attempt = 1;
+        let mut rounds = 0;
+        loop {
+            match request(&pool.nodes[node].client).await {
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < max_attempts && is_transient_failure(&e.to_string()) => {
+                    node = pool.fail_over(node);
+                    if node == first {
+                        rounds += 1;
+                        tokio::time::sleep(self.retry.delay(rounds)).await;
+                    }
+                    attempt += 1;
+                }
+                Err(source) => return Err(RpcError::Transport { action, source }),
+            }
+        }
+    }
+
+    /// Get the network the RPC node serves: its passphrase, protocol version and friendbot
+    pub async fn get_network(&self) -> Result<GetNetworkResponse> {
+        self.call(&self.pool(), "get network", |client| client.get_network())
+            .await
+    }
+
+    /// Check that every endpoint serves the network of the configured passphrase
+    ///
+    /// A wrong passphrase otherwise only shows up as signature failures when transactions
+    /// are submitted. Endpoints that can't be reached are warned about and skipped, unless
+    /// none can be.
+    pub async fn verify_network(&self) -> Result<()> {
+        self.verify_pool(&self.pool()).await
+    }
+
+    /// Check that every endpoint of `pool` serves the configured network
+    async fn verify_pool(&self, pool: &RpcPool) -> Result<()> {
+        let mut checked = 0;
+        let mut unreachable = None;
+        for node in pool.nodes.iter().chain(&pool.history) {
+            match node.client.get_network().await {
+                Ok(network) => {
+                    check_passphrase(
+                        &node.redacted,
+                        &self.network_passphrase,
+                        &network.passphrase,
+                    )?;
+                    checked += 1;
+                }
+                Err(source) => {
+                    eprintln!(
+                        "Warning: Couldn't check the network of RPC endpoint {}: {}",
+                        node.redacted, source
+                    );
+                    unreachable.get_or_insert(source);
+                }
+            }
+        }
+        match unreachable {
+            Some(source) if checked == 0 => Err(RpcError::Transport {
+                action: "get network",
+                source,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Make a call to `node` once, or through [`call`](Self::call) on `pool` if there's
+    /// no node
+    async fn call_node<'a, T, F, Fut>(
+        &self,
+        pool: &'a RpcPool,
+        node: Option<&'a RpcNode>,
+        action: &'static str,
+        mut request: F,
+    ) -> Result<T>
+    where
+        F: FnMut(&'a Client) -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<T, stellar_rpc_client::Error>>,
+    {
+        match node {
+            Some(node) => request(&node.client)
+                .await
+                .map_err(|source| RpcError::Transport { action, source }),
+            None => self.call(pool, action, request).await,
+        }
+    }
+
+    /// Get the inclusion fees transactions paid in recent ledgers
+    pub async fn get_fee_stats(&self) -> Result<GetFeeStatsResponse> {
+        self.call(&self.pool(), "get fee stats", |client| {
+            client.get_fee_stats()
         })
+        .await
+    }
+
+    /// The inclusion fee to bid on a Soroban transaction, per [`InclusionFeeOptions`]
+    ///
+    /// Falls back to the base fee if the fee stats can't be fetched, rather than failing
+    /// the prepare.
+    async fn inclusion_fee(&self) -> u32 {
+        if let Some((fetched, fee)) = *self.inclusion_fee_cache.lock().unwrap() {
+            if fetched.elapsed() < FEE_STATS_TTL {
+                return fee;
+            }
+        }
+
+        let stats = match self.get_fee_stats().await {
+            Ok(stats) => stats.soroban_inclusion_fee,
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to get fee stats, bidding the base fee: {}",
+                    e
+                );
+                return BASE_FEE;
+            }
+        };
+        let percentile = match self.inclusion_fee.percentile {
+            10 => &stats.p10,
+            20 => &stats.p20,
+            30 => &stats.p30,
+            40 => &stats.p40,
+            50 => &stats.p50,
+            60 => &stats.p60,
+            70 => &stats.p70,
+            80 => &stats.p80,
+            90 => &stats.p90,
+            95 => &stats.p95,
+            _ => &stats.p99,
+        };
+        let fee = percentile.parse::<u32>().unwrap_or(BASE_FEE).max(BASE_FEE);
+
+        *self.inclusion_fee_cache.lock().unwrap() = Some((Instant::now(), fee));
+        fee
     }
 
     /// Get the contract instance storage entry
@@ -47,12 +1078,95 @@ impl SorobanRpc {
             });
 
         // Fetch the contract instance entry
-        let response = self.client.get_ledger_entries(&[ledger_key]).await?;
+        self.read_ledger_entry(ledger_key)
+            .await?
+            .ok_or_else(|| RpcError::EntryNotFound("Contract instance entry".to_string()))
+    }
 
-        response
-            .entries
-            .and_then(|e| e.into_iter().next())
-            .context("Contract instance entry not found")
+    /// Get the WASM code the contract instance is running
+    pub async fn get_contract_wasm(&self) -> Result<Vec<u8>> {
+        let instance = self.get_contract_instance().await?;
+        let entry_data =
+            stellar_xdr::curr::LedgerEntryData::from_xdr_base64(&instance.xdr, Limits::none())?;
+
+        // Navigate: ContractData -> ContractInstance -> executable -> WASM hash
+        let wasm_hash = match entry_data {
+            stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) => {
+                match contract_data.val {
+                    ScVal::ContractInstance(instance) => match instance.executable {
+                        stellar_xdr::curr::ContractExecutable::Wasm(hash) => hash,
+                        stellar_xdr::curr::ContractExecutable::StellarAsset => {
+                            return Err(RpcError::UnexpectedEntry(
+                                "contract is a Stellar Asset Contract and has no WASM".to_string(),
+                            ))
+                        }
+                    },
+                    _ => {
+                        return Err(RpcError::UnexpectedEntry(
+                            "expected a contract instance".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(RpcError::UnexpectedEntry(
+                    "expected contract data".to_string(),
+                ))
+            }
+        };
+
+        let code_key =
+            stellar_xdr::curr::LedgerKey::ContractCode(stellar_xdr::curr::LedgerKeyContractCode {
+                hash: wasm_hash,
+            });
+
+        let entry = self
+            .get_ledger_entry(code_key)
+            .await?
+            .ok_or_else(|| RpcError::EntryNotFound("Contract code entry".to_string()))?;
+
+        match stellar_xdr::curr::LedgerEntryData::from_xdr_base64(&entry.xdr, Limits::none())? {
+            stellar_xdr::curr::LedgerEntryData::ContractCode(code) => Ok(code.code.to_vec()),
+            _ => Err(RpcError::UnexpectedEntry(
+                "expected contract code".to_string(),
+            )),
+        }
+    }
+
+    /// Get the network's Soroban config setting entries (in no particular order)
+    pub async fn get_config_settings(
+        &self,
+        ids: &[ConfigSettingId],
+    ) -> Result<Vec<ConfigSettingEntry>> {
+        let keys: Vec<LedgerKey> = ids
+            .iter()
+            .map(|id| {
+                LedgerKey::ConfigSetting(LedgerKeyConfigSetting {
+                    config_setting_id: *id,
+                })
+            })
+            .collect();
+
+        let response = self
+            .call(&self.pool(), "get ledger entries", |client| {
+                client.get_ledger_entries(&keys)
+            })
+            .await?;
+        let mut settings = Vec::new();
+        for entry in response.entries.unwrap_or_default() {
+            match stellar_xdr::curr::LedgerEntryData::from_xdr_base64(&entry.xdr, Limits::none())? {
+                stellar_xdr::curr::LedgerEntryData::ConfigSetting(setting) => {
+                    settings.push(setting)
+                }
+                _ => {
+                    return Err(RpcError::UnexpectedEntry(
+                        "expected a config setting".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(settings)
     }
 
     /// Parse a value from instance storage by key name
@@ -60,34 +1174,30 @@ impl SorobanRpc {
         entry: &LedgerEntryResult,
         key_name: &str,
     ) -> Result<ScVal> {
-        // Decode the LedgerEntryData from base64 XDR
-        let entry_data =
-            stellar_xdr::curr::LedgerEntryData::from_xdr_base64(&entry.xdr, Limits::none())
-                .context("Failed to decode XDR")?;
-
-        // Navigate: ContractData -> ContractInstance -> storage -> find key
-        if let stellar_xdr::curr::LedgerEntryData::ContractData(contract_data) = entry_data {
-            if let ScVal::ContractInstance(instance) = contract_data.val {
-                let storage_map = instance
-                    .storage
-                    .context("ContractInstance has no storage map")?;
-
-                // Instance storage keys are wrapped: Vec(ScVec([Symbol("KeyName")]))
-                for map_entry in storage_map.iter() {
-                    if let ScVal::Vec(Some(vec)) = &map_entry.key {
-                        if let Some(ScVal::Symbol(sym)) = vec.first() {
-                            if sym.to_utf8_string_lossy() == key_name {
-                                return Ok(map_entry.val.clone());
-                            }
-                        }
+        let storage_map = instance_storage_map(&entry.xdr)?;
+
+        // Instance storage keys are wrapped: Vec(ScVec([Symbol("KeyName")]))
+        for map_entry in storage_map.iter() {
+            if let ScVal::Vec(Some(vec)) = &map_entry.key {
+                if let Some(ScVal::Symbol(sym)) = vec.first() {
+                    if sym.to_utf8_string_lossy() == key_name {
+                        return Ok(map_entry.val.clone());
                     }
                 }
-
-                anyhow::bail!("Key '{}' not found in instance storage", key_name);
             }
         }
 
-        anyhow::bail!("Unexpected ledger entry structure")
+        Err(RpcError::EntryNotFound(format!(
+            "Key '{}' in instance storage",
+            key_name
+        )))
+    }
+
+    /// Read every key and value in the contract's instance storage, e.g. to see why a
+    /// FarmIndex or FarmBlock lookup isn't finding what it expects
+    pub async fn dump_instance_storage(&self) -> Result<InstanceStorage> {
+        let instance = self.get_contract_instance().await?;
+        InstanceStorage::from_entry_xdr(&instance.xdr, instance.last_modified_ledger)
     }
 
     /// Build a transaction to invoke a contract function
@@ -103,34 +1213,26 @@ impl SorobanRpc {
         args: Vec<ScVal>,
     ) -> Result<Transaction> {
         // Parse the source account public key
-        let source_strkey =
-            Strkey::from_string(source_account).context("Failed to parse source account")?;
-
-        let source_public_key = match source_strkey {
-            Strkey::PublicKeyEd25519(pk) => PublicKey::PublicKeyTypeEd25519(Uint256(pk.0)),
-            _ => anyhow::bail!("Invalid source account key type"),
-        };
-
-        // Get the account sequence number
-        let account_response = self.client.get_account(source_account).await?;
-        let sequence = account_response.seq_num.0 as i64 + 1;
-
-        // Extract bytes from public key
-        let account_bytes = match source_public_key {
-            PublicKey::PublicKeyTypeEd25519(ref uint256) => uint256.0,
-        };
+        let account_bytes = Self::parse_account_key(source_account, "source account")?;
 
         // Build the invoke contract host function
         let contract_address = ScAddress::Contract(Hash(self.contract_id.0.clone()));
-        let function_symbol = stellar_xdr::curr::ScSymbol(
-            function_name.try_into().context("Function name too long")?,
-        );
+        let function_symbol =
+            stellar_xdr::curr::ScSymbol(function_name.try_into().map_err(|_| {
+                RpcError::InvalidInput(format!("Function name '{}' is too long", function_name))
+            })?);
 
         let invoke_args = stellar_xdr::curr::InvokeContractArgs {
             contract_address,
             function_name: function_symbol,
             args: args.try_into()?,
         };
+        let intent = format!(
+            "{}({})",
+            function_name,
+            invoke_args.args.to_xdr_base64(Limits::none())?
+        );
+        let sequence = self.next_sequence(source_account, &intent).await?;
 
         let host_function = stellar_xdr::curr::HostFunction::InvokeContract(invoke_args);
 
@@ -157,106 +1259,707 @@ impl SorobanRpc {
         Ok(transaction)
     }
 
+    /// The sequence number for a new transaction from `source_account`
+    ///
+    /// Transactions prepared for the account before others land (from any session) get
+    /// consecutive numbers. Fails with [`RpcError::SequenceContention`] only while the
+    /// account has [`MAX_RESERVATIONS`](crate::sequence::MAX_RESERVATIONS) numbers held.
+    async fn next_sequence(&self, source_account: &str, intent: &str) -> Result<i64> {
+        let account_response = self
+            .call(&self.pool(), "get account", |client| {
+                client.get_account(source_account)
+            })
+            .await?;
+
+        self.sequences
+            .allocate(source_account, account_response.seq_num.0, intent)
+            .map_err(RpcError::SequenceContention)
+    }
+
+    /// Free the sequence number of a built transaction that won't be signed, e.g. because
+    /// its simulation failed, so other transactions from the account aren't held up
+    pub fn release_sequence(&self, transaction: &Transaction) {
+        if let Some(account) = Self::sequence_account(transaction) {
+            self.sequences.release(&account, transaction.seq_num.0);
+        }
+    }
+
+    /// The account whose sequence number a transaction uses, if it's a plain G... source
+    fn sequence_account(transaction: &Transaction) -> Option<String> {
+        let MuxedAccount::Ed25519(Uint256(account)) = &transaction.source_account else {
+            return None;
+        };
+        Some(stellar_strkey::ed25519::PublicKey(*account).to_string())
+    }
+
+    /// Get the sequence number of the latest ledger known to the RPC node
+    pub async fn get_latest_ledger(&self) -> Result<u32> {
+        let response = self
+            .call(&self.pool(), "get latest ledger", |client| {
+                client.get_latest_ledger()
+            })
+            .await?;
+
+        Ok(response.sequence)
+    }
+
     /// Simulate a transaction to get resource requirements and fees
+    ///
+    /// Contract invocations are cached by (source, function, args, latest ledger), so
+    /// re-preparing the same call within one ledger (e.g. after a dismissed wallet popup)
+    /// reuses the previous simulation instead of simulating again.
     pub async fn simulate_transaction(
         &self,
         transaction: &Transaction,
     ) -> Result<SimulateTransactionResponse> {
+        let cache_key = match Self::invocation_cache_key(transaction)? {
+            Some((source, function, args)) => {
+                let ledger = self.get_latest_ledger().await?;
+                Some((source, function, args, ledger))
+            }
+            None => None,
+        };
+
+        if let Some(key) = &cache_key {
+            if let Some((_, simulation)) = self.simulation_cache.lock().unwrap().get(key) {
+                return Ok(simulation.clone());
+            }
+        }
+
         // Wrap transaction in envelope for simulation
         let envelope = TransactionEnvelope::Tx(stellar_xdr::curr::TransactionV1Envelope {
             tx: transaction.clone(),
             signatures: stellar_xdr::curr::VecM::default(),
         });
 
-        self.client
-            .simulate_transaction_envelope(&envelope)
-            .await
-            .context("Failed to simulate transaction")
+        let simulation = self
+            .call(&self.pool(), "simulate transaction", |client| {
+                client.simulate_transaction_envelope(&envelope)
+            })
+            .await?;
+
+        // Only successful simulations are worth reusing
+        if let Some(key) = cache_key {
+            if simulation.error.is_none() {
+                let mut cache = self.simulation_cache.lock().unwrap();
+                // Results from older ledgers can never be hit again
+                cache.retain(|(_, _, _, ledger), (simulated_at, _)| {
+                    *ledger >= key.3 && simulated_at.elapsed() < SIMULATION_CACHE_TTL
+                });
+                cache.insert(key, (Instant::now(), simulation.clone()));
+            }
+        }
+
+        Ok(simulation)
+    }
+
+    /// Extract (source, function, args XDR) from a contract invocation transaction
+    ///
+    /// Returns None for transactions that don't invoke a contract function.
+    fn invocation_cache_key(transaction: &Transaction) -> Result<Option<(String, String, String)>> {
+        let Some(operation) = transaction.operations.first() else {
+            return Ok(None);
+        };
+
+        let OperationBody::InvokeHostFunction(invoke_op) = &operation.body else {
+            return Ok(None);
+        };
+
+        let stellar_xdr::curr::HostFunction::InvokeContract(invoke_args) = &invoke_op.host_function
+        else {
+            return Ok(None);
+        };
+
+        let source = transaction.source_account.to_xdr_base64(Limits::none())?;
+        let function = invoke_args.function_name.to_utf8_string_lossy();
+        let args = invoke_args.args.to_xdr_base64(Limits::none())?;
+
+        Ok(Some((source, function, args)))
+    }
+
+    /// Apply simulation results to a transaction
+    ///
+    /// The fee is the simulated resource fee plus an inclusion fee from recent fee stats.
+    pub async fn apply_simulation_to_transaction(
+        &self,
+        mut transaction: Transaction,
+        simulation: &SimulateTransactionResponse,
+    ) -> Result<Transaction> {
+        // Extract simulation results
+        let first_result = simulation
+            .results
+            .first()
+            .ok_or_else(|| RpcError::SimulationFailed("no results".to_string()))?;
+
+        // Get the transaction data from the simulation response
+        // Check if transaction_data is empty (simulation might not need Soroban data)
+        if simulation.transaction_data.is_empty() {
+            return Err(RpcError::SimulationFailed(
+                "no transaction data".to_string(),
+            ));
+        }
+
+        // Parse the SorobanTransactionData
+        let soroban_tx_data = stellar_xdr::curr::SorobanTransactionData::from_xdr_base64(
+            &simulation.transaction_data,
+            Limits::none(),
+        )?;
+
+        // Update the transaction with Soroban data
+        // Extract auth from simulation if available
+        if !first_result.auth.is_empty() {
+            // Parse auth entries
+            let auth_entries: Vec<stellar_xdr::curr::SorobanAuthorizationEntry> = first_result
+                .auth
+                .iter()
+                .filter_map(|xdr| {
+                    stellar_xdr::curr::SorobanAuthorizationEntry::from_xdr_base64(
+                        xdr,
+                        Limits::none(),
+                    )
+                    .ok()
+                })
+                .collect();
+
+            // Convert VecM to Vec, modify, and convert back
+            let mut operations: Vec<_> = transaction.operations.to_vec();
+            if let Some(operation) = operations.get_mut(0) {
+                if let OperationBody::InvokeHostFunction(ref mut invoke_op) = operation.body {
+                    invoke_op.auth = auth_entries.try_into()?;
+                }
+            }
+            transaction.operations = operations.try_into()?;
+        }
+
+        // Update transaction extension with Soroban data
+        transaction.ext = TransactionExt::V1(stellar_xdr::curr::SorobanTransactionData {
+            ext: stellar_xdr::curr::ExtensionPoint::V0,
+            resources: soroban_tx_data.resources,
+            resource_fee: soroban_tx_data.resource_fee,
+        });
+
+        // Update fee with simulation results
+        transaction.fee = total_fee(simulation.min_resource_fee, self.inclusion_fee().await)?;
+
+        Ok(transaction)
+    }
+
+    /// Simulate a transaction whose address authorizations are already signed, and update
+    /// its resources and fee to match while keeping the signatures
+    ///
+    /// Simulating before signing doesn't run a smart wallet's `__check_auth`, so the
+    /// resources from prepare fall short of what verifying a passkey signature costs. The
+    /// simulation cache is bypassed: it holds the unsigned simulation of the same call.
+    pub async fn resimulate_signed_auth(&self, tx_xdr: &str) -> Result<Transaction> {
+        let envelope = TransactionEnvelope::from_xdr_base64(tx_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+        let TransactionEnvelope::Tx(v1) = &envelope else {
+            return Err(RpcError::BadTransaction(
+                "unsupported envelope type".to_string(),
+            ));
+        };
+        let mut transaction = v1.tx.clone();
+
+        let simulation = self
+            .call(&self.pool(), "simulate transaction", |client| {
+                client.simulate_transaction_envelope(&envelope)
+            })
+            .await?;
+        if let Some(error) = &simulation.error {
+            return Err(RpcError::SimulationFailed(error.to_string()));
+        }
+
+        let soroban_tx_data = stellar_xdr::curr::SorobanTransactionData::from_xdr_base64(
+            &simulation.transaction_data,
+            Limits::none(),
+        )?;
+        transaction.ext = TransactionExt::V1(soroban_tx_data);
+        transaction.fee = total_fee(simulation.min_resource_fee, self.inclusion_fee().await)?;
+
+        Ok(transaction)
+    }
+
+    /// Set the ledger until which the address authorizations of a simulated invocation
+    /// can be signed and used
+    ///
+    /// Simulation leaves it at 0. It's only needed when an authorizing account (e.g. the
+    /// farmer) isn't the transaction source, since that account signs over it separately.
+    pub fn set_auth_expiration(
+        &self,
+        mut transaction: Transaction,
+        valid_until_ledger: u32,
+    ) -> Result<Transaction> {
+        let mut operations: Vec<_> = transaction.operations.to_vec();
+        for operation in &mut operations {
+            let OperationBody::InvokeHostFunction(invoke_op) = &mut operation.body else {
+                continue;
+            };
+            let mut auth = invoke_op.auth.to_vec();
+            for entry in &mut auth {
+                if let SorobanCredentials::Address(credentials) = &mut entry.credentials {
+                    credentials.signature_expiration_ledger = valid_until_ledger;
+                }
+            }
+            invoke_op.auth = auth.try_into()?;
+        }
+        transaction.operations = operations.try_into()?;
+        Ok(transaction)
+    }
+
+    /// Build a RestoreFootprint transaction for the archived entries a simulation reported
+    ///
+    /// The preamble carries the footprint to restore and its resource fee, so the
+    /// transaction needs no further simulation. Once it lands, the original call can be
+    /// simulated again.
+    pub async fn build_restore_transaction(
+        &self,
+        source_account: &str,
+        preamble: &RestorePreamble,
+    ) -> Result<Transaction> {
+        let account_bytes = Self::parse_account_key(source_account, "source account")?;
+
+        let soroban_tx_data = stellar_xdr::curr::SorobanTransactionData::from_xdr_base64(
+            &preamble.transaction_data,
+            Limits::none(),
+        )?;
+
+        let intent = format!("restore({})", preamble.transaction_data);
+        let sequence = self.next_sequence(source_account, &intent).await?;
+
+        let operation = Operation {
+            source_account: None,
+            body: OperationBody::RestoreFootprint(stellar_xdr::curr::RestoreFootprintOp {
+                ext: stellar_xdr::curr::ExtensionPoint::V0,
+            }),
+        };
+
+        let transaction = Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256(account_bytes)),
+            fee: total_fee(preamble.min_resource_fee, self.inclusion_fee().await)?,
+            seq_num: SequenceNumber(sequence),
+            cond: Preconditions::None,
+            memo: stellar_xdr::curr::Memo::None,
+            operations: vec![operation].try_into()?,
+            ext: TransactionExt::V1(soroban_tx_data),
+        };
+
+        Ok(transaction)
+    }
+
+    /// Submit a signed transaction to the network
+    pub async fn submit_transaction(&self, signed_tx_xdr: &str) -> Result<String> {
+        let envelope = TransactionEnvelope::from_xdr_base64(signed_tx_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+
+        // The sequence number the transaction reserved when it was prepared
+        let transaction = match &envelope {
+            TransactionEnvelope::Tx(v1) => Some(&v1.tx),
+            TransactionEnvelope::TxFeeBump(fee_bump) => match &fee_bump.tx.inner_tx {
+                FeeBumpTransactionInnerTx::Tx(inner) => Some(&inner.tx),
+            },
+            TransactionEnvelope::TxV0(_) => None,
+        };
+        let reservation = transaction.and_then(|transaction| {
+            Self::sequence_account(transaction).map(|account| (account, transaction.seq_num.0))
+        });
+
+        let response = self
+            .call(&self.pool(), "submit transaction", |client| {
+                client.send_transaction(&envelope)
+            })
+            .await;
+
+        // A rejected transaction never uses its number; a sent one keeps it until it lands
+        if let Some((account, sequence)) = reservation {
+            match &response {
+                Ok(_) => self.sequences.submitted(&account, sequence),
+                Err(_) => self.sequences.release(&account, sequence),
+            }
+        }
+        let response = response?;
+
+        // Convert Hash to hex string
+        let hash_hex = hex::encode(response.0);
+
+        Ok(hash_hex)
+    }
+
+    /// Wait for a submitted transaction to succeed or fail
+    ///
+    /// Polls getTransaction every `options.poll_interval`. If the transaction is still
+    /// not in a ledger after `options.timeout`, returns it as [`TransactionStatus::Pending`].
+    pub async fn confirm_transaction(
+        &self,
+        tx_hash: &str,
+        options: &ConfirmationOptions,
+    ) -> Result<SubmittedTransaction> {
+        let started = Instant::now();
+        while started.elapsed() < options.timeout {
+            tokio::time::sleep(options.poll_interval).await;
+
+            // A failed poll is retried; the node may be briefly unavailable
+            let response = match self.get_transaction(tx_hash).await {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("Warning: Failed to poll transaction {}: {}", tx_hash, e);
+                    continue;
+                }
+            };
+
+            let status = match response.status.as_str() {
+                "SUCCESS" => {
+                    self.clear_ledger_cache();
+                    TransactionStatus::Success
+                }
+                "FAILED" => TransactionStatus::Failed,
+                _ => continue,
+            };
+            let result_xdr = response
+                .result
+                .map(|result| result.to_xdr_base64(Limits::none()))
+                .transpose()?;
+
+            return Ok(SubmittedTransaction {
+                hash: tx_hash.to_string(),
+                status,
+                result_xdr,
+            });
+        }
+
+        Ok(SubmittedTransaction {
+            hash: tx_hash.to_string(),
+            status: TransactionStatus::Pending,
+            result_xdr: None,
+        })
+    }
+
+    /// Wait for a submitted transaction to reach a ledger, rebroadcasting it if it goes missing
+    ///
+    /// Polls getTransaction until the status is no longer NOT_FOUND. Each time it has been
+    /// NOT_FOUND for `policy.not_found_after` since the last send, the same signed envelope
+    /// is sent again, up to `policy.max_rebroadcasts` times. Every rebroadcast and the
+    /// outcome are logged, and written to `policy.audit_log` if set.
+    ///
+    /// Returns the final getTransaction response (SUCCESS or FAILED)
+    pub async fn watch_transaction(
+        &self,
+        signed_tx_xdr: &str,
+        tx_hash: &str,
+        policy: &RebroadcastPolicy,
+    ) -> Result<GetTransactionResponse> {
+        let envelope = TransactionEnvelope::from_xdr_base64(signed_tx_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+
+        let audit = |event: &str, attempt: Option<u32>, error: Option<String>| {
+            let at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs());
+            policy.audit(RebroadcastRecord {
+                at,
+                hash: tx_hash,
+                event,
+                attempt,
+                error,
+            });
+        };
+
+        let mut last_sent = Instant::now();
+        let mut rebroadcasts = 0;
+        loop {
+            tokio::time::sleep(WATCH_POLL_INTERVAL).await;
+
+            // A failed poll is treated like NOT_FOUND; the node may be briefly unavailable
+            match self.get_transaction(tx_hash).await {
+                Ok(response) if response.status != "NOT_FOUND" => {
+                    if response.status == "SUCCESS" {
+                        self.clear_ledger_cache();
+                    }
+                    if rebroadcasts > 0 {
+                        audit(&response.status, None, None);
+                    }
+                    return Ok(response);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: Failed to poll transaction {}: {}", tx_hash, e),
+            }
+
+            if last_sent.elapsed() < policy.not_found_after {
+                continue;
+            }
+            if rebroadcasts >= policy.max_rebroadcasts {
+                let dropped = RpcError::TransactionDropped {
+                    hash: tx_hash.to_string(),
+                    after_secs: policy.not_found_after.as_secs(),
+                    rebroadcasts,
+                };
+                audit("DROPPED", None, Some(dropped.to_string()));
+                return Err(dropped);
+            }
+
+            rebroadcasts += 1;
+            println!(
+                "Rebroadcasting transaction {} (attempt {} of {})",
+                tx_hash, rebroadcasts, policy.max_rebroadcasts
+            );
+            let pool = self.pool();
+            let client = &pool.nodes[pool.active_node()].client;
+            let error = client.send_transaction(&envelope).await.err();
+            if let Some(e) = &error {
+                eprintln!("Warning: Rebroadcast of {} failed: {}", tx_hash, e);
+            }
+            audit(
+                "rebroadcast",
+                Some(rebroadcasts),
+                error.map(|e| self.redact(&e.to_string())),
+            );
+            last_sent = Instant::now();
+        }
+    }
+
+    /// Fetch a transaction's status, result and meta by its hex-encoded hash
+    pub async fn get_transaction(&self, tx_hash: &str) -> Result<GetTransactionResponse> {
+        self.transaction_on(&self.pool(), None, tx_hash).await
+    }
+
+    /// Get a transaction that may be older than the node's retention window, asking the
+    /// history endpoint (if set) when the others no longer know it
+    ///
+    /// Not for polling a pending transaction: NOT_FOUND is normal then.
+    pub async fn get_historical_transaction(
+        &self,
+        tx_hash: &str,
+    ) -> Result<GetTransactionResponse> {
+        let response = self.get_transaction(tx_hash).await?;
+        let pool = self.pool();
+        let Some(history) = pool
+            .history
+            .as_ref()
+            .filter(|_| response.status == "NOT_FOUND")
+        else {
+            return Ok(response);
+        };
+        self.transaction_on(&pool, Some(history), tx_hash).await
+    }
+
+    /// Get a transaction from `node`, or the endpoints of `pool` if None
+    async fn transaction_on(
+        &self,
+        pool: &RpcPool,
+        node: Option<&RpcNode>,
+        tx_hash: &str,
+    ) -> Result<GetTransactionResponse> {
+        // Must be 32 bytes of hex
+        let hash_bytes: [u8; 32] = hex::decode(tx_hash)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| RpcError::BadHash(tx_hash.to_string()))?;
+        let hash = Hash(hash_bytes);
+
+        self.call_node(pool, node, "get transaction", |client| {
+            client.get_transaction(&hash)
+        })
+        .await
+    }
+
+    /// The transaction in an envelope, looking inside a fee bump to the one it pays for
+    fn envelope_transaction(tx_xdr: &str) -> Result<Transaction> {
+        let envelope = TransactionEnvelope::from_xdr_base64(tx_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+
+        match envelope {
+            TransactionEnvelope::Tx(envelope) => Ok(envelope.tx),
+            TransactionEnvelope::TxFeeBump(envelope) => {
+                let FeeBumpTransactionInnerTx::Tx(inner) = envelope.tx.inner_tx;
+                Ok(inner.tx)
+            }
+            TransactionEnvelope::TxV0(_) => Err(RpcError::BadTransaction(
+                "unsupported envelope type".to_string(),
+            )),
+        }
+    }
+
+    /// Get the source account (G... address) of a transaction envelope in XDR
+    ///
+    /// For a fee bump this is the inner transaction's source, not the sponsor.
+    pub fn transaction_source_account(tx_xdr: &str) -> Result<String> {
+        let source_account = Self::envelope_transaction(tx_xdr)?.source_account;
+
+        let account_bytes = match source_account {
+            MuxedAccount::Ed25519(Uint256(bytes)) => bytes,
+            MuxedAccount::MuxedEd25519(muxed) => muxed.ed25519.0,
+        };
+
+        Ok(stellar_strkey::ed25519::PublicKey(account_bytes).to_string())
     }
 
-    /// Apply simulation results to a transaction
-    pub fn apply_simulation_to_transaction(
-        &self,
-        mut transaction: Transaction,
-        simulation: &SimulateTransactionResponse,
-    ) -> Result<Transaction> {
-        // Extract simulation results
-        let first_result = simulation
-            .results
-            .first()
-            .context("No simulation results found")?;
+    /// Who a farming transaction is for: the address in its contract call's first argument
+    /// (the `farmer` of plant, work and harvest), or its source account if there is none
+    ///
+    /// The two differ when a fee source pays, and a contract farmer is never the source.
+    pub fn transaction_farmer(tx_xdr: &str) -> Result<String> {
+        let tx = Self::envelope_transaction(tx_xdr)?;
 
-        // Get the transaction data from the simulation response
-        // Check if transaction_data is empty (simulation might not need Soroban data)
-        if simulation.transaction_data.is_empty() {
-            anyhow::bail!("No transaction data in simulation response");
+        let farmer = tx.operations.first().and_then(|operation| {
+            let OperationBody::InvokeHostFunction(invoke_op) = &operation.body else {
+                return None;
+            };
+            let stellar_xdr::curr::HostFunction::InvokeContract(invoke_args) =
+                &invoke_op.host_function
+            else {
+                return None;
+            };
+            match invoke_args.args.first() {
+                Some(ScVal::Address(address)) => Some(Self::address_strkey(address)),
+                _ => None,
+            }
+        });
+        match farmer {
+            Some(farmer) => Ok(farmer),
+            None => Self::transaction_source_account(tx_xdr),
         }
+    }
 
-        // Parse the SorobanTransactionData
-        let soroban_tx_data = stellar_xdr::curr::SorobanTransactionData::from_xdr_base64(
-            &simulation.transaction_data,
-            Limits::none(),
-        )
-        .context("Failed to parse soroban transaction data")?;
-
-        // Update the transaction with Soroban data
-        // Extract auth from simulation if available
-        if !first_result.auth.is_empty() {
-            // Parse auth entries
-            let auth_entries: Vec<stellar_xdr::curr::SorobanAuthorizationEntry> = first_result
-                .auth
-                .iter()
-                .filter_map(|xdr| {
-                    stellar_xdr::curr::SorobanAuthorizationEntry::from_xdr_base64(
-                        xdr,
-                        Limits::none(),
-                    )
-                    .ok()
-                })
-                .collect();
+    /// Wrap a signed transaction in a fee bump paid by `fee_source`, returning the unsigned
+    /// fee bump envelope (base64 XDR)
+    ///
+    /// Lets a sponsor pay for a transaction the farmer already signed: the inner transaction
+    /// keeps its source, sequence number and signatures, and only the sponsor signs the
+    /// result. `max_fee` (stroops) is the most the sponsor pays; the network wants at least
+    /// the inner resource fee plus the inner inclusion fee rate for one extra operation.
+    pub fn wrap_fee_bump(inner_signed_xdr: &str, fee_source: &str, max_fee: i64) -> Result<String> {
+        let envelope = TransactionEnvelope::from_xdr_base64(inner_signed_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+        let TransactionEnvelope::Tx(inner) = envelope else {
+            return Err(RpcError::BadTransaction(
+                "only v1 transactions can be fee-bumped".to_string(),
+            ));
+        };
+        if inner.signatures.is_empty() {
+            return Err(RpcError::BadTransaction(
+                "the inner transaction must be signed before it is fee-bumped".to_string(),
+            ));
+        }
 
-            // Convert VecM to Vec, modify, and convert back
-            let mut operations: Vec<_> = transaction.operations.to_vec();
-            if let Some(operation) = operations.get_mut(0) {
-                if let OperationBody::InvokeHostFunction(ref mut invoke_op) = operation.body {
-                    invoke_op.auth = auth_entries
-                        .try_into()
-                        .context("Failed to convert auth entries")?;
-                }
-            }
-            transaction.operations = operations.try_into()?;
+        let minimum = Self::minimum_fee_bump(&inner.tx);
+        if max_fee < minimum {
+            return Err(RpcError::InvalidInput(format!(
+                "max fee {} is below the {} stroops this fee bump needs",
+                max_fee, minimum
+            )));
         }
 
-        // Update transaction extension with Soroban data
-        transaction.ext = TransactionExt::V1(stellar_xdr::curr::SorobanTransactionData {
-            ext: stellar_xdr::curr::ExtensionPoint::V0,
-            resources: soroban_tx_data.resources,
-            resource_fee: soroban_tx_data.resource_fee,
+        let fee_source_bytes = Self::parse_account_key(fee_source, "fee source")?;
+        let fee_bump = TransactionEnvelope::TxFeeBump(FeeBumpTransactionEnvelope {
+            tx: FeeBumpTransaction {
+                fee_source: MuxedAccount::Ed25519(Uint256(fee_source_bytes)),
+                fee: max_fee,
+                inner_tx: FeeBumpTransactionInnerTx::Tx(inner),
+                ext: FeeBumpTransactionExt::V0,
+            },
+            signatures: Default::default(),
         });
 
-        // Update fee with simulation results
-        let resource_fee = simulation.min_resource_fee as i64;
-        let base_fee = 100i64; // Base inclusion fee
-        transaction.fee = (base_fee + resource_fee) as u32;
+        Ok(fee_bump.to_xdr_base64(Limits::none())?)
+    }
 
-        Ok(transaction)
+    /// The lowest outer fee the network accepts for a fee bump of `inner`
+    fn minimum_fee_bump(inner: &Transaction) -> i64 {
+        let resource_fee = match &inner.ext {
+            TransactionExt::V1(soroban_data) => soroban_data.resource_fee,
+            TransactionExt::V0 => 0,
+        };
+        let operations = inner.operations.len().max(1) as i64;
+        let inclusion_rate =
+            ((i64::from(inner.fee) - resource_fee) / operations).max(i64::from(BASE_FEE));
+        resource_fee + inclusion_rate * (operations + 1)
     }
 
-    /// Submit a signed transaction to the network
-    pub async fn submit_transaction(&self, signed_tx_xdr: &str) -> Result<String> {
-        let envelope = TransactionEnvelope::from_xdr_base64(signed_tx_xdr, Limits::none())
-            .context("Failed to parse signed transaction XDR")?;
+    /// The outer fee for retry `retry` of a fee bump of a signed transaction: the network's
+    /// minimum, with its inclusion fee raised per the [`FeeEscalation`]
+    pub fn escalated_fee_bump(&self, inner_signed_xdr: &str, retry: u32) -> Result<i64> {
+        let envelope = TransactionEnvelope::from_xdr_base64(inner_signed_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+        let TransactionEnvelope::Tx(inner) = envelope else {
+            return Err(RpcError::BadTransaction(
+                "only v1 transactions can be fee-bumped".to_string(),
+            ));
+        };
+        let resource_fee = match &inner.tx.ext {
+            TransactionExt::V1(soroban_data) => soroban_data.resource_fee,
+            TransactionExt::V0 => 0,
+        };
+        let inclusion_fee = Self::minimum_fee_bump(&inner.tx) - resource_fee;
+        let escalated = self
+            .fee_escalation
+            .inclusion_fee(u32::try_from(inclusion_fee).unwrap_or(u32::MAX), retry);
+        Ok(resource_fee + i64::from(escalated))
+    }
 
-        let response = self
-            .client
-            .send_transaction(&envelope)
-            .await
-            .context("Failed to submit transaction")?;
+    /// Raise the inclusion fee of an unsigned transaction for retry `retry` of it, per the
+    /// [`FeeEscalation`]; the resource fee is kept
+    pub fn escalate_inclusion_fee(&self, tx_xdr: &str, retry: u32) -> Result<String> {
+        let mut envelope = TransactionEnvelope::from_xdr_base64(tx_xdr, Limits::none())
+            .map_err(|e| RpcError::BadTransaction(e.to_string()))?;
+        let TransactionEnvelope::Tx(inner) = &mut envelope else {
+            return Err(RpcError::BadTransaction(
+                "expected a v1 transaction envelope".to_string(),
+            ));
+        };
+        let tx = &mut inner.tx;
+        let resource_fee = match &tx.ext {
+            TransactionExt::V1(soroban_data) => soroban_data.resource_fee,
+            TransactionExt::V0 => 0,
+        };
+        // Fees only grow, so a fee below the resource fee was never valid
+        let inclusion_fee = u32::try_from(i64::from(tx.fee) - resource_fee)
+            .map_err(|_| RpcError::BadTransaction("fee is below the resource fee".to_string()))?;
+        tx.fee = total_fee(
+            resource_fee.max(0) as u64,
+            self.fee_escalation.inclusion_fee(inclusion_fee, retry),
+        )?;
+        Ok(envelope.to_xdr_base64(Limits::none())?)
+    }
 
-        // Convert Hash to hex string
-        let hash_hex = hex::encode(response.0);
+    /// The strkey (G... or C...) of a contract `Address`
+    pub fn address_strkey(address: &ScAddress) -> String {
+        match address {
+            ScAddress::Account(stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(
+                key,
+            ))) => stellar_strkey::ed25519::PublicKey(key.0).to_string(),
+            ScAddress::Contract(hash) => Contract(hash.0).to_string(),
+        }
+    }
 
-        Ok(hash_hex)
+    /// Parse an account address (G...) into its ed25519 public key bytes
+    ///
+    /// `what` names the address in the error, e.g. "source account".
+    pub fn parse_account_key(address: &str, what: &'static str) -> Result<[u8; 32]> {
+        match Strkey::from_string(address) {
+            Ok(Strkey::PublicKeyEd25519(pk)) => Ok(pk.0),
+            _ => Err(RpcError::BadStrkey {
+                what,
+                value: address.to_string(),
+            }),
+        }
+    }
+
+    /// The account paying for a classic transaction, its key, and the source to set on
+    /// operations acting for `account` when that's a different account
+    ///
+    /// With a distinct fee source the transaction needs both accounts' signatures.
+    fn fee_paying_source<'a>(
+        account: &'a str,
+        account_bytes: [u8; 32],
+        fee_source: Option<&'a str>,
+    ) -> Result<(&'a str, [u8; 32], Option<MuxedAccount>)> {
+        match fee_source {
+            Some(fee_source) if fee_source != account => Ok((
+                fee_source,
+                Self::parse_account_key(fee_source, "fee source")?,
+                Some(MuxedAccount::Ed25519(Uint256(account_bytes))),
+            )),
+            _ => Ok((account, account_bytes, None)),
+        }
     }
 
     /// Check if an account has a trustline to a specific asset and get the balance
@@ -274,24 +1977,12 @@ impl SorobanRpc {
         asset_issuer: &str,
     ) -> Result<(bool, i64)> {
         // Parse addresses
-        let account_strkey =
-            Strkey::from_string(account_address).context("Failed to parse account address")?;
-        let issuer_strkey =
-            Strkey::from_string(asset_issuer).context("Failed to parse issuer address")?;
-
-        let account_id = match account_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(pk.0)))
-            }
-            _ => anyhow::bail!("Invalid account key type"),
-        };
-
-        let issuer_id = match issuer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(pk.0)))
-            }
-            _ => anyhow::bail!("Invalid issuer key type"),
-        };
+        let account_id = stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            Self::parse_account_key(account_address, "account address")?,
+        )));
+        let issuer_id = stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            Self::parse_account_key(asset_issuer, "issuer address")?,
+        )));
 
         // Create TrustLineAsset
         let asset = if asset_code.len() <= 4 {
@@ -327,38 +2018,31 @@ impl SorobanRpc {
         };
 
         // Construct the ledger key for the trustline
-        let trustline_key =
-            stellar_xdr::curr::LedgerKey::Trustline(stellar_xdr::curr::LedgerKeyTrustLine {
+        let trustline_keys = [stellar_xdr::curr::LedgerKey::Trustline(
+            stellar_xdr::curr::LedgerKeyTrustLine {
                 account_id: account_id.clone(),
                 asset,
-            });
+            },
+        )];
 
-        // Try to get the trustline ledger entry
-        match self.client.get_ledger_entries(&[trustline_key]).await {
-            Ok(response) => {
-                if let Some(entries) = response.entries {
-                    if let Some(entry) = entries.first() {
-                        // Parse the trustline entry to get the balance
-                        let entry_data = stellar_xdr::curr::LedgerEntryData::from_xdr_base64(
-                            &entry.xdr,
-                            Limits::none(),
-                        )?;
-
-                        if let stellar_xdr::curr::LedgerEntryData::Trustline(trustline) = entry_data
-                        {
-                            // Return (true, balance)
-                            return Ok((true, trustline.balance));
-                        }
-                    }
-                }
-                // Trustline doesn't exist
-                Ok((false, 0))
-            }
-            Err(_) => {
-                // If the request failed or entry doesn't exist, trustline doesn't exist
-                Ok((false, 0))
+        // Get the trustline ledger entry; a failed request isn't a missing trustline
+        let response = self
+            .call(&self.pool(), "get ledger entries", |client| {
+                client.get_ledger_entries(&trustline_keys)
+            })
+            .await?;
+        if let Some(entry) = response.entries.and_then(|e| e.into_iter().next()) {
+            // Parse the trustline entry to get the balance
+            let entry_data =
+                stellar_xdr::curr::LedgerEntryData::from_xdr_base64(&entry.xdr, Limits::none())?;
+
+            if let stellar_xdr::curr::LedgerEntryData::Trustline(trustline) = entry_data {
+                // Return (true, balance)
+                return Ok((true, trustline.balance));
             }
         }
+        // Trustline doesn't exist
+        Ok((false, 0))
     }
 
     /// Get the network passphrase
@@ -367,14 +2051,138 @@ impl SorobanRpc {
     }
 
     /// Get a ledger entry by key (exposed for custom queries)
+    ///
+    /// Reads are cached per [`LedgerCacheOptions`].
     pub async fn get_ledger_entry(
         &self,
         key: stellar_xdr::curr::LedgerKey,
     ) -> Result<Option<LedgerEntryResult>> {
-        match self.client.get_ledger_entries(&[key]).await {
-            Ok(response) => Ok(response.entries.and_then(|e| e.into_iter().next())),
-            Err(_) => Ok(None),
+        self.read_ledger_entry(key).await
+    }
+
+    /// Get many ledger entries, in requests of up to 200 keys
+    ///
+    /// Duplicate keys are fetched once, and up to 4 requests run at a time. A failed
+    /// request doesn't fail the batch: its keys are reported in
+    /// [`LedgerEntriesBatch::failed`], so callers can tell a missing entry from an
+    /// unknown one. Only when every request fails is the first error returned.
+    pub async fn get_ledger_entries_batch(
+        &self,
+        keys: Vec<LedgerKey>,
+    ) -> Result<LedgerEntriesBatch> {
+        let chunks = ledger_key_chunks(keys);
+        let request_count = chunks.len();
+
+        let pool = self.pool();
+        let pool = &pool;
+        let mut responses = stream::iter(chunks)
+            .map(|chunk| async move {
+                let response = self
+                    .call(pool, "get ledger entries", |client| {
+                        client.get_ledger_entries(&chunk)
+                    })
+                    .await;
+                (chunk, response)
+            })
+            .buffer_unordered(LEDGER_ENTRY_REQUEST_CONCURRENCY);
+
+        let mut batch = LedgerEntriesBatch::default();
+        while let Some((chunk, response)) = responses.next().await {
+            match response {
+                Ok(response) => {
+                    for entry in response.entries.unwrap_or_default() {
+                        let key = LedgerKey::from_xdr_base64(&entry.key, Limits::none())?;
+                        batch.entries.insert(key, entry);
+                    }
+                }
+                Err(e) => {
+                    let index = batch.errors.len();
+                    batch
+                        .failed
+                        .extend(chunk.into_iter().map(|key| (key, index)));
+                    batch.errors.push(e);
+                }
+            }
+        }
+
+        if request_count > 0 && batch.errors.len() == request_count {
+            return Err(batch.errors.swap_remove(0));
+        }
+        Ok(batch)
+    }
+
+    /// Get the contract's events from `start_ledger` on, reading at most `max_pages` pages
+    ///
+    /// The node only keeps events for its retention window (about a day on testnet), so
+    /// older start ledgers are rejected by the RPC, unless a history endpoint is set (see
+    /// [`set_history_endpoint`](Self::set_history_endpoint)) to read them from instead.
+    pub async fn get_contract_events(
+        &self,
+        start_ledger: u32,
+        max_pages: usize,
+    ) -> Result<Vec<ContractEvent>> {
+        let pool = self.pool();
+        match self
+            .contract_events(&pool, None, start_ledger, max_pages)
+            .await
+        {
+            Err(e) if pool.history.is_some() && is_before_retention(&e.to_string()) => {
+                self.contract_events(&pool, pool.history.as_ref(), start_ledger, max_pages)
+                    .await
+            }
+            events => events,
+        }
+    }
+
+    /// Page through the contract's events on `node`, or the endpoints of `pool` if None
+    async fn contract_events(
+        &self,
+        pool: &RpcPool,
+        node: Option<&RpcNode>,
+        start_ledger: u32,
+        max_pages: usize,
+    ) -> Result<Vec<ContractEvent>> {
+        let contract_ids = [self.contract_id.to_string()];
+        let mut start = EventStart::Ledger(start_ledger);
+        let mut events = Vec::new();
+
+        for _ in 0..max_pages {
+            let response = self
+                .call_node(pool, node, "get events", |client| {
+                    client.get_events(
+                        start.clone(),
+                        Some(EventType::Contract),
+                        &contract_ids,
+                        &[],
+                        Some(EVENTS_PAGE_LIMIT),
+                    )
+                })
+                .await?;
+
+            let page_len = response.events.len();
+            let Some(last) = response.events.last() else {
+                break;
+            };
+            start = EventStart::Cursor(last.id.clone());
+
+            for event in response.events {
+                events.push(ContractEvent {
+                    ledger: event.ledger,
+                    topics: event
+                        .topic
+                        .iter()
+                        .map(|topic| ScVal::from_xdr_base64(topic, Limits::none()))
+                        .collect::<std::result::Result<_, _>>()?,
+                    value: ScVal::from_xdr_base64(&event.value, Limits::none())?,
+                });
+            }
+
+            if page_len < EVENTS_PAGE_LIMIT {
+                break;
+            }
         }
+
+        Ok(events)
     }
 
     /// Get the contract ID (exposed for building custom ledger keys)
@@ -389,15 +2197,47 @@ impl SorobanRpc {
     ///
     /// Returns the balance in stroops, or None if the account doesn't exist
     pub async fn get_xlm_balance(&self, account_address: &str) -> Result<Option<i64>> {
-        match self.client.get_account(account_address).await {
-            Ok(account) => {
-                // Balance is already an i64 in the account response
-                Ok(Some(account.balance))
-            }
-            Err(_) => {
-                // Account doesn't exist
-                Ok(None)
-            }
+        let account = self.get_account_entry(account_address).await?;
+        Ok(account.map(|account| account.balance))
+    }
+
+    /// Get the XLM an account can spend: its balance less the minimum reserve and what
+    /// its offers have locked up
+    ///
+    /// Returns stroops, or None if the account doesn't exist
+    pub async fn get_spendable_xlm(&self, account_address: &str) -> Result<Option<i64>> {
+        let account = self.get_account_entry(account_address).await?;
+        Ok(account.as_ref().map(spendable_xlm))
+    }
+
+    /// Read an account's entry, or None if the account doesn't exist
+    async fn get_account_entry(
+        &self,
+        account_address: &str,
+    ) -> Result<Option<stellar_xdr::curr::AccountEntry>> {
+        // Read the account entry directly: a missing entry means the account doesn't
+        // exist, while a failed request is passed on
+        let account_keys = [LedgerKey::Account(stellar_xdr::curr::LedgerKeyAccount {
+            account_id: stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+                Self::parse_account_key(account_address, "account address")?,
+            ))),
+        })];
+        let response = self
+            .call(&self.pool(), "get ledger entries", |client| {
+                client.get_ledger_entries(&account_keys)
+            })
+            .await?;
+        let Some(entry) = response.entries.and_then(|e| e.into_iter().next()) else {
+            return Ok(None);
+        };
+        let entry_data =
+            stellar_xdr::curr::LedgerEntryData::from_xdr_base64(&entry.xdr, Limits::none())?;
+        match entry_data {
+            stellar_xdr::curr::LedgerEntryData::Account(account) => Ok(Some(account)),
+            _ => Err(RpcError::UnexpectedEntry(format!(
+                "{} is not an account entry",
+                account_address
+            ))),
         }
     }
 
@@ -407,40 +2247,29 @@ impl SorobanRpc {
     /// * `source_account` - The public key of the account adding the trustline
     /// * `asset_code` - The asset code (e.g., "KALE")
     /// * `asset_issuer` - The asset issuer's public key
+    /// * `fee_source` - Another account to pay the fee, if the trusting account shouldn't
     pub async fn build_add_trustline_transaction(
         &self,
         source_account: &str,
         asset_code: &str,
         asset_issuer: &str,
+        fee_source: Option<&str>,
     ) -> Result<Transaction> {
         // Parse the source account public key
-        let source_strkey =
-            Strkey::from_string(source_account).context("Failed to parse source account")?;
-
-        let source_public_key = match source_strkey {
-            Strkey::PublicKeyEd25519(pk) => PublicKey::PublicKeyTypeEd25519(Uint256(pk.0)),
-            _ => anyhow::bail!("Invalid source account key type"),
-        };
+        let account_bytes = Self::parse_account_key(source_account, "source account")?;
+        let (payer, payer_bytes, operation_source) =
+            Self::fee_paying_source(source_account, account_bytes, fee_source)?;
 
         // Parse issuer
-        let issuer_strkey =
-            Strkey::from_string(asset_issuer).context("Failed to parse issuer address")?;
+        let issuer_id = stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            Self::parse_account_key(asset_issuer, "issuer address")?,
+        )));
 
-        let issuer_id = match issuer_strkey {
-            Strkey::PublicKeyEd25519(pk) => {
-                stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(pk.0)))
-            }
-            _ => anyhow::bail!("Invalid issuer key type"),
-        };
-
-        // Get the account sequence number
-        let account_response = self.client.get_account(source_account).await?;
-        let sequence = account_response.seq_num.0 as i64 + 1;
-
-        // Extract bytes from public key
-        let account_bytes = match source_public_key {
-            PublicKey::PublicKeyTypeEd25519(ref uint256) => uint256.0,
-        };
+        let intent = format!(
+            "trustline({}:{}:{})",
+            source_account, asset_code, asset_issuer
+        );
+        let sequence = self.next_sequence(payer, &intent).await?;
 
         // Create the asset (ChangeTrustAsset type for ChangeTrust operation)
         let asset = if asset_code.len() <= 4 {
@@ -477,7 +2306,7 @@ impl SorobanRpc {
 
         // Create ChangeTrust operation
         let operation = Operation {
-            source_account: None,
+            source_account: operation_source,
             body: OperationBody::ChangeTrust(stellar_xdr::curr::ChangeTrustOp {
                 line: asset,
                 limit: i64::MAX, // Maximum limit
@@ -486,7 +2315,7 @@ impl SorobanRpc {
 
         // Build the transaction
         let transaction = Transaction {
-            source_account: MuxedAccount::Ed25519(Uint256(account_bytes)),
+            source_account: MuxedAccount::Ed25519(Uint256(payer_bytes)),
             fee: 100, // Base fee for simple operations
             seq_num: SequenceNumber(sequence),
             cond: Preconditions::None,
@@ -497,4 +2326,414 @@ impl SorobanRpc {
 
         Ok(transaction)
     }
+
+    /// Build a transaction paying a credit asset to several accounts
+    ///
+    /// # Arguments
+    /// * `source_account` - The public key of the paying account
+    /// * `asset_code` - The asset code (e.g., "KALE", at most 4 characters)
+    /// * `asset_issuer` - The asset issuer's public key
+    /// * `payments` - (destination public key, amount in stroops) pairs, at most 100
+    /// * `fee_source` - Another account to pay the fee, if the paying account shouldn't
+    pub async fn build_payments_transaction(
+        &self,
+        source_account: &str,
+        asset_code: &str,
+        asset_issuer: &str,
+        payments: &[(String, i64)],
+        fee_source: Option<&str>,
+    ) -> Result<Transaction> {
+        if payments.is_empty() || payments.len() > 100 {
+            return Err(RpcError::InvalidInput(
+                "A transaction must contain between 1 and 100 payments".to_string(),
+            ));
+        }
+
+        // Parse the source account public key
+        let account_bytes = Self::parse_account_key(source_account, "source account")?;
+        let (payer, payer_bytes, operation_source) =
+            Self::fee_paying_source(source_account, account_bytes, fee_source)?;
+
+        // Parse issuer
+        let issuer_id = stellar_xdr::curr::AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            Self::parse_account_key(asset_issuer, "issuer address")?,
+        )));
+
+        if asset_code.len() > 4 {
+            return Err(RpcError::InvalidInput(
+                "Only 4-character asset codes are supported for payments".to_string(),
+            ));
+        }
+        let asset = stellar_xdr::curr::Asset::CreditAlphanum4(stellar_xdr::curr::AlphaNum4 {
+            asset_code: stellar_xdr::curr::AssetCode4(
+                asset_code
+                    .as_bytes()
+                    .iter()
+                    .chain(std::iter::repeat(&0u8))
+                    .take(4)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            ),
+            issuer: issuer_id,
+        });
+
+        let operations = payments
+            .iter()
+            .map(|(destination, amount)| {
+                let destination = MuxedAccount::Ed25519(Uint256(Self::parse_account_key(
+                    destination,
+                    "destination",
+                )?));
+
+                Ok(Operation {
+                    source_account: operation_source.clone(),
+                    body: OperationBody::Payment(stellar_xdr::curr::PaymentOp {
+                        destination,
+                        asset: asset.clone(),
+                        amount: *amount,
+                    }),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let intent = format!("payments({}:{:?})", source_account, payments);
+        let sequence = self.next_sequence(payer, &intent).await?;
+
+        // Build the transaction
+        let transaction = Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256(payer_bytes)),
+            fee: 100 * operations.len() as u32, // Base fee per operation
+            seq_num: SequenceNumber(sequence),
+            cond: Preconditions::None,
+            memo: stellar_xdr::curr::Memo::None,
+            operations: operations.try_into()?,
+            ext: TransactionExt::V0,
+        };
+
+        Ok(transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_xdr::curr::{AccountId, LedgerKeyAccount};
+
+    fn account_key(n: u32) -> LedgerKey {
+        let mut key = [0u8; 32];
+        key[..4].copy_from_slice(&n.to_be_bytes());
+        LedgerKey::Account(LedgerKeyAccount {
+            account_id: AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(key))),
+        })
+    }
+
+    #[test]
+    fn test_ledger_key_chunks_boundaries() {
+        let sizes = |count: u32| -> Vec<usize> {
+            ledger_key_chunks((0..count).map(account_key).collect())
+                .iter()
+                .map(Vec::len)
+                .collect()
+        };
+
+        assert_eq!(sizes(0), Vec::<usize>::new());
+        assert_eq!(sizes(1), vec![1]);
+        assert_eq!(sizes(199), vec![199]);
+        assert_eq!(sizes(200), vec![200]);
+        assert_eq!(sizes(201), vec![200, 1]);
+        assert_eq!(sizes(400), vec![200, 200]);
+        assert_eq!(sizes(401), vec![200, 200, 1]);
+
+        // Duplicates don't spill into an extra request
+        let keys = (0..200).chain(0..50).map(account_key).collect();
+        assert_eq!(ledger_key_chunks(keys).len(), 1);
+    }
+
+    #[test]
+    fn test_batch_failure_is_per_request() {
+        let batch = LedgerEntriesBatch {
+            entries: HashMap::new(),
+            failed: HashMap::from([(account_key(1), 0), (account_key(2), 1)]),
+            errors: vec![
+                RpcError::EntryUnavailable("first request".to_string()),
+                RpcError::EntryUnavailable("second request".to_string()),
+            ],
+        };
+
+        let reason = |n| batch.failure(&account_key(n)).map(|e| e.to_string());
+        assert!(reason(1).unwrap().contains("first request"));
+        assert!(reason(2).unwrap().contains("second request"));
+        assert_eq!(reason(3), None);
+    }
+
+    #[test]
+    fn test_wrap_fee_bump_keeps_inner_transaction() {
+        use stellar_xdr::curr::{
+            DecoratedSignature, Memo, Signature, SignatureHint, TransactionV1Envelope,
+        };
+
+        let farmer = stellar_strkey::ed25519::PublicKey([1; 32]).to_string();
+        let sponsor = stellar_strkey::ed25519::PublicKey([2; 32]).to_string();
+        let inner = |signatures: Vec<DecoratedSignature>| {
+            TransactionEnvelope::Tx(TransactionV1Envelope {
+                tx: Transaction {
+                    source_account: MuxedAccount::Ed25519(Uint256([1; 32])),
+                    fee: 300,
+                    seq_num: SequenceNumber(5),
+                    cond: Preconditions::None,
+                    memo: Memo::None,
+                    operations: Default::default(),
+                    ext: TransactionExt::V0,
+                },
+                signatures: signatures.try_into().unwrap(),
+            })
+            .to_xdr_base64(Limits::none())
+            .unwrap()
+        };
+        let signed = inner(vec![DecoratedSignature {
+            hint: SignatureHint([0; 4]),
+            signature: Signature(vec![0; 64].try_into().unwrap()),
+        }]);
+
+        // Unsigned inner transactions and fees below the inner rate for two operations fail
+        assert!(SorobanRpc::wrap_fee_bump(&inner(vec![]), &sponsor, 1000).is_err());
+        assert!(SorobanRpc::wrap_fee_bump(&signed, &sponsor, 599).is_err());
+
+        let wrapped = SorobanRpc::wrap_fee_bump(&signed, &sponsor, 600).unwrap();
+        let TransactionEnvelope::TxFeeBump(envelope) =
+            TransactionEnvelope::from_xdr_base64(&wrapped, Limits::none()).unwrap()
+        else {
+            panic!("expected a fee bump envelope");
+        };
+        assert_eq!(envelope.tx.fee, 600);
+        assert_eq!(
+            envelope.tx.fee_source,
+            MuxedAccount::Ed25519(Uint256([2; 32]))
+        );
+        assert!(envelope.signatures.is_empty());
+
+        // The farmer, not the sponsor, is still who the transaction is for
+        assert_eq!(
+            SorobanRpc::transaction_source_account(&wrapped).unwrap(),
+            farmer
+        );
+        assert_eq!(SorobanRpc::transaction_farmer(&wrapped).unwrap(), farmer);
+    }
+
+    #[test]
+    fn test_retry_only_transient_failures_with_growing_delays() {
+        assert!(is_transient_failure(
+            "HTTP status server error (503 Service Unavailable)"
+        ));
+        assert!(is_transient_failure("Too Many Requests"));
+        assert!(is_transient_failure(
+            "error trying to connect: Connection refused"
+        ));
+        assert!(is_transient_failure("Request timeout"));
+        assert!(!is_transient_failure("Account not found: GABC"));
+        assert!(!is_transient_failure(
+            "invalid params: start ledger out of range"
+        ));
+
+        // Requests before the retention window go to the history endpoint instead
+        assert!(is_before_retention(
+            "invalid params: start ledger out of range"
+        ));
+        assert!(is_before_retention(
+            "startLedger must be between the oldest ledger: 100 and the latest ledger: 200"
+        ));
+        assert!(!is_before_retention("Account not found: GABC"));
+
+        let policy = RetryPolicy::default();
+        let first = policy.delay(1);
+        assert!(first >= policy.base_delay / 2 && first <= policy.base_delay);
+        let late = policy.delay(10);
+        assert!(late >= policy.max_delay / 2 && late <= policy.max_delay);
+    }
+
+    #[test]
+    fn test_instance_storage_from_entry() {
+        use stellar_xdr::curr::{
+            ContractDataDurability, ContractDataEntry, ContractExecutable, ExtensionPoint,
+            Int128Parts, LedgerEntryData, ScContractInstance, ScMapEntry, ScSymbol,
+        };
+
+        let key = |name: &str, args: Vec<ScVal>| {
+            let mut items = vec![ScVal::Symbol(ScSymbol(name.try_into().unwrap()))];
+            items.extend(args);
+            ScVal::Vec(Some(items.try_into().unwrap()))
+        };
+        let storage = vec![
+            ScMapEntry {
+                key: key("FarmIndex", vec![]),
+                val: ScVal::U32(1234),
+            },
+            ScMapEntry {
+                key: key("FarmBlock", vec![]),
+                val: ScVal::I128(Int128Parts { hi: 1, lo: 0 }),
+            },
+            ScMapEntry {
+                key: key("Block", vec![ScVal::U32(12)]),
+                val: ScVal::Bytes(vec![0xab, 0xcd].try_into().unwrap()),
+            },
+        ];
+        let entry = LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([7; 32])),
+            key: ScVal::LedgerKeyContractInstance,
+            durability: ContractDataDurability::Persistent,
+            val: ScVal::ContractInstance(ScContractInstance {
+                executable: ContractExecutable::Wasm(Hash([1; 32])),
+                storage: Some(storage.try_into().unwrap()),
+            }),
+        });
+        let xdr = entry.to_xdr_base64(Limits::none()).unwrap();
+
+        let storage = InstanceStorage::from_entry_xdr(&xdr, 99).unwrap();
+        assert_eq!(storage.last_modified_ledger, 99);
+        assert_eq!(storage.entries["FarmIndex"], ScVal::U32(1234));
+        assert_eq!(
+            storage.to_json(),
+            serde_json::json!({
+                "Block(12)": "abcd",
+                "FarmBlock": "18446744073709551616",
+                "FarmIndex": 1234,
+            })
+        );
+
+        // Any other entry is not an instance
+        let not_instance = LedgerEntryData::ContractData(ContractDataEntry {
+            ext: ExtensionPoint::V0,
+            contract: ScAddress::Contract(Hash([7; 32])),
+            key: ScVal::U32(1),
+            durability: ContractDataDurability::Temporary,
+            val: ScVal::U32(1),
+        });
+        let xdr = not_instance.to_xdr_base64(Limits::none()).unwrap();
+        assert!(InstanceStorage::from_entry_xdr(&xdr, 99).is_err());
+    }
+
+    #[test]
+    fn test_total_fee_rejects_overflow() {
+        assert_eq!(total_fee(1_000, BASE_FEE).unwrap(), 1_100);
+        assert_eq!(total_fee(u32::MAX as u64 - 100, 100).unwrap(), u32::MAX);
+
+        // Too big for u32 on its own, and only once the inclusion fee is added
+        for (resource_fee, inclusion_fee) in [(u32::MAX as u64 + 1, 100), (u32::MAX as u64, 1)] {
+            assert!(matches!(
+                total_fee(resource_fee, inclusion_fee),
+                Err(RpcError::FeeOverflow { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_spendable_xlm() {
+        use stellar_xdr::curr::{
+            AccountEntry, AccountEntryExt, AccountEntryExtensionV1, AccountEntryExtensionV1Ext,
+            AccountEntryExtensionV2, AccountEntryExtensionV2Ext, AccountId, Liabilities,
+            Thresholds,
+        };
+
+        let mut account = AccountEntry {
+            account_id: AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([1; 32]))),
+            balance: 100_000_000,
+            seq_num: SequenceNumber(1),
+            num_sub_entries: 1,
+            inflation_dest: None,
+            flags: 0,
+            home_domain: Default::default(),
+            thresholds: Thresholds([1, 0, 0, 0]),
+            signers: Default::default(),
+            ext: AccountEntryExt::V0,
+        };
+        // 10 XLM less 1.5 XLM of reserve: the account and a trustline
+        assert_eq!(spendable_xlm(&account), 85_000_000);
+
+        account.ext = AccountEntryExt::V1(AccountEntryExtensionV1 {
+            liabilities: Liabilities {
+                buying: 0,
+                selling: 20_000_000,
+            },
+            ext: AccountEntryExtensionV1Ext::V2(AccountEntryExtensionV2 {
+                num_sponsored: 1,
+                num_sponsoring: 0,
+                signer_sponsoring_i_ds: Default::default(),
+                ext: AccountEntryExtensionV2Ext::V0,
+            }),
+        });
+        assert_eq!(spendable_xlm(&account), 70_000_000);
+
+        account.balance = 10_000_000;
+        assert_eq!(spendable_xlm(&account), 0);
+    }
+
+    #[test]
+    fn test_fee_escalation() {
+        let escalation = FeeEscalation {
+            percent_per_retry: 20,
+            max_inclusion_fee: 150,
+        };
+        let bids: Vec<_> = (0..4)
+            .map(|retry| escalation.inclusion_fee(100, retry))
+            .collect();
+        assert_eq!(bids, vec![100, 120, 144, 150]);
+        // The cap only limits escalation, never the first bid
+        assert_eq!(escalation.inclusion_fee(500, 3), 500);
+        assert_eq!(FeeEscalation::default().inclusion_fee(100, 5), 100);
+    }
+
+    #[test]
+    fn test_check_passphrase() {
+        let testnet = "Test SDF Network ; September 2015";
+        let mainnet = "Public Global Stellar Network ; September 2015";
+        assert!(check_passphrase("https://rpc", testnet, testnet).is_ok());
+
+        let error = check_passphrase("https://rpc", testnet, mainnet).unwrap_err();
+        assert!(matches!(error, RpcError::NetworkMismatch { .. }));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "RPC endpoint https://rpc is on network \"{}\", but the configured passphrase is \"{}\"",
+                mainnet, testnet
+            )
+        );
+    }
+
+    #[test]
+    fn test_rebroadcast_audit_log() {
+        let path =
+            std::env::temp_dir().join(format!("kale-rebroadcast-audit-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let policy = RebroadcastPolicy {
+            audit_log: Some(path.clone()),
+            ..RebroadcastPolicy::default()
+        };
+
+        for (event, attempt) in [("rebroadcast", Some(1)), ("SUCCESS", None)] {
+            policy.audit(RebroadcastRecord {
+                at: 1700000000,
+                hash: "abcd",
+                event,
+                attempt,
+                error: None,
+            });
+        }
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::json!({"at": 1700000000, "hash": "abcd", "event": "rebroadcast", "attempt": 1}),
+                serde_json::json!({"at": 1700000000, "hash": "abcd", "event": "SUCCESS"}),
+            ]
+        );
+    }
 }
@@ -0,0 +1,252 @@
+use crate::farmer::mine;
+use crate::headless::{check_success, FarmCall, HeadlessFarmer};
+use crate::sign_agent::Keys;
+use anyhow::{Context, Result};
+use galacticplayground::config::Config;
+use galacticplayground::rpc::SorobanRpc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// How often `wait_block` checks whether the farm has moved to a new block
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A sequence of farming calls, recorded by `--record` and replayed by `script run`
+///
+/// ```yaml
+/// steps:
+///   - step: plant
+///     amount: 10000000
+///   - step: mine
+///     secs: 30
+///   - step: work
+///   - step: wait_block
+///   - step: harvest
+/// ```
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// Plant in the current block; stake in stroops
+    Plant { amount: i64 },
+    /// Mine the current block for `secs`, or until a hash has `target_zeros` leading zeros
+    Mine {
+        secs: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target_zeros: Option<u32>,
+    },
+    /// Submit work; the nonce and block of the last `mine` step if left out
+    Work {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        block: Option<u32>,
+    },
+    /// Harvest a block; the one last worked if left out
+    Harvest {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        block: Option<u32>,
+    },
+    /// Wait until the farm moves to a new block
+    WaitBlock,
+}
+
+impl Step {
+    /// The step that replays a call made from the command line
+    pub fn from_call(call: &FarmCall) -> Result<Self> {
+        Ok(match *call {
+            FarmCall::Plant(amount) => Step::Plant {
+                amount: i64::try_from(amount).context("Stake is too large to record")?,
+            },
+            FarmCall::Work(nonce, block) => Step::Work {
+                nonce: Some(nonce),
+                block,
+            },
+            FarmCall::Harvest(block) => Step::Harvest { block: Some(block) },
+        })
+    }
+}
+
+impl Script {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("Invalid script {}", path.display()))
+    }
+
+    /// Append a step to the script at `path`, creating it if it doesn't exist
+    pub fn record(path: &Path, step: Step) -> Result<()> {
+        let mut script = if path.exists() {
+            Self::from_file(path)?
+        } else {
+            Self::default()
+        };
+        script.steps.push(step);
+        std::fs::write(path, serde_yaml::to_string(&script)?)
+            .with_context(|| format!("Failed to write script {}", path.display()))
+    }
+}
+
+/// Run a script's steps in order with a local secret key, stopping at the first that fails
+///
+/// Runs against whichever network and config the command line picks, so a script recorded
+/// on testnet can be replayed on futurenet or a local network. With `record`, each step
+/// that succeeds is appended to that script, with the nonces and blocks it resolved to.
+pub async fn run(
+    config: &Config,
+    keys: Keys,
+    script: &Script,
+    record: Option<&Path>,
+) -> Result<()> {
+    let farmer = HeadlessFarmer::new(config, keys).await?;
+    println!("Farmer: {}", farmer.farmer());
+
+    // The last nonce mined and the block it was for, and the last block worked
+    let mut mined: Option<(u64, u32)> = None;
+    let mut worked: Option<u32> = None;
+    for (number, step) in script.steps.iter().enumerate() {
+        println!(
+            "\nStep {} of {}: {:?}",
+            number + 1,
+            script.steps.len(),
+            step
+        );
+        let resolved = match step {
+            Step::Plant { amount } => {
+                execute(&farmer, FarmCall::Plant(i128::from(*amount))).await?;
+                step.clone()
+            }
+            Step::Mine { secs, target_zeros } => {
+                let (block_index, entropy) = farmer.kale().get_block_info().await?;
+                let entropy = entropy.context("Nobody has planted in the current block yet")?;
+                let farmer_key = SorobanRpc::parse_account_key(&farmer.farmer(), "farmer")?;
+                let budget = Duration::from_secs(*secs);
+                let target = target_zeros.unwrap_or(u32::MAX);
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                let (nonce, zeros, _) = tokio::task::spawn_blocking(move || {
+                    mine(block_index, &entropy, &farmer_key, target, budget, threads)
+                })
+                .await?;
+                println!("Mined nonce {} with {} zeros", nonce, zeros);
+                mined = Some((nonce, block_index));
+                step.clone()
+            }
+            Step::Work { nonce, block } => {
+                let (nonce, block) = match (nonce, mined) {
+                    (Some(nonce), _) => (*nonce, *block),
+                    (None, Some((nonce, mined_block))) => (nonce, block.or(Some(mined_block))),
+                    (None, None) => anyhow::bail!(
+                        "Step {}: work has no nonce and nothing was mined before it",
+                        number + 1
+                    ),
+                };
+                execute(&farmer, FarmCall::Work(nonce, block)).await?;
+                worked = Some(match block {
+                    Some(block) => block,
+                    None => farmer.kale().get_block_index().await?,
+                });
+                Step::Work {
+                    nonce: Some(nonce),
+                    block,
+                }
+            }
+            Step::Harvest { block } => {
+                let block = block.or(worked).with_context(|| {
+                    format!(
+                        "Step {}: harvest has no block and none was worked before it",
+                        number + 1
+                    )
+                })?;
+                execute(&farmer, FarmCall::Harvest(block)).await?;
+                Step::Harvest { block: Some(block) }
+            }
+            Step::WaitBlock => {
+                let start = farmer.kale().get_block_index().await?;
+                println!("Waiting for block {} to end...", start);
+                while farmer.kale().get_block_index().await? == start {
+                    tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+                }
+                step.clone()
+            }
+        };
+        if let Some(path) = record {
+            Script::record(path, resolved)?;
+        }
+    }
+
+    println!("\n✓ Ran {} steps", script.steps.len());
+    Ok(())
+}
+
+/// Run a farming call, failing unless it succeeds
+async fn execute(farmer: &HeadlessFarmer, call: FarmCall) -> Result<()> {
+    let submitted = farmer.execute(&call, 0).await?;
+    println!("Transaction hash: {}", submitted.hash);
+    check_success(call.name(), &submitted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_round_trips_through_yaml() {
+        let script: Script = serde_yaml::from_str(
+            "
+            steps:
+              - step: plant
+                amount: 10000000
+              - step: mine
+                secs: 30
+              - step: work
+              - step: wait_block
+              - step: harvest
+                block: 42
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            script.steps,
+            vec![
+                Step::Plant { amount: 10_000_000 },
+                Step::Mine {
+                    secs: 30,
+                    target_zeros: None
+                },
+                Step::Work {
+                    nonce: None,
+                    block: None
+                },
+                Step::WaitBlock,
+                Step::Harvest { block: Some(42) },
+            ]
+        );
+        let yaml = serde_yaml::to_string(&script).unwrap();
+        assert_eq!(serde_yaml::from_str::<Script>(&yaml).unwrap(), script);
+
+        assert!(serde_yaml::from_str::<Script>("steps:\n  - step: plant\n    amout: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_record_appends_steps() {
+        let path = std::env::temp_dir().join(format!("kale-script-{}.yaml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        Script::record(&path, Step::from_call(&FarmCall::Plant(100)).unwrap()).unwrap();
+        Script::record(&path, Step::from_call(&FarmCall::Harvest(7)).unwrap()).unwrap();
+        let script = Script::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            script.steps,
+            vec![
+                Step::Plant { amount: 100 },
+                Step::Harvest { block: Some(7) }
+            ]
+        );
+    }
+}
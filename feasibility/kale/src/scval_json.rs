@@ -0,0 +1,400 @@
+use serde_json::{Map, Value};
+use stellar_strkey::Strkey;
+use stellar_xdr::curr::{
+    AccountId, Duration, Hash, Int128Parts, Int256Parts, PublicKey, ScAddress, ScMap,
+    ScSpecTypeDef, ScString, ScSymbol, ScVal, ScVec, TimePoint, UInt128Parts, UInt256Parts,
+    Uint256,
+};
+use thiserror::Error;
+
+use crate::diagnostics::describe_value;
+use crate::spec::type_name;
+
+/// Why JSON couldn't be converted to an ScVal of the wanted type
+#[derive(Debug, Error)]
+pub enum ScValJsonError {
+    #[error("Expected {expected}, got {value}")]
+    Mismatch { expected: String, value: Value },
+
+    /// User-defined types, `Val` and `Result` have no single JSON form
+    #[error("Can't convert JSON to {0} without its definition")]
+    Unsupported(String),
+
+    /// A string or collection over the XDR size limits
+    #[error("XDR error: {0}")]
+    Xdr(#[from] stellar_xdr::curr::Error),
+}
+
+/// Convert an ScVal to JSON, e.g. to return decoded contract data instead of XDR
+///
+/// Integers wider than 32 bits are decimal strings, since JavaScript can't represent them
+/// exactly. Bytes are hex, addresses strkeys, `Void` is null and map keys are their JSON as
+/// text. Values with no JSON form (errors, contract instances) become their compact
+/// description.
+pub fn to_json(value: &ScVal) -> Value {
+    match value {
+        ScVal::Void => Value::Null,
+        ScVal::Bool(value) => Value::Bool(*value),
+        ScVal::U32(value) => Value::from(*value),
+        ScVal::I32(value) => Value::from(*value),
+        ScVal::U64(value) => Value::String(value.to_string()),
+        ScVal::I64(value) => Value::String(value.to_string()),
+        ScVal::Timepoint(value) => Value::String(value.0.to_string()),
+        ScVal::Duration(value) => Value::String(value.0.to_string()),
+        ScVal::U128(value) => {
+            Value::String(((u128::from(value.hi) << 64) | u128::from(value.lo)).to_string())
+        }
+        ScVal::I128(value) => {
+            Value::String(((i128::from(value.hi) << 64) | i128::from(value.lo)).to_string())
+        }
+        ScVal::U256(value) => Value::String(u256_decimal([
+            value.hi_hi,
+            value.hi_lo,
+            value.lo_hi,
+            value.lo_lo,
+        ])),
+        ScVal::I256(value) => {
+            let limbs = [value.hi_hi as u64, value.hi_lo, value.lo_hi, value.lo_lo];
+            Value::String(if value.hi_hi < 0 {
+                format!("-{}", u256_decimal(negate(limbs)))
+            } else {
+                u256_decimal(limbs)
+            })
+        }
+        ScVal::Bytes(bytes) => Value::String(hex::encode(bytes.as_slice())),
+        ScVal::String(string) => Value::String(string.to_utf8_string_lossy()),
+        ScVal::Symbol(symbol) => Value::String(symbol.to_utf8_string_lossy()),
+        ScVal::Vec(Some(items)) => items.iter().map(to_json).collect(),
+        ScVal::Map(Some(map)) => map
+            .iter()
+            .map(|entry| (key_text(&entry.key), to_json(&entry.val)))
+            .collect::<Map<_, _>>()
+            .into(),
+        // Addresses are described as their strkey
+        other => Value::String(describe_value(other)),
+    }
+}
+
+/// Convert JSON to an ScVal of a contract spec type, the reverse of [`to_json`]
+///
+/// Integers may be JSON numbers or decimal strings. Map entries are sorted by key, as the
+/// host requires.
+pub fn from_json(value: &Value, type_def: &ScSpecTypeDef) -> Result<ScVal, ScValJsonError> {
+    let mismatch = || ScValJsonError::Mismatch {
+        expected: type_name(type_def),
+        value: value.clone(),
+    };
+
+    let sc_val = match type_def {
+        ScSpecTypeDef::Void if value.is_null() => ScVal::Void,
+        ScSpecTypeDef::Bool => ScVal::Bool(value.as_bool().ok_or_else(mismatch)?),
+        ScSpecTypeDef::U32 => ScVal::U32(integer(value).ok_or_else(mismatch)?),
+        ScSpecTypeDef::I32 => ScVal::I32(integer(value).ok_or_else(mismatch)?),
+        ScSpecTypeDef::U64 => ScVal::U64(integer(value).ok_or_else(mismatch)?),
+        ScSpecTypeDef::I64 => ScVal::I64(integer(value).ok_or_else(mismatch)?),
+        ScSpecTypeDef::Timepoint => {
+            ScVal::Timepoint(TimePoint(integer(value).ok_or_else(mismatch)?))
+        }
+        ScSpecTypeDef::Duration => ScVal::Duration(Duration(integer(value).ok_or_else(mismatch)?)),
+        ScSpecTypeDef::U128 => {
+            let value: u128 = integer(value).ok_or_else(mismatch)?;
+            ScVal::U128(UInt128Parts {
+                hi: (value >> 64) as u64,
+                lo: value as u64,
+            })
+        }
+        ScSpecTypeDef::I128 => {
+            let value: i128 = integer(value).ok_or_else(mismatch)?;
+            ScVal::I128(Int128Parts {
+                hi: (value >> 64) as i64,
+                lo: value as u64,
+            })
+        }
+        ScSpecTypeDef::U256 => {
+            let [hi_hi, hi_lo, lo_hi, lo_lo] =
+                decimal(value).and_then(parse_u256).ok_or_else(mismatch)?;
+            ScVal::U256(UInt256Parts {
+                hi_hi,
+                hi_lo,
+                lo_hi,
+                lo_lo,
+            })
+        }
+        ScSpecTypeDef::I256 => {
+            let [hi_hi, hi_lo, lo_hi, lo_lo] =
+                decimal(value).and_then(parse_i256).ok_or_else(mismatch)?;
+            ScVal::I256(Int256Parts {
+                hi_hi: hi_hi as i64,
+                hi_lo,
+                lo_hi,
+                lo_lo,
+            })
+        }
+        ScSpecTypeDef::Bytes => ScVal::Bytes(hex_bytes(value).ok_or_else(mismatch)?.try_into()?),
+        ScSpecTypeDef::BytesN(bytes_n) => {
+            let bytes = hex_bytes(value)
+                .filter(|bytes| bytes.len() == bytes_n.n as usize)
+                .ok_or_else(mismatch)?;
+            ScVal::Bytes(bytes.try_into()?)
+        }
+        ScSpecTypeDef::String => {
+            ScVal::String(ScString(value.as_str().ok_or_else(mismatch)?.try_into()?))
+        }
+        ScSpecTypeDef::Symbol => {
+            ScVal::Symbol(ScSymbol(value.as_str().ok_or_else(mismatch)?.try_into()?))
+        }
+        ScSpecTypeDef::Address => ScVal::Address(address(value).ok_or_else(mismatch)?),
+        ScSpecTypeDef::Option(_) if value.is_null() => ScVal::Void,
+        ScSpecTypeDef::Option(option) => from_json(value, &option.value_type)?,
+        ScSpecTypeDef::Vec(vec_type) => {
+            let items = value
+                .as_array()
+                .ok_or_else(mismatch)?
+                .iter()
+                .map(|item| from_json(item, &vec_type.element_type))
+                .collect::<Result<Vec<_>, _>>()?;
+            ScVal::Vec(Some(ScVec(items.try_into()?)))
+        }
+        ScSpecTypeDef::Tuple(tuple) => {
+            let items = value
+                .as_array()
+                .filter(|items| items.len() == tuple.value_types.len())
+                .ok_or_else(mismatch)?
+                .iter()
+                .zip(tuple.value_types.iter())
+                .map(|(item, type_def)| from_json(item, type_def))
+                .collect::<Result<Vec<_>, _>>()?;
+            ScVal::Vec(Some(ScVec(items.try_into()?)))
+        }
+        ScSpecTypeDef::Map(map) => {
+            let entries = value
+                .as_object()
+                .ok_or_else(mismatch)?
+                .iter()
+                .map(|(key, value)| {
+                    let key = from_json(&Value::String(key.clone()), &map.key_type)?;
+                    Ok((key, from_json(value, &map.value_type)?))
+                })
+                .collect::<Result<Vec<_>, ScValJsonError>>()?;
+            ScVal::Map(Some(ScMap::sorted_from_pairs::<ScVal, ScVal, _>(entries)?))
+        }
+        ScSpecTypeDef::Val
+        | ScSpecTypeDef::Udt(_)
+        | ScSpecTypeDef::Result(_)
+        | ScSpecTypeDef::Error => return Err(ScValJsonError::Unsupported(type_name(type_def))),
+        ScSpecTypeDef::Void => return Err(mismatch()),
+    };
+    Ok(sc_val)
+}
+
+/// A map key as object key text: strings as they are, anything else as its JSON
+fn key_text(key: &ScVal) -> String {
+    match to_json(key) {
+        Value::String(text) => text,
+        other => other.to_string(),
+    }
+}
+
+/// An integer given as a JSON number or a decimal string
+fn integer<T: std::str::FromStr>(value: &Value) -> Option<T> {
+    match value {
+        Value::Number(number) => number.to_string().parse().ok(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The decimal digits of an integer too wide for a JSON number
+fn decimal(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(number) if number.is_i64() || number.is_u64() => Some(number.to_string()),
+        Value::String(text) => Some(text.clone()),
+        _ => None,
+    }
+}
+
+fn hex_bytes(value: &Value) -> Option<Vec<u8>> {
+    hex::decode(value.as_str()?).ok()
+}
+
+/// A G... account or C... contract strkey
+fn address(value: &Value) -> Option<ScAddress> {
+    match Strkey::from_string(value.as_str()?).ok()? {
+        Strkey::PublicKeyEd25519(key) => Some(ScAddress::Account(AccountId(
+            PublicKey::PublicKeyTypeEd25519(Uint256(key.0)),
+        ))),
+        Strkey::Contract(contract) => Some(ScAddress::Contract(Hash(contract.0))),
+        _ => None,
+    }
+}
+
+/// A 256-bit unsigned integer, as big-endian 64-bit limbs, in decimal
+fn u256_decimal(mut limbs: [u64; 4]) -> String {
+    let mut digits = Vec::new();
+    loop {
+        // Divide by 10, carrying the remainder down from the most significant limb
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut() {
+            let current = (remainder << 64) | u128::from(*limb);
+            *limb = (current / 10) as u64;
+            remainder = current % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+        if limbs == [0; 4] {
+            break;
+        }
+    }
+    digits
+        .iter()
+        .rev()
+        .map(|&digit| char::from(digit))
+        .collect()
+}
+
+/// Parse a decimal 256-bit unsigned integer into big-endian 64-bit limbs
+fn parse_u256(text: String) -> Option<[u64; 4]> {
+    if text.is_empty() {
+        return None;
+    }
+    let mut limbs = [0u64; 4];
+    for c in text.chars() {
+        // Multiply by 10 and add the digit, carrying up from the least significant limb
+        let mut carry = u128::from(c.to_digit(10)?);
+        for limb in limbs.iter_mut().rev() {
+            let current = u128::from(*limb) * 10 + carry;
+            *limb = current as u64;
+            carry = current >> 64;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(limbs)
+}
+
+/// Parse a decimal 256-bit signed integer into big-endian two's complement limbs
+fn parse_i256(text: String) -> Option<[u64; 4]> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits.to_string()),
+        None => (false, text),
+    };
+    let magnitude = parse_u256(digits)?;
+    let limbs = if negative {
+        negate(magnitude)
+    } else {
+        magnitude
+    };
+    // The sign bit has to come out as asked for, or the magnitude was out of range
+    let is_negative = limbs[0] >> 63 == 1;
+    (is_negative == (negative && magnitude != [0; 4])).then_some(limbs)
+}
+
+/// Two's complement negation of big-endian limbs
+fn negate(limbs: [u64; 4]) -> [u64; 4] {
+    let mut negated = limbs.map(|limb| !limb);
+    for limb in negated.iter_mut().rev() {
+        let (sum, overflow) = limb.overflowing_add(1);
+        *limb = sum;
+        if !overflow {
+            break;
+        }
+    }
+    negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use stellar_xdr::curr::{ScSpecTypeMap, ScSpecTypeOption, ScSpecTypeUdt, ScSpecTypeVec};
+
+    fn round_trip(value: &Value, type_def: &ScSpecTypeDef) -> Value {
+        to_json(&from_json(value, type_def).unwrap())
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let account = stellar_strkey::ed25519::PublicKey([1; 32]).to_string();
+        let contract = stellar_strkey::Contract([2; 32]).to_string();
+        let max_u256 = u256_decimal([u64::MAX; 4]);
+        let min_i256 = format!("-{}", u256_decimal([1 << 63, 0, 0, 0]));
+        for (value, type_def) in [
+            (json!(7), ScSpecTypeDef::U32),
+            (json!(-7), ScSpecTypeDef::I32),
+            (
+                json!("-170141183460469231731687303715884105728"),
+                ScSpecTypeDef::I128,
+            ),
+            (
+                json!("340282366920938463463374607431768211455"),
+                ScSpecTypeDef::U128,
+            ),
+            (json!(max_u256), ScSpecTypeDef::U256),
+            (json!(min_i256), ScSpecTypeDef::I256),
+            (json!("-1"), ScSpecTypeDef::I256),
+            (json!("abcd"), ScSpecTypeDef::Bytes),
+            (json!(account), ScSpecTypeDef::Address),
+            (json!(contract), ScSpecTypeDef::Address),
+            (json!("FarmIndex"), ScSpecTypeDef::Symbol),
+            (json!(null), ScSpecTypeDef::Void),
+        ] {
+            assert_eq!(
+                round_trip(&value, &type_def),
+                value,
+                "{}",
+                type_name(&type_def)
+            );
+        }
+
+        // Numbers wider than 32 bits come back as strings
+        assert_eq!(round_trip(&json!(5), &ScSpecTypeDef::U64), json!("5"));
+        assert_eq!(
+            u256_decimal([0, 0, 1, 0]),
+            (u128::from(u64::MAX) + 1).to_string()
+        );
+
+        let stakes = ScSpecTypeDef::Map(Box::new(ScSpecTypeMap {
+            key_type: Box::new(ScSpecTypeDef::U32),
+            value_type: Box::new(ScSpecTypeDef::Option(Box::new(ScSpecTypeOption {
+                value_type: Box::new(ScSpecTypeDef::I128),
+            }))),
+        }));
+        let value = json!({ "12": "5000000", "3": null });
+        let ScVal::Map(Some(map)) = from_json(&value, &stakes).unwrap() else {
+            panic!("expected a map");
+        };
+        // Sorted by the key's value, not its text
+        assert_eq!(map[0].key, ScVal::U32(3));
+        assert_eq!(to_json(&ScVal::Map(Some(map))), value);
+    }
+
+    #[test]
+    fn test_from_json_rejects() {
+        let vec_of_u32 = ScSpecTypeDef::Vec(Box::new(ScSpecTypeVec {
+            element_type: Box::new(ScSpecTypeDef::U32),
+        }));
+        assert!(matches!(
+            from_json(&json!([1, -2]), &vec_of_u32),
+            Err(ScValJsonError::Mismatch { .. })
+        ));
+        assert!(from_json(&json!(u64::from(u32::MAX) + 1), &ScSpecTypeDef::U32).is_err());
+        assert!(from_json(&json!(1.5), &ScSpecTypeDef::I128).is_err());
+        assert!(from_json(&json!("0x12"), &ScSpecTypeDef::U256).is_err());
+        assert!(from_json(
+            &json!(u256_decimal([1 << 63, 0, 0, 0])),
+            &ScSpecTypeDef::I256
+        )
+        .is_err());
+        assert!(from_json(&json!("abc"), &ScSpecTypeDef::Bytes).is_err());
+        assert!(from_json(&json!("GABC"), &ScSpecTypeDef::Address).is_err());
+        assert!(from_json(&json!(0), &ScSpecTypeDef::Void).is_err());
+
+        let udt = ScSpecTypeDef::Udt(ScSpecTypeUdt {
+            name: "Pail".try_into().unwrap(),
+        });
+        assert!(matches!(
+            from_json(&json!({}), &udt),
+            Err(ScValJsonError::Unsupported(name)) if name == "Pail"
+        ));
+    }
+}
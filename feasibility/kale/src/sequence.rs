@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a prepared transaction holds its sequence number
+///
+/// Long enough to sign in a wallet; an abandoned transaction stops blocking others after.
+pub const RESERVATION_TTL: Duration = Duration::from_secs(60);
+
+/// Most sequence numbers one account may have held at once
+///
+/// Each needs the ones before it to land first, so past this many a new transaction would
+/// mostly wait on abandoned ones.
+pub const MAX_RESERVATIONS: usize = 16;
+
+/// A sequence number handed out to a prepared transaction that hasn't landed yet
+#[derive(Debug, Clone)]
+struct Reservation {
+    sequence: i64,
+    /// What the transaction does; preparing the same thing again takes over the reservation.
+    /// None once the transaction was submitted, so the number isn't handed out again.
+    intent: Option<String>,
+    reserved: Instant,
+}
+
+/// Every sequence number the account can take is held by other prepared transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceContention {
+    pub account: String,
+    /// The oldest held number, the first to be freed
+    pub sequence: i64,
+    /// When that hold runs out, if its transaction hasn't landed by then
+    pub retry_after_secs: u64,
+}
+
+/// Hands out consecutive sequence numbers per account to prepared transactions
+///
+/// Two transactions prepared for one account before either lands would otherwise get the
+/// same sequence number, so whichever is submitted second fails with `txBadSeq`. Instead
+/// each gets its own: the lowest number above the account's that no other transaction
+/// holds. The manager is shared by every session of a server, so a prepare from one device
+/// sees what another device prepared, and a shared fee source can pay for many farmers at
+/// once. Reservations live in memory only.
+#[derive(Debug, Default)]
+pub struct SequenceManager {
+    reservations: Mutex<HashMap<String, Vec<Reservation>>>,
+}
+
+impl SequenceManager {
+    /// Reserve a sequence number after `current`, the account's sequence on the ledger
+    ///
+    /// A reservation ends when the ledger reaches it (the transaction landed), when it's
+    /// released, or after [`RESERVATION_TTL`]. Preparing the same `intent` again reuses its
+    /// number. Fails only while [`MAX_RESERVATIONS`] numbers are held for the account.
+    pub fn allocate(
+        &self,
+        account: &str,
+        current: i64,
+        intent: &str,
+    ) -> Result<i64, SequenceContention> {
+        let mut reservations = self.reservations.lock().unwrap();
+        reservations.retain(|_, held| {
+            held.retain(|reservation| reservation.reserved.elapsed() < RESERVATION_TTL);
+            !held.is_empty()
+        });
+
+        let held = reservations.entry(account.to_string()).or_default();
+        held.retain(|reservation| reservation.sequence > current);
+
+        if let Some(reservation) = held
+            .iter_mut()
+            .find(|reservation| reservation.intent.as_deref() == Some(intent))
+        {
+            reservation.reserved = Instant::now();
+            return Ok(reservation.sequence);
+        }
+
+        if held.len() >= MAX_RESERVATIONS {
+            let oldest = held
+                .iter()
+                .min_by_key(|reservation| reservation.reserved)
+                .expect("at least MAX_RESERVATIONS held");
+            let remaining = RESERVATION_TTL.saturating_sub(oldest.reserved.elapsed());
+            return Err(SequenceContention {
+                account: account.to_string(),
+                sequence: oldest.sequence,
+                retry_after_secs: remaining.as_secs() + 1,
+            });
+        }
+
+        // After the last held number, or in a gap a released one left
+        let sequence = (current + 1..)
+            .find(|sequence| !held.iter().any(|r| r.sequence == *sequence))
+            .expect("fewer than MAX_RESERVATIONS held");
+        held.push(Reservation {
+            sequence,
+            intent: Some(intent.to_string()),
+            reserved: Instant::now(),
+        });
+        Ok(sequence)
+    }
+
+    /// Give up a reservation whose transaction won't be submitted after all, or that the
+    /// network rejected, so the number can be handed out again
+    pub fn release(&self, account: &str, sequence: i64) {
+        let mut reservations = self.reservations.lock().unwrap();
+        if let Some(held) = reservations.get_mut(account) {
+            held.retain(|reservation| reservation.sequence != sequence);
+        }
+    }
+
+    /// Note that the transaction holding `sequence` was sent
+    ///
+    /// It keeps the number until the ledger reaches it or [`RESERVATION_TTL`] passes, but
+    /// no longer belongs to its intent: preparing the same call again gets a new number.
+    pub fn submitted(&self, account: &str, sequence: i64) {
+        let mut reservations = self.reservations.lock().unwrap();
+        if let Some(reservation) = reservations
+            .get_mut(account)
+            .and_then(|held| held.iter_mut().find(|r| r.sequence == sequence))
+        {
+            reservation.intent = None;
+            reservation.reserved = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_hands_out_consecutive_numbers() {
+        let sequences = SequenceManager::default();
+        assert_eq!(sequences.allocate("GA", 10, "plant"), Ok(11));
+        // Preparing the same transaction again (e.g. after a dismissed wallet popup) is fine
+        assert_eq!(sequences.allocate("GA", 10, "plant"), Ok(11));
+        // A different transaction, e.g. from a second device, gets the next number
+        assert_eq!(sequences.allocate("GA", 10, "harvest"), Ok(12));
+
+        // Other accounts are unaffected, and holds end once the ledger reaches them
+        assert_eq!(sequences.allocate("GB", 3, "harvest"), Ok(4));
+        assert_eq!(sequences.allocate("GA", 12, "work"), Ok(13));
+    }
+
+    #[test]
+    fn test_release_and_submit() {
+        let sequences = SequenceManager::default();
+        assert_eq!(sequences.allocate("GA", 10, "plant"), Ok(11));
+        assert_eq!(sequences.allocate("GA", 10, "work"), Ok(12));
+
+        // A released number fills the gap it left
+        sequences.release("GA", 11);
+        assert_eq!(sequences.allocate("GA", 10, "harvest"), Ok(11));
+
+        // A submitted transaction keeps its number, but re-preparing the call gets another
+        sequences.submitted("GA", 12);
+        assert_eq!(sequences.allocate("GA", 10, "work"), Ok(13));
+    }
+
+    #[test]
+    fn test_allocate_refuses_past_max_reservations() {
+        let sequences = SequenceManager::default();
+        for i in 0..MAX_RESERVATIONS {
+            assert!(sequences.allocate("GA", 10, &format!("call {}", i)).is_ok());
+        }
+
+        let contention = sequences.allocate("GA", 10, "one more").unwrap_err();
+        assert_eq!(contention.sequence, 11);
+        assert!(contention.retry_after_secs <= RESERVATION_TTL.as_secs() + 1);
+    }
+}
@@ -0,0 +1,440 @@
+use anyhow::{Context, Result};
+use galacticplayground::signer::Signer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::Mutex;
+
+/// The hidden first argument that starts the binary as a signing agent
+pub const COMMAND: &str = "sign-agent";
+
+/// Which of the keys signs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyRole {
+    Farmer,
+    FeeSource,
+}
+
+/// The keys a headless farmer signs with
+pub enum Keys {
+    /// Parsed into this process
+    Local {
+        farmer: Signer,
+        fee_source: Option<Box<Signer>>,
+    },
+    /// Held by a sandboxed agent process; see [`SignAgent`]
+    Agent(SignAgent),
+}
+
+impl Keys {
+    pub fn local(secret_key: &str, fee_source_secret_key: Option<&str>) -> Result<Self> {
+        Ok(Keys::Local {
+            farmer: Signer::from_secret(secret_key)?,
+            fee_source: fee_source_secret_key
+                .map(|secret_key| Signer::from_secret(secret_key).map(Box::new))
+                .transpose()?,
+        })
+    }
+
+    /// The farmer's account (G...)
+    pub fn farmer(&self) -> String {
+        match self {
+            Keys::Local { farmer, .. } => farmer.public_key(),
+            Keys::Agent(agent) => agent.farmer.clone(),
+        }
+    }
+
+    /// The fee source's account (G...), if another account pays the fees
+    pub fn fee_source(&self) -> Option<String> {
+        match self {
+            Keys::Local { fee_source, .. } => fee_source.as_ref().map(|key| key.public_key()),
+            Keys::Agent(agent) => agent.fee_source.clone(),
+        }
+    }
+
+    /// The key that signs envelopes: whoever is the transaction source
+    pub fn payer(&self) -> KeyRole {
+        match self.fee_source() {
+            Some(_) => KeyRole::FeeSource,
+            None => KeyRole::Farmer,
+        }
+    }
+
+    /// Sign a prepared transaction with one of the keys; see [`Signer::sign`]
+    pub fn sign(&self, key: KeyRole, tx_xdr: &str, network_passphrase: &str) -> Result<String> {
+        match self {
+            Keys::Local { farmer, fee_source } => {
+                select(farmer, fee_source.as_deref(), key)?.sign(tx_xdr, network_passphrase)
+            }
+            Keys::Agent(agent) => agent.call(&Request::Sign {
+                key,
+                xdr: tx_xdr.to_string(),
+                network_passphrase: network_passphrase.to_string(),
+            }),
+        }
+    }
+
+    /// Sign the farmer's contract authorizations; see [`Signer::sign_auth_entries`]
+    pub fn sign_auth_entries(&self, tx_xdr: &str, network_passphrase: &str) -> Result<String> {
+        match self {
+            Keys::Local { farmer, .. } => farmer.sign_auth_entries(tx_xdr, network_passphrase),
+            Keys::Agent(agent) => agent.call(&Request::SignAuthEntries {
+                xdr: tx_xdr.to_string(),
+                network_passphrase: network_passphrase.to_string(),
+            }),
+        }
+    }
+}
+
+fn select<'a>(
+    farmer: &'a Signer,
+    fee_source: Option<&'a Signer>,
+    key: KeyRole,
+) -> Result<&'a Signer> {
+    match key {
+        KeyRole::Farmer => Ok(farmer),
+        KeyRole::FeeSource => fee_source.context("No fee source key was given"),
+    }
+}
+
+/// The secrets an agent starts with, sent over its socket rather than in its arguments or
+/// environment, which other processes of the same user can read
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Setup {
+    secret_key: Option<String>,
+    fee_source_secret_key: Option<String>,
+}
+
+/// The agent's answer to [`Setup`] once it is locked down
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Ready {
+    farmer: String,
+    fee_source: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum Request {
+    #[serde(rename_all = "camelCase")]
+    Sign {
+        key: KeyRole,
+        xdr: String,
+        network_passphrase: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SignAuthEntries {
+        xdr: String,
+        network_passphrase: String,
+    },
+}
+
+/// Signs in a copy of this binary that holds the secret keys and nothing else
+///
+/// The agent reads its key file (or is sent the secrets) before locking itself down: on
+/// Linux it can't be traced or have its memory read by other processes of the user,
+/// Landlock takes away filesystem and TCP access, and seccomp kills it on any system call
+/// beyond reading requests, signing and answering. Requests are JSON lines over a socket
+/// pair that only this process holds the other end of, so a compromise of the farming
+/// process can ask for signatures but can't take the keys.
+pub struct SignAgent {
+    farmer: String,
+    fee_source: Option<String>,
+    connection: Mutex<BufReader<UnixStream>>,
+    child: Option<Child>,
+}
+
+impl SignAgent {
+    /// Start an agent with the farmer's seed from `key_file` (read by the agent, so the seed
+    /// never enters this process) or `secret_key`
+    pub fn spawn(
+        key_file: Option<&Path>,
+        secret_key: Option<&str>,
+        fee_source_secret_key: Option<&str>,
+    ) -> Result<Self> {
+        anyhow::ensure!(
+            cfg!(target_os = "linux"),
+            "The isolated signer needs Linux (seccomp and Landlock)"
+        );
+        let (ours, theirs) = UnixStream::pair()?;
+
+        let mut command = std::process::Command::new(std::env::current_exe()?);
+        command.arg(COMMAND);
+        if let Some(path) = key_file {
+            command.arg("--key-file").arg(path);
+        }
+        // The socket is the agent's stdin; nothing else, the environment included, is passed on
+        let child = command
+            .env_clear()
+            .stdin(Stdio::from(OwnedFd::from(theirs)))
+            .stdout(Stdio::null())
+            .spawn()
+            .context("Failed to start the signing agent")?;
+        // Drop our copy of the agent's end, so reads see the agent exiting
+        drop(command);
+
+        let mut agent = Self::connect(
+            ours,
+            &Setup {
+                secret_key: secret_key.map(str::to_string),
+                fee_source_secret_key: fee_source_secret_key.map(str::to_string),
+            },
+        )?;
+        agent.child = Some(child);
+        Ok(agent)
+    }
+
+    fn connect(stream: UnixStream, setup: &Setup) -> Result<Self> {
+        let mut connection = BufReader::new(stream);
+        send(connection.get_mut(), setup)?;
+        let ready: Ready = receive(&mut connection)?
+            .context("The signing agent exited before it was ready; see its error above")?;
+        Ok(Self {
+            farmer: ready.farmer,
+            fee_source: ready.fee_source,
+            connection: Mutex::new(connection),
+            child: None,
+        })
+    }
+
+    fn call(&self, request: &Request) -> Result<String> {
+        let mut connection = self.connection.lock().unwrap();
+        send(connection.get_mut(), request)?;
+        let response: std::result::Result<String, String> =
+            receive(&mut connection)?.context("The signing agent exited")?;
+        response.map_err(anyhow::Error::msg)
+    }
+}
+
+impl Drop for SignAgent {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn send<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream
+        .write_all(&line)
+        .context("Failed to reach the signing agent")
+}
+
+/// The next message, or None at the end of the stream
+fn receive<T: DeserializeOwned>(stream: &mut BufReader<UnixStream>) -> Result<Option<T>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(
+        serde_json::from_str(&line).context("Invalid signing agent message")?,
+    ))
+}
+
+/// Run as the signing agent: `sign-agent [--key-file PATH]`, with the socket as stdin
+///
+/// Called before the async runtime starts, so the agent is a single thread when it locks
+/// itself down.
+pub fn main() -> Result<()> {
+    let mut args = std::env::args().skip(2);
+    let key_file = match (args.next().as_deref(), args.next()) {
+        (None, _) => None,
+        (Some("--key-file"), Some(path)) => Some(PathBuf::from(path)),
+        _ => anyhow::bail!("Usage: {} [--key-file PATH]", COMMAND),
+    };
+
+    let stream = UnixStream::from(std::io::stdin().as_fd().try_clone_to_owned()?);
+    let mut connection = BufReader::new(stream);
+    let setup: Setup = receive(&mut connection)?.context("No setup was sent")?;
+    let secret_key = match &key_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => setup.secret_key.context("No secret key was sent")?,
+    };
+    let farmer = Signer::from_secret(&secret_key)?;
+    let fee_source = setup
+        .fee_source_secret_key
+        .as_deref()
+        .map(Signer::from_secret)
+        .transpose()?;
+    drop(secret_key);
+
+    lock_down()?;
+    serve(connection, &farmer, fee_source.as_ref())
+}
+
+/// Answer requests until the other end closes the socket
+fn serve(
+    mut connection: BufReader<UnixStream>,
+    farmer: &Signer,
+    fee_source: Option<&Signer>,
+) -> Result<()> {
+    send(
+        connection.get_mut(),
+        &Ready {
+            farmer: farmer.public_key(),
+            fee_source: fee_source.map(Signer::public_key),
+        },
+    )?;
+    while let Some(request) = receive::<Request>(&mut connection)? {
+        let signed = match request {
+            Request::Sign {
+                key,
+                xdr,
+                network_passphrase,
+            } => select(farmer, fee_source, key)
+                .and_then(|signer| signer.sign(&xdr, &network_passphrase)),
+            Request::SignAuthEntries {
+                xdr,
+                network_passphrase,
+            } => farmer.sign_auth_entries(&xdr, &network_passphrase),
+        };
+        send(
+            connection.get_mut(),
+            &signed.map_err(|e| format!("{:#}", e)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Everything the agent does once locked down: socket reads and writes, memory for
+/// parsing and signing, and exiting
+#[cfg(target_os = "linux")]
+const ALLOWED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_recvfrom,
+    libc::SYS_sendto,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_madvise,
+    libc::SYS_brk,
+    libc::SYS_futex,
+    libc::SYS_sigaltstack,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_close,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+];
+
+#[cfg(target_os = "linux")]
+fn lock_down() -> Result<()> {
+    use landlock::{Access, AccessFs, AccessNet, Ruleset, RulesetAttr, RulesetStatus, ABI};
+    use seccompiler::{BpfProgram, SeccompAction, SeccompFilter};
+
+    // Not dumpable: other processes of the user, a compromised parent included, can't
+    // ptrace the agent or read its memory through /proc
+    // SAFETY: prctl with integer arguments only changes this process's flags
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0
+        || unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0
+    {
+        return Err(std::io::Error::last_os_error()).context("Failed to lock down the agent");
+    }
+
+    let abi = ABI::V5;
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .handle_access(AccessNet::from_all(abi))?
+        .create()?
+        .restrict_self()
+        .context("Failed to apply Landlock rules")?;
+    if matches!(status.ruleset, RulesetStatus::NotEnforced) {
+        eprintln!("Warning: this kernel has no Landlock; the signing agent relies on seccomp");
+    }
+
+    let filter: BpfProgram = SeccompFilter::new(
+        ALLOWED_SYSCALLS
+            .iter()
+            .map(|&syscall| (syscall, Vec::new()))
+            .collect(),
+        SeccompAction::KillProcess,
+        SeccompAction::Allow,
+        std::env::consts::ARCH.try_into()?,
+    )?
+    .try_into()?;
+    seccompiler::apply_filter(&filter).context("Failed to apply the seccomp filter")?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lock_down() -> Result<()> {
+    anyhow::bail!("The isolated signer needs Linux (seccomp and Landlock)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_xdr::curr::{
+        Limits, Memo, MuxedAccount, Preconditions, SequenceNumber, Transaction,
+        TransactionEnvelope, TransactionExt, TransactionV1Envelope, Uint256, WriteXdr,
+    };
+
+    const SECRET: &str = "SBGWSG6BTNCKCOB3DIFBGCVMUPQFYPA2G4O34RMTB343OYPXU5DJDVMN";
+
+    #[test]
+    fn test_agent_signs_like_the_local_key() {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut connection = BufReader::new(theirs);
+            receive::<Setup>(&mut connection).unwrap();
+            let farmer = Signer::from_secret(SECRET).unwrap();
+            serve(connection, &farmer, None)
+        });
+        let agent = Keys::Agent(
+            SignAgent::connect(
+                ours,
+                &Setup {
+                    secret_key: None,
+                    fee_source_secret_key: None,
+                },
+            )
+            .unwrap(),
+        );
+        let local = Keys::local(SECRET, None).unwrap();
+        assert_eq!(agent.farmer(), local.farmer());
+        assert_eq!(agent.fee_source(), None);
+        assert_eq!(agent.payer(), KeyRole::Farmer);
+
+        let unsigned = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: Transaction {
+                source_account: MuxedAccount::Ed25519(Uint256([1; 32])),
+                fee: 100,
+                seq_num: SequenceNumber(1),
+                cond: Preconditions::None,
+                memo: Memo::None,
+                operations: Default::default(),
+                ext: TransactionExt::V0,
+            },
+            signatures: Default::default(),
+        })
+        .to_xdr_base64(Limits::none())
+        .unwrap();
+        let passphrase = "Test SDF Network ; September 2015";
+        assert_eq!(
+            agent.sign(KeyRole::Farmer, &unsigned, passphrase).unwrap(),
+            local.sign(KeyRole::Farmer, &unsigned, passphrase).unwrap()
+        );
+
+        // Errors come back as errors, and the agent keeps serving
+        let error = agent
+            .sign(KeyRole::FeeSource, &unsigned, passphrase)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "No fee source key was given");
+        assert!(agent.sign_auth_entries(&unsigned, passphrase).is_err());
+        assert!(agent.sign(KeyRole::Farmer, "AAAA", passphrase).is_err());
+
+        drop(agent);
+        assert!(server.join().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signer as _, SigningKey};
+use sha2::{Digest, Sha256};
+use stellar_xdr::curr::{
+    AccountId, DecoratedSignature, Hash, HashIdPreimage, HashIdPreimageSorobanAuthorization,
+    Limits, OperationBody, PublicKey, ReadXdr, ScAddress, ScBytes, ScMap, ScMapEntry, ScSymbol,
+    ScVal, ScVec, Signature, SignatureHint, SorobanAddressCredentials, SorobanAuthorizedInvocation,
+    SorobanCredentials, Transaction, TransactionEnvelope, TransactionSignaturePayload,
+    TransactionSignaturePayloadTaggedTransaction, Uint256, WriteXdr,
+};
+
+use crate::keys;
+
+/// Signs prepared transactions with a local secret key, for running without a wallet
+///
+/// Anything that can read the secret can spend the account's funds; keep it in an
+/// environment variable or secret store rather than on the command line.
+pub struct Signer {
+    key: SigningKey,
+}
+
+impl Signer {
+    /// A signer for an `S...` secret seed
+    pub fn from_secret(secret_seed: &str) -> Result<Self> {
+        let seed = stellar_strkey::ed25519::PrivateKey::from_string(secret_seed.trim())
+            .map_err(|_| anyhow::anyhow!("Invalid secret seed; expected S..."))?;
+        Ok(Self {
+            key: SigningKey::from_bytes(&seed.0),
+        })
+    }
+
+    /// The account (G...) this signer signs for
+    pub fn public_key(&self) -> String {
+        keys::public_key_strkey(&self.key)
+    }
+
+    /// Sign a prepared transaction (base64 envelope XDR), returning the signed XDR
+    ///
+    /// The signature is added to any already on the envelope.
+    pub fn sign(&self, tx_xdr: &str, network_passphrase: &str) -> Result<String> {
+        let envelope = TransactionEnvelope::from_xdr_base64(tx_xdr, Limits::none())
+            .context("Invalid transaction XDR")?;
+        let TransactionEnvelope::Tx(mut envelope) = envelope else {
+            anyhow::bail!("Only v1 transaction envelopes can be signed");
+        };
+
+        let hash = transaction_hash(&envelope.tx, network_passphrase)?;
+        let signature = self.key.sign(&hash).to_bytes();
+        let public_key = self.key.verifying_key().to_bytes();
+        let hint: [u8; 4] = public_key[28..].try_into().expect("4 bytes");
+
+        let mut signatures = envelope.signatures.to_vec();
+        signatures.push(DecoratedSignature {
+            hint: SignatureHint(hint),
+            signature: Signature(signature.to_vec().try_into()?),
+        });
+        envelope.signatures = signatures
+            .try_into()
+            .context("Transaction already has the maximum number of signatures")?;
+
+        Ok(TransactionEnvelope::Tx(envelope).to_xdr_base64(Limits::none())?)
+    }
+
+    /// Sign the contract authorizations this signer's account gives in a prepared
+    /// transaction, returning the updated (still unsigned) XDR
+    ///
+    /// Needed when another account is the transaction source and pays the fee: the farmer
+    /// then authorizes the call instead of signing the envelope. Fails if the transaction
+    /// asks nothing of this account.
+    pub fn sign_auth_entries(&self, tx_xdr: &str, network_passphrase: &str) -> Result<String> {
+        let envelope = TransactionEnvelope::from_xdr_base64(tx_xdr, Limits::none())
+            .context("Invalid transaction XDR")?;
+        let TransactionEnvelope::Tx(mut envelope) = envelope else {
+            anyhow::bail!("Only v1 transaction envelopes can be signed");
+        };
+
+        let public_key = self.key.verifying_key().to_bytes();
+        let address = ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            public_key,
+        ))));
+
+        let mut signed = 0;
+        let mut operations = envelope.tx.operations.to_vec();
+        for operation in &mut operations {
+            let OperationBody::InvokeHostFunction(invoke_op) = &mut operation.body else {
+                continue;
+            };
+            let mut auth = invoke_op.auth.to_vec();
+            for entry in &mut auth {
+                let SorobanCredentials::Address(credentials) = &mut entry.credentials else {
+                    continue;
+                };
+                if credentials.address != address {
+                    continue;
+                }
+                if credentials.signature_expiration_ledger == 0 {
+                    anyhow::bail!("Authorization has no expiration ledger; prepare it again");
+                }
+
+                let payload =
+                    authorization_payload(credentials, &entry.root_invocation, network_passphrase)?;
+                let signature = self.key.sign(&payload).to_bytes();
+
+                // The account contract expects a list of {public_key, signature} maps
+                let signature_map = ScMap(
+                    vec![
+                        ScMapEntry {
+                            key: ScVal::Symbol(ScSymbol("public_key".try_into()?)),
+                            val: ScVal::Bytes(ScBytes(public_key.to_vec().try_into()?)),
+                        },
+                        ScMapEntry {
+                            key: ScVal::Symbol(ScSymbol("signature".try_into()?)),
+                            val: ScVal::Bytes(ScBytes(signature.to_vec().try_into()?)),
+                        },
+                    ]
+                    .try_into()?,
+                );
+                credentials.signature = ScVal::Vec(Some(ScVec(
+                    vec![ScVal::Map(Some(signature_map))].try_into()?,
+                )));
+                signed += 1;
+            }
+            invoke_op.auth = auth.try_into()?;
+        }
+        envelope.tx.operations = operations.try_into()?;
+
+        if signed == 0 {
+            anyhow::bail!(
+                "Transaction has no authorizations for {}",
+                self.public_key()
+            );
+        }
+        Ok(TransactionEnvelope::Tx(envelope).to_xdr_base64(Limits::none())?)
+    }
+}
+
+/// The hash signers sign: SHA-256 of the transaction tagged with the network ID
+pub fn transaction_hash(tx: &Transaction, network_passphrase: &str) -> Result<[u8; 32]> {
+    let payload = TransactionSignaturePayload {
+        network_id: Hash(Sha256::digest(network_passphrase.as_bytes()).into()),
+        tagged_transaction: TransactionSignaturePayloadTaggedTransaction::Tx(tx.clone()),
+    };
+    Ok(Sha256::digest(payload.to_xdr(Limits::none())?).into())
+}
+
+/// The hash an address signs to authorize a contract call: SHA-256 of the invocation with
+/// its nonce, expiration ledger and the network ID
+pub fn authorization_payload(
+    credentials: &SorobanAddressCredentials,
+    invocation: &SorobanAuthorizedInvocation,
+    network_passphrase: &str,
+) -> Result<[u8; 32]> {
+    let preimage = HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+        network_id: Hash(Sha256::digest(network_passphrase.as_bytes()).into()),
+        nonce: credentials.nonce,
+        signature_expiration_ledger: credentials.signature_expiration_ledger,
+        invocation: invocation.clone(),
+    });
+    Ok(Sha256::digest(preimage.to_xdr(Limits::none())?).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Verifier;
+    use stellar_xdr::curr::{
+        HostFunction, InvokeContractArgs, InvokeHostFunctionOp, Memo, MuxedAccount, Operation,
+        Preconditions, SequenceNumber, SorobanAuthorizationEntry, SorobanAuthorizedFunction,
+        TransactionExt, TransactionV1Envelope,
+    };
+
+    fn test_signer() -> Signer {
+        Signer::from_secret("SBGWSG6BTNCKCOB3DIFBGCVMUPQFYPA2G4O34RMTB343OYPXU5DJDVMN").unwrap()
+    }
+
+    #[test]
+    fn test_sign_adds_verifiable_signature() {
+        let signer = test_signer();
+        assert_eq!(
+            signer.public_key(),
+            "GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6"
+        );
+
+        let tx = Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256(signer.key.verifying_key().to_bytes())),
+            fee: 100,
+            seq_num: SequenceNumber(1),
+            cond: Preconditions::None,
+            memo: Memo::None,
+            operations: Default::default(),
+            ext: TransactionExt::V0,
+        };
+        let unsigned = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: tx.clone(),
+            signatures: Default::default(),
+        })
+        .to_xdr_base64(Limits::none())
+        .unwrap();
+
+        let passphrase = "Test SDF Network ; September 2015";
+        let signed = signer.sign(&unsigned, passphrase).unwrap();
+        let TransactionEnvelope::Tx(envelope) =
+            TransactionEnvelope::from_xdr_base64(&signed, Limits::none()).unwrap()
+        else {
+            panic!("expected a v1 envelope");
+        };
+
+        assert_eq!(envelope.signatures.len(), 1);
+        let signature =
+            ed25519_dalek::Signature::from_slice(&envelope.signatures[0].signature.0).unwrap();
+        let hash = transaction_hash(&tx, passphrase).unwrap();
+        assert!(signer.key.verifying_key().verify(&hash, &signature).is_ok());
+    }
+
+    #[test]
+    fn test_sign_auth_entries_for_own_account() {
+        let farmer = test_signer();
+        let farmer_key = farmer.key.verifying_key().to_bytes();
+        let invocation = SorobanAuthorizedInvocation {
+            function: SorobanAuthorizedFunction::ContractFn(InvokeContractArgs {
+                contract_address: ScAddress::Contract(Hash([1; 32])),
+                function_name: ScSymbol("plant".try_into().unwrap()),
+                args: Default::default(),
+            }),
+            sub_invocations: Default::default(),
+        };
+        let entry = SorobanAuthorizationEntry {
+            credentials: SorobanCredentials::Address(SorobanAddressCredentials {
+                address: ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+                    farmer_key,
+                )))),
+                nonce: 7,
+                signature_expiration_ledger: 1000,
+                signature: ScVal::Void,
+            }),
+            root_invocation: invocation.clone(),
+        };
+
+        // A fee source (not the farmer) is the transaction source
+        let tx = Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256([2; 32])),
+            fee: 100,
+            seq_num: SequenceNumber(1),
+            cond: Preconditions::None,
+            memo: Memo::None,
+            operations: vec![Operation {
+                source_account: None,
+                body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+                    host_function: HostFunction::InvokeContract(InvokeContractArgs {
+                        contract_address: ScAddress::Contract(Hash([1; 32])),
+                        function_name: ScSymbol("plant".try_into().unwrap()),
+                        args: Default::default(),
+                    }),
+                    auth: vec![entry].try_into().unwrap(),
+                }),
+            }]
+            .try_into()
+            .unwrap(),
+            ext: TransactionExt::V0,
+        };
+        let unsigned = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx,
+            signatures: Default::default(),
+        })
+        .to_xdr_base64(Limits::none())
+        .unwrap();
+
+        let passphrase = "Test SDF Network ; September 2015";
+        let signed = farmer.sign_auth_entries(&unsigned, passphrase).unwrap();
+        let TransactionEnvelope::Tx(envelope) =
+            TransactionEnvelope::from_xdr_base64(&signed, Limits::none()).unwrap()
+        else {
+            panic!("expected a v1 envelope");
+        };
+        assert!(envelope.signatures.is_empty());
+
+        let OperationBody::InvokeHostFunction(invoke_op) = &envelope.tx.operations[0].body else {
+            panic!("expected an invocation");
+        };
+        let SorobanCredentials::Address(credentials) = &invoke_op.auth[0].credentials else {
+            panic!("expected address credentials");
+        };
+        let ScVal::Vec(Some(signatures)) = &credentials.signature else {
+            panic!("expected a signature list");
+        };
+        let ScVal::Map(Some(signature_map)) = &signatures[0] else {
+            panic!("expected a signature map");
+        };
+        let ScVal::Bytes(signature) = &signature_map[1].val else {
+            panic!("expected signature bytes");
+        };
+
+        let preimage = HashIdPreimage::SorobanAuthorization(HashIdPreimageSorobanAuthorization {
+            network_id: Hash(Sha256::digest(passphrase.as_bytes()).into()),
+            nonce: 7,
+            signature_expiration_ledger: 1000,
+            invocation,
+        });
+        let hash = Sha256::digest(preimage.to_xdr(Limits::none()).unwrap());
+        let signature = ed25519_dalek::Signature::from_slice(signature.as_slice()).unwrap();
+        assert!(farmer.key.verifying_key().verify(&hash, &signature).is_ok());
+
+        // The call asks nothing of other accounts
+        let other = Signer {
+            key: SigningKey::from_bytes(&[9; 32]),
+        };
+        assert!(other.sign_auth_entries(&unsigned, passphrase).is_err());
+    }
+}
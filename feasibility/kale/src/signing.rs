@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a signing job is kept after its last update
+const JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Where a prepared transaction is in an external signing flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningState {
+    /// Prepared, but no signer has reported anything yet
+    Pending,
+    /// A signer opened the transaction
+    Viewed,
+    Signed,
+    Rejected,
+}
+
+impl SigningState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SigningState::Pending => "pending",
+            SigningState::Viewed => "viewed",
+            SigningState::Signed => "signed",
+            SigningState::Rejected => "rejected",
+        }
+    }
+
+    fn is_final(self) -> bool {
+        matches!(self, SigningState::Signed | SigningState::Rejected)
+    }
+}
+
+/// A prepared transaction awaiting a signature from outside the browser
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningJob {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+    pub kind: &'static str, // plant, work or harvest
+    pub state: SigningState,
+    /// Why the signer rejected the transaction, if they said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip)]
+    updated: Instant,
+}
+
+/// A status report that doesn't apply to the job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportError {
+    UnknownJob,
+    /// The job was already signed or rejected
+    Finished(SigningState),
+    /// Only viewed, signed and rejected can be reported
+    NotReportable,
+}
+
+impl std::fmt::Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportError::UnknownJob => write!(f, "Unknown or expired signing job"),
+            ReportError::Finished(state) => {
+                write!(f, "Signing job was already {}", state.as_str())
+            }
+            ReportError::NotReportable => {
+                write!(f, "Signers can only report viewed, signed or rejected")
+            }
+        }
+    }
+}
+
+/// Signing jobs by ID, so external signers (mobile wallets, multisig coordinators) can
+/// report progress and the UI can show it instead of guessing from timeouts
+///
+/// Jobs live in memory; a restart forgets them, and the signer then gets "unknown job".
+#[derive(Default)]
+pub struct SigningJobs {
+    jobs: Mutex<HashMap<String, SigningJob>>,
+    counter: AtomicU64,
+}
+
+impl SigningJobs {
+    /// Start tracking a prepared transaction, returning its job ID
+    pub fn create(&self, public_key: &str, kind: &'static str) -> String {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| job.updated.elapsed() < JOB_TTL);
+
+        let id = self.new_id(public_key);
+        jobs.insert(
+            id.clone(),
+            SigningJob {
+                public_key: public_key.to_string(),
+                kind,
+                state: SigningState::Pending,
+                reason: None,
+                updated: Instant::now(),
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<SigningJob> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.get(id)
+            .filter(|job| job.updated.elapsed() < JOB_TTL)
+            .cloned()
+    }
+
+    /// Record a signer's progress report, returning the updated job
+    ///
+    /// Signed and rejected are final; reporting viewed after either is refused rather
+    /// than letting a late report move the job backwards.
+    pub fn report(
+        &self,
+        id: &str,
+        state: SigningState,
+        reason: Option<String>,
+    ) -> Result<SigningJob, ReportError> {
+        if state == SigningState::Pending {
+            return Err(ReportError::NotReportable);
+        }
+
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs
+            .get_mut(id)
+            .filter(|job| job.updated.elapsed() < JOB_TTL)
+            .ok_or(ReportError::UnknownJob)?;
+        if job.state.is_final() {
+            // Signers may retry a report whose response they never got
+            if job.state == state {
+                return Ok(job.clone());
+            }
+            return Err(ReportError::Finished(job.state));
+        }
+
+        job.state = state;
+        job.reason = reason.filter(|_| state == SigningState::Rejected);
+        job.updated = Instant::now();
+        Ok(job.clone())
+    }
+
+    /// Unguessable, since knowing a job ID is what lets a signer report on it
+    fn new_id(&self, public_key: &str) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let mut hasher = Sha3_256::new();
+        hasher.update(public_key.as_bytes());
+        hasher.update(nanos.to_le_bytes());
+        hasher.update(self.counter.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    }
+}
+
+/// Where signers report progress on a job
+pub fn status_path(id: &str) -> String {
+    format!("/api/signing/{}/status", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_transitions() {
+        let jobs = SigningJobs::default();
+        let id = jobs.create("GA", "plant");
+        assert_eq!(jobs.get(&id).unwrap().state, SigningState::Pending);
+
+        jobs.report(&id, SigningState::Viewed, None).unwrap();
+        let job = jobs
+            .report(
+                &id,
+                SigningState::Rejected,
+                Some("Wrong amount".to_string()),
+            )
+            .unwrap();
+        assert_eq!(job.reason.as_deref(), Some("Wrong amount"));
+
+        assert!(jobs.report(&id, SigningState::Rejected, None).is_ok());
+        assert_eq!(
+            jobs.report(&id, SigningState::Signed, None).unwrap_err(),
+            ReportError::Finished(SigningState::Rejected)
+        );
+        assert_eq!(
+            jobs.report("missing", SigningState::Viewed, None)
+                .unwrap_err(),
+            ReportError::UnknownJob
+        );
+    }
+}
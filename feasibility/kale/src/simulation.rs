@@ -0,0 +1,399 @@
+use crate::farmer::FarmBackend;
+use crate::headless::FarmCall;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use galacticplayground::contracts::kale::{
+    count_leading_zeros, normalize, work_hash, DifficultyEstimate, FarmError, UnharvestedBlock,
+    DIFFICULTY_SAMPLES,
+};
+use galacticplayground::rpc::SorobanRpc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Ledgers in a block: five minutes at five seconds each
+pub const LEDGERS_PER_BLOCK: u32 = 60;
+
+/// A farmer's entry in a block
+#[derive(Debug, Clone)]
+struct Pail {
+    stake: i128,
+    planted_at: u32,
+    /// Leading zeros and the gap in ledgers between plant and work
+    work: Option<(u32, u32)>,
+    harvested: bool,
+}
+
+#[derive(Debug, Default)]
+struct Block {
+    /// Set by the block's first plant
+    entropy: Option<[u8; 32]>,
+    pails: BTreeMap<String, Pail>,
+}
+
+#[derive(Debug)]
+struct FarmState {
+    ledger: u32,
+    block_index: u32,
+    blocks: BTreeMap<u32, Block>,
+    balances: BTreeMap<String, i128>,
+    /// Farming calls still to fail as if the RPC dropped them
+    failing_calls: u32,
+    transactions: u64,
+}
+
+/// A KALE farm in memory, so farming automation can be tested without the network
+///
+/// Blocks roll over only when the test calls [`next_block`](Self::next_block), and ledgers
+/// only advance with it or [`advance_ledgers`](Self::advance_ledgers), so every run is
+/// the same. Plant, work and harvest follow the contract's rules, and harvest pays out with
+/// [`reward`](Self::reward). Competing farmers call the methods here directly; the farmer
+/// under test goes through a [`SimulatedFarmer`].
+pub struct SimulatedFarm {
+    block_reward: i128,
+    state: Mutex<FarmState>,
+}
+
+impl SimulatedFarm {
+    /// A farm at the start of block 1, paying `block_reward` stroops per block
+    pub fn new(block_reward: i128) -> Arc<Self> {
+        Arc::new(Self {
+            block_reward,
+            state: Mutex::new(FarmState {
+                ledger: 0,
+                block_index: 1,
+                blocks: BTreeMap::new(),
+                balances: BTreeMap::new(),
+                failing_calls: 0,
+                transactions: 0,
+            }),
+        })
+    }
+
+    /// The farmer under test, as the daemon sees the farm
+    pub fn farmer(self: &Arc<Self>, address: &str) -> SimulatedFarmer {
+        SimulatedFarmer {
+            farm: self.clone(),
+            address: address.to_string(),
+        }
+    }
+
+    /// Close the current block and start the next
+    pub fn next_block(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.block_index += 1;
+        state.ledger = (state.ledger / LEDGERS_PER_BLOCK + 1) * LEDGERS_PER_BLOCK;
+    }
+
+    /// Move time forward within the current block
+    pub fn advance_ledgers(&self, ledgers: u32) {
+        let mut state = self.state.lock().unwrap();
+        let block_end = (state.ledger / LEDGERS_PER_BLOCK + 1) * LEDGERS_PER_BLOCK - 1;
+        state.ledger = (state.ledger + ledgers).min(block_end);
+    }
+
+    /// Fail the next `calls` farming calls from farmers under test, as a dropped RPC would
+    pub fn fail_next_calls(&self, calls: u32) {
+        self.state.lock().unwrap().failing_calls = calls;
+    }
+
+    pub fn fund(&self, farmer: &str, amount: i128) {
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .balances
+            .entry(farmer.to_string())
+            .or_default() += amount;
+    }
+
+    /// KALE balance in stroops
+    pub fn balance(&self, farmer: &str) -> i128 {
+        let state = self.state.lock().unwrap();
+        state.balances.get(farmer).copied().unwrap_or_default()
+    }
+
+    pub fn plant(&self, farmer: &str, stake: i128) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let (block_index, ledger) = (state.block_index, state.ledger);
+        let available = state.balances.get(farmer).copied().unwrap_or_default();
+        if stake < 0 {
+            return Err(FarmError::NegativeStake { requested: stake }.into());
+        }
+        if stake > available {
+            return Err(FarmError::InsufficientBalance {
+                requested: stake,
+                available: available as i64,
+            }
+            .into());
+        }
+        let block = state.blocks.entry(block_index).or_default();
+        if block.pails.contains_key(farmer) {
+            bail!("Farmer already planted in block {}", block_index);
+        }
+        block.entropy.get_or_insert_with(|| entropy(block_index));
+        block.pails.insert(
+            farmer.to_string(),
+            Pail {
+                stake,
+                planted_at: ledger,
+                work: None,
+                harvested: false,
+            },
+        );
+        *state.balances.entry(farmer.to_string()).or_default() -= stake;
+        Ok(())
+    }
+
+    /// Submit work in the current block, returning its leading zeros
+    pub fn work(&self, farmer: &str, nonce: u64) -> Result<u32> {
+        let farmer_key = SorobanRpc::parse_account_key(farmer, "farmer")?;
+        let mut state = self.state.lock().unwrap();
+        let (block_index, ledger) = (state.block_index, state.ledger);
+        let Some(block) = state.blocks.get_mut(&block_index) else {
+            return Err(FarmError::NoPail { block_index }.into());
+        };
+        let entropy = block.entropy.unwrap_or_default();
+        let Some(pail) = block.pails.get_mut(farmer) else {
+            return Err(FarmError::NoPail { block_index }.into());
+        };
+        let candidate_zeros =
+            count_leading_zeros(&work_hash(block_index, nonce, &entropy, &farmer_key));
+        if let Some((existing_zeros, _)) = pail.work {
+            if existing_zeros >= candidate_zeros {
+                return Err(FarmError::WorkAlreadyBetter {
+                    existing_zeros,
+                    candidate_zeros,
+                }
+                .into());
+            }
+        }
+        pail.work = Some((candidate_zeros, ledger - pail.planted_at));
+        Ok(candidate_zeros)
+    }
+
+    /// Harvest a closed block, crediting and returning the reward plus the stake
+    pub fn harvest(&self, farmer: &str, block_index: u32) -> Result<i128> {
+        let Some(reward) = self.reward(farmer, block_index) else {
+            bail!("Nothing to harvest in block {}", block_index);
+        };
+        let mut state = self.state.lock().unwrap();
+        if block_index >= state.block_index {
+            bail!("Block {} hasn't closed yet", block_index);
+        }
+        let block = state.blocks.get_mut(&block_index);
+        let pail = block.and_then(|block| block.pails.get_mut(farmer));
+        match pail {
+            Some(pail) if !pail.harvested => pail.harvested = true,
+            _ => return Err(FarmError::NoPail { block_index }.into()),
+        }
+        *state.balances.entry(farmer.to_string()).or_default() += reward;
+        Ok(reward)
+    }
+
+    /// What harvesting would pay a farmer who worked the block: their share of the block
+    /// reward, plus their stake back
+    ///
+    /// Modelled on the contract: stake, gap (ledgers between plant and work) and zeros are
+    /// each scaled between the lowest and highest among the block's workers, and the reward
+    /// is split in proportion to the sum. Where all workers are equal on a component, each
+    /// gets its full score. `None` if the farmer has no unharvested work in the block.
+    pub fn reward(&self, farmer: &str, block_index: u32) -> Option<i128> {
+        let state = self.state.lock().unwrap();
+        let block = state.blocks.get(&block_index)?;
+        let pail = block.pails.get(farmer).filter(|pail| !pail.harvested)?;
+        let (zeros, gap) = pail.work?;
+
+        let workers: Vec<(i128, i128, i128)> = block
+            .pails
+            .values()
+            .filter_map(|pail| {
+                let (zeros, gap) = pail.work?;
+                Some((pail.stake, gap as i128, zeros as i128))
+            })
+            .collect();
+        let range = |component: fn(&(i128, i128, i128)) -> i128| {
+            let values = workers.iter().map(component);
+            (values.clone().min().unwrap_or(0), values.max().unwrap_or(0))
+        };
+        let ranges = [range(|w| w.0), range(|w| w.1), range(|w| w.2)];
+        let score = |worker: &(i128, i128, i128)| -> i128 {
+            [worker.0, worker.1, worker.2]
+                .into_iter()
+                .zip(ranges)
+                .map(|(value, (min, max))| normalize(value, min, max))
+                .sum()
+        };
+
+        let total: i128 = workers.iter().map(&score).sum();
+        let own = score(&(pail.stake, gap as i128, zeros as i128));
+        Some(self.block_reward * own / total + pail.stake)
+    }
+
+    /// Search for a nonce giving the farmer's work exactly this many leading zeros in the
+    /// current block, e.g. for a competing farmer
+    pub fn find_nonce(&self, farmer: &str, zeros: u32) -> u64 {
+        let farmer_key = SorobanRpc::parse_account_key(farmer, "farmer").unwrap();
+        let state = self.state.lock().unwrap();
+        let block_index = state.block_index;
+        let entropy = state
+            .blocks
+            .get(&block_index)
+            .and_then(|block| block.entropy)
+            .unwrap_or_default();
+        (0..)
+            .find(|nonce| {
+                count_leading_zeros(&work_hash(block_index, *nonce, &entropy, &farmer_key)) == zeros
+            })
+            .unwrap()
+    }
+
+    /// Take one of the calls set to fail, if any are left
+    fn call_fails(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let fails = state.failing_calls > 0;
+        state.failing_calls = state.failing_calls.saturating_sub(1);
+        fails
+    }
+}
+
+/// A block's entropy, fixed per block so runs repeat
+fn entropy(block_index: u32) -> [u8; 32] {
+    use sha3::{Digest, Keccak256};
+
+    Keccak256::digest(block_index.to_be_bytes()).into()
+}
+
+/// A distinct, valid farmer address for each seed
+pub fn address(seed: u8) -> String {
+    stellar_strkey::ed25519::PublicKey([seed; 32]).to_string()
+}
+
+/// One farmer's view of a [`SimulatedFarm`]
+pub struct SimulatedFarmer {
+    farm: Arc<SimulatedFarm>,
+    address: String,
+}
+
+#[async_trait]
+impl FarmBackend for SimulatedFarmer {
+    fn farmer(&self) -> String {
+        self.address.clone()
+    }
+
+    async fn get_block_info(&self) -> Result<(u32, Option<[u8; 32]>)> {
+        let state = self.farm.state.lock().unwrap();
+        let entropy = state
+            .blocks
+            .get(&state.block_index)
+            .and_then(|block| block.entropy);
+        Ok((state.block_index, entropy))
+    }
+
+    async fn get_pail_data(&self, block_index: u32) -> Result<(bool, bool, u32)> {
+        let state = self.farm.state.lock().unwrap();
+        let pail = state
+            .blocks
+            .get(&block_index)
+            .and_then(|block| block.pails.get(&self.address))
+            .filter(|pail| !pail.harvested);
+        Ok(match pail.map(|pail| pail.work) {
+            Some(Some((zeros, _))) => (true, true, zeros),
+            Some(None) => (true, false, 0),
+            None => (false, false, 0),
+        })
+    }
+
+    async fn find_unharvested_blocks(&self, lookback: u32) -> Result<Vec<UnharvestedBlock>> {
+        let state = self.farm.state.lock().unwrap();
+        let first = state.block_index.saturating_sub(lookback);
+        Ok(state
+            .blocks
+            .range(first..state.block_index)
+            .filter_map(|(block_index, block)| {
+                let pail = block.pails.get(&self.address)?;
+                (!pail.harvested).then(|| UnharvestedBlock {
+                    block_index: *block_index,
+                    has_worked: pail.work.is_some(),
+                    leading_zeros: pail.work.map_or(0, |(zeros, _)| zeros),
+                    live_until_ledger: None,
+                    expires_in_ledgers: None,
+                })
+            })
+            .collect())
+    }
+
+    async fn estimate_difficulty(&self) -> Result<DifficultyEstimate> {
+        let state = self.farm.state.lock().unwrap();
+        let first = state.block_index.saturating_sub(DIFFICULTY_SAMPLES);
+        let zeros: Vec<(u32, u32)> = state
+            .blocks
+            .range(first..state.block_index)
+            .filter_map(|(_, block)| {
+                let worked = block.pails.values().filter_map(|pail| pail.work);
+                let zeros: Vec<u32> = worked.map(|(zeros, _)| zeros).collect();
+                Some((*zeros.iter().min()?, *zeros.iter().max()?))
+            })
+            .collect();
+        // Simulated blocks are evenly spaced, so the gap is always the nominal one
+        Ok(DifficultyEstimate::from_samples(
+            state.block_index,
+            &zeros,
+            &[],
+        ))
+    }
+
+    async fn execute(&self, call: &FarmCall, _retry: u32) -> Result<String> {
+        if self.farm.call_fails() {
+            bail!("Simulated RPC failure");
+        }
+        match *call {
+            FarmCall::Plant(stake) => self.farm.plant(&self.address, stake)?,
+            FarmCall::Work(nonce, mined) => {
+                let current = self.farm.state.lock().unwrap().block_index;
+                if let Some(mined) = mined.filter(|mined| *mined != current) {
+                    return Err(FarmError::BlockAdvanced { mined, current }.into());
+                }
+                self.farm.work(&self.address, nonce)?;
+            }
+            FarmCall::Harvest(block_index) => {
+                self.farm.harvest(&self.address, block_index)?;
+            }
+        }
+        let mut state = self.farm.state.lock().unwrap();
+        state.transactions += 1;
+        Ok(format!("{:064x}", state.transactions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reward_splits_block_between_workers() {
+        let farm = SimulatedFarm::new(1_000_000);
+        let (a, b, idle) = (address(1), address(2), address(3));
+        for farmer in [&a, &b, &idle] {
+            farm.fund(farmer, 500);
+        }
+
+        farm.plant(&a, 100).unwrap();
+        farm.plant(&b, 300).unwrap();
+        farm.plant(&idle, 200).unwrap();
+        assert!(farm.plant(&a, 100).is_err());
+        farm.work(&a, farm.find_nonce(&a, 1)).unwrap();
+        farm.advance_ledgers(30);
+        assert_eq!(farm.work(&b, farm.find_nonce(&b, 2)).unwrap(), 2);
+
+        // B has the most stake, gap and zeros, so A scores nothing on any of them
+        assert_eq!(farm.reward(&a, 1), Some(100));
+        assert_eq!(farm.reward(&b, 1), Some(1_000_000 + 300));
+        assert_eq!(farm.reward(&idle, 1), None);
+
+        assert!(farm.harvest(&b, 1).is_err(), "block 1 is still open");
+        farm.next_block();
+        assert_eq!(farm.harvest(&b, 1).unwrap(), 1_000_300);
+        assert_eq!(farm.balance(&b), 1_000_500);
+        assert!(farm.harvest(&b, 1).is_err());
+    }
+}
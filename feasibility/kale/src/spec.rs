@@ -0,0 +1,249 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::Cursor;
+use stellar_xdr::curr::{
+    Limited, Limits, ReadXdr, ScSpecEntry, ScSpecFunctionV0, ScSpecTypeDef, ScVal,
+};
+
+/// Name of the WASM custom section holding the contract's interface spec
+const SPEC_SECTION_NAME: &str = "contractspecv0";
+
+/// The function interface of a contract, as embedded in its WASM by the Soroban SDK
+pub struct ContractSpec {
+    functions: HashMap<String, ScSpecFunctionV0>,
+}
+
+impl ContractSpec {
+    /// Parse the contract spec from the contract's WASM code
+    pub fn from_wasm(wasm: &[u8]) -> Result<Self> {
+        let section = custom_section(wasm, SPEC_SECTION_NAME)?
+            .context("Contract WASM has no contractspecv0 section")?;
+
+        // The section is a plain concatenation of XDR-encoded ScSpecEntry values
+        let mut reader = Limited::new(Cursor::new(section), Limits::none());
+        let entries = ScSpecEntry::read_xdr_iter(&mut reader)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to decode contract spec entries")?;
+
+        let functions = entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                ScSpecEntry::FunctionV0(function) => {
+                    Some((function.name.to_utf8_string_lossy(), function))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self { functions })
+    }
+
+    /// Check the argument count and types of a call against the function's spec
+    ///
+    /// Returns a precise error like "work expects BytesN<32> for `hash`, got Bytes(31)"
+    /// instead of waiting for an opaque host error from simulation.
+    pub fn validate_args(&self, function_name: &str, args: &[ScVal]) -> Result<()> {
+        let function = self
+            .functions
+            .get(function_name)
+            .with_context(|| format!("Contract has no function named '{}'", function_name))?;
+
+        if function.inputs.len() != args.len() {
+            anyhow::bail!(
+                "{} expects {} arguments, got {}",
+                function_name,
+                function.inputs.len(),
+                args.len()
+            );
+        }
+
+        for (input, arg) in function.inputs.iter().zip(args) {
+            if !value_matches_type(arg, &input.type_) {
+                anyhow::bail!(
+                    "{} expects {} for `{}`, got {}",
+                    function_name,
+                    type_name(&input.type_),
+                    input.name.to_utf8_string_lossy(),
+                    value_description(arg)
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Find a custom section by name in a WASM module
+fn custom_section<'a>(wasm: &'a [u8], name: &str) -> Result<Option<&'a [u8]>> {
+    if wasm.len() < 8 || &wasm[0..4] != b"\0asm" {
+        anyhow::bail!("Contract code is not a WASM module");
+    }
+
+    // Skip the magic number and version
+    let mut offset = 8;
+    while offset < wasm.len() {
+        let section_id = wasm[offset];
+        offset += 1;
+        let size = read_leb128_u32(wasm, &mut offset)? as usize;
+        let end = offset
+            .checked_add(size)
+            .filter(|end| *end <= wasm.len())
+            .context("WASM section extends past the end of the module")?;
+
+        // Custom sections have id 0 and start with their name
+        if section_id == 0 {
+            let mut name_offset = offset;
+            let name_len = read_leb128_u32(wasm, &mut name_offset)? as usize;
+            let name_end = name_offset + name_len;
+            if name_end <= end && &wasm[name_offset..name_end] == name.as_bytes() {
+                return Ok(Some(&wasm[name_end..end]));
+            }
+        }
+
+        offset = end;
+    }
+
+    Ok(None)
+}
+
+/// Decode an unsigned LEB128 integer, advancing the offset
+fn read_leb128_u32(bytes: &[u8], offset: &mut usize) -> Result<u32> {
+    let mut result = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes
+            .get(*offset)
+            .context("Unexpected end of WASM while reading LEB128")?;
+        *offset += 1;
+        result |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    anyhow::bail!("LEB128 value too large")
+}
+
+/// Check whether a value is acceptable for a spec type
+///
+/// User-defined types are accepted as-is, since resolving them needs the full UDT spec.
+fn value_matches_type(value: &ScVal, type_def: &ScSpecTypeDef) -> bool {
+    match (type_def, value) {
+        (ScSpecTypeDef::Val, _) => true,
+        (ScSpecTypeDef::Udt(_), _) => true,
+        (ScSpecTypeDef::Result(_), _) => true,
+        (ScSpecTypeDef::Bool, ScVal::Bool(_)) => true,
+        (ScSpecTypeDef::Void, ScVal::Void) => true,
+        (ScSpecTypeDef::Error, ScVal::Error(_)) => true,
+        (ScSpecTypeDef::U32, ScVal::U32(_)) => true,
+        (ScSpecTypeDef::I32, ScVal::I32(_)) => true,
+        (ScSpecTypeDef::U64, ScVal::U64(_)) => true,
+        (ScSpecTypeDef::I64, ScVal::I64(_)) => true,
+        (ScSpecTypeDef::Timepoint, ScVal::Timepoint(_)) => true,
+        (ScSpecTypeDef::Duration, ScVal::Duration(_)) => true,
+        (ScSpecTypeDef::U128, ScVal::U128(_)) => true,
+        (ScSpecTypeDef::I128, ScVal::I128(_)) => true,
+        (ScSpecTypeDef::U256, ScVal::U256(_)) => true,
+        (ScSpecTypeDef::I256, ScVal::I256(_)) => true,
+        (ScSpecTypeDef::Bytes, ScVal::Bytes(_)) => true,
+        (ScSpecTypeDef::BytesN(bytes_n), ScVal::Bytes(bytes)) => bytes.len() == bytes_n.n as usize,
+        (ScSpecTypeDef::String, ScVal::String(_)) => true,
+        (ScSpecTypeDef::Symbol, ScVal::Symbol(_)) => true,
+        (ScSpecTypeDef::Address, ScVal::Address(_)) => true,
+        (ScSpecTypeDef::Option(_), ScVal::Void) => true,
+        (ScSpecTypeDef::Option(option), value) => value_matches_type(value, &option.value_type),
+        (ScSpecTypeDef::Vec(_), ScVal::Vec(None)) => true,
+        (ScSpecTypeDef::Vec(vec_type), ScVal::Vec(Some(items))) => items
+            .iter()
+            .all(|item| value_matches_type(item, &vec_type.element_type)),
+        (ScSpecTypeDef::Tuple(tuple), ScVal::Vec(Some(items))) => {
+            tuple.value_types.len() == items.len()
+                && tuple
+                    .value_types
+                    .iter()
+                    .zip(items.iter())
+                    .all(|(item_type, item)| value_matches_type(item, item_type))
+        }
+        (ScSpecTypeDef::Map(_), ScVal::Map(_)) => true,
+        _ => false,
+    }
+}
+
+/// Render a spec type the way it is written in Soroban contract code
+pub(crate) fn type_name(type_def: &ScSpecTypeDef) -> String {
+    match type_def {
+        ScSpecTypeDef::BytesN(bytes_n) => format!("BytesN<{}>", bytes_n.n),
+        ScSpecTypeDef::Option(option) => format!("Option<{}>", type_name(&option.value_type)),
+        ScSpecTypeDef::Vec(vec_type) => format!("Vec<{}>", type_name(&vec_type.element_type)),
+        ScSpecTypeDef::Map(map) => format!(
+            "Map<{}, {}>",
+            type_name(&map.key_type),
+            type_name(&map.value_type)
+        ),
+        ScSpecTypeDef::Udt(udt) => udt.name.to_utf8_string_lossy(),
+        ScSpecTypeDef::U32 => "u32".to_string(),
+        ScSpecTypeDef::I32 => "i32".to_string(),
+        ScSpecTypeDef::U64 => "u64".to_string(),
+        ScSpecTypeDef::I64 => "i64".to_string(),
+        ScSpecTypeDef::U128 => "u128".to_string(),
+        ScSpecTypeDef::I128 => "i128".to_string(),
+        ScSpecTypeDef::U256 => "U256".to_string(),
+        ScSpecTypeDef::I256 => "I256".to_string(),
+        ScSpecTypeDef::Bool => "bool".to_string(),
+        ScSpecTypeDef::Void => "()".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Describe a value's type (and length, for bytes) for error messages
+fn value_description(value: &ScVal) -> String {
+    match value {
+        ScVal::Bytes(bytes) => format!("Bytes({})", bytes.len()),
+        ScVal::Vec(Some(items)) => format!("Vec({})", items.len()),
+        ScVal::Map(Some(entries)) => format!("Map({})", entries.len()),
+        other => {
+            // The variant name without its payload, e.g. "U32"
+            let debug = format!("{:?}", other);
+            debug.split('(').next().unwrap_or(&debug).to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_xdr::curr::{ScBytes, ScSpecFunctionInputV0, ScSpecTypeBytesN, ScSymbol, VecM};
+
+    fn work_spec() -> ContractSpec {
+        let inputs = vec![ScSpecFunctionInputV0 {
+            doc: "".try_into().unwrap(),
+            name: "hash".try_into().unwrap(),
+            type_: ScSpecTypeDef::BytesN(ScSpecTypeBytesN { n: 32 }),
+        }];
+        let function = ScSpecFunctionV0 {
+            doc: "".try_into().unwrap(),
+            name: ScSymbol("work".try_into().unwrap()),
+            inputs: inputs.try_into().unwrap(),
+            outputs: VecM::default(),
+        };
+
+        ContractSpec {
+            functions: HashMap::from([("work".to_string(), function)]),
+        }
+    }
+
+    #[test]
+    fn test_validate_args() {
+        let spec = work_spec();
+
+        let hash = ScVal::Bytes(ScBytes(vec![0u8; 32].try_into().unwrap()));
+        assert!(spec.validate_args("work", &[hash]).is_ok());
+
+        let short_hash = ScVal::Bytes(ScBytes(vec![0u8; 31].try_into().unwrap()));
+        let error = spec.validate_args("work", &[short_hash]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "work expects BytesN<32> for `hash`, got Bytes(31)"
+        );
+
+        assert!(spec.validate_args("work", &[]).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// First file descriptor systemd passes to a socket-activated service
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// The listening socket systemd passed us, if the server was socket-activated
+///
+/// Follows the `sd_listen_fds` protocol: `LISTEN_PID` must name this process and
+/// `LISTEN_FDS` count the sockets starting at fd 3; only the first is used. The
+/// variables are removed so child processes don't take the socket for their own.
+pub fn activated_listener() -> Result<Option<std::net::TcpListener>> {
+    let (Ok(pid), Ok(fds)) = (std::env::var("LISTEN_PID"), std::env::var("LISTEN_FDS")) else {
+        return Ok(None);
+    };
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Ok(None);
+    }
+    let fds: i32 = fds.parse().context("LISTEN_FDS must be a number")?;
+    if fds < 1 {
+        return Ok(None);
+    }
+
+    // SAFETY: systemd hands this process ownership of fds 3..3+LISTEN_FDS, and nothing
+    // else in the process uses them
+    let listener = unsafe {
+        <std::net::TcpListener as std::os::unix::io::FromRawFd>::from_raw_fd(SD_LISTEN_FDS_START)
+    };
+    listener
+        .set_nonblocking(true)
+        .context("Failed to make the activated socket non-blocking")?;
+
+    Ok(Some(listener))
+}
+
+/// Tell systemd about a state change, e.g. `READY=1`, if it's watching
+///
+/// Does nothing unless `NOTIFY_SOCKET` is set (a `Type=notify` service). Failures are
+/// logged rather than returned; the server works the same without notifications.
+pub fn notify(state: &str) {
+    let Ok(socket_path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    if let Err(e) = send_notification(&socket_path, state) {
+        eprintln!("Warning: Failed to notify systemd ({}): {}", state, e);
+    }
+}
+
+fn send_notification(socket_path: &str, state: &str) -> std::io::Result<()> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+
+    // A leading @ names a socket in the abstract namespace
+    if let Some(name) = socket_path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+            return Ok(());
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = name;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "abstract sockets are Linux-only",
+            ));
+        }
+    }
+
+    socket.send_to(state.as_bytes(), socket_path)?;
+    Ok(())
+}
+
+/// A `Type=notify` service unit running `exe` from `working_dir`
+///
+/// Settings like `KALE_RPC_URL` go in the optional `/etc/default/<name>` environment file.
+/// `systemctl reload` sends SIGHUP, which rereads the config file's RPC endpoints.
+pub fn service_unit(name: &str, exe: &Path, working_dir: &Path) -> String {
+    format!(
+        "[Unit]
+Description=Galactic Playground KALE farming server
+Requires={name}.socket
+After=network-online.target {name}.socket
+Wants=network-online.target
+
+[Service]
+Type=notify
+ExecStart={exe}
+ExecReload=/bin/kill -HUP $MAINPID
+WorkingDirectory={working_dir}
+EnvironmentFile=-/etc/default/{name}
+Restart=on-failure
+RestartSec=5
+
+[Install]
+WantedBy=multi-user.target
+",
+        name = name,
+        exe = exe.display(),
+        working_dir = working_dir.display(),
+    )
+}
+
+/// A socket unit listening on the server's port, so connections made while the service
+/// restarts queue up instead of being refused
+pub fn socket_unit(name: &str, port: u16) -> String {
+    format!(
+        "[Unit]
+Description=Galactic Playground KALE farming server socket
+
+[Socket]
+ListenStream=127.0.0.1:{port}
+Service={name}.service
+
+[Install]
+WantedBy=sockets.target
+",
+        name = name,
+        port = port,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_notification() {
+        let path = std::env::temp_dir().join(format!("kale-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        send_notification(path.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buffer = [0u8; 16];
+        let len = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"READY=1");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Numbers bundles, so prepares started in the same second get their own files
+static BUNDLES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// Where bundles of each prepare's intermediate artifacts are written, if anywhere
+///
+/// Meant for bug reports about transactions that fail to assemble or are rejected after
+/// signing: the bundle has every step from the unsigned transaction to the final envelope.
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// None disables tracing
+    pub dir: Option<PathBuf>,
+}
+
+impl TraceOptions {
+    /// Load the bundle directory from `KALE_TRACE_DIR`, creating it; unset disables tracing
+    pub fn from_env() -> anyhow::Result<Self> {
+        let dir = std::env::var_os("KALE_TRACE_DIR").map(PathBuf::from);
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create KALE_TRACE_DIR {}", dir.display()))?;
+        }
+        Ok(Self { dir })
+    }
+}
+
+/// One step of a prepare, as recorded in the bundle
+#[derive(Debug, Serialize)]
+struct TraceStep {
+    step: &'static str,
+    /// Since the prepare started
+    #[serde(rename = "atMs")]
+    at_ms: u64,
+    data: Value,
+}
+
+/// A bundle file: what was prepared, each step, and how it ended
+#[derive(Debug, Serialize)]
+struct TraceBundle<'a> {
+    version: &'static str,
+    function: &'a str,
+    #[serde(rename = "startedAt")]
+    started_at: u64, // unix seconds
+    context: &'a Map<String, Value>,
+    steps: &'a [TraceStep],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The intermediate artifacts of preparing one transaction, written to a JSON bundle when
+/// the prepare ends
+///
+/// Does nothing unless [`TraceOptions::dir`] is set. Prepared transactions are unsigned,
+/// so the bundle holds no signatures; endpoint credentials are stripped by the `redact`
+/// function given to [`finish`](Self::finish). Account addresses are kept, since they are
+/// in the XDR the bundle exists to reproduce.
+pub struct PrepareTrace {
+    dir: Option<PathBuf>,
+    function: String,
+    started: Instant,
+    started_at: u64,
+    context: Map<String, Value>,
+    steps: Vec<TraceStep>,
+}
+
+impl PrepareTrace {
+    /// Start tracing a prepare of `function` (a contract function or operation name)
+    pub fn new(options: &TraceOptions, function: &str) -> Self {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        Self {
+            dir: options.dir.clone(),
+            function: function.to_string(),
+            started: Instant::now(),
+            started_at,
+            context: Map::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Whether anything will be written, to skip encoding artifacts nobody will read
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Note something about the prepare as a whole, e.g. the source account or endpoint
+    pub fn context(&mut self, key: &str, value: impl Serialize) {
+        if self.is_enabled() {
+            self.context.insert(key.to_string(), to_value(value));
+        }
+    }
+
+    /// Record an intermediate artifact
+    pub fn step(&mut self, step: &'static str, data: impl Serialize) {
+        if self.is_enabled() {
+            let at_ms = self.started.elapsed().as_millis() as u64;
+            self.steps.push(TraceStep {
+                step,
+                at_ms,
+                data: to_value(data),
+            });
+        }
+    }
+
+    /// Write the bundle, with the error if the prepare failed; returns its path
+    ///
+    /// Every string in the bundle goes through `redact` first. A bundle that can't be
+    /// written is only warned about: tracing never fails a prepare.
+    pub fn finish<E: std::fmt::Display>(
+        self,
+        error: Option<&E>,
+        redact: impl Fn(&str) -> String,
+    ) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let bundle = TraceBundle {
+            version: env!("CARGO_PKG_VERSION"),
+            function: &self.function,
+            started_at: self.started_at,
+            context: &self.context,
+            steps: &self.steps,
+            error: error.map(ToString::to_string),
+        };
+        let mut value = to_value(&bundle);
+        redact_strings(&mut value, &redact);
+
+        let path = dir.join(format!(
+            "prepare-{}-{}-{}.json",
+            self.started_at,
+            BUNDLES_WRITTEN.fetch_add(1, Ordering::Relaxed),
+            self.function
+        ));
+        let json = serde_json::to_string_pretty(&value).expect("trace bundles always serialize");
+        match std::fs::write(&path, json) {
+            Ok(()) => {
+                println!("Wrote prepare trace {}", path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: Failed to write prepare trace {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value)
+        .unwrap_or_else(|e| Value::String(format!("<unserializable: {}>", e)))
+}
+
+/// Apply `redact` to every string in a JSON value, keys included
+fn redact_strings(value: &mut Value, redact: &impl Fn(&str) -> String) {
+    match value {
+        Value::String(text) => *text = redact(text),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| redact_strings(item, redact)),
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    redact_strings(&mut value, redact);
+                    (redact(&key), value)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_bundle_is_redacted() {
+        let dir = std::env::temp_dir().join(format!("kale-trace-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = TraceOptions {
+            dir: Some(dir.clone()),
+        };
+
+        let mut trace = PrepareTrace::new(&options, "plant");
+        trace.context("endpoint", "https://rpc.example.com?apikey=secret");
+        trace.step("unsigned", serde_json::json!({ "xdr": "AAAA" }));
+        let error = "error sending request for url (https://rpc.example.com?apikey=secret)";
+        let path = trace
+            .finish(Some(&error), |text| text.replace("secret", "***"))
+            .unwrap();
+
+        let bundle: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(bundle["function"], "plant");
+        assert_eq!(bundle["steps"][0]["step"], "unsigned");
+        assert_eq!(bundle["steps"][0]["data"]["xdr"], "AAAA");
+        assert!(!bundle.to_string().contains("secret"));
+
+        // Disabled traces record and write nothing
+        let mut trace = PrepareTrace::new(&TraceOptions::default(), "plant");
+        trace.step("unsigned", "AAAA");
+        assert!(trace.finish(None::<&String>, str::to_string).is_none());
+    }
+}
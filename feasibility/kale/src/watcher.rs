@@ -0,0 +1,179 @@
+use futures_util::future::BoxFuture;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::api::KaleApi;
+
+/// Updates a slow subscriber may fall behind by before it starts missing some
+const CHANNEL_CAPACITY: usize = 16;
+
+/// The farm's current block, as last seen by a [`BlockWatcher`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockUpdate {
+    pub block_index: u32,
+    /// None until someone plants in the block
+    pub entropy: Option<[u8; 32]>,
+    /// The block index before this update, if any was seen
+    pub previous_index: Option<u32>,
+}
+
+impl BlockUpdate {
+    /// Whether the farm moved to a new block, rather than the current one getting its entropy
+    pub fn is_new_block(&self) -> bool {
+        self.previous_index != Some(self.block_index)
+    }
+}
+
+type Callback = Box<dyn Fn(BlockUpdate) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// One poller of the farm's block that everything interested in block changes shares
+///
+/// Each time the block index or its entropy changes, the update is sent to every
+/// [`subscribe`](Self::subscribe)r and every callback registered with
+/// [`on_block`](Self::on_block) is spawned with it.
+pub struct BlockWatcher {
+    interval: Duration,
+    sender: broadcast::Sender<BlockUpdate>,
+    current: Mutex<Option<BlockUpdate>>,
+    callbacks: Mutex<Vec<Callback>>,
+    paused: AtomicBool,
+}
+
+impl BlockWatcher {
+    /// A watcher polling every `interval` once [`run`](Self::run)
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            current: Mutex::new(None),
+            callbacks: Mutex::new(Vec::new()),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Stop or restart polling; while paused, nobody hears of new blocks
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Receive every update from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<BlockUpdate> {
+        self.sender.subscribe()
+    }
+
+    /// Run `callback` on its own task for every update from now on
+    pub fn on_block<F, Fut>(&self, callback: F)
+    where
+        F: Fn(BlockUpdate) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .push(Box::new(move |update| Box::pin(callback(update))));
+    }
+
+    /// The latest update, if the block has been read yet
+    pub fn current(&self) -> Option<BlockUpdate> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Record a read of the current block, notifying everyone if it changed
+    ///
+    /// Returns the update if there was one.
+    pub fn observe(&self, block_index: u32, entropy: Option<[u8; 32]>) -> Option<BlockUpdate> {
+        let update = {
+            let mut current = self.current.lock().unwrap();
+            let previous_index = current.as_ref().map(|current| current.block_index);
+            let unchanged = current.as_ref().is_some_and(|current| {
+                current.block_index == block_index && current.entropy == entropy
+            });
+            if unchanged {
+                return None;
+            }
+            let update = BlockUpdate {
+                block_index,
+                entropy,
+                previous_index,
+            };
+            *current = Some(update.clone());
+            update
+        };
+
+        // No receivers just means nobody has subscribed
+        let _ = self.sender.send(update.clone());
+        for callback in self.callbacks.lock().unwrap().iter() {
+            tokio::spawn(callback(update.clone()));
+        }
+        Some(update)
+    }
+
+    /// Poll the farm's block until the task is dropped
+    ///
+    /// A failed read is logged and tried again at the next interval. Intervals while
+    /// [paused](Self::set_paused) are skipped without a read.
+    pub async fn run<K: KaleApi + ?Sized>(&self, kale: &K) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if self.is_paused() {
+                continue;
+            }
+            match kale.get_block_info().await {
+                Ok((block_index, entropy)) => {
+                    self.observe(block_index, entropy);
+                }
+                Err(e) => eprintln!("Warning: Failed to poll block info: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_observe_notifies_on_change() {
+        let watcher = BlockWatcher::new(Duration::from_secs(5));
+        let mut updates = watcher.subscribe();
+        let (sender, mut called) = tokio::sync::mpsc::unbounded_channel();
+        watcher.on_block(move |update| {
+            let sender = sender.clone();
+            async move {
+                let _ = sender.send(update.block_index);
+            }
+        });
+
+        let first = watcher.observe(7, None).unwrap();
+        assert!(first.is_new_block());
+        assert_eq!(watcher.observe(7, None), None);
+
+        // Entropy arriving is an update, but not a new block
+        let planted = watcher.observe(7, Some([1; 32])).unwrap();
+        assert!(!planted.is_new_block());
+        let next = watcher.observe(8, None).unwrap();
+        assert_eq!(next.previous_index, Some(7));
+        assert!(next.is_new_block());
+
+        assert_eq!(updates.recv().await.unwrap(), first);
+        assert_eq!(updates.recv().await.unwrap(), planted);
+        assert_eq!(updates.recv().await.unwrap(), next);
+        // Callbacks run on their own tasks, in no particular order
+        let mut called_with = Vec::new();
+        for _ in 0..3 {
+            called_with.push(called.recv().await.unwrap());
+        }
+        called_with.sort();
+        assert_eq!(called_with, [7, 7, 8]);
+        assert_eq!(watcher.current(), Some(next));
+    }
+}